@@ -1,25 +1,84 @@
-use clap::Parser;
+use clap::{ArgMatches, Parser, ValueEnum};
 use plox::{
-	align_ranges,
-	cli::{CatArgs, Cli, CliCommand, StatArgs, build_cli},
-	error::Error,
-	gnuplot,
+	backend::{self, Backend, DisplayOptions},
+	cli::{
+		CatArgs, Cli, CliCommand, ConfigArgs, ConfigCommand, GrepArgs, HistArgs, ReportArgs,
+		StatArgs, StylesArgs, TopArgs, build_cli,
+	},
+	error::{EXIT_CODE_INTERRUPTED, EXIT_CODE_IO, EXIT_CODE_USAGE, Error, ErrorFormat},
 	graph_cli_builder::{self},
-	graph_config::{GraphConfig, Line, Panel},
-	logging::{self, APPV},
-	match_preview_cli_builder, process_log, resolved_graph_config,
+	graph_config::{self, DataSource, GraphConfig, InputFilesContext, Line, Panel},
+	interrupt,
+	logging::{self, APPV, MESSAGES, MessageFormat},
+	match_preview_cli_builder,
+	process_log::{self, StatAggregation, StatOptions},
+	report::ReportSection,
+	resolved_graph_config::{self, ResolvedGraphConfig},
 };
-use std::{process::ExitCode, time::Instant};
-use tracing::{debug, error, info, trace};
+use std::{path::PathBuf, process::ExitCode, time::Instant};
+use tracing::{debug, error, info, trace, warn};
 
 fn main() -> ExitCode {
-	match inner_main() {
-		Err(Error::TomlError(_)) => ExitCode::FAILURE,
-		Err(Error::LogProcessing(crate::process_log::Error::TimestampExtractionFailure(
+	let matches = match build_cli().try_get_matches() {
+		Ok(matches) => matches,
+		Err(e) if e.use_stderr() && requested_error_format() == ErrorFormat::Json => {
+			let message = e.render().to_string();
+			eprintln!("{}", serde_json::json!({ "code": "cli-parse", "message": message.trim() }));
+			return ExitCode::from(EXIT_CODE_USAGE);
+		},
+		Err(e) => e.exit(),
+	};
+	// With `--output -` stdout is reserved for the rendered output.
+	let output_to_stdout = matches
+		.subcommand_matches("graph")
+		.and_then(|graph_matches| graph_matches.get_one::<PathBuf>("output"))
+		.is_some_and(|output| output.as_os_str() == "-");
+	let message_format =
+		matches.get_one::<MessageFormat>("message_format").copied().unwrap_or_default();
+	let verbosity = matches.get_count("verbose");
+	let log_file = matches.get_one::<PathBuf>("log_file").map(PathBuf::as_path);
+	let tracing_init = if message_format == MessageFormat::Json {
+		logging::init_tracing_json(output_to_stdout, verbosity, log_file)
+	} else if output_to_stdout {
+		logging::init_tracing_on_stderr(true, verbosity, log_file)
+	} else {
+		logging::init_tracing(matches.get_flag("quiet"), verbosity, log_file)
+	};
+	if let Err(e) = tracing_init {
+		let log_file = log_file.expect("only the log file creation can fail");
+		eprintln!("Error while creating log file '{}': {e}", log_file.display());
+		return ExitCode::from(EXIT_CODE_IO);
+	}
+	let error_format = matches.get_one::<ErrorFormat>("error_format").copied().unwrap_or_default();
+
+	info!(
+		target: MESSAGES,
+		command = matches.subcommand_name().unwrap_or_default(),
+		version = env!("CARGO_PKG_VERSION"),
+		"started"
+	);
+	interrupt::install_handler();
+	let result = inner_main(&matches);
+	if interrupt::is_interrupted() {
+		// The reported error (if any) is just the consequence of the interruption.
+		warn!("Interrupted, partial output files were removed.");
+		info!(target: MESSAGES, exit_code = EXIT_CODE_INTERRUPTED, "finished");
+		return ExitCode::from(EXIT_CODE_INTERRUPTED);
+	}
+	let Err(e) = result else {
+		info!(target: MESSAGES, exit_code = 0, "finished");
+		return ExitCode::SUCCESS;
+	};
+
+	match &e {
+		_ if error_format == ErrorFormat::Json => eprintln!("{}", e.to_json()),
+		Error::TomlError(_) => {},
+		Error::EmptyGraph => warn!("{e}"),
+		Error::LogProcessing(crate::process_log::Error::TimestampExtractionFailure(
 			file,
 			ts,
 			log,
-		))) => {
+		)) => {
 			// error!("{:?}", e);
 			error!("Error occured when extracting timestamp from '{}' log file", file.display());
 			error!("Timestamp format given was: {ts:?}");
@@ -34,20 +93,25 @@ fn main() -> ExitCode {
 			error!(
 				"You can also use '-t' or  `--ignore-invalid-timestamps` to ignore lines with invalid or no timestamp."
 			);
-			ExitCode::FAILURE
 		},
-		Err(e) => {
-			error!("{}", e);
-			ExitCode::FAILURE
-		},
-		Ok(_) => ExitCode::SUCCESS,
+		_ => error!("{}", e),
 	}
+	info!(target: MESSAGES, exit_code = e.exit_code(), "finished");
+	ExitCode::from(e.exit_code())
+}
+
+/// Returns the error format given in the command line arguments, which could not be parsed.
+fn requested_error_format() -> ErrorFormat {
+	let args = std::env::args().collect::<Vec<_>>();
+	let value = args.iter().enumerate().find_map(|(i, arg)| match arg.as_str() {
+		"--error-format" => args.get(i + 1).map(String::as_str),
+		arg => arg.strip_prefix("--error-format="),
+	});
+	value.and_then(|v| ErrorFormat::from_str(v, true).ok()).unwrap_or_default()
 }
 
-fn inner_main() -> Result<(), Error> {
-	let matches = build_cli().get_matches();
+fn inner_main(matches: &ArgMatches) -> Result<(), Error> {
 	let verbose_level = matches.get_count("verbose");
-	logging::init_tracing(matches.get_flag("quiet"), verbose_level);
 
 	if let Some(graph_matches) = matches.subcommand_matches("match-preview") {
 		let (config, shared_context) =
@@ -57,7 +121,7 @@ fn inner_main() -> Result<(), Error> {
 		process_log::regex_match_preview(config, shared_context, verbose_level)
 			.map_err(Into::<Error>::into)?;
 	} else if let Some(graph_matches) = matches.subcommand_matches("graph") {
-		let (config, shared_context) = graph_cli_builder::build_from_matches(graph_matches)?;
+		let (config, mut shared_context) = graph_cli_builder::build_from_matches(graph_matches)?;
 
 		trace!(target:APPV, "Provided input graph config:{config:#?}");
 		trace!(target:APPV, "Provided SharedGraphContext:{shared_context:#?}");
@@ -65,6 +129,9 @@ fn inner_main() -> Result<(), Error> {
 		if let Some(output_config_path) = shared_context.output_config_path() {
 			config.save_to_file(output_config_path)?;
 		}
+		if let Some(output_minimal_config_path) = shared_context.output_minimal_config_path() {
+			config.save_minimal_to_file(output_minimal_config_path)?;
+		}
 
 		let mut resolved_config =
 			resolved_graph_config::expand_graph_config_with_ctx(&config, &shared_context)?;
@@ -74,52 +141,129 @@ fn inner_main() -> Result<(), Error> {
 			.map_err(Into::<Error>::into)?;
 		debug!(target:APPV,"Input files processed in: {:?}", now.elapsed());
 
-		let now = Instant::now();
-		align_ranges::resolve_panels_ranges(&mut resolved_config, &shared_context)
-			.map_err(Into::<Error>::into)?;
-		debug!(target:APPV,"Ranges resolved in: {:?}", now.elapsed());
+		// Lack of data is the primary failure here, placeholder rendering errors are only reported.
+		if resolved_config.is_empty() {
+			match backend::render_graph(&resolved_config, &shared_context) {
+				Ok(()) => info!("Placeholder graph was generated."),
+				Err(e) => warn!("Placeholder graph could not be generated: {e}"),
+			}
+			return Err(Error::EmptyGraph);
+		}
+
+		resolved_graph_config::prepare_for_render(&mut resolved_config, &shared_context)?;
 
 		let now = Instant::now();
-		if !shared_context.output_graph_ctx.plotly_backend {
-			gnuplot::run_gnuplot(&resolved_config, &shared_context)?;
-		} else {
-			plox::plotly_backend::write_plotly_html(&resolved_config, &shared_context)?;
+		for (suffix, config) in resolved_config.into_outputs(&shared_context) {
+			shared_context.set_output_suffix(suffix);
+			backend::render_graph(&config, &shared_context)?;
 		}
-		debug!(target:APPV,"gnuplot done in: {:?}", now.elapsed());
+		debug!(target:APPV,"{} done in: {:?}", shared_context.backend(), now.elapsed());
 	} else {
 		//todo histogram, etc..
 		let c = Cli::parse();
 		match c.command {
-			CliCommand::Cat(CatArgs { input_files_ctx, command: source }) => {
-				let line = Line::new_with_data_source(source.into());
-				let config =
-					GraphConfig { panels: vec![Panel::builder().with_lines(vec![line]).build()] };
-				let mut resolved_graph_config = resolved_graph_config::expand_graph_config(
-					&config,
-					input_files_ctx.input(),
-					false,
-				)?;
-				process_log::process_inputs(&mut resolved_graph_config, &input_files_ctx)
-					.map_err(Into::<Error>::into)?;
+			CliCommand::Cat(CatArgs {
+				input_files_ctx,
+				columns,
+				format,
+				keep_text,
+				follow,
+				from_cache,
+				command: source,
+			}) => {
+				let aggregation = source.aggregation();
+				let mut line = Line::new_with_data_source(source.into());
+				line.params.keep_text = keep_text;
+				if follow {
+					let config = GraphConfig {
+						panels: vec![Panel::builder().with_lines(vec![line]).build()],
+					};
+					let resolved_graph_config = resolved_graph_config::expand_graph_config(
+						&config,
+						input_files_ctx.input(),
+						false,
+					)?;
+					process_log::follow_values(
+						&resolved_graph_config,
+						&input_files_ctx,
+						aggregation,
+						&columns,
+						format,
+					)?;
+					return Ok(());
+				}
+				let resolved_graph_config =
+					resolve_single_line_config(line, &input_files_ctx, from_cache)?;
 
-				process_log::display_values(&resolved_graph_config)?;
+				process_log::display_values(&resolved_graph_config, aggregation, &columns, format)?;
 			},
 			CliCommand::Stat(StatArgs {
 				input_files_ctx,
 				command: source,
 				buckets_count,
+				log_buckets,
 				precision,
+				percentiles,
+				metrics,
+				group_by,
+				show_outliers,
+				from_cache,
+				correlate,
+				correlate_guard,
+				align,
+				align_interval,
+				scatter,
+				periodogram,
+				resample_interval,
+				top_periods,
 			}) => {
-				let line = Line::new_with_data_source(source.into());
-				let config =
-					GraphConfig { panels: vec![Panel::builder().with_lines(vec![line]).build()] };
-				let mut resolved_graph_config = resolved_graph_config::expand_graph_config(
-					&config,
-					input_files_ctx.input(),
-					false,
-				)?;
-				process_log::process_inputs(&mut resolved_graph_config, &input_files_ctx)
-					.map_err(Into::<Error>::into)?;
+				let aggregation = source.aggregation();
+				if let Some(field) = correlate {
+					let x_line = Line::new_with_data_source(source.into());
+					let y_line = Line::new_with_data_source(DataSource::new_plot_field(
+						correlate_guard,
+						field,
+					));
+					let labels = (x_line.data_source.title(), y_line.data_source.title());
+					let x_config = resolve_single_line_config(x_line, &input_files_ctx, None)?;
+					let y_config = resolve_single_line_config(y_line, &input_files_ctx, None)?;
+
+					let pairs = process_log::correlated_values(
+						(&x_config, aggregation),
+						(&y_config, StatAggregation::None),
+						align,
+						align_interval,
+					)?;
+					process_log::display_correlation(&pairs);
+
+					if let Some(image_path) = scatter {
+						backend::render_scatter(
+							&pairs,
+							&format!("{} vs {}", labels.1, labels.0),
+							(&labels.0, &labels.1),
+							image_path,
+						)?;
+					}
+					return Ok(());
+				}
+
+				if periodogram {
+					let line = Line::new_with_data_source(source.into());
+					let config = resolve_single_line_config(line, &input_files_ctx, from_cache)?;
+					let periodogram = process_log::periodogram(
+						&config,
+						aggregation,
+						resample_interval,
+						top_periods,
+					)?;
+					process_log::display_periodogram(&periodogram);
+					return Ok(());
+				}
+
+				let mut line = Line::new_with_data_source(source.into());
+				line.params.label_regex = group_by;
+				let resolved_graph_config =
+					resolve_single_line_config(line, &input_files_ctx, from_cache)?;
 
 				let (precision, width) = if precision.len() == 2 {
 					(Some(precision[0]), Some(precision[1]))
@@ -127,15 +271,151 @@ fn inner_main() -> Result<(), Error> {
 					(None, None)
 				};
 
-				process_log::display_stats(
-					&resolved_graph_config,
+				let options = StatOptions {
 					buckets_count,
-					precision,
+					log_buckets,
 					width,
+					precision,
+					percentiles,
+					metrics,
+					aggregation,
+					outliers_count: show_outliers,
+				};
+				process_log::display_stats(&resolved_graph_config, &options)?;
+			},
+			CliCommand::Hist(HistArgs {
+				input_files_ctx,
+				buckets_count,
+				log_buckets,
+				cdf,
+				output,
+				plotly_backend,
+				do_not_display,
+				display_absolute_paths,
+				from_cache,
+				command: source,
+			}) => {
+				let aggregation = source.aggregation();
+				let line = Line::new_with_data_source(source.into());
+				let title = format!("histogram: {}", line.data_source.title());
+				let resolved_graph_config =
+					resolve_single_line_config(line, &input_files_ctx, from_cache)?;
+
+				let buckets = process_log::histogram_buckets(
+					&resolved_graph_config,
+					aggregation,
+					buckets_count,
+					log_buckets,
+				)?;
+
+				backend::render_histogram(
+					Backend::from_plotly_flag(plotly_backend),
+					&buckets,
+					&title,
+					cdf,
+					log_buckets,
+					output,
+					DisplayOptions { display_absolute_paths, do_not_display },
 				)?;
 			},
+			CliCommand::Top(TopArgs {
+				input_files_ctx,
+				label,
+				top,
+				by,
+				precision,
+				from_cache,
+				command: source,
+			}) => {
+				let aggregation = source.aggregation();
+				let mut line = Line::new_with_data_source(source.into());
+				line.params.label_regex = Some(label);
+				let resolved_graph_config =
+					resolve_single_line_config(line, &input_files_ctx, from_cache)?;
+
+				process_log::display_top(&resolved_graph_config, aggregation, top, by, precision)?;
+			},
+			CliCommand::Grep(GrepArgs { input_files_ctx, time_range, command: source }) => {
+				let line = Line::new_with_data_source(source.into());
+				let mut resolved_graph_config =
+					resolve_single_line_config(line, &input_files_ctx, None)?;
+
+				let time_range = match time_range {
+					Some(time_range) => {
+						resolved_graph_config.populate_line_ranges()?;
+						let total_range = resolved_graph_config.global_time_range()?;
+						Some(time_range.resolve(total_range, input_files_ctx.timestamp_format())?)
+					},
+					None => None,
+				};
+				process_log::display_matched_lines(
+					&resolved_graph_config,
+					&input_files_ctx,
+					time_range,
+				)?;
+			},
+			CliCommand::Report(ReportArgs {
+				input_files_ctx,
+				configs,
+				section_titles,
+				title,
+				output,
+				do_not_display,
+			}) => {
+				let sections = configs
+					.iter()
+					.enumerate()
+					.map(|(i, config_path)| {
+						let section_title = section_titles.get(i).cloned();
+						ReportSection::build(config_path, section_title, &input_files_ctx)
+					})
+					.collect::<Result<Vec<_>, _>>()?;
+				backend::render_report(&title, &sections, output, do_not_display)?;
+			},
+			CliCommand::Styles(StylesArgs { sample, do_not_display }) => {
+				for (kind, names) in graph_config::style_names() {
+					println!("{kind}:");
+					for name in names {
+						println!("  {name}");
+					}
+				}
+				if let Some(sample) = sample {
+					backend::render_styles_sample(sample, do_not_display)?;
+				}
+			},
+			CliCommand::Config(ConfigArgs { command: ConfigCommand::ToCli { config } }) => {
+				let args = graph_cli_builder::config_file_to_cli_args(&config)?;
+				println!("plox graph {}", graph_cli_builder::shell_join(&args));
+			},
+			CliCommand::Config(ConfigArgs { command: ConfigCommand::Schema }) => {
+				println!("{}", graph_cli_builder::config_json_schema());
+			},
 		}
 	}
 
 	Ok(())
 }
+
+/// Resolves the config of `stat` and `cat` commands, which contains a single line.
+///
+/// Log files are processed (or CSV cache is used if it exists). If `from_cache` is given, values
+/// are read directly from that CSV file.
+fn resolve_single_line_config(
+	line: Line,
+	input_files_ctx: &InputFilesContext,
+	from_cache: Option<PathBuf>,
+) -> Result<ResolvedGraphConfig, Error> {
+	if let Some(from_cache) = from_cache {
+		return Ok(ResolvedGraphConfig::new_from_csv_file(line, &from_cache));
+	}
+
+	let config = GraphConfig { panels: vec![Panel::builder().with_lines(vec![line]).build()] };
+	let mut resolved_graph_config = resolved_graph_config::expand_graph_config(
+		&config,
+		&input_files_ctx.logical_input(),
+		false,
+	)?;
+	process_log::process_inputs(&mut resolved_graph_config, input_files_ctx)
+		.map_err(Into::<Error>::into)?;
+	Ok(resolved_graph_config)
+}
@@ -7,7 +7,7 @@ use crate::{
 		GraphFullContext, PanelAlignmentMode, PanelRangeMode, TimeRangeArg, TimestampFormat,
 	},
 	logging::APPV,
-	resolved_graph_config::{ResolvedGraphConfig, ResolvedPanel},
+	resolved_graph_config::{ResolvedGraphConfig, ResolvedPanel, UnresolvedCsvFileError},
 };
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use std::{
@@ -35,6 +35,8 @@ pub enum Error {
 		"Incorrect time range for all lines: {0} {1} (try re-running with '--force-csv-regen')"
 	)]
 	IncorrectRangeError(NaiveDateTime, NaiveDateTime),
+	#[error(transparent)]
+	UnresolvedCsvFile(#[from] UnresolvedCsvFileError),
 }
 
 fn csv_range_from_file(path: &PathBuf) -> Result<Option<(NaiveDateTime, NaiveDateTime)>, Error> {
@@ -72,12 +74,16 @@ fn csv_range_from_file(path: &PathBuf) -> Result<Option<(NaiveDateTime, NaiveDat
 impl ResolvedGraphConfig {
 	/// Sets the time range for every line in the config.
 	///
-	/// This reads a shared cvs files, and extracts the time range for every line in config.
+	/// This reads a shared cvs files, and extracts the time range for every line in config. Lines
+	/// with already known time range (e.g. set while processing input files) are skipped.
 	/// Requires the CSV files to be resolved.
 	pub fn populate_line_ranges(&mut self) -> Result<(), Error> {
 		for panel in &mut self.panels {
 			for line in &mut panel.lines {
-				if let Some(range) = csv_range_from_file(&line.expect_shared_csv_filename())? {
+				if line.time_range().is_some() {
+					continue;
+				}
+				if let Some(range) = csv_range_from_file(&line.resolved_csv_filename()?)? {
 					line.set_time_range(range.0, range.1);
 				} else {
 					debug!(target:LOG_TARGET, "empty CSV time range for line: {:#?}", line);
@@ -0,0 +1,203 @@
+//! Selection of the rendering backend.
+//!
+//! Graphs, histograms and scatter plots can be rendered either with `gnuplot` or with `plotly`.
+//! Both backends are optional cargo features (`gnuplot` and `plotly`), enabled by default. Library
+//! users interested only in the extraction engine (see [`crate::pipeline`]) can disable them with
+//! `default-features = false`.
+//!
+//! Requesting a backend which was not compiled in results in [`Error::BackendNotEnabled`].
+
+use crate::{
+	error::Error, graph_config::GraphFullContext, process_log::HistoBucket, report::ReportSection,
+	resolved_graph_config::ResolvedGraphConfig,
+};
+use std::path::PathBuf;
+
+/// Message displayed in the placeholder graph generated when there is no data for any line.
+pub const EMPTY_GRAPH_MESSAGE: &str =
+	"No data for any line. Check the timestamp format, guards and patterns.";
+
+/// The backend used to render the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum Backend {
+	/// Static image rendered by `gnuplot`.
+	#[strum(serialize = "gnuplot")]
+	Gnuplot,
+	/// Interactive self-contained html page.
+	#[strum(serialize = "plotly")]
+	Plotly,
+}
+
+impl Backend {
+	/// Selects the backend based on the `--plotly-backend` CLI flag.
+	pub fn from_plotly_flag(plotly_backend: bool) -> Self {
+		if plotly_backend { Self::Plotly } else { Self::Gnuplot }
+	}
+
+	/// Tells if the backend was compiled in.
+	pub fn is_enabled(&self) -> bool {
+		match self {
+			Self::Gnuplot => cfg!(feature = "gnuplot"),
+			Self::Plotly => cfg!(feature = "plotly"),
+		}
+	}
+
+	/// Name of the cargo feature enabling the backend.
+	pub fn feature_name(&self) -> &'static str {
+		match self {
+			Self::Gnuplot => "gnuplot",
+			Self::Plotly => "plotly",
+		}
+	}
+}
+
+/// Controls how the rendered output is presented to the user.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayOptions {
+	/// Print absolute paths of generated files.
+	pub display_absolute_paths: bool,
+	/// Do not open the generated file in the image viewer (or browser).
+	pub do_not_display: bool,
+}
+
+/// Renders the graph with the backend selected in the context.
+///
+/// If all lines are empty, the placeholder graph containing [`EMPTY_GRAPH_MESSAGE`] is rendered.
+///
+/// With `--no-clobber`, [`Error::OutputFileExists`] is returned if any of the output files exists.
+#[cfg_attr(not(all(feature = "gnuplot", feature = "plotly")), allow(unused_variables))]
+pub fn render_graph(config: &ResolvedGraphConfig, context: &GraphFullContext) -> Result<(), Error> {
+	if context.no_clobber() {
+		let paths = context.get_graph_output_path();
+		if let Some(existing) = paths.files().into_iter().find(|f| f.exists()) {
+			return Err(Error::OutputFileExists(existing.to_path_buf()));
+		}
+	}
+	match context.backend() {
+		#[cfg(feature = "gnuplot")]
+		Backend::Gnuplot => Ok(crate::gnuplot::run_gnuplot(config, context)?),
+		#[cfg(feature = "plotly")]
+		Backend::Plotly => Ok(crate::plotly_backend::write_plotly_html(config, context)?),
+		#[allow(unreachable_patterns)]
+		backend => Err(Error::BackendNotEnabled(backend)),
+	}
+}
+
+/// Renders the histogram of given buckets with the selected backend.
+///
+/// If `output` is not given, `hist.png` (or `hist.html` for plotly) is used.
+#[cfg_attr(not(all(feature = "gnuplot", feature = "plotly")), allow(unused_variables))]
+pub fn render_histogram(
+	backend: Backend,
+	buckets: &[HistoBucket],
+	title: &str,
+	cdf: bool,
+	log_scale_x: bool,
+	output: Option<PathBuf>,
+	display: DisplayOptions,
+) -> Result<(), Error> {
+	match backend {
+		#[cfg(feature = "gnuplot")]
+		Backend::Gnuplot => {
+			let image_path = output.unwrap_or_else(|| PathBuf::from("hist.png"));
+			let script_path = image_path.with_extension("gnuplot");
+			crate::gnuplot::write_histogram_script(
+				buckets,
+				title,
+				cdf,
+				log_scale_x,
+				&script_path,
+				&image_path,
+			)?;
+			crate::gnuplot::execute_gnuplot_script(
+				script_path,
+				image_path,
+				display.display_absolute_paths,
+				display.do_not_display,
+				std::path::Path::new(crate::graph_config::DEFAULT_GNUPLOT_BIN),
+				None,
+			)?;
+			Ok(())
+		},
+		#[cfg(feature = "plotly")]
+		Backend::Plotly => {
+			let html_path = output.unwrap_or_else(|| PathBuf::from("hist.html"));
+			crate::plotly_backend::write_histogram_html(buckets, title, cdf, &html_path)?;
+			crate::plotly_backend::display_html(html_path, display.do_not_display);
+			Ok(())
+		},
+		#[allow(unreachable_patterns)]
+		backend => Err(Error::BackendNotEnabled(backend)),
+	}
+}
+
+/// Renders the report sections into a single html page (see [`crate::report`]).
+///
+/// Reports are only supported by the plotly backend.
+#[cfg_attr(not(feature = "plotly"), allow(unused_variables))]
+pub fn render_report(
+	title: &str,
+	sections: &[ReportSection],
+	html_path: PathBuf,
+	do_not_display: bool,
+) -> Result<(), Error> {
+	#[cfg(feature = "plotly")]
+	{
+		crate::plotly_backend::write_report_html(title, sections, &html_path)?;
+		crate::plotly_backend::display_html(html_path, do_not_display);
+		Ok(())
+	}
+	#[cfg(not(feature = "plotly"))]
+	Err(Error::BackendNotEnabled(Backend::Plotly))
+}
+
+/// Renders the sample image of all predefined styles into `image_path` (see `plox styles`).
+///
+/// Samples are only rendered by the gnuplot backend.
+#[cfg_attr(not(feature = "gnuplot"), allow(unused_variables))]
+pub fn render_styles_sample(image_path: PathBuf, do_not_display: bool) -> Result<(), Error> {
+	#[cfg(feature = "gnuplot")]
+	{
+		let script_path = image_path.with_extension("gnuplot");
+		crate::gnuplot::write_styles_script(&script_path, &image_path)?;
+		crate::gnuplot::execute_gnuplot_script(
+			script_path,
+			image_path,
+			false,
+			do_not_display,
+			std::path::Path::new(crate::graph_config::DEFAULT_GNUPLOT_BIN),
+			None,
+		)?;
+		Ok(())
+	}
+	#[cfg(not(feature = "gnuplot"))]
+	Err(Error::BackendNotEnabled(Backend::Gnuplot))
+}
+
+/// Renders the scatter plot of given `(x, y)` pairs into `image_path`.
+///
+/// Scatter plots are only supported by the gnuplot backend.
+#[cfg_attr(not(feature = "gnuplot"), allow(unused_variables))]
+pub fn render_scatter(
+	pairs: &[(f64, f64)],
+	title: &str,
+	labels: (&str, &str),
+	image_path: PathBuf,
+) -> Result<(), Error> {
+	#[cfg(feature = "gnuplot")]
+	{
+		let script_path = image_path.with_extension("gnuplot");
+		crate::gnuplot::write_scatter_script(pairs, title, labels, &script_path, &image_path)?;
+		crate::gnuplot::execute_gnuplot_script(
+			script_path,
+			image_path,
+			false,
+			false,
+			std::path::Path::new(crate::graph_config::DEFAULT_GNUPLOT_BIN),
+			None,
+		)?;
+		Ok(())
+	}
+	#[cfg(not(feature = "gnuplot"))]
+	Err(Error::BackendNotEnabled(Backend::Gnuplot))
+}
@@ -0,0 +1,185 @@
+//! Conformance of the backends.
+//!
+//! The same resolved config is rendered with the gnuplot backend (script text) and the plotly
+//! backend (JSON of the panels), and the style of every line and the layout of every panel is
+//! checked in both outputs, so the backends do not drift apart.
+
+use crate::{
+	align_ranges, gnuplot, graph_cli_builder, plotly_backend, process_log,
+	resolved_graph_config::expand_graph_config_with_ctx,
+};
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+
+/// Outputs of both backends for the same config.
+struct Rendered {
+	/// The gnuplot script.
+	script: String,
+	/// Panels of the plotly page, with the JSON strings of the template input parsed.
+	panels: Vec<Value>,
+}
+
+impl Rendered {
+	/// Plot entry of the gnuplot script drawing the line with the given title (or its prefix).
+	fn gnuplot_entry(&self, title: &str) -> &str {
+		let title = format!(" title '{title}");
+		self.script.lines().find(|line| line.contains(&title)).expect(&self.script)
+	}
+
+	/// Plotly trace of the line with the given title (or its prefix).
+	fn plotly_trace(&self, title: &str) -> &Value {
+		self.panels
+			.iter()
+			.flat_map(|panel| panel["traces_json"].as_array().expect("traces are array"))
+			.find(|trace| trace["name"].as_str().is_some_and(|name| name.starts_with(title)))
+			.unwrap_or_else(|| panic!("no trace '{title}': {:#?}", self.panels))
+	}
+}
+
+/// Processes the `input` files with the given graph arguments, and renders the graph with both
+/// backends. Intermediate files are written to `dir`.
+fn render(input: &'static str, dir: &'static str, args: Vec<&'static str>) -> Rendered {
+	let args = ["--input", input, "--cache-dir", dir].into_iter().chain(args);
+	let (config, context) = graph_cli_builder::build_from_cli_args(args.collect()).unwrap();
+	let mut config = expand_graph_config_with_ctx(&config, &context).unwrap();
+	process_log::process_inputs(&mut config, &context.input_files_ctx).unwrap();
+	align_ranges::resolve_panels_ranges(&mut config, &context).unwrap();
+	if context.shared_yrange() {
+		process_log::resolve_shared_y_ranges(&mut config).unwrap();
+	}
+
+	let script_path = Path::new(dir).join("graph.gnuplot");
+	gnuplot::write_gnuplot_script(&config, &context, &script_path, None).unwrap();
+	let script = fs::read_to_string(&script_path).unwrap();
+
+	let panels = plotly_backend::build_panels(&config, &context, "plot")
+		.unwrap()
+		.iter()
+		.map(|panel| {
+			let mut panel = serde_json::to_value(panel).unwrap();
+			for (key, value) in panel.as_object_mut().unwrap() {
+				if key.ends_with("_json") {
+					*value = serde_json::from_str(value.as_str().unwrap()).unwrap();
+				}
+			}
+			panel
+		})
+		.collect();
+	Rendered { script, panels }
+}
+
+#[test]
+fn test_steps_parity() {
+	let rendered = render(
+		"tests/examples/default.log",
+		"tests/.output/conformance-steps",
+		vec!["--plot", "om_module", "x", "--title", "steps", "--style", "steps"]
+			.into_iter()
+			.chain(["--dash-style", "dashed", "--line-width", "4", "--line-color", "red"])
+			.collect(),
+	);
+	let entry = rendered.gnuplot_entry("steps");
+	assert!(entry.contains(r#"with steps dt 2 lw 4 lc rgb "red" axes x1y1"#), "{entry}");
+	assert!(!entry.contains(" pt ") && !entry.contains(" ps "), "{entry}");
+
+	let trace = rendered.plotly_trace("steps");
+	assert_eq!(trace["mode"], "lines");
+	assert_eq!(
+		trace["line"],
+		json!({ "width": 2.0, "color": "red", "dash": "dash", "shape": "hv" })
+	);
+	assert_eq!(trace["marker"], Value::Null);
+	assert_eq!(trace["yaxis"], "y");
+}
+
+#[test]
+fn test_marker_only_parity() {
+	let rendered = render(
+		"tests/examples/default.log",
+		"tests/.output/conformance-markers",
+		vec!["--plot", "om_module", "x", "--title", "markers", "--style", "points"]
+			.into_iter()
+			.chain(["--marker-type", "square", "--marker-size", "3", "--marker-color", "blue"])
+			.chain(["--plot", "x_module", "x00", "--title", "line-color", "--style", "points"])
+			.chain(["--line-color", "green"])
+			.collect(),
+	);
+	let entry = rendered.gnuplot_entry("markers");
+	assert!(entry.contains(r#"with points pt 4 ps 3 lc rgb "blue" axes x1y1"#), "{entry}");
+	let trace = rendered.plotly_trace("markers");
+	assert_eq!(trace["mode"], "markers");
+	assert_eq!(trace["marker"], json!({ "symbol": "square", "size": 3, "color": "blue" }));
+
+	// Markers take the color of the line.
+	let entry = rendered.gnuplot_entry("line-color");
+	assert!(entry.contains(r#"with points lc rgb "green" ps "#), "{entry}");
+	let trace = rendered.plotly_trace("line-color");
+	assert_eq!(trace["mode"], "markers");
+	assert_eq!(trace["marker"]["color"], "green");
+}
+
+#[test]
+fn test_y2_and_log_scale_parity() {
+	let rendered = render(
+		"tests/examples/default.log",
+		"tests/.output/conformance-y2",
+		vec!["--yaxis-scale", "log", "--y2axis-scale", "log", "--y2range", "1,1000"]
+			.into_iter()
+			.chain(["--plot", "om_module", "x", "--title", "left"])
+			.chain(["--plot", "x_module", "x00", "--title", "right", "--yaxis", "y2"])
+			.collect(),
+	);
+	assert!(rendered.gnuplot_entry("left").contains("axes x1y1"));
+	assert!(rendered.gnuplot_entry("right").contains("axes x1y2"));
+	assert_eq!(rendered.plotly_trace("left")["yaxis"], "y");
+	assert_eq!(rendered.plotly_trace("right")["yaxis"], "y2");
+
+	let script = &rendered.script;
+	assert!(script.contains("set logscale y 10\n"), "{script}");
+	assert!(script.contains("set logscale y2 10\n"), "{script}");
+	assert!(script.contains("set y2range [1:1000]\n"), "{script}");
+	let panel = &rendered.panels[0];
+	assert_eq!(panel["yaxis_scale"], "log");
+	assert_eq!(panel["yaxis2_json"]["type"], "log");
+	// Range of the log axis is given in log10 units.
+	assert_eq!(panel["yaxis2_json"]["range"], json!([0.0, 3.0]));
+	assert_eq!(panel["yaxis2_json"]["autorange"], false);
+}
+
+#[test]
+fn test_ranges_parity() {
+	let rendered = render(
+		"tests/examples/default.log,tests/examples/default-other.log",
+		"tests/.output/conformance-ranges",
+		vec!["--time-range", "0.25,0.75", "--per-file-panels", "--shared-yrange"]
+			.into_iter()
+			.chain(["--plot", "x_module", "x00"])
+			.collect(),
+	);
+	let script = &rendered.script;
+	let xranges = script
+		.lines()
+		.filter(|line| line.starts_with("set xrange "))
+		.collect::<Vec<_>>();
+	let yranges = script
+		.lines()
+		.filter(|line| line.starts_with("set yrange [") && !line.contains("*:*"))
+		.collect::<Vec<_>>();
+	assert_eq!(xranges.len(), 2, "{script}");
+	assert_eq!(yranges.len(), 2, "{script}");
+
+	// Panels are drawn bottom-up by gnuplot.
+	for (panel, (xrange, yrange)) in rendered.panels.iter().rev().zip(xranges.iter().zip(yranges)) {
+		let [start, end] = [0, 1].map(|i| {
+			panel["xaxis_json"]["range"][i]
+				.as_str()
+				.expect("time range is set")
+				.replace(' ', "T")
+		});
+		// Time format of the gnuplot script has no fractional seconds.
+		assert_eq!(*xrange, format!("set xrange [\"{}\":\"{}\"]", &start[..19], &end[..19]));
+
+		let [min, max] = [0, 1].map(|i| panel["yaxis_json"]["range"][i].as_f64().unwrap());
+		assert_eq!(*yrange, format!("set yrange [{min}:{max}]"));
+	}
+}
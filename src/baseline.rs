@@ -0,0 +1,232 @@
+//! Subtraction of the reference series from the values of the line (see
+//! [`crate::graph_config::LineParams::baseline`]).
+//!
+//! This is the transform stage between the log processing and the backends: the CSV file of the
+//! line is rewritten into a new one, holding the difference between the plotted values of the line
+//! and the values of the reference line interpolated at the same time. Relative regressions
+//! between two runs or two nodes are then visible directly.
+
+use crate::{
+	logging::APPV,
+	process_log::{parse_record_timestamp, record_derived_csvs},
+	resolved_graph_config::{ResolvedGraphConfig, ResolvedLine, UnresolvedCsvFileError},
+};
+use chrono::NaiveDateTime;
+use std::{
+	hash::{DefaultHasher, Hash, Hasher},
+	path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("CSV error while accessing file '{0}': {1}")]
+	CsvError(PathBuf, csv::Error),
+	#[error("Malformed record in CSV file '{0}' (this is bug)")]
+	MalformedRecord(PathBuf),
+	#[error("Baseline '{baseline}' of line '{line}' does not match the title of any other line.")]
+	UnknownBaseline { line: String, baseline: String },
+	#[error(transparent)]
+	UnresolvedCsvFile(#[from] UnresolvedCsvFileError),
+}
+
+/// Plotted values of the line, sorted by time.
+fn read_series(line: &ResolvedLine) -> Result<Vec<(NaiveDateTime, f64)>, Error> {
+	let path = line.resolved_csv_filename()?;
+	let mut rdr = csv::Reader::from_path(&path).map_err(|e| Error::CsvError(path.clone(), e))?;
+	let column = value_column_index(&mut rdr, &path, line.csv_data_column_for_plot())?;
+	let mut series = vec![];
+	for record in rdr.records() {
+		let record = record.map_err(|e| Error::CsvError(path.clone(), e))?;
+		let timestamp = record_timestamp(&record, &path)?;
+		if let Some(value) = record.get(column).and_then(|v| v.parse::<f64>().ok()) {
+			series.push((timestamp, value));
+		}
+	}
+	series.sort_by_key(|(timestamp, _)| *timestamp);
+	Ok(series)
+}
+
+/// Index of the `column` in the CSV file read by `rdr`.
+pub(crate) fn value_column_index<R: std::io::Read>(
+	rdr: &mut csv::Reader<R>,
+	path: &Path,
+	column: &str,
+) -> Result<usize, Error> {
+	let headers = rdr.headers().map_err(|e| Error::CsvError(path.to_path_buf(), e))?;
+	headers
+		.iter()
+		.position(|h| h == column)
+		.ok_or_else(|| Error::MalformedRecord(path.into()))
+}
+
+fn record_timestamp(record: &csv::StringRecord, path: &Path) -> Result<NaiveDateTime, Error> {
+	record
+		.get(0)
+		.zip(record.get(1))
+		.and_then(|(date, time)| parse_record_timestamp(date, time))
+		.ok_or_else(|| Error::MalformedRecord(path.into()))
+}
+
+/// Value of the series at the given time, linearly interpolated between the neighbouring values.
+///
+/// `None` is returned outside of the time range of the series.
+fn interpolate(series: &[(NaiveDateTime, f64)], timestamp: NaiveDateTime) -> Option<f64> {
+	let i = series.partition_point(|(t, _)| *t < timestamp);
+	let (t1, v1) = series.get(i)?;
+	if *t1 == timestamp {
+		return Some(*v1);
+	}
+	let (t0, v0) = series.get(i.checked_sub(1)?)?;
+	let ratio =
+		(timestamp - *t0).num_microseconds()? as f64 / (*t1 - *t0).num_microseconds()? as f64;
+	Some(v0 + (v1 - v0) * ratio)
+}
+
+/// Finds the reference line of `line` by its title (displayed or configured one).
+///
+/// If many lines match, the one drawn for the same input file is preferred.
+fn find_baseline<'a>(
+	config: &'a ResolvedGraphConfig,
+	line: &ResolvedLine,
+	baseline: &str,
+	has_multiple_input_files: bool,
+) -> Option<&'a ResolvedLine> {
+	let candidates = config
+		.all_lines()
+		.filter(|other| !std::ptr::eq(*other, line))
+		.filter(|other| {
+			other.title(has_multiple_input_files) == baseline
+				|| other.line.params.title.as_deref() == Some(baseline)
+		})
+		.collect::<Vec<_>>();
+	candidates
+		.iter()
+		.find(|other| other.source_file_name() == line.source_file_name())
+		.or(candidates.first())
+		.copied()
+}
+
+/// CSV file holding the difference between the line and its baseline.
+struct Difference {
+	path: PathBuf,
+	data_points_count: usize,
+	time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+}
+
+/// Writes the difference between the values of the line and the baseline into a new CSV file.
+fn write_difference(
+	line: &ResolvedLine,
+	baseline: &[(NaiveDateTime, f64)],
+	baseline_path: &Path,
+) -> Result<Difference, Error> {
+	let path = line.resolved_csv_filename()?;
+	let mut hasher = DefaultHasher::new();
+	baseline_path.hash(&mut hasher);
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	let output_path = path.with_file_name(format!("{stem}.baseline-{:016x}.csv", hasher.finish()));
+
+	let mut rdr = csv::Reader::from_path(&path).map_err(|e| Error::CsvError(path.clone(), e))?;
+	let column = value_column_index(&mut rdr, &path, line.csv_data_column_for_plot())?;
+	let mut wtr = csv::Writer::from_path(&output_path)
+		.map_err(|e| Error::CsvError(output_path.clone(), e))?;
+	let headers = rdr.headers().map_err(|e| Error::CsvError(path.clone(), e))?.clone();
+	wtr.write_record(&headers)
+		.map_err(|e| Error::CsvError(output_path.clone(), e))?;
+
+	let mut count = 0;
+	let mut range: Option<(NaiveDateTime, NaiveDateTime)> = None;
+	for record in rdr.records() {
+		let record = record.map_err(|e| Error::CsvError(path.clone(), e))?;
+		let timestamp = record_timestamp(&record, &path)?;
+		let Some(value) = record.get(column).and_then(|v| v.parse::<f64>().ok()) else {
+			continue;
+		};
+		let Some(reference) = interpolate(baseline, timestamp) else {
+			continue;
+		};
+		let difference = (value - reference).to_string();
+		let record = record
+			.iter()
+			.enumerate()
+			.map(|(i, field)| if i == column { difference.as_str() } else { field });
+		wtr.write_record(record).map_err(|e| Error::CsvError(output_path.clone(), e))?;
+		count += 1;
+		range = Some(range.map_or((timestamp, timestamp), |(start, end)| {
+			(start.min(timestamp), end.max(timestamp))
+		}));
+	}
+	wtr.flush().map_err(|e| Error::CsvError(output_path.clone(), e.into()))?;
+	record_derived_csvs(&[(output_path.clone(), (count, range))]);
+	Ok(Difference { path: output_path, data_points_count: count, time_range: range })
+}
+
+/// Subtracts the baseline from the values of every line with the baseline configured.
+///
+/// Must be called after [`crate::process_log::process_inputs`], and before the time ranges are
+/// resolved. Baselines are always read from the original CSV files, so the baseline line can have
+/// its own baseline too.
+pub fn subtract_baselines(
+	config: &mut ResolvedGraphConfig,
+	has_multiple_input_files: bool,
+) -> Result<(), Error> {
+	let mut differences = vec![];
+	for (panel_index, panel) in config.panels.iter().enumerate() {
+		for (line_index, line) in panel.lines.iter().enumerate() {
+			let Some(baseline) = line.line.params.baseline.as_deref() else {
+				continue;
+			};
+			let reference = find_baseline(config, line, baseline, has_multiple_input_files)
+				.ok_or_else(|| Error::UnknownBaseline {
+					line: line.title(has_multiple_input_files),
+					baseline: baseline.to_string(),
+				})?;
+			if line.is_empty() {
+				continue;
+			}
+			let series = if reference.is_empty() { vec![] } else { read_series(reference)? };
+			let difference = write_difference(line, &series, &reference.resolved_csv_filename()?)?;
+			if difference.data_points_count == 0 {
+				warn!(target:APPV, line = line.title(has_multiple_input_files), baseline,
+					"No values overlapping with the baseline in time.");
+			}
+			differences.push((panel_index, line_index, difference));
+		}
+	}
+
+	for (panel_index, line_index, difference) in differences {
+		let Difference { path, data_points_count, time_range } = difference;
+		let line = &mut config.panels[panel_index].lines[line_index];
+		debug!(target:APPV, "Baseline subtracted, {data_points_count} values written to {}", path.display());
+		line.set_shared_csv_filename(&path);
+		line.set_data_points_count(data_points_count);
+		if let Some((start, end)) = time_range {
+			line.set_time_range(start, end);
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ts(s: u32) -> NaiveDateTime {
+		chrono::NaiveDate::from_ymd_opt(2025, 1, 1)
+			.unwrap()
+			.and_hms_opt(0, 0, s)
+			.unwrap()
+	}
+
+	#[test]
+	fn test_interpolate() {
+		let series = [(ts(10), 1.0), (ts(20), 3.0), (ts(30), 2.0)];
+		assert_eq!(interpolate(&series, ts(10)), Some(1.0));
+		assert_eq!(interpolate(&series, ts(15)), Some(2.0));
+		assert_eq!(interpolate(&series, ts(25)), Some(2.5));
+		assert_eq!(interpolate(&series, ts(30)), Some(2.0));
+		assert_eq!(interpolate(&series, ts(5)), None);
+		assert_eq!(interpolate(&series, ts(31)), None);
+		assert_eq!(interpolate(&[], ts(31)), None);
+	}
+}
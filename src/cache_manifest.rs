@@ -0,0 +1,341 @@
+//! Manifest of the CSV cache directory.
+//!
+//! The manifest (`manifest.toml` stored next to the CSV files) records the number of records and
+//! the time range of every CSV file written to the cache directory. This allows to resolve the data
+//! points count and line time ranges for cache hits without re-reading the CSV files.
+//!
+//! The manifest is only an optimization: entries are validated against the size and modification
+//! time of the CSV file, and missing, stale or unreadable entries simply fall back to reading the
+//! CSV file.
+//!
+//! The manifest also records when every CSV file was last used, so the least recently used files
+//! can be deleted when the cache exceeds `--cache-max-size` (see [`prune_cache`]). This includes
+//! the CSV files derived from the cached ones (see [`record_derived_csvs`]).
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, BTreeSet, HashSet},
+	fs,
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+const LOG_TARGET: &str = "cache_manifest";
+
+/// Name of the manifest file within the cache directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.toml";
+
+/// Format of timestamps stored in the manifest.
+const MANIFEST_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// Records count and time range of the CSV file.
+pub(crate) type CsvSummary = (usize, Option<(NaiveDateTime, NaiveDateTime)>);
+
+/// Summary of a single CSV file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+	/// Size of the CSV file (in bytes) the summary was computed for.
+	size: u64,
+	/// Modification time of the CSV file (milliseconds since UNIX epoch).
+	mtime: u64,
+	/// Number of records.
+	count: usize,
+	/// Timestamp of the first record.
+	start: Option<String>,
+	/// Timestamp of the last record.
+	end: Option<String>,
+	/// Time the CSV file was last written or used (milliseconds since UNIX epoch).
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	accessed: Option<u64>,
+}
+
+/// The manifest of a single cache directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct CacheManifest {
+	#[serde(skip)]
+	path: PathBuf,
+	/// Entries indexed by the CSV file name.
+	#[serde(default)]
+	files: BTreeMap<String, ManifestEntry>,
+	#[serde(skip)]
+	modified: bool,
+}
+
+impl CacheManifest {
+	/// Loads the manifest of given cache directory. Missing or malformed manifest results in an
+	/// empty one.
+	pub fn load(cache_dir: &Path) -> Self {
+		let path = cache_dir.join(MANIFEST_FILE_NAME);
+		let manifest = fs::read_to_string(&path).ok().and_then(|content| {
+			toml::from_str::<Self>(&content)
+				.inspect_err(
+					|e| debug!(target: LOG_TARGET, ?path, "Ignoring malformed manifest: {e}"),
+				)
+				.ok()
+		});
+		Self { path, ..manifest.unwrap_or_default() }
+	}
+
+	/// Returns the summary of the CSV file, if it is recorded and the file was not changed since.
+	pub fn summary(&self, csv_file: &Path) -> Option<CsvSummary> {
+		let entry = self.files.get(&Self::key(csv_file)?)?;
+		let metadata = fs::metadata(csv_file).ok()?;
+		if metadata.len() != entry.size || Self::mtime(&metadata) != Some(entry.mtime) {
+			debug!(target: LOG_TARGET, ?csv_file, "Stale manifest entry");
+			return None;
+		}
+		let parse = |ts: &Option<String>| {
+			ts.as_ref()
+				.and_then(|ts| NaiveDateTime::parse_from_str(ts, MANIFEST_TIMESTAMP_FORMAT).ok())
+		};
+		let range = match (parse(&entry.start), parse(&entry.end)) {
+			(Some(start), Some(end)) => Some((start, end)),
+			(None, None) if entry.count == 0 => None,
+			_ => return None,
+		};
+		Some((entry.count, range))
+	}
+
+	/// Records the summary of the (freshly written) CSV file.
+	pub fn insert(&mut self, csv_file: &Path, (count, range): CsvSummary) {
+		let Some(key) = Self::key(csv_file) else { return };
+		let Some((metadata, mtime)) =
+			fs::metadata(csv_file).ok().and_then(|m| Self::mtime(&m).map(|t| (m, t)))
+		else {
+			return;
+		};
+		let format = |ts: NaiveDateTime| ts.format(MANIFEST_TIMESTAMP_FORMAT).to_string();
+		let entry = ManifestEntry {
+			size: metadata.len(),
+			mtime,
+			count,
+			start: range.map(|r| format(r.0)),
+			end: range.map(|r| format(r.1)),
+			accessed: Self::now(),
+		};
+		self.files.insert(key, entry);
+		self.modified = true;
+	}
+
+	/// Records the use of the cached CSV file.
+	pub fn touch(&mut self, csv_file: &Path) {
+		let Some(entry) = Self::key(csv_file).and_then(|key| self.files.get_mut(&key)) else {
+			return;
+		};
+		entry.accessed = Self::now();
+		self.modified = true;
+	}
+
+	/// Writes the manifest, if it was modified. Failures are only reported as warnings.
+	pub fn save(&self) {
+		if !self.modified {
+			return;
+		}
+		let result = toml::to_string(self)
+			.map_err(|e| e.to_string())
+			.and_then(|content| fs::write(&self.path, content).map_err(|e| e.to_string()));
+		if let Err(e) = result {
+			warn!(target: LOG_TARGET, "Could not write cache manifest {}: {e}", self.path.display());
+		}
+	}
+
+	fn now() -> Option<u64> {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+		u64::try_from(now.as_millis()).ok()
+	}
+
+	fn mtime(metadata: &fs::Metadata) -> Option<u64> {
+		let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+		u64::try_from(mtime.as_millis()).ok()
+	}
+
+	fn key(csv_file: &Path) -> Option<String> {
+		csv_file.file_name().map(|f| f.to_string_lossy().into_owned())
+	}
+}
+
+/// Outcome of [`prune_cache`].
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PruneStats {
+	/// Number of deleted CSV files.
+	pub removed: usize,
+	/// Total size of deleted CSV files (in bytes).
+	pub freed: u64,
+	/// Total size of CSV files left in the cache (in bytes).
+	pub remaining: u64,
+}
+
+/// Deletes the least recently used CSV files of the cache until their total size does not exceed
+/// `max_size`.
+///
+/// All cache directories (holding the manifest) found within `dirs` are considered, and only the
+/// CSV files recorded in the manifests are ever deleted. The last use of a file is taken from the
+/// manifest, or from its modification time if the use was not recorded. Files in `in_use` are kept
+/// even if the limit cannot be met.
+pub(crate) fn prune_cache<'a>(
+	dirs: impl IntoIterator<Item = &'a Path>,
+	max_size: u64,
+	in_use: &HashSet<PathBuf>,
+) -> PruneStats {
+	let mut cache_dirs = BTreeSet::new();
+	dirs.into_iter().for_each(|dir| find_cache_dirs(dir, &mut cache_dirs));
+	let in_use = in_use.iter().filter_map(|f| f.canonicalize().ok()).collect::<HashSet<_>>();
+
+	let mut manifests = cache_dirs.iter().map(|dir| CacheManifest::load(dir)).collect::<Vec<_>>();
+	// (last use, size, index of manifest, CSV file)
+	let mut files = vec![];
+	let mut stats = PruneStats::default();
+	for (index, (dir, manifest)) in cache_dirs.iter().zip(&mut manifests).enumerate() {
+		let count = manifest.files.len();
+		manifest.files.retain(|name, _| dir.join(name).is_file());
+		manifest.modified |= manifest.files.len() != count;
+		for (name, entry) in &manifest.files {
+			let csv_file = dir.join(name);
+			let Ok(metadata) = fs::metadata(&csv_file) else { continue };
+			let accessed = entry.accessed.or_else(|| CacheManifest::mtime(&metadata));
+			stats.remaining += metadata.len();
+			files.push((accessed.unwrap_or_default(), metadata.len(), index, csv_file));
+		}
+	}
+	files.sort();
+
+	for (_, size, index, csv_file) in files {
+		if stats.remaining <= max_size {
+			break;
+		}
+		if csv_file.canonicalize().is_ok_and(|f| in_use.contains(&f)) {
+			continue;
+		}
+		if let Err(e) = fs::remove_file(&csv_file) {
+			warn!(target: LOG_TARGET, "Could not delete cache file {}: {e}", csv_file.display());
+			continue;
+		}
+		debug!(target: LOG_TARGET, ?csv_file, "Pruned from cache");
+		if let Some(key) = CacheManifest::key(&csv_file) {
+			manifests[index].files.remove(&key);
+			manifests[index].modified = true;
+		}
+		stats.removed += 1;
+		stats.freed += size;
+		stats.remaining -= size;
+	}
+
+	manifests.iter().for_each(CacheManifest::save);
+	stats
+}
+
+/// Records the summaries of the CSV files derived from the cached CSV files (e.g. transformed values
+/// or folded periods), so they are pruned along with other cached files.
+///
+/// Derived files written outside of the cache directories (not holding the manifest) are not
+/// recorded.
+pub(crate) fn record_derived_csvs<'a>(csv_files: impl IntoIterator<Item = (&'a Path, CsvSummary)>) {
+	let mut manifests: BTreeMap<&Path, CacheManifest> = BTreeMap::new();
+	for (csv_file, summary) in csv_files {
+		let Some(dir) = csv_file.parent() else { continue };
+		if !dir.join(MANIFEST_FILE_NAME).is_file() {
+			continue;
+		}
+		manifests
+			.entry(dir)
+			.or_insert_with(|| CacheManifest::load(dir))
+			.insert(csv_file, summary);
+	}
+	manifests.values().for_each(CacheManifest::save);
+}
+
+/// Collects the directories holding the manifest within `dir` (including `dir` itself). Symbolic
+/// links are not followed.
+fn find_cache_dirs(dir: &Path, cache_dirs: &mut BTreeSet<PathBuf>) {
+	let Ok(entries) = fs::read_dir(dir) else { return };
+	if dir.join(MANIFEST_FILE_NAME).is_file() {
+		cache_dirs.insert(dir.to_path_buf());
+	}
+	for entry in entries.flatten() {
+		if entry.file_type().is_ok_and(|t| t.is_dir()) {
+			find_cache_dirs(&entry.path(), cache_dirs);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::NaiveDate;
+
+	#[test]
+	fn test_manifest_roundtrip() {
+		let dir = std::env::temp_dir().join(format!("plox-manifest-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let csv = dir.join("a.csv");
+		let empty_csv = dir.join("b.csv");
+		fs::write(&csv, "date,time,value\n2025-01-01,10:00:00.000,1\n").unwrap();
+		fs::write(&empty_csv, "date,time,value\n").unwrap();
+
+		let ts = |s| NaiveDate::from_ymd_opt(2025, 1, 1).unwrap().and_hms_milli_opt(10, 0, s, 5);
+		let summary = (2, Some((ts(0).unwrap(), ts(7).unwrap())));
+
+		let mut manifest = CacheManifest::load(&dir);
+		assert_eq!(manifest.summary(&csv), None);
+		manifest.insert(&csv, summary);
+		manifest.insert(&empty_csv, (0, None));
+		manifest.save();
+
+		let manifest = CacheManifest::load(&dir);
+		assert_eq!(manifest.summary(&csv), Some(summary));
+		assert_eq!(manifest.summary(&empty_csv), Some((0, None)));
+
+		// Regenerated file invalidates the entry.
+		fs::write(&csv, "date,time,value\n").unwrap();
+		assert_eq!(manifest.summary(&csv), None);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_prune_cache() {
+		let root = std::env::temp_dir().join(format!("plox-prune-{}", std::process::id()));
+		let record = |dir: &str, name: &str, accessed: u64| {
+			let dir = root.join(dir);
+			fs::create_dir_all(&dir).unwrap();
+			let csv = dir.join(name);
+			fs::write(&csv, "x".repeat(100)).unwrap();
+			let mut manifest = CacheManifest::load(&dir);
+			manifest.insert(&csv, (0, None));
+			manifest.files.get_mut(name).unwrap().accessed = Some(accessed);
+			manifest.save();
+			csv
+		};
+		let x = record("a", "x.csv", 1);
+		let y = record("a", "y.csv", 3);
+		let z = record("b/c", "z.csv", 2);
+		let untracked = root.join("b/c/untracked.csv");
+		fs::write(&untracked, "x".repeat(500)).unwrap();
+
+		let in_use = HashSet::from([x.clone()]);
+		let stats = prune_cache([root.as_path()], 150, &in_use);
+		assert_eq!(stats, PruneStats { removed: 2, freed: 200, remaining: 100 });
+		assert!(x.exists() && untracked.exists());
+		assert!(!y.exists() && !z.exists());
+		assert!(CacheManifest::load(&root.join("b/c")).files.is_empty());
+		assert_eq!(CacheManifest::load(&root.join("a")).files.len(), 1);
+
+		// Nothing is deleted within the limit.
+		assert_eq!(prune_cache([root.as_path()], 100, &HashSet::new()).removed, 0);
+
+		// Derived files are only recorded in cache directories.
+		let derived = root.join("a/x.transform-0.csv");
+		let outside = root.join("x.transform-0.csv");
+		fs::write(&derived, "x".repeat(100)).unwrap();
+		fs::write(&outside, "x".repeat(100)).unwrap();
+		record_derived_csvs([(derived.as_path(), (0, None)), (outside.as_path(), (0, None))]);
+		assert!(!root.join(MANIFEST_FILE_NAME).exists());
+		let stats = prune_cache([root.as_path()], 100, &in_use);
+		assert_eq!(stats, PruneStats { removed: 1, freed: 100, remaining: 100 });
+		assert!(!derived.exists() && outside.exists());
+
+		fs::remove_dir_all(&root).unwrap();
+	}
+}
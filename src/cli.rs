@@ -1,8 +1,19 @@
 //! This tiny module defines the overall command-line interface for plox.
 //! It sets up the top-level argument parser, wires in the subcommands, and handles user input.
 
-use crate::graph_config::{DataSource, EventDeltaSpec, FieldCaptureSpec, InputFilesContext};
+use crate::{
+	error::ErrorFormat,
+	graph_config::{
+		DataSource, EventDeltaSpec, FieldCaptureSpec, InputFilesContext, MatchedText, TimeRangeArg,
+	},
+	logging::MessageFormat,
+	process_log::{
+		CatColumn, CatFormat, CorrelationAlignment, DEFAULT_PERCENTILES, DEFAULT_STAT_METRICS,
+		StatAggregation, StatMetric, TopOrder,
+	},
+};
 use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -20,6 +31,28 @@ pub struct Cli {
 	#[arg(short = 'q', long, action = clap::ArgAction::SetTrue, default_value_t = false)]
 	pub quiet: bool,
 
+	/// Format of the reported errors.
+	///
+	/// With `json` a failure is reported as a single JSON object printed to stderr, containing
+	/// a stable error `code` and a human readable `message`.
+	#[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+	pub error_format: ErrorFormat,
+
+	/// Format of the messages reported while running.
+	///
+	/// With `json` (meant for IDE and CI integrations) every message is printed to stdout as
+	/// a single line JSON object with the `reason` key: `started`, `file-processed`, `cache-hit`,
+	/// `cache-miss`, `output`, `warning`, `error` and `finished`. Other messages are not printed.
+	#[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+	pub message_format: MessageFormat,
+
+	/// Write the log messages into the given file.
+	///
+	/// The file receives the messages enabled by `-v` / `-vv`, while the console is kept at the
+	/// info level. Useful for capturing the traces of long runs.
+	#[arg(long, value_name = "FILE")]
+	pub log_file: Option<PathBuf>,
+
 	#[command(subcommand)]
 	pub command: CliCommand,
 }
@@ -33,6 +66,10 @@ pub const EXTRA_HELP: &str = color_print::cstr!(
 
 Try `plox match-preview --verbose` to debug matching issues.
 
+Data source parameters can also be given by name, in any order (e.g. `--plot guard=om_module field=duration`),
+which avoids the ambiguity of the single positional value (a field, not a guard). Names are listed in the data
+source help (e.g. `<<guard>`, `<<field>`). Values are named only if all of them are given as `<<NAME>=<<VALUE>`.
+
 <bold><underline>Timestamp format:</underline></bold>
 The tool is designed to parse timestamped logs. The timestamp format used in the log file shall be passed as the `--timestamp-format` parameter.
 
@@ -58,12 +95,20 @@ If catpure group for units is not provided, no conversion is made.
 Regex pattern does not match the timestamp. Timestamp will be striped and the remainder
 for the log line will matched against regex.
 
+Patterns using lookaround or backreferences (not supported by the default regex engine) are
+matched with the slower backtracking engine.
+
+Regexes equivalent to the default pattern of a field and starting with "\b" (e.g.
+"\bduration=([\d\.]+)(\w+)?") share the CSV cache with the plain field ("duration"). Any
+other regex has its own CSV cache file.
+
 <underline>Examples</underline>:
 - "duration"                       | matches "5s" in "duration=5s"
 - "\bduration:([\d\.]+)(\w+)?"     | matches "5s" in log: "duration:5s"
 - "\bvalue:([\d\.]+)?"             | matches "75" in log: "value:75" (no units)
 - "^\s+(?:[\d\.]+\s+){3}([\d\.]+)" | matches 4th column (whitespace separated)
 - "txs=\(\d+,\s+(\d+)\)"           | matches '124' in "txs=(99,124)
+- "(?<<=peer=bob )lat=(\d+)"        | matches "7" in "peer=bob lat=7" (lookbehind)
 "#
 );
 
@@ -71,11 +116,25 @@ for the log line will matched against regex.
 pub enum CliCommand {
 	Stat(StatArgs),
 	Cat(CatArgs),
+	Hist(HistArgs),
+	Top(TopArgs),
+	Grep(GrepArgs),
+	Report(ReportArgs),
+	Styles(StylesArgs),
+	Config(ConfigArgs),
 }
 
 /// Represents the different ways a line's data can be sourced from logs in order to display some stats.
 #[derive(Clone, Debug, PartialEq, Subcommand)]
 pub enum StatDataSource {
+	/// Count occurrences of `pattern` within consecutive time intervals.
+	///
+	/// Intervals with no occurrences are also counted, which is useful for gap analysis.
+	EventCount(RawEventIntervalSpec),
+
+	/// Compute the rate (occurrences per second) of `pattern` within consecutive time intervals.
+	EventRate(RawEventIntervalSpec),
+
 	/// Extract the time delta between consecutive occurrences of `pattern`.
 	EventDelta(RawEventDeltaSpec),
 
@@ -83,6 +142,12 @@ pub enum StatDataSource {
 	///
 	/// This is the most common data source type.
 	FieldValue(RawFieldCaptureSpec),
+
+	/// Extract a value of the JSON log line by the dotted path (e.g. `fields.duration`).
+	JsonField(RawJsonFieldSpec),
+
+	/// Extract a value of the `key=value` pair of the logfmt log line.
+	Logfmt(RawLogfmtFieldSpec),
 }
 
 #[derive(Args, Debug, Clone, PartialEq)]
@@ -124,6 +189,48 @@ pub struct RawEventDeltaSpec {
 	pub inputs: Vec<String>,
 }
 
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct RawJsonFieldSpec {
+	/// [GUARD] - Optional guard on another field: `<PATH>=<VALUE>` (e.g. `level=INFO`)
+	///
+	/// <PATH> - Dotted path to the value within the JSON object (e.g. `fields.duration`).
+	///
+	/// Provide either just <PATH>, or <GUARD> <PATH>.
+	#[arg(required = true, num_args = 1..=2, value_names = ["GUARD", "PATH"])]
+	pub inputs: Vec<String>,
+}
+
+impl From<RawJsonFieldSpec> for DataSource {
+	fn from(raw: RawJsonFieldSpec) -> Self {
+		match raw.inputs.len() {
+			1 => DataSource::new_json_field(None, raw.inputs[0].clone()),
+			2 => DataSource::new_json_field(Some(raw.inputs[0].clone()), raw.inputs[1].clone()),
+			_ => panic!("clap args mess. this is bug"),
+		}
+	}
+}
+
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct RawLogfmtFieldSpec {
+	/// [GUARD] - Optional guard string to quickly filter out log lines using `strcmp`
+	///
+	/// <KEY> - The key of the value (e.g. `duration`).
+	///
+	/// Provide either just <KEY>, or <GUARD> <KEY>.
+	#[arg(required = true, num_args = 1..=2, value_names = ["GUARD", "KEY"])]
+	pub inputs: Vec<String>,
+}
+
+impl From<RawLogfmtFieldSpec> for DataSource {
+	fn from(raw: RawLogfmtFieldSpec) -> Self {
+		match raw.inputs.len() {
+			1 => DataSource::new_logfmt_field(None, raw.inputs[0].clone()),
+			2 => DataSource::new_logfmt_field(Some(raw.inputs[0].clone()), raw.inputs[1].clone()),
+			_ => panic!("clap args mess. this is bug"),
+		}
+	}
+}
+
 impl From<RawEventDeltaSpec> for EventDeltaSpec {
 	fn from(raw: RawEventDeltaSpec) -> Self {
 		match raw.inputs.len() {
@@ -137,11 +244,63 @@ impl From<RawEventDeltaSpec> for EventDeltaSpec {
 	}
 }
 
+#[derive(Args, Debug, Clone, PartialEq)]
+pub struct RawEventIntervalSpec {
+	/// [GUARD] - Optional guard string to quickly filter out log lines using `strcmp`
+	///
+	/// <PATTERN> - Substring or regex pattern to match in log lines.
+	///
+	/// Refer to "Plot Field Regex" help section for more details.
+	///
+	/// Provide either just <PATTERN>, or <GUARD> <PATTERN>.
+	#[arg(required = true, num_args = 1..=2, value_names = ["GUARD", "PATTERN"])]
+	pub inputs: Vec<String>,
+
+	/// Length of the time interval in seconds (e.g. `0.5`, `60`).
+	#[arg(long, default_value_t = 1.0, value_parser = parse_interval)]
+	pub interval: f64,
+}
+
+impl From<RawEventIntervalSpec> for DataSource {
+	fn from(raw: RawEventIntervalSpec) -> Self {
+		match raw.inputs.len() {
+			1 => DataSource::new_event_count(None, raw.inputs[0].clone()),
+			2 => DataSource::new_event_count(Some(raw.inputs[0].clone()), raw.inputs[1].clone()),
+			_ => panic!("clap args mess. this is bug"),
+		}
+	}
+}
+
+fn parse_interval(s: &str) -> Result<f64, String> {
+	let interval = s.trim().parse::<f64>().map_err(|e| format!("Interval parse error: {e}"))?;
+	if !(interval > 0.0 && interval.is_finite()) {
+		return Err(format!("Interval must be positive, given: {interval}"));
+	}
+	Ok(interval)
+}
+
+impl StatDataSource {
+	/// Tells how the extracted records shall be aggregated before computing stats.
+	pub fn aggregation(&self) -> StatAggregation {
+		match self {
+			StatDataSource::EventCount(spec) => StatAggregation::CountPerInterval(spec.interval),
+			StatDataSource::EventRate(spec) => StatAggregation::RatePerInterval(spec.interval),
+			StatDataSource::EventDelta(_)
+			| StatDataSource::FieldValue(_)
+			| StatDataSource::JsonField(_)
+			| StatDataSource::Logfmt(_) => StatAggregation::None,
+		}
+	}
+}
+
 impl From<StatDataSource> for DataSource {
 	fn from(value: StatDataSource) -> Self {
 		match value {
+			StatDataSource::EventCount(spec) | StatDataSource::EventRate(spec) => spec.into(),
 			StatDataSource::FieldValue(spec) => DataSource::FieldValue(spec.into()),
 			StatDataSource::EventDelta(spec) => DataSource::EventDelta(spec.into()),
+			StatDataSource::JsonField(spec) => spec.into(),
+			StatDataSource::Logfmt(spec) => spec.into(),
 		}
 	}
 }
@@ -152,6 +311,31 @@ pub struct CatArgs {
 	#[clap(flatten)]
 	pub input_files_ctx: InputFilesContext,
 
+	/// Comma-separated list of columns to be displayed, in given order (e.g. `ts,value,delta`)
+	#[arg(long, value_enum, value_delimiter = ',', default_values_t = [CatColumn::Value])]
+	pub columns: Vec<CatColumn>,
+
+	/// Output format
+	#[arg(long, value_enum, default_value_t = CatFormat::Plain)]
+	pub format: CatFormat,
+
+	/// Keep the matched text (`match`) or the whole log line (`line`), displayed in `text` column
+	#[arg(long, value_enum, value_name = "WHAT")]
+	pub keep_text: Option<MatchedText>,
+
+	/// Keep printing values as new lines are appended to the log file
+	///
+	/// Cache files are neither used nor created. Not supported for `event-count` and
+	/// `event-rate`.
+	#[arg(long, default_value_t = false, conflicts_with = "from_cache")]
+	pub follow: bool,
+
+	/// Read values from previously created CSV cache file instead of log file
+	///
+	/// The data source shall match the one used to create the cache file.
+	#[arg(long, value_name = "FILE", conflicts_with = "input")]
+	pub from_cache: Option<PathBuf>,
+
 	#[command(subcommand)]
 	pub command: StatDataSource,
 }
@@ -166,18 +350,299 @@ pub struct StatArgs {
 	#[arg(long, default_value_t = 10)]
 	pub buckets_count: u64,
 
+	/// Use exponentially growing histogram buckets
+	///
+	/// Better suited for heavy-tailed distributions (e.g. latencies) than equal-width buckets.
+	/// Non-positive values are counted in an extra leading bucket.
+	#[arg(long, default_value_t = false)]
+	pub log_buckets: bool,
+
 	/// Float precision and width to be used when printing bucket range
 	#[clap(long, num_args = 2)]
 	pub precision: Vec<usize>,
 
+	/// Comma-separated list of percentiles to be displayed (e.g. `50,90,99,99.9`)
+	///
+	/// Each value must be within `[0, 100]` range. The 50th percentile is displayed as median.
+	#[arg(
+		long,
+		value_delimiter = ',',
+		value_parser = parse_percentile,
+		default_values_t = DEFAULT_PERCENTILES,
+	)]
+	pub percentiles: Vec<f64>,
+
+	/// Comma-separated list of metrics to be displayed, in given order (e.g. `count,sum,stddev`)
+	#[arg(long, value_enum, value_delimiter = ',', default_values_t = DEFAULT_STAT_METRICS)]
+	pub metrics: Vec<StatMetric>,
+
+	/// Regex with a single capture group used to partition values into groups.
+	///
+	/// Regex is applied to every matched line (e.g. `peer=(\w+)`), the captured text is used as the
+	/// group label. A stats row is displayed for every group, the histogram is not displayed.
+	/// Lines not matching the regex are grouped under `-` label.
+	#[arg(long, value_name = "REGEX")]
+	pub group_by: Option<String>,
+
+	/// Display N largest and N smallest values together with the log lines they were extracted
+	/// from.
+	#[arg(long, value_name = "N")]
+	pub show_outliers: Option<usize>,
+
+	/// Numeric field (or regex) to be correlated with the values of the data source.
+	///
+	/// Values of both series are aligned in time and Pearson and Spearman correlation
+	/// coefficients are displayed instead of stats.
+	#[arg(long, value_name = "FIELD", conflicts_with_all = ["group_by", "show_outliers", "from_cache"])]
+	pub correlate: Option<String>,
+
+	/// Optional guard string for the `--correlate` field.
+	#[arg(long, value_name = "GUARD", requires = "correlate")]
+	pub correlate_guard: Option<String>,
+
+	/// How the correlated series are aligned in time.
+	#[arg(long, value_enum, default_value_t = CorrelationAlignment::Nearest, requires = "correlate")]
+	pub align: CorrelationAlignment,
+
+	/// Alignment interval in seconds.
+	///
+	/// For `nearest` alignment it is the maximal time distance of paired values, for `bucket`
+	/// alignment it is the length of the time interval.
+	#[arg(long, default_value_t = 1.0, value_parser = parse_interval, requires = "correlate")]
+	pub align_interval: f64,
+
+	/// Render the scatter plot of correlated values into given image file (gnuplot).
+	#[arg(long, value_name = "FILE", requires = "correlate")]
+	pub scatter: Option<PathBuf>,
+
+	/// Display the dominant periods of the values (periodogram) instead of stats.
+	///
+	/// Values are resampled to a fixed interval and the strongest frequencies of their spectrum
+	/// are displayed, e.g. to confirm that a latency spike happens every 90 seconds. Power of the
+	/// harmonics (e.g. of periodic spikes) is included in the power of the fundamental period.
+	#[arg(long, default_value_t = false, conflicts_with_all = ["group_by", "show_outliers", "correlate"])]
+	pub periodogram: bool,
+
+	/// Resampling interval of the `--periodogram` in seconds.
+	///
+	/// Every sample is the mean of the values within the interval. Shortest detectable period is
+	/// twice the interval.
+	#[arg(long, default_value_t = 1.0, value_parser = parse_interval, requires = "periodogram")]
+	pub resample_interval: f64,
+
+	/// Number of dominant periods displayed by the `--periodogram`.
+	#[arg(long, value_name = "N", default_value_t = 5, requires = "periodogram")]
+	pub top_periods: usize,
+
+	/// Read values from previously created CSV cache file instead of log file
+	///
+	/// Useful if the original log is no longer available. The data source shall match the one used
+	/// to create the cache file. Log lines are not displayed for outliers.
+	#[arg(long, value_name = "FILE", conflicts_with = "input")]
+	pub from_cache: Option<PathBuf>,
+
+	#[command(subcommand)]
+	pub command: StatDataSource,
+}
+
+/// Render the histogram of extracted values as an image (gnuplot) or html page (plotly).
+#[derive(Debug, Args)]
+pub struct HistArgs {
+	#[clap(flatten)]
+	pub input_files_ctx: InputFilesContext,
+
+	/// Histogram buckets count
+	#[arg(long, default_value_t = 10)]
+	pub buckets_count: u64,
+
+	/// Use exponentially growing histogram buckets
+	///
+	/// X axis is displayed in log scale. Non-positive values are counted in an extra leading
+	/// bucket.
+	#[arg(long, default_value_t = false)]
+	pub log_buckets: bool,
+
+	/// Overlay the cumulative distribution (CDF) of values, drawn against the right y axis
+	#[arg(long, default_value_t = false)]
+	pub cdf: bool,
+
+	/// Path to the output file
+	///
+	/// Defaults to `hist.png` (or `hist.html` when plotly backend is used).
+	#[arg(long, short = 'o', value_name = "FILE")]
+	pub output: Option<PathBuf>,
+
+	/// Use plotly backend and render the histogram as html page
+	#[arg(long, short = 'p', default_value_t = false)]
+	pub plotly_backend: bool,
+
+	/// Do not display the rendered histogram
+	#[arg(long, short = 'x', default_value_t = false)]
+	pub do_not_display: bool,
+
+	/// Display absolute paths of the output files
+	#[arg(long, short = 'a', default_value_t = false)]
+	pub display_absolute_paths: bool,
+
+	/// Read values from previously created CSV cache file instead of log file
+	///
+	/// The data source shall match the one used to create the cache file.
+	#[arg(long, value_name = "FILE", conflicts_with = "input")]
+	pub from_cache: Option<PathBuf>,
+
 	#[command(subcommand)]
 	pub command: StatDataSource,
 }
 
+/// Rank labels captured from matched lines by occurrence count or by sum of extracted values.
+#[derive(Debug, Args)]
+pub struct TopArgs {
+	#[clap(flatten)]
+	pub input_files_ctx: InputFilesContext,
+
+	/// Regex with a single capture group used to extract the label from every matched line.
+	///
+	/// E.g. `peer=(\w+)`. Lines not matching the regex are ranked under `-` label.
+	#[arg(long, value_name = "REGEX")]
+	pub label: String,
+
+	/// Number of labels to be displayed
+	#[arg(long, short = 'n', default_value_t = 10)]
+	pub top: usize,
+
+	/// Rank labels by occurrence count or by sum of extracted values
+	#[arg(long, value_enum, default_value_t = TopOrder::Count)]
+	pub by: TopOrder,
+
+	/// Float precision used when printing sum of values
+	#[arg(long, default_value_t = 2)]
+	pub precision: usize,
+
+	/// Read values from previously created CSV cache file instead of log file
+	///
+	/// The data source shall match the one used to create the cache file.
+	#[arg(long, value_name = "FILE", conflicts_with = "input")]
+	pub from_cache: Option<PathBuf>,
+
+	#[command(subcommand)]
+	pub command: StatDataSource,
+}
+
+/// Print log lines matched by the data source, annotated with extracted value and delta.
+///
+/// Every matched line is prefixed with its line number, extracted value and time delta (in
+/// milliseconds) from the previous match. For `event-count` and `event-rate` data sources the
+/// value is the number of occurrences so far, `--interval` is ignored.
+#[derive(Debug, Args)]
+pub struct GrepArgs {
+	#[clap(flatten)]
+	pub input_files_ctx: InputFilesContext,
+
+	/// Print only lines within the given time range.
+	///
+	/// Can be specified as either:
+	/// - A relative range in `[0.0, 1.0]` (relative to the range of all matched lines),
+	/// - Two timestamp strings.
+	///
+	/// Timestamp strings must be compatible with the `--timestamp-format`.
+	#[arg(long, value_parser = TimeRangeArg::parse_time_range)]
+	pub time_range: Option<TimeRangeArg>,
+
+	#[command(subcommand)]
+	pub command: StatDataSource,
+}
+
+/// List all colors, markers, dash styles and plot styles available for lines.
+///
+/// Names are printed as accepted by the line options (e.g. `--line-color`, `--marker-type`).
+#[derive(Debug, Args)]
+pub struct StylesArgs {
+	/// Also render the sample image of all styles into the given PNG file
+	#[arg(long, value_name = "FILE")]
+	pub sample: Option<PathBuf>,
+
+	/// Do not display the rendered sample image
+	#[arg(long, short = 'x', default_value_t = false)]
+	pub do_not_display: bool,
+}
+
+/// Convert the graph config between its representations.
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+	#[command(subcommand)]
+	pub command: ConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+	/// Print the `plox graph` command line equivalent to the graph config file.
+	///
+	/// Handy for sharing one-liners. Only the panel and line params differing from the defaults
+	/// are given, input files shall be added with `--input`.
+	ToCli {
+		/// Path to the TOML graph config file
+		#[arg(value_name = "FILE")]
+		config: PathBuf,
+	},
+	/// Print the JSON Schema of the TOML graph config file.
+	///
+	/// Editors can use it for completion and validation of hand-written configs, e.g. with the
+	/// `#:schema plox-config.schema.json` directive at the top of the config file (taplo, Even
+	/// Better TOML).
+	Schema,
+}
+
+/// Assemble a single HTML page from many graph configs.
+///
+/// Every config is rendered as a section of the page, containing its interactive (plotly) panels
+/// followed by the table of stats of every line. Useful for incident write-ups, which would
+/// otherwise require stitching of many outputs.
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+	#[clap(flatten)]
+	pub input_files_ctx: InputFilesContext,
+
+	/// Graph config file (TOML) rendered as a section of the report. Can be given many times.
+	///
+	/// Config files can be created with `plox graph --write-config`.
+	#[arg(long = "config", value_name = "FILE", required = true)]
+	pub configs: Vec<PathBuf>,
+
+	/// Title of the section. Given in the order of `--config` files.
+	///
+	/// The name of the config file is used for sections without title.
+	#[arg(long = "section-title", value_name = "TITLE")]
+	pub section_titles: Vec<String>,
+
+	/// Title of the report
+	#[arg(long, default_value = "plox report")]
+	pub title: String,
+
+	/// Path to the output html file
+	#[arg(long, short = 'o', value_name = "FILE", default_value = "report.html")]
+	pub output: PathBuf,
+
+	/// Do not open the report in the browser
+	#[arg(long, short = 'x', default_value_t = false)]
+	pub do_not_display: bool,
+}
+
+fn parse_percentile(s: &str) -> Result<f64, String> {
+	let p = s.trim().parse::<f64>().map_err(|e| format!("Percentile parse error: {e}"))?;
+	if !(0.0..=100.0).contains(&p) {
+		return Err(format!("Percentile must be within [0, 100] range, given: {p}"));
+	}
+	Ok(p)
+}
+
 pub fn build_cli() -> clap::Command {
 	Cli::command()
 		.subcommand(crate::graph_cli_builder::build_cli())
 		.subcommand(crate::match_preview_cli_builder::build_cli())
 		.mut_subcommand("stat", |subcmd| subcmd.after_long_help(EXTRA_HELP))
 		.mut_subcommand("cat", |subcmd| subcmd.after_long_help(EXTRA_HELP))
+		.mut_subcommand("hist", |subcmd| subcmd.after_long_help(EXTRA_HELP))
+		.mut_subcommand("top", |subcmd| subcmd.after_long_help(EXTRA_HELP))
+		.mut_subcommand("grep", |subcmd| subcmd.after_long_help(EXTRA_HELP))
+		.mut_subcommand("report", |subcmd| subcmd.after_long_help(EXTRA_HELP))
 }
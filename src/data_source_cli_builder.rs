@@ -1,6 +1,7 @@
 //! Common utils for building data source related args and parsing them.
 
 use std::{
+	collections::HashMap,
 	num::{ParseFloatError, ParseIntError},
 	str::ParseBoolError,
 };
@@ -26,6 +27,10 @@ impl DataSource {
 	const CLI_NAME_EVENT: &str = "event";
 	const CLI_NAME_EVENT_COUNT: &str = "event-count";
 	const CLI_NAME_EVENT_DELTA: &str = "event-delta";
+	const CLI_NAME_ANNOTATION: &str = "annotate";
+	const CLI_NAME_JSON_FIELD: &str = "json-field";
+	const CLI_NAME_LOGFMT_FIELD: &str = "logfmt";
+	const CLI_NAME_CSV_FILE: &str = "csv-file";
 
 	pub fn get_cli_ids() -> Vec<String> {
 		DummyDataSourceSubcommand::command()
@@ -33,6 +38,45 @@ impl DataSource {
 			.map(|sc| sc.get_name().to_string().clone())
 			.collect()
 	}
+
+	/// Returns the CLI arguments defining the data source, e.g. `["--plot", "guard", "field"]`.
+	///
+	/// This is the reverse of [`DataSource::try_from_flag`].
+	pub fn cli_args(&self) -> Vec<String> {
+		let (id, guard, mut args) = match self {
+			DataSource::EventValue { guard, pattern, yvalue } => {
+				(Self::CLI_NAME_EVENT, guard, vec![pattern.clone(), yvalue.to_string()])
+			},
+			DataSource::EventCount { guard, pattern } => {
+				(Self::CLI_NAME_EVENT_COUNT, guard, vec![pattern.clone()])
+			},
+			DataSource::EventDelta(EventDeltaSpec { guard, pattern }) => {
+				(Self::CLI_NAME_EVENT_DELTA, guard, vec![pattern.clone()])
+			},
+			DataSource::Annotation { guard, pattern } => {
+				(Self::CLI_NAME_ANNOTATION, guard, vec![pattern.clone()])
+			},
+			DataSource::JsonField { guard, path } => {
+				(Self::CLI_NAME_JSON_FIELD, guard, vec![path.clone()])
+			},
+			DataSource::LogfmtField { guard, key } => {
+				(Self::CLI_NAME_LOGFMT_FIELD, guard, vec![key.clone()])
+			},
+			DataSource::FieldValue(FieldCaptureSpec { guard, field }) => {
+				(Self::CLI_NAME_PLOT_FIELD, guard, vec![field.clone()])
+			},
+			DataSource::CsvFile(CsvFileSpec { csv_file, time_column, value_column }) => (
+				Self::CLI_NAME_CSV_FILE,
+				&None,
+				vec![csv_file.display().to_string(), time_column.clone(), value_column.clone()],
+			),
+		};
+		if let Some(guard) = guard {
+			args.insert(0, guard.clone());
+		}
+		args.insert(0, format!("--{id}"));
+		args
+	}
 }
 
 impl DataSource {
@@ -43,7 +87,14 @@ impl DataSource {
 	//
 	// This could be worked around by specifying '--plot "x y"' but it is not convenient.
 	// So manual parsing is required.
+	//
+	// Values can also be given by name (in any order), e.g. `--plot guard=om_module field=x`.
 	pub fn try_from_flag(id: &str, val: &[&String]) -> Result<Self, Error> {
+		let named = Self::positional_from_named(id, val)?;
+		let val = match &named {
+			Some(named) => named.iter().collect::<Vec<_>>(),
+			None => val.to_vec(),
+		};
 		Ok(match id {
 			Self::CLI_NAME_EVENT => match val.len() {
 				2 => DataSource::EventValue {
@@ -124,6 +175,62 @@ impl DataSource {
 					)));
 				},
 			},
+			Self::CLI_NAME_ANNOTATION => match val.len() {
+				1 => DataSource::Annotation { guard: None, pattern: val[0].to_string() },
+				2 => DataSource::Annotation {
+					guard: Some(val[0].to_string()),
+					pattern: val[1].to_string(),
+				},
+				_ => {
+					return Err(Error::GeneralCliParseError(format!(
+						"Bad parameter count ({}) for {}. This is bug.",
+						val.len(),
+						id
+					)));
+				},
+			},
+			Self::CLI_NAME_JSON_FIELD => match val.len() {
+				1 => DataSource::JsonField { guard: None, path: val[0].to_string() },
+				2 => DataSource::JsonField {
+					guard: Some(val[0].to_string()),
+					path: val[1].to_string(),
+				},
+				_ => {
+					return Err(Error::GeneralCliParseError(format!(
+						"Bad parameter count ({}) for {}. This is bug.",
+						val.len(),
+						id
+					)));
+				},
+			},
+			Self::CLI_NAME_LOGFMT_FIELD => match val.len() {
+				1 => DataSource::LogfmtField { guard: None, key: val[0].to_string() },
+				2 => DataSource::LogfmtField {
+					guard: Some(val[0].to_string()),
+					key: val[1].to_string(),
+				},
+				_ => {
+					return Err(Error::GeneralCliParseError(format!(
+						"Bad parameter count ({}) for {}. This is bug.",
+						val.len(),
+						id
+					)));
+				},
+			},
+			Self::CLI_NAME_CSV_FILE => match val.len() {
+				3 => DataSource::CsvFile(CsvFileSpec {
+					csv_file: val[0].into(),
+					time_column: val[1].to_string(),
+					value_column: val[2].to_string(),
+				}),
+				_ => {
+					return Err(Error::GeneralCliParseError(format!(
+						"Bad parameter count ({}) for {}. This is bug.",
+						val.len(),
+						id
+					)));
+				},
+			},
 			_ => {
 				return Err(Error::GeneralCliParseError(format!(
 					"Unknown DataSource id:{}. This is bug",
@@ -134,6 +241,51 @@ impl DataSource {
 	}
 }
 
+impl DataSource {
+	/// Converts the named values (e.g. `guard=om_module field=x`) into the positional ones.
+	///
+	/// Values are named only if every value is `<NAME>=<VALUE>` with the name of the data source
+	/// parameter (`-` can be used instead of `_`). Otherwise `None` is returned and values are
+	/// positional, e.g. the `level=INFO` guard of `--json-field level=INFO duration`.
+	fn positional_from_named(id: &str, val: &[&String]) -> Result<Option<Vec<String>>, Error> {
+		let command = DummyDataSourceSubcommand::command();
+		let Some(sub) = command.find_subcommand(id) else {
+			return Ok(None);
+		};
+		let params = sub.get_arguments().collect::<Vec<_>>();
+		let mut named = HashMap::new();
+		for v in val {
+			let Some((raw_name, value)) = v.split_once('=') else {
+				return Ok(None);
+			};
+			let name = raw_name.replace('-', "_");
+			if !params.iter().any(|p| p.get_id() == name.as_str()) {
+				return Ok(None);
+			}
+			if named.insert(name, value).is_some() {
+				return Err(Error::GeneralCliParseError(format!(
+					"Parameter '{raw_name}' given more than once for --{id}."
+				)));
+			}
+		}
+
+		let mut positional = vec![];
+		for param in params {
+			match named.get(param.get_id().as_str()) {
+				Some(value) => positional.push(value.to_string()),
+				None if param.is_required_set() => {
+					return Err(Error::GeneralCliParseError(format!(
+						"Missing parameter '{}' for --{id}.",
+						param.get_id()
+					)));
+				},
+				None => {},
+			}
+		}
+		Ok(Some(positional))
+	}
+}
+
 /// Dummy helper wrapper for `CommandFactory`
 ///
 /// Used for injecting DataSource args and their parameters.
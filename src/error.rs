@@ -2,9 +2,28 @@
 //!
 //! It defines the main `Error` type, wraps lower-level errors, and ensures consistent reporting.
 //! Intended to provide clear, friendly messages when something goes wrong.
+//!
+//! Every error also has a stable machine-readable code (see [`Error::code`]), which is reported
+//! by the CLI with `--error-format json`, and is mapped to one of the CLI exit codes (see
+//! [`Error::exit_code`]).
 
 use std::io;
 
+/// Exit code of the CLI for failures not covered by other exit codes.
+pub const EXIT_CODE_FAILURE: u8 = 1;
+/// Exit code of the CLI for invalid arguments, config files or patterns.
+pub const EXIT_CODE_USAGE: u8 = 2;
+/// Exit code of the CLI when no data was found for any line of the graph.
+pub const EXIT_CODE_EMPTY_GRAPH: u8 = 3;
+/// Exit code of the CLI when the timestamp could not be extracted from the log line.
+pub const EXIT_CODE_TIMESTAMP: u8 = 4;
+/// Exit code of the CLI when the `gnuplot` command is not available.
+pub const EXIT_CODE_GNUPLOT_MISSING: u8 = 5;
+/// Exit code of the CLI for I/O errors (e.g. missing input file).
+pub const EXIT_CODE_IO: u8 = 6;
+/// Exit code of the CLI interrupted with Ctrl-C (`SIGINT`) or `SIGTERM`.
+pub const EXIT_CODE_INTERRUPTED: u8 = 130;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("CLI parsing error: {0}")]
@@ -23,6 +42,7 @@ pub enum Error {
 	#[error("Other error. {0}")]
 	Other(#[from] Box<dyn std::error::Error + Send + Sync>),
 
+	#[cfg(feature = "gnuplot")]
 	#[error("GNU plot script error. {0}")]
 	GnuPlotCreationError(#[from] crate::gnuplot::Error),
 
@@ -32,6 +52,174 @@ pub enum Error {
 	#[error("Time ranges resolution error. {0}")]
 	TimeRangesResolution(#[from] crate::align_ranges::Error),
 
+	#[error("Period folding error. {0}")]
+	PeriodFold(#[from] crate::period_fold::Error),
+
+	#[error("Baseline subtraction error. {0}")]
+	Baseline(#[from] crate::baseline::Error),
+
+	#[error("Values transform error. {0}")]
+	Transforms(#[from] crate::transforms::Error),
+
+	#[cfg(feature = "plotly")]
 	#[error("Plotly generation error. {0}")]
 	PlotlyError(#[from] crate::plotly_backend::Error),
+
+	#[error(
+		"The {0} backend is not available, plox was built without the '{feature}' feature.",
+		feature = .0.feature_name()
+	)]
+	BackendNotEnabled(crate::backend::Backend),
+
+	#[error("Invalid file id {id} of line '{line}'. Available input files: {inputs}")]
+	InvalidFileId { line: String, id: usize, inputs: String },
+
+	#[error("All lines are empty. No data or bad timestamp or bad guard/regex?")]
+	EmptyGraph,
+
+	#[error("Output file '{0}' already exists (remove it or use '--auto-number').")]
+	OutputFileExists(std::path::PathBuf),
+}
+
+/// Format of the errors reported by the CLI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum ErrorFormat {
+	/// Human readable messages.
+	#[default]
+	Text,
+	/// Single JSON object printed to stderr: `{"code": "...", "message": "..."}`.
+	Json,
+}
+
+impl Error {
+	/// Stable, machine-readable code of the error.
+	///
+	/// Codes are kebab-case strings which are not changed between releases, so wrapper scripts can
+	/// rely on them (e.g. to decide whether to retry). Errors from log processing report the code
+	/// of the underlying failure (e.g. `timestamp-extraction`, `input-io`).
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::CliParseError(_) | Self::CliParseError2(_) => "cli-parse",
+			Self::IoError(..) => "io",
+			Self::TomlError(_) => "config-parse",
+			Self::Other(_) => "other",
+			#[cfg(feature = "gnuplot")]
+			Self::GnuPlotCreationError(_) => "gnuplot",
+			Self::LogProcessing(e) => e.code(),
+			Self::TimeRangesResolution(_) => "time-range-resolution",
+			Self::PeriodFold(_) => "period-fold",
+			Self::Baseline(_) => "baseline",
+			Self::Transforms(_) => "transforms",
+			#[cfg(feature = "plotly")]
+			Self::PlotlyError(_) => "plotly",
+			Self::BackendNotEnabled(_) => "backend-not-enabled",
+			Self::InvalidFileId { .. } => "invalid-file-id",
+			Self::EmptyGraph => "empty-graph",
+			Self::OutputFileExists(_) => "output-file-exists",
+		}
+	}
+
+	/// Exit code of the CLI reporting this error.
+	///
+	/// Errors are grouped into a few categories, so automation can branch on the kind of failure.
+	/// Usage errors reported by the CLI parser itself also exit with [`EXIT_CODE_USAGE`].
+	pub fn exit_code(&self) -> u8 {
+		use crate::{align_ranges::Error as RangeError, process_log::Error as LogError};
+		match self {
+			Self::CliParseError(_)
+			| Self::CliParseError2(_)
+			| Self::TomlError(_)
+			| Self::BackendNotEnabled(_)
+			| Self::InvalidFileId { .. } => EXIT_CODE_USAGE,
+			Self::IoError(..) | Self::OutputFileExists(_) => EXIT_CODE_IO,
+			Self::EmptyGraph | Self::TimeRangesResolution(RangeError::EmptyRangeError) => {
+				EXIT_CODE_EMPTY_GRAPH
+			},
+			Self::TimeRangesResolution(RangeError::IoError(_) | RangeError::FileError(..)) => {
+				EXIT_CODE_IO
+			},
+			Self::PeriodFold(crate::period_fold::Error::FileError(..)) => EXIT_CODE_IO,
+			Self::Baseline(crate::baseline::Error::UnknownBaseline { .. }) => EXIT_CODE_USAGE,
+			Self::Transforms(crate::transforms::Error::TooManySamples(..)) => EXIT_CODE_USAGE,
+			Self::LogProcessing(e) => match e {
+				LogError::TimestampExtractionFailure(..) => EXIT_CODE_TIMESTAMP,
+				LogError::FileIoError(..)
+				| LogError::InvalidInputFile(..)
+				| LogError::CatOutputError(_) => EXIT_CODE_IO,
+				LogError::Regex(_)
+				| LogError::RegexCapturesGroupsInvalidCount(_)
+				| LogError::LabelRegexCapturesGroupsInvalidCount(_)
+				| LogError::TimeRangeParsingError(_)
+				| LogError::CatCmdManyInputFiles
+				| LogError::CatFollowUnsupportedDataSource
+				| LogError::CsvColumnNotFound(..)
+				| LogError::TooManySamples(..)
+				| LogError::TooManyIntervals(..) => EXIT_CODE_USAGE,
+				_ => EXIT_CODE_FAILURE,
+			},
+			#[cfg(feature = "gnuplot")]
+			Self::GnuPlotCreationError(crate::gnuplot::Error::GnuplotCommandNotAvailable(..)) => {
+				EXIT_CODE_GNUPLOT_MISSING
+			},
+			#[cfg(feature = "gnuplot")]
+			Self::GnuPlotCreationError(
+				crate::gnuplot::Error::IoError(_)
+				| crate::gnuplot::Error::ScriptCreationError(..)
+				| crate::gnuplot::Error::ExtraPreambleReadError(..),
+			) => EXIT_CODE_IO,
+			_ => EXIT_CODE_FAILURE,
+		}
+	}
+
+	/// Returns the error as JSON object containing the code and the message.
+	pub fn to_json(&self) -> serde_json::Value {
+		serde_json::json!({ "code": self.code(), "message": self.to_string() })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	#[test]
+	fn test_error_codes() {
+		let e = Error::IoError("a.log".into(), io::Error::other("boom"));
+		assert_eq!(e.code(), "io");
+
+		let e: Error = crate::process_log::Error::FileIoError(
+			PathBuf::from("a.log"),
+			io::Error::from(io::ErrorKind::NotFound),
+		)
+		.into();
+		assert_eq!(e.code(), "input-io");
+
+		let e: Error = crate::process_log::Error::CatCmdManyInputFiles.into();
+		assert_eq!(
+			e.to_json(),
+			serde_json::json!({
+				"code": "cat-many-input-files",
+				"message": "Logs processing error. Cat command supports only one input file."
+			})
+		);
+	}
+
+	#[test]
+	fn test_exit_codes() {
+		let log_error = |e: crate::process_log::Error| Error::from(e).exit_code();
+		assert_eq!(log_error(crate::process_log::Error::CatCmdManyInputFiles), EXIT_CODE_USAGE);
+		assert_eq!(
+			log_error(crate::process_log::Error::FileIoError(
+				PathBuf::from("a.log"),
+				io::Error::from(io::ErrorKind::NotFound),
+			)),
+			EXIT_CODE_IO
+		);
+		assert_eq!(Error::EmptyGraph.exit_code(), EXIT_CODE_EMPTY_GRAPH);
+		assert_eq!(
+			Error::from(crate::align_ranges::Error::EmptyRangeError).exit_code(),
+			EXIT_CODE_EMPTY_GRAPH
+		);
+		assert_eq!(Error::Other("boom".into()).exit_code(), EXIT_CODE_FAILURE);
+	}
 }
@@ -5,15 +5,20 @@
 //! of gnuplot and the saving of resulting graph images.
 
 use crate::{
+	backend::EMPTY_GRAPH_MESSAGE,
 	graph_config::{
-		AxisScale, Color, DashStyle, GraphFullContext, MarkerType, OutputFilePaths, PlotStyle,
-		YAxis,
+		Alpha, AxisRange, AxisScale, Color, DEFAULT_XTICS_FORMAT, DashStyle, GapThreshold,
+		GraphFullContext, MarkerType, OutputFilePaths, PlotStyle, SmoothStyle, YAxis,
+		value_enum_name,
 	},
-	logging::APPV,
+	logging::{APPV, output_message},
+	process_log::{self, HistoBucket, parse_record_timestamp},
+	provenance::Provenance,
 	resolved_graph_config::{ResolvedGraphConfig, ResolvedLine},
 };
+use chrono::NaiveDateTime;
 use std::{
-	fs::File,
+	fs::{self, File},
 	io::{self, Write},
 	path::{Path, PathBuf},
 	process::{Command, ExitStatus},
@@ -36,6 +41,8 @@ pub enum Error {
 	GnuplotNonZeroExitCode(ExitStatus, String, String),
 	#[error("Error while creating gnuplot script '{0}': {1}")]
 	ScriptCreationError(PathBuf, io::Error),
+	#[error("Error while reading gnuplot preamble '{0}': {1}")]
+	ExtraPreambleReadError(PathBuf, io::Error),
 	#[error("Incorrect input files (this is bug).")]
 	IncorrectOutputFiles,
 	#[error("Parsing log error: {0} (this is bug?)")]
@@ -63,8 +70,8 @@ impl MarkerType {
 
 impl Color {
 	/// Returns the gnuplot color specification, e.g. `lc rgb "red"`.
-	pub fn to_gnuplot(&self) -> &'static str {
-		match self {
+	pub fn to_gnuplot(&self) -> String {
+		let spec = match self {
 			Color::Red => "lc rgb \"red\"",
 			Color::Blue => "lc rgb \"blue\"",
 			Color::Green => "lc rgb \"green\"",
@@ -88,6 +95,51 @@ impl Color {
 			Color::DarkTurquoise => "lc rgb \"dark-turquoise\"",
 			Color::Yellow => "lc rgb \"yellow\"",
 			Color::Black => "lc rgb \"black\"",
+			Color::Rgb(rgb) => return format!("lc rgb \"#{rgb:06x}\""),
+		};
+		spec.into()
+	}
+
+	/// Returns the RGB value of the color, as defined by gnuplot.
+	fn rgb(&self) -> u32 {
+		match self {
+			Color::Red => 0xff0000,
+			Color::Blue => 0x0000ff,
+			Color::Green => 0x00ff00,
+			Color::Orange => 0xffa500,
+			Color::Purple => 0xc080ff,
+			Color::Cyan => 0x00ffff,
+			Color::Magenta => 0xff00ff,
+			Color::Goldenrod => 0xffc020,
+			Color::Brown => 0xa52a2a,
+			Color::Olive => 0xa08020,
+			Color::Navy => 0x000080,
+			Color::DarkGreen => 0x006400,
+			Color::DarkOrange => 0xc04000,
+			Color::Violet => 0xee82ee,
+			Color::Coral => 0xff7f50,
+			Color::Salmon => 0xfa8072,
+			Color::SteelBlue => 0x306080,
+			Color::DarkMagenta => 0xc000ff,
+			Color::DarkCyan => 0x00eeee,
+			Color::DarkYellow => 0xc8c800,
+			Color::DarkTurquoise => 0x00ced1,
+			Color::Yellow => 0xffff00,
+			Color::Black => 0x000000,
+			Color::Rgb(rgb) => *rgb,
+		}
+	}
+
+	/// Returns the gnuplot color specification with given opacity, e.g. `lc rgb "#80ff0000"`.
+	///
+	/// Gnuplot expects the transparency in the most significant byte (`00` is opaque).
+	pub fn to_gnuplot_with_alpha(&self, alpha: Option<Alpha>) -> String {
+		match alpha {
+			Some(Alpha(alpha)) => {
+				let transparency = ((1.0 - alpha) * 255.0).round() as u8;
+				format!("lc rgb \"#{transparency:02x}{:06x}\"", self.rgb())
+			},
+			None => self.to_gnuplot(),
 		}
 	}
 }
@@ -103,6 +155,16 @@ impl PlotStyle {
 		}
 	}
 }
+impl SmoothStyle {
+	/// Returns the gnuplot smoothing snippet, e.g. `"smooth csplines"`
+	pub fn to_gnuplot(&self) -> &'static str {
+		match self {
+			SmoothStyle::Bezier => "smooth bezier",
+			SmoothStyle::Csplines => "smooth csplines",
+			SmoothStyle::Sbezier => "smooth sbezier",
+		}
+	}
+}
 impl DashStyle {
 	/// Returns the gnuplot dash (line type) snippet, e.g. `"lt 2"`
 	pub fn to_gnuplot(&self) -> &'static str {
@@ -116,6 +178,14 @@ impl DashStyle {
 	}
 }
 
+impl AxisRange {
+	/// Returns the gnuplot range snippet, e.g. `[0:*]`
+	pub fn to_gnuplot(&self) -> String {
+		let bound = |b: Option<f64>| b.map(|b| b.to_string()).unwrap_or_else(|| "*".into());
+		format!("[{}:{}]", bound(self.min), bound(self.max))
+	}
+}
+
 use strum::IntoEnumIterator;
 
 #[derive(Debug, Clone, Copy)]
@@ -137,6 +207,9 @@ impl Style {
 	}
 }
 
+/// Number of line types defined in the script.
+const DEFAULT_STYLES_COUNT: usize = 20;
+
 fn build_default_styles() -> Vec<Style> {
 	let mut styles = Vec::new();
 	for dash in DashStyle::iter() {
@@ -152,20 +225,21 @@ fn build_default_styles() -> Vec<Style> {
 /// # Arguments
 /// * `config` - The full graph configuration (panels and lines).
 /// * `output_script_path` - The path where the .gnu file will be written.
-/// * `output_image_path` - The path to the output PNG file.
+/// * `output_image_path` - The path to the output PNG file. If not given, gnuplot writes the image
+///   to its stdout.
 pub fn write_gnuplot_script(
 	config: &ResolvedGraphConfig,
 	context: &GraphFullContext,
 	output_script_path: &PathBuf,
-	output_image_path: &Path,
+	output_image_path: Option<&Path>,
 ) -> Result<(), Error> {
 	let mut file = File::create(output_script_path)
 		.map_err(|e| Error::ScriptCreationError(output_script_path.clone(), e))?;
 	let num_non_empty_panels = config.panels.iter().filter(|p| !p.is_empty()).count();
 	let plot_margin = 0.005;
-	let plot_height = 1.0 / num_non_empty_panels as f64 - plot_margin;
+	let panel_heights = config.panel_heights();
 
-	let has_multiple_input_files = context.input().len() > 1;
+	let has_multiple_input_files = context.logical_input().len() > 1;
 
 	//write to gnuplot script wrapper
 	macro_rules! gpwr {
@@ -174,11 +248,35 @@ pub fn write_gnuplot_script(
 	    });
 	}
 
+	for (key, value) in Provenance::collect(context.input(), context.hash_inputs()).entries() {
+		gpwr!(file, "# {key}: {value}")?;
+	}
 	gpwr!(file, "set terminal pngcairo enhanced font 'arial,10' fontscale 3.0 size 7560, 5500")?;
-	gpwr!(file, "set output '{}'", output_image_path.display())?;
+	// Paths of the portable script are relative to the script (see `--portable-script`). Images
+	// written to stdout are not portable.
+	let portable = context.portable_script() && output_image_path.is_some();
+	if let Some(output_image_path) = output_image_path {
+		let output_image_path = match output_image_path.file_name() {
+			Some(file_name) if portable => Path::new(file_name),
+			_ => output_image_path,
+		};
+		gpwr!(file, "set output '{}'", output_image_path.display())?;
+	}
+
+	if num_non_empty_panels == 0 {
+		gpwr!(
+			file,
+			"set label 1 '{EMPTY_GRAPH_MESSAGE}' at screen 0.5,0.5 center font 'arial bold,14' noenhanced"
+		)?;
+		gpwr!(file, "unset border")?;
+		gpwr!(file, "unset tics")?;
+		gpwr!(file, "unset key")?;
+		gpwr!(file, "plot [0:1][0:1] NaN notitle")?;
+		return Ok(());
+	}
 
 	{
-		let styles = build_default_styles().into_iter().take(20);
+		let styles = build_default_styles().into_iter().take(DEFAULT_STYLES_COUNT);
 		for (i, style) in styles.enumerate() {
 			gpwr!(file, "{}", style.line_style(i + 1))?;
 		}
@@ -187,30 +285,56 @@ pub fn write_gnuplot_script(
 	gpwr!(file, "set datafile separator ','")?;
 	gpwr!(file, "set xdata time")?;
 	gpwr!(file, "set timefmt '%Y-%m-%dT%H:%M:%S'")?;
-	gpwr!(file, "set format x '%H:%M:%S'")?;
+	let xtics_format = context.xtics_format().unwrap_or(DEFAULT_XTICS_FORMAT).replace('\'', "''");
+	gpwr!(file, "set format x '{xtics_format}'")?;
+	if let Some(interval) = context.xtics() {
+		gpwr!(file, "set xtics {}", interval.0)?;
+	}
+	match context.xtics_rotate() {
+		Some(angle) if angle > 0 => gpwr!(file, "set xtics rotate by {angle} right")?,
+		Some(angle) if angle < 0 => gpwr!(file, "set xtics rotate by {angle} left")?,
+		_ => {},
+	}
 	gpwr!(file, "set mxtics 10")?;
 	gpwr!(file, "set grid xtics mxtics")?;
 	gpwr!(file, "set grid ytics mytics")?;
 	gpwr!(file, "set ytics nomirror")?;
 	gpwr!(file, "set key noenhanced")?;
+	if let Some(extra_path) = context.gnuplot_extra() {
+		let extra = fs::read_to_string(extra_path)
+			.map_err(|e| Error::ExtraPreambleReadError(extra_path.to_path_buf(), e))?;
+		gpwr!(file, "# --gnuplot-extra: {}", extra_path.display())?;
+		gpwr!(file, "{}", extra.trim_end())?;
+	}
 	gpwr!(file, "set multiplot")?;
 	gpwr!(file, "set lmargin at screen 0.035")?;
 	gpwr!(file, "set rmargin at screen 0.975")?;
 
 	gpwr!(file, "combine_datetime(date_col,time_col) = strcol(date_col) . 'T' . strcol(time_col)")?;
 
+	let external_annotations = match context.annotations_file() {
+		Some(path) => process_log::read_annotations_file(path, context.timestamp_format())?,
+		None => vec![],
+	};
+
 	let mut i = 0;
-	for panel in config.panels.iter().rev() {
+	let mut y_position = 0.0;
+	for (panel, height) in config.panels.iter().zip(panel_heights).rev() {
 		debug!(target:LOG_TARGET,"drawing: {:#?}",panel);
 		if panel.is_empty() {
 			continue;
 		}
 
-		let y_position = plot_height * i as f64;
 		i += 1;
 		gpwr!(file, "set origin 0.0,{}", y_position)?;
-		gpwr!(file, "set size 1.0,{}", plot_height)?;
+		gpwr!(file, "set size 1.0,{}", height - plot_margin)?;
+		y_position += height - plot_margin;
 		gpwr!(file, "unset label")?;
+		// Panels are drawn bottom-up, the bottom one keeps the tick labels.
+		if context.xtics_bottom_only() && i == 2 {
+			gpwr!(file, "set format x ''")?;
+			gpwr!(file, "set bmargin 1")?;
+		}
 		{
 			let mut x = -0.03;
 			for (i, title_line) in panel.title().into_iter().enumerate() {
@@ -222,6 +346,25 @@ pub fn write_gnuplot_script(
 				x += 0.005;
 			}
 		}
+		// The footer is drawn once, below the bottom panel.
+		if let (1, Some(sampling)) = (i, context.input_files_ctx.sample()) {
+			gpwr!(
+				file,
+				"set label '{}' at screen 0.995,0.002 right font 'arial,7' noenhanced",
+				sampling.description()
+			)?;
+		}
+
+		if panel.params.stats_box.unwrap_or(false) {
+			let text = panel.stats_box(has_multiple_input_files)?.join("\\n");
+			if !text.is_empty() {
+				gpwr!(
+					file,
+					"set label \"{}\" at graph 0.99,0.97 right front boxed font 'arial,8' noenhanced",
+					text.replace('"', "\\\"")
+				)?;
+			}
+		}
 
 		match panel.params.yaxis_scale {
 			Some(AxisScale::Linear) | None => gpwr!(file, "unset logscale y")?,
@@ -232,6 +375,36 @@ pub fn write_gnuplot_script(
 			gpwr!(file, "set y2tics nomirror")?;
 			gpwr!(file, "set my2tics 10")?;
 		};
+		if let Some(label) = &panel.params.y2label {
+			gpwr!(file, "set y2label '{label}' noenhanced")?;
+		}
+		// Units of the values are shown on the tick labels, as the panel title is placed where the
+		// Y-axis label would be.
+		let mut units_axes = vec![];
+		for (axis, name) in [(YAxis::Y, "y"), (YAxis::Y2, "y2")] {
+			if let Some(units) = panel.axis_units(axis)? {
+				gpwr!(file, "set format {name} '%g {}'", units.replace('%', "%%"))?;
+				units_axes.push(name);
+			}
+		}
+		if let Some(range) = &panel.shared_yrange {
+			gpwr!(file, "set yrange {}", range.to_gnuplot())?;
+		}
+		if let Some(range) = panel.y2range() {
+			gpwr!(file, "set y2range {}", range.to_gnuplot())?;
+		}
+		let swimlanes = panel.swimlanes();
+		if !swimlanes.is_empty() {
+			let tics = swimlanes
+				.iter()
+				.map(|(row, name)| format!("\"{}\" {row}", name.replace('"', "\\\"")))
+				.collect::<Vec<_>>();
+			gpwr!(file, "set ytics ({}) noenhanced", tics.join(", "))?;
+			gpwr!(file, "set yrange [-0.5:{}]", swimlanes.len() as f64 - 0.5)?;
+		}
+		if let Some(AxisScale::Log) = panel.params.y2axis_scale {
+			gpwr!(file, "set logscale y2 10")?;
+		}
 
 		if let Some((start, end)) = panel.time_range {
 			let format = "%Y-%m-%dT%H:%M:%S"; // must match `set timefmt`
@@ -239,7 +412,35 @@ pub fn write_gnuplot_script(
 		}
 
 		let mut non_empty_lines = vec![];
-		for (j, line) in panel.lines.iter().enumerate() {
+		let mut has_annotations = false;
+		if let Some(value) = panel.params.hline {
+			gpwr!(
+				file,
+				"set arrow from graph 0, first {value} to graph 1, first {value} nohead lc rgb \"red\" dt 2 front"
+			)?;
+		}
+		for (timestamp, text) in &external_annotations {
+			if panel
+				.time_range
+				.is_some_and(|(start, end)| *timestamp < start || *timestamp > end)
+			{
+				continue;
+			}
+			gpwr!(file, "{}", annotation_commands(None, *timestamp, text))?;
+			has_annotations = true;
+		}
+		for (j, line) in panel.lines_in_drawing_order().into_iter().enumerate() {
+			if line.line.data_source.is_annotation() {
+				for (timestamp, text) in line.annotations(panel.time_range)? {
+					gpwr!(
+						file,
+						"{}",
+						annotation_commands(line.line.params.line_color, timestamp, &text)
+					)?;
+					has_annotations = true;
+				}
+				continue;
+			}
 			let has_data_points = if let Some((start, end)) = panel.time_range {
 				let has_data_points = line.has_data_points_in_time_range(start, end)?;
 				if !has_data_points {
@@ -257,8 +458,21 @@ pub fn write_gnuplot_script(
 				let csv_data_path = line
 					.shared_csv_filename()
 					.ok_or(Error::CvsFilesResolutionError(Box::new(line.clone())))?;
-				gpwr!(file, "csv_data_file_{j:04} = '{}'", csv_data_path.display())?;
-				non_empty_lines.push((j, line));
+				let gap_threshold = line.line.params.gap_threshold;
+				let data_ref = if gap_threshold.is_some() || context.inline_data() {
+					let name = format!("$csv_data_{j:04}");
+					gpwr!(file, "{}", datablock(&name, &csv_data_path, gap_threshold)?)?;
+					name
+				} else {
+					let csv_data_path = if portable {
+						portable_data_file(output_script_path, &csv_data_path)?
+					} else {
+						csv_data_path.display().to_string()
+					};
+					gpwr!(file, "csv_data_file_{j:04} = '{csv_data_path}'")?;
+					format!("csv_data_file_{j:04}")
+				};
+				non_empty_lines.push((line, data_ref));
 			}
 		}
 
@@ -275,9 +489,14 @@ pub fn write_gnuplot_script(
 				title = ?panel.title(),
 				"No data points for panel.");
 		};
-		for (j, line) in non_empty_lines {
+		let non_empty_lines_count = non_empty_lines.len();
+		for (k, (line, data_ref)) in non_empty_lines.into_iter().enumerate() {
 			let mut style_parts: Vec<String> = Vec::new();
 
+			// `smooth` must precede the `with` clause.
+			if let Some(smooth_style) = &line.line.params.smooth_style {
+				style_parts.push(smooth_style.to_gnuplot().into());
+			}
 			style_parts.push(line.line.params.style.to_gnuplot().into());
 			if let Some(dash_style) = &line.line.params.dash_style {
 				style_parts.push(dash_style.to_gnuplot().into());
@@ -287,8 +506,11 @@ pub fn write_gnuplot_script(
 				style_parts.push(format!("lw {}", line_width));
 			}
 
-			if let Some(color) = &line.line.params.line_color {
-				style_parts.push(color.to_gnuplot().into());
+			let alpha = line.line.params.alpha;
+			// Translucent line needs explicit color, the one of the default line type is used.
+			let default_color = alpha.and_then(|_| Color::iter().take(DEFAULT_STYLES_COUNT).nth(k));
+			if let Some(color) = line.line.params.line_color.or(default_color) {
+				style_parts.push(color.to_gnuplot_with_alpha(alpha));
 			}
 
 			if matches!(line.line.params.style, PlotStyle::LinesPoints | PlotStyle::Points) {
@@ -298,7 +520,7 @@ pub fn write_gnuplot_script(
 				style_parts.push(format!("ps {}", line.line.params.marker_size));
 
 				if let Some(mcol) = &line.line.params.marker_color {
-					style_parts.push(mcol.to_gnuplot().into());
+					style_parts.push(mcol.to_gnuplot_with_alpha(alpha));
 				}
 			}
 
@@ -314,15 +536,35 @@ pub fn write_gnuplot_script(
 				style_parts.join(" ")
 			};
 
+			let title = if line.line.params.no_legend_entry.unwrap_or(false) {
+				"notitle".to_string()
+			} else {
+				format!("title '{}'", line.title(has_multiple_input_files))
+			};
+			// Values are ignored in the swimlane panel, events are drawn at the row of the file.
+			let y = match panel.swimlane_row(line) {
+				Some(row) => format!("({row})"),
+				None => format!("'{}'", line.csv_data_column_for_plot()),
+			};
 			write!(
 				file,
-				"   csv_data_file_{j:04} using (combine_datetime('date','time')):'{}' {} title '{}'",
-				line.csv_data_column_for_plot(),
-				style,
-				line.title(has_multiple_input_files),
+				"   {data_ref} using (combine_datetime('date','time')):{y} {style} {title}"
 			)?;
+			let primary_axis = !matches!(line.line.params.yaxis, Some(YAxis::Y2));
+			if let Some(threshold) =
+				panel.breach_threshold().filter(|_| primary_axis && swimlanes.is_empty())
+			{
+				let column = line.csv_data_column_for_plot();
+				write!(
+					file,
+					", \\\n   {data_ref} using (combine_datetime('date','time')):\
+					 (column('{column}') > {threshold} ? column('{column}') : 1/0) \
+					 with points pt 7 ps {} lc rgb \"red\" axes x1y1 notitle",
+					line.line.params.marker_size,
+				)?;
+			}
 
-			if j != panel.lines.len() - 1 {
+			if k != non_empty_lines_count - 1 {
 				gpwr!(file, ", \\")?;
 			} else {
 				gpwr!(file, "")?;
@@ -330,12 +572,147 @@ pub fn write_gnuplot_script(
 		}
 		gpwr!(file, "unset y2tics")?;
 		gpwr!(file, "unset my2tics")?;
+		if has_annotations || panel.params.hline.is_some() {
+			gpwr!(file, "unset arrow")?;
+		}
+		if panel.params.y2label.is_some() {
+			gpwr!(file, "unset y2label")?;
+		}
+		for name in units_axes {
+			gpwr!(file, "set format {name} '% h'")?;
+		}
+		if !swimlanes.is_empty() {
+			gpwr!(file, "set ytics autofreq")?;
+		}
+		if panel.shared_yrange.is_some() || !swimlanes.is_empty() {
+			gpwr!(file, "set yrange [*:*]")?;
+		}
+		if panel.y2range().is_some() {
+			gpwr!(file, "set y2range [*:*]")?;
+		}
+		if let Some(AxisScale::Log) = panel.params.y2axis_scale {
+			gpwr!(file, "unset logscale y2")?;
+		}
 	}
 
 	gpwr!(file, "unset multiplot")?;
 	Ok(())
 }
 
+/// Returns the commands drawing the annotation: the vertical line at the time of the match and
+/// the captured text next to it.
+fn annotation_commands(color: Option<Color>, timestamp: NaiveDateTime, text: &str) -> String {
+	let at = timestamp.format("%Y-%m-%dT%H:%M:%S%.3f"); // must match `set timefmt`
+	let color = color.as_ref().map_or("lc rgb \"gray40\"".into(), Color::to_gnuplot);
+	format!(
+		"set arrow from \"{at}\", graph 0 to \"{at}\", graph 1 nohead dt 3 {color} back\n\
+		 set label '{}' at \"{at}\", graph 0.98 right rotate by 90 offset -0.5,0 font 'arial,8' noenhanced front",
+		text.replace('\'', "''")
+	)
+}
+
+/// Returns the gnuplot datablock holding the content of the CSV file.
+///
+/// Blank record (breaking the line) is inserted between consecutive records further apart than
+/// `gap_threshold` (if given).
+fn datablock(
+	name: &str,
+	csv_path: &Path,
+	gap_threshold: Option<GapThreshold>,
+) -> Result<String, Error> {
+	let content = fs::read_to_string(csv_path)?;
+	let mut block = format!("{name} << EOD\n");
+	let mut prev = None;
+	for (i, record) in content.lines().enumerate() {
+		// The first record is the header.
+		if i > 0 {
+			let mut columns = record.splitn(3, ',');
+			let ts = parse_record_timestamp(
+				columns.next().unwrap_or_default(),
+				columns.next().unwrap_or_default(),
+			);
+			let is_gap = |prev, ts| gap_threshold.is_some_and(|t| t.is_gap(prev, ts));
+			if matches!((prev, ts), (Some(prev), Some(ts)) if is_gap(prev, ts)) {
+				block.push('\n');
+			}
+			prev = ts.or(prev);
+		}
+		block.push_str(record);
+		block.push('\n');
+	}
+	block.push_str("EOD");
+	Ok(block)
+}
+
+/// Write a gnuplot script drawing the histogram of given buckets.
+///
+/// Bucket data is embedded into the script. If `cdf` is set, the cumulative distribution is drawn
+/// against the secondary axis. With `log_scale_x` the buckets not entirely positive cannot be drawn
+/// and are skipped (with a warning), but they still count into the cumulative distribution.
+pub fn write_histogram_script(
+	buckets: &[HistoBucket],
+	title: &str,
+	cdf: bool,
+	log_scale_x: bool,
+	output_script_path: &PathBuf,
+	output_image_path: &Path,
+) -> Result<(), Error> {
+	let mut file = File::create(output_script_path)
+		.map_err(|e| Error::ScriptCreationError(output_script_path.clone(), e))?;
+
+	macro_rules! gpwr {
+	    ($dst:expr, $($arg:tt)*) => ({
+	        writeln!($dst, $($arg)*).map_err(|e| Error::ScriptCreationError(output_script_path.clone(),e))
+	    });
+	}
+
+	gpwr!(file, "set terminal pngcairo enhanced font 'arial,10' fontscale 2.0 size 3000, 2000")?;
+	gpwr!(file, "set output '{}'", output_image_path.display())?;
+	gpwr!(file, "set title '{}' noenhanced", title.replace('\'', "''"))?;
+	gpwr!(file, "set datafile separator ','")?;
+	gpwr!(file, "set style fill solid 0.5 border -1")?;
+	gpwr!(file, "set grid xtics ytics")?;
+	gpwr!(file, "set ytics nomirror")?;
+	gpwr!(file, "set key noenhanced")?;
+	gpwr!(file, "set xlabel 'value'")?;
+	gpwr!(file, "set ylabel 'count'")?;
+	gpwr!(file, "set yrange [0:*]")?;
+	if log_scale_x {
+		gpwr!(file, "set logscale x 10")?;
+	}
+
+	let total = buckets.iter().map(|b| b.count).sum::<u64>().max(1);
+	let mut cumulative = 0;
+	gpwr!(file, "$histogram << EOD")?;
+	let mut skipped = 0;
+	for b in buckets {
+		cumulative += b.count;
+		if log_scale_x && b.start <= 0.0 {
+			skipped += b.count;
+			continue;
+		}
+		gpwr!(file, "{},{},{},{}", b.start, b.end, b.count, cumulative as f64 / total as f64)?;
+	}
+	gpwr!(file, "EOD")?;
+	if skipped > 0 {
+		warn!(target:APPV, "{skipped} non-positive values cannot be drawn with logarithmic buckets, skipped.");
+	}
+
+	if cdf {
+		gpwr!(file, "set y2tics nomirror")?;
+		gpwr!(file, "set y2range [0:1]")?;
+		gpwr!(file, "set y2label 'CDF'")?;
+	}
+
+	let center = if log_scale_x { "(sqrt($1*$2))" } else { "(($1+$2)/2)" };
+	write!(file, "plot $histogram using {center}:3:($2-$1) with boxes title 'count'")?;
+	if cdf {
+		write!(file, ", $histogram using 2:4 with lines lw 3 axes x1y2 title 'CDF'")?;
+	}
+	gpwr!(file, "")?;
+	Ok(())
+}
+
 fn path_to_display(path: &Path) -> &Path {
 	let Ok(cwd) = std::env::current_dir() else {
 		return path;
@@ -343,33 +720,294 @@ fn path_to_display(path: &Path) -> &Path {
 	path.strip_prefix(&cwd).unwrap_or(path)
 }
 
+/// Directory the paths in the script are relative to (see `--portable-script`), `None` if the paths
+/// are absolute.
+fn portable_script_dir(context: &GraphFullContext, script_path: &Path) -> Option<PathBuf> {
+	context.portable_script().then(|| {
+		script_path
+			.parent()
+			.filter(|dir| !dir.as_os_str().is_empty())
+			.unwrap_or(Path::new("."))
+			.into()
+	})
+}
+
+/// Copies the CSV file into the data directory of the portable script (see `--portable-script`).
+///
+/// Returns the path of the copy relative to the script.
+fn portable_data_file(script_path: &Path, csv_path: &Path) -> Result<String, Error> {
+	let stem = script_path.file_stem().unwrap_or_default().to_string_lossy();
+	let data_dir = PathBuf::from(format!("{stem}-data"));
+	let file_name = csv_path.file_name().unwrap_or_default();
+	let target = script_path.with_file_name(&data_dir);
+	fs::create_dir_all(&target).map_err(|e| Error::ScriptCreationError(target.clone(), e))?;
+	let target = target.join(file_name);
+	fs::copy(csv_path, &target).map_err(|e| Error::ScriptCreationError(target, e))?;
+	Ok(data_dir.join(file_name).display().to_string())
+}
+
 /// Write gnuplot script and immediately execute it with `gnuplot`.
 pub fn run_gnuplot(config: &ResolvedGraphConfig, context: &GraphFullContext) -> Result<(), Error> {
-	let OutputFilePaths::Gnuplot((image_path, script_path)) = context.get_graph_output_path()
-	else {
-		return Err(Error::IncorrectOutputFiles);
+	let (image_path, script_path) = match context.get_graph_output_path() {
+		OutputFilePaths::Gnuplot(paths) => paths,
+		OutputFilePaths::Stdout => return run_gnuplot_to_stdout(config, context),
+		OutputFilePaths::Plotly(_) => return Err(Error::IncorrectOutputFiles),
+	};
+
+	write_gnuplot_script(config, context, &script_path, Some(&image_path))?;
+	if context.output_graph_ctx.script_only {
+		let script_path = if context.output_graph_ctx.display_absolute_paths {
+			&script_path
+		} else {
+			path_to_display(&script_path)
+		};
+		info!(target:APPV,"Script saved: {}", script_path.display());
+		output_message("script", script_path);
+		return Ok(());
+	}
+	let working_dir = portable_script_dir(context, &script_path);
+	execute_gnuplot_script(
+		script_path,
+		image_path,
+		context.output_graph_ctx.display_absolute_paths,
+		context.output_graph_ctx.do_not_display,
+		context.gnuplot_bin(),
+		working_dir.as_deref(),
+	)
+}
+
+/// Renders the image to stdout (`--output -`).
+///
+/// The script is written to a temporary file, which is removed afterwards. With `--script-only`
+/// (or `PLOX_SKIP_GNUPLOT`), the script itself is written to stdout, so it can be piped to gnuplot.
+fn run_gnuplot_to_stdout(
+	config: &ResolvedGraphConfig,
+	context: &GraphFullContext,
+) -> Result<(), Error> {
+	let script_path = std::env::temp_dir().join(format!("plox-{}.gnuplot", std::process::id()));
+	write_gnuplot_script(config, context, &script_path, None)?;
+
+	let result = if context.output_graph_ctx.script_only
+		|| std::env::var("PLOX_SKIP_GNUPLOT").is_ok()
+	{
+		fs::read(&script_path).map_err(Into::into)
+	} else {
+		let gnuplot_bin = context.gnuplot_bin();
+		Command::new(gnuplot_bin)
+			.arg(&script_path)
+			.output()
+			.map_err(|e| Error::GnuplotCommandNotAvailable(gnuplot_bin.display().to_string(), e))
+			.and_then(|output| {
+				if output.status.success() {
+					report_gnuplot_warnings(&String::from_utf8_lossy(&output.stderr));
+					Ok(output.stdout)
+				} else {
+					Err(Error::GnuplotNonZeroExitCode(
+						output.status,
+						String::new(),
+						String::from_utf8_lossy(&output.stderr).to_string(),
+					))
+				}
+			})
 	};
+	let _ = fs::remove_file(&script_path);
+
+	let mut stdout = io::stdout().lock();
+	stdout.write_all(&result?)?;
+	stdout.flush()?;
+	Ok(())
+}
+
+/// Warning reported by gnuplot while executing the script.
+#[derive(Debug, PartialEq)]
+struct GnuplotWarning {
+	/// Title of the line the warning refers to, if it could be determined.
+	line_title: Option<String>,
+	message: String,
+}
+
+/// Extracts the warnings from the gnuplot stderr.
+///
+/// For plot entries gnuplot echoes the whole `plot` command (continuation lines joined) with the
+/// caret pointing at the offending entry, followed by the `"script" line N: warning: ...` line.
+/// The title of the entry preceding the caret is reported with the warning.
+fn parse_gnuplot_warnings(stderr: &str) -> Vec<GnuplotWarning> {
+	let lines = stderr.lines().collect::<Vec<_>>();
+	let mut warnings = vec![];
+	for (i, line) in lines.iter().enumerate() {
+		let Some((_, message)) = line.split_once("warning: ") else {
+			continue;
+		};
+		let line_title = i
+			.checked_sub(2)
+			.and_then(|echo| {
+				let caret = lines[echo + 1].trim_end();
+				caret.ends_with('^').then(|| (lines[echo], caret.len() - 1))
+			})
+			.and_then(|(echo, caret)| plot_entry_title(echo, caret));
+		warnings.push(GnuplotWarning { line_title, message: message.trim().to_string() });
+	}
+	warnings
+}
+
+/// Returns the title of the plot entry preceding the `caret` position in the echoed `plot` command.
+fn plot_entry_title(command: &str, caret: usize) -> Option<String> {
+	let mut caret = caret.min(command.len());
+	while !command.is_char_boundary(caret) {
+		caret -= 1;
+	}
+	let entry_start = ["csv_data_file_", "$csv_data_"]
+		.iter()
+		.filter_map(|data_ref| command[..caret].rfind(data_ref))
+		.max()?;
+	let entry = &command[entry_start..caret];
+	let (_, title) = entry.split_once(" title '")?;
+	title.split_once('\'').map(|(title, _)| title.to_string())
+}
+
+/// Surfaces the warnings reported by gnuplot (e.g. skipped data files), so silently dropped lines
+/// are noticed.
+fn report_gnuplot_warnings(stderr: &str) {
+	for warning in parse_gnuplot_warnings(stderr) {
+		match warning.line_title {
+			Some(line) => warn!(target:APPV, line, "gnuplot: {}", warning.message),
+			None => warn!(target:APPV, "gnuplot: {}", warning.message),
+		}
+	}
+}
+
+/// Writes the gnuplot script rendering the sample of all predefined colors, markers, dash styles
+/// and plot styles, each entry titled with its name.
+pub fn write_styles_script(
+	output_script_path: &PathBuf,
+	output_image_path: &Path,
+) -> Result<(), Error> {
+	let mut file = File::create(output_script_path)
+		.map_err(|e| Error::ScriptCreationError(output_script_path.clone(), e))?;
 
-	write_gnuplot_script(config, context, &script_path, &image_path)?;
-	let script_path = if context.output_graph_ctx.display_absolute_paths {
+	macro_rules! gpwr {
+	    ($dst:expr, $($arg:tt)*) => ({
+	        writeln!($dst, $($arg)*).map_err(|e| Error::ScriptCreationError(output_script_path.clone(),e))
+	    });
+	}
+
+	gpwr!(file, "set terminal pngcairo enhanced font 'arial,10' fontscale 2.0 size 3000, 2000")?;
+	gpwr!(file, "set output '{}'", output_image_path.display())?;
+	gpwr!(file, "set multiplot layout 2,2")?;
+	gpwr!(file, "set key outside right vertical maxrows 12 noenhanced")?;
+	gpwr!(file, "unset xtics")?;
+	gpwr!(file, "unset ytics")?;
+	gpwr!(file, "set samples 12")?;
+	gpwr!(file, "set xrange [0:1]")?;
+
+	let colors = Color::iter()
+		.map(|c| (c.to_string(), format!("with lines lw 8 {}", c.to_gnuplot())))
+		.collect::<Vec<_>>();
+	let markers = MarkerType::iter()
+		.map(|m| {
+			(value_enum_name(&m), format!("with points {} ps 3 lc rgb \"black\"", m.to_gnuplot()))
+		})
+		.collect::<Vec<_>>();
+	let dash_styles = DashStyle::iter()
+		.map(|d| {
+			(value_enum_name(&d), format!("with lines {} lw 4 lc rgb \"black\"", d.to_gnuplot()))
+		})
+		.collect::<Vec<_>>();
+	let plot_styles = PlotStyle::iter()
+		.map(|p| (value_enum_name(&p), format!("{} ps 2", p.to_gnuplot())))
+		.collect::<Vec<_>>();
+
+	for (title, entries, function) in [
+		("colors", colors, ""),
+		("markers", markers, ""),
+		("dash styles", dash_styles, ""),
+		("plot styles", plot_styles, " + 0.3*sin(2*pi*x)"),
+	] {
+		gpwr!(file, "set title '{title}'")?;
+		gpwr!(file, "set yrange [0:{}]", entries.len() + 1)?;
+		gpwr!(file, "plot \\")?;
+		let count = entries.len();
+		for (k, (name, style)) in entries.into_iter().enumerate() {
+			let separator = if k != count - 1 { ", \\" } else { "" };
+			gpwr!(file, "   {}{function} {style} title '{name}'{separator}", count - k)?;
+		}
+	}
+	gpwr!(file, "unset multiplot")?;
+	Ok(())
+}
+
+/// Writes the gnuplot script rendering the scatter plot of given `(x, y)` pairs.
+pub fn write_scatter_script(
+	pairs: &[(f64, f64)],
+	title: &str,
+	(x_label, y_label): (&str, &str),
+	output_script_path: &PathBuf,
+	output_image_path: &Path,
+) -> Result<(), Error> {
+	let mut file = File::create(output_script_path)
+		.map_err(|e| Error::ScriptCreationError(output_script_path.clone(), e))?;
+
+	macro_rules! gpwr {
+	    ($dst:expr, $($arg:tt)*) => ({
+	        writeln!($dst, $($arg)*).map_err(|e| Error::ScriptCreationError(output_script_path.clone(),e))
+	    });
+	}
+
+	gpwr!(file, "set terminal pngcairo enhanced font 'arial,10' fontscale 2.0 size 3000, 2000")?;
+	gpwr!(file, "set output '{}'", output_image_path.display())?;
+	gpwr!(file, "set title '{}' noenhanced", title.replace('\'', "''"))?;
+	gpwr!(file, "set datafile separator ','")?;
+	gpwr!(file, "set grid xtics ytics")?;
+	gpwr!(file, "set key noenhanced")?;
+	gpwr!(file, "set xlabel '{}' noenhanced", x_label.replace('\'', "''"))?;
+	gpwr!(file, "set ylabel '{}' noenhanced", y_label.replace('\'', "''"))?;
+
+	gpwr!(file, "$scatter << EOD")?;
+	for (x, y) in pairs {
+		gpwr!(file, "{x},{y}")?;
+	}
+	gpwr!(file, "EOD")?;
+	gpwr!(file, "plot $scatter using 1:2 with points pt 7 ps 1 notitle")?;
+	Ok(())
+}
+
+/// Execute the gnuplot script with given gnuplot command and display the generated image (unless
+/// disabled).
+///
+/// If `working_dir` is given, gnuplot is executed there, so paths relative to it (see
+/// `--portable-script`) are resolved.
+pub fn execute_gnuplot_script(
+	script_path: PathBuf,
+	image_path: PathBuf,
+	display_absolute_paths: bool,
+	do_not_display: bool,
+	gnuplot_bin: &Path,
+	working_dir: Option<&Path>,
+) -> Result<(), Error> {
+	let script_path = if display_absolute_paths {
 		script_path
 	} else {
 		path_to_display(&script_path).to_path_buf()
 	};
 	info!(target:APPV,"Script saved: {}", script_path.display());
+	output_message("script", &script_path);
 
 	if std::env::var("PLOX_SKIP_GNUPLOT").is_ok() {
 		info!(target:APPV, "PLOX_SKIP_GNUPLOT is set, skipping gnuplot execution and image generation.");
 		return Ok(());
 	}
 
-	const GNUPLOT_CMD: &str = "gnuplot";
-
-	Command::new(GNUPLOT_CMD)
+	Command::new(gnuplot_bin)
 		.output()
-		.map_err(|e| Error::GnuplotCommandNotAvailable(GNUPLOT_CMD.into(), e))?;
+		.map_err(|e| Error::GnuplotCommandNotAvailable(gnuplot_bin.display().to_string(), e))?;
 
-	let output = Command::new(GNUPLOT_CMD).arg(&script_path).output()?;
+	let output = match working_dir {
+		Some(dir) => Command::new(gnuplot_bin)
+			.current_dir(dir)
+			.arg(script_path.file_name().unwrap_or_default())
+			.output()?,
+		None => Command::new(gnuplot_bin).arg(&script_path).output()?,
+	};
 
 	if !output.status.success() {
 		return Err(Error::GnuplotNonZeroExitCode(
@@ -379,12 +1017,13 @@ pub fn run_gnuplot(config: &ResolvedGraphConfig, context: &GraphFullContext) ->
 		));
 	}
 
-	let image_path = if context.output_graph_ctx.display_absolute_paths {
+	let image_path = if display_absolute_paths {
 		image_path
 	} else {
 		path_to_display(&image_path).to_path_buf()
 	};
 	info!(target:APPV,"Image  saved: {}", image_path.display());
+	output_message("image", &image_path);
 
 	if !output.stdout.is_empty() {
 		debug!(target:APPV,"--- gnuplot stdout ---");
@@ -394,10 +1033,10 @@ pub fn run_gnuplot(config: &ResolvedGraphConfig, context: &GraphFullContext) ->
 	if !output.stderr.is_empty() {
 		debug!(target:APPV,"--- gnuplot stderr ---");
 		debug!(target:APPV,"\n{}", String::from_utf8_lossy(&output.stderr));
+		report_gnuplot_warnings(&String::from_utf8_lossy(&output.stderr));
 	}
 
-	let do_not_open =
-		context.output_graph_ctx.do_not_display || std::env::var("PLOX_DO_NOT_DISPLAY").is_ok();
+	let do_not_open = do_not_display || std::env::var("PLOX_DO_NOT_DISPLAY").is_ok();
 
 	if !do_not_open {
 		let cmd = if let Ok(viewer_cmd_path) = std::env::var("PLOX_IMAGE_VIEWER") {
@@ -424,3 +1063,72 @@ pub fn run_gnuplot(config: &ResolvedGraphConfig, context: &GraphFullContext) ->
 	}
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_gnuplot_warnings() {
+		let command = "plot    csv_data_file_0000 using 1:'value' with lines title 'a',    csv_data_file_0001 using 1:'value' with lines title 'b'";
+		let stderr = format!(
+			"{command}\n{}^\n\
+			 \"graph.gnuplot\" line 47: warning: Skipping data file with no valid points\n\
+			 \"graph.gnuplot\" line 50: warning: some other warning\n",
+			" ".repeat(command.len())
+		);
+		assert_eq!(
+			parse_gnuplot_warnings(&stderr),
+			vec![
+				GnuplotWarning {
+					line_title: Some("b".into()),
+					message: "Skipping data file with no valid points".into()
+				},
+				GnuplotWarning { line_title: None, message: "some other warning".into() },
+			]
+		);
+	}
+
+	#[test]
+	fn test_histogram_script() {
+		let dir = std::env::temp_dir().join(format!("plox-histogram-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let script = dir.join("histogram.gnuplot");
+		let buckets = [
+			HistoBucket { start: -1.0, end: 2.0, count: 2 },
+			HistoBucket { start: 2.0, end: 4.0, count: 1 },
+			HistoBucket { start: 4.0, end: 8.0, count: 1 },
+		];
+		write_histogram_script(&buckets, "it's", true, true, &script, &dir.join("h.png")).unwrap();
+		let content = fs::read_to_string(&script).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(content.contains("set title 'it''s' noenhanced\n"), "{content}");
+		assert!(content.contains("$histogram << EOD\n2,4,1,0.75\n4,8,1,1\nEOD\n"), "{content}");
+	}
+
+	#[test]
+	fn test_scatter_script() {
+		let dir = std::env::temp_dir().join(format!("plox-scatter-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let script = dir.join("scatter.gnuplot");
+		let labels = ("x='a'", "y's");
+		write_scatter_script(&[(1.0, 2.0)], "it's", labels, &script, &dir.join("s.png")).unwrap();
+		let content = fs::read_to_string(&script).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(content.contains("set title 'it''s' noenhanced\n"), "{content}");
+		assert!(content.contains("set xlabel 'x=''a''' noenhanced\n"), "{content}");
+		assert!(content.contains("set ylabel 'y''s' noenhanced\n"), "{content}");
+	}
+
+	#[test]
+	fn test_plot_entry_title() {
+		let command = "plot    csv_data_file_0000 using 1:2 title 'a',    $csv_data_0001 using 1:2 notitle,    csv_data_file_0002 using 1:2 title 'c'";
+		let caret_after = |s: &str| command.find(s).unwrap() + s.len();
+		assert_eq!(plot_entry_title(command, caret_after("title 'a'")), Some("a".into()));
+		assert_eq!(plot_entry_title(command, caret_after("notitle")), None);
+		assert_eq!(plot_entry_title(command, command.len()), Some("c".into()));
+		assert_eq!(plot_entry_title(command, 2), None);
+	}
+}
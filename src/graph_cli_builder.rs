@@ -4,11 +4,15 @@
 //! This complex logic here is necessary because Clap alone cannot support ordered, repeated, multi-flag patterns
 //! like: `--plot ... --panel --event ... --plot ...`.  
 
-use crate::{cli::EXTRA_HELP, data_source_cli_builder::build_data_source_cli, graph_config::*};
+use crate::{
+	cli::EXTRA_HELP, data_source_cli_builder::build_data_source_cli, graph_config::*,
+	utils::expand_path,
+};
 use clap::{
 	Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum,
 	value_parser,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
 	collections::BTreeMap,
@@ -16,7 +20,7 @@ use std::{
 	path::{Path, PathBuf},
 	str::{FromStr, ParseBoolError},
 };
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
 pub const LOG_TARGET: &str = "graph_cli_builder";
 
@@ -40,6 +44,8 @@ pub enum Error {
 	MissingLineDataSource,
 	#[error("Unknown line param {0:?}")]
 	UnknownLineParam(String),
+	#[error("Environment variable '{1}' used in path '{0}' is not set")]
+	UnsetPathVariable(PathBuf, String),
 }
 
 impl From<String> for Error {
@@ -50,7 +56,7 @@ impl From<String> for Error {
 
 /// Helper for deserializing a GraphConfig which may contain extra options from
 /// [`GraphInOutContext`]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct GraphConfigWithContext {
 	#[serde(flatten)]
@@ -67,11 +73,31 @@ impl GraphConfigWithContext {
 			error!(?error, "Reading toml error");
 			crate::error::Error::IoError(format!("{}", path.display()), error)
 		})?;
-		toml::from_str(&content).map_err(|e| {
+		let mut config: Self = toml::from_str(&content).map_err(|e| {
 			let r = annotate_toml_error(&e, &content, &path.display().to_string());
 			error!("{r}");
-			e.into()
-		})
+			crate::error::Error::from(e)
+		})?;
+		config.expand_paths()?;
+		Ok(config)
+	}
+
+	/// Expands `~` and the environment variables (`$VAR`, `${VAR}`) in the paths given in the
+	/// config file, so shared configs do not need user-specific absolute paths.
+	fn expand_paths(&mut self) -> Result<(), Error> {
+		let expand = |path: &mut PathBuf| -> Result<(), Error> {
+			*path = expand_path(path).map_err(|var| Error::UnsetPathVariable(path.clone(), var))?;
+			Ok(())
+		};
+		for path in self.config.paths_mut().chain(self.context.paths_mut()) {
+			expand(path)?;
+		}
+		for label in self.input.input_labels_mut() {
+			let mut path = PathBuf::from(&label.path);
+			expand(&mut path)?;
+			label.path = path.to_string_lossy().into_owned();
+		}
+		Ok(())
 	}
 }
 
@@ -80,6 +106,19 @@ impl GraphConfigWithContext {
 /// This builder allows you to specify the line's data source via [`DataSource`]
 /// (e.g. [`DataSource::FieldValue`], [`DataSource::EventValue`]) and then apply
 /// styling or configuration parameters (e.g. color, axis) via [`LineParams`].
+///
+/// ```
+/// use plox::graph_config::{Color, Line, PlotStyle, YAxis};
+///
+/// let line = Line::builder()
+///     .with_plot_field_line(Some("txpool".into()), "duration".into())
+///     .with_style(PlotStyle::Steps)
+///     .with_line_color(Color::Red)
+///     .with_yaxis(YAxis::Y2)
+///     .build()
+///     .unwrap();
+/// assert_eq!(line.params.line_color, Some(Color::Red));
+/// ```
 #[derive(Debug, Default)]
 pub struct LineBuilder {
 	/// Optional core data source for the line.
@@ -87,9 +126,15 @@ pub struct LineBuilder {
 	params: LineParams,
 }
 
+impl Line {
+	pub fn builder() -> LineBuilder {
+		LineBuilder::new()
+	}
+}
+
 impl LineBuilder {
 	/// Create a new empty builder.
-	fn new() -> Self {
+	pub fn new() -> Self {
 		Self { ..Self::default() }
 	}
 
@@ -118,19 +163,208 @@ impl LineBuilder {
 			LineParam::MarkerSize(w) => self.params.marker_size = w,
 			LineParam::DashStyle(s) => self.params.dash_style = Some(s),
 			LineParam::Title(s) => self.params.title = Some(s),
+			LineParam::GapThreshold(t) => self.params.gap_threshold = Some(t),
+			LineParam::ZOrder(z) => self.params.z_order = Some(z),
+			LineParam::Alpha(a) => self.params.alpha = Some(a),
+			LineParam::SmoothStyle(s) => self.params.smooth_style = Some(s),
+			LineParam::NoLegendEntry(n) => self.params.no_legend_entry = Some(n),
+			LineParam::KeepText(t) => self.params.keep_text = Some(t),
+			LineParam::ValueUnits(u) => self.params.value_units = Some(u),
+			LineParam::ValueMap(m) => self.params.value_map = Some(m),
+			LineParam::Baseline(b) => self.params.baseline = Some(b),
+			LineParam::Normalize(n) => self.params.normalize = Some(n),
+			LineParam::Transforms(t) => self.params.transforms = Some(t),
 		}
 		self
 	}
 
+	/// Sets the data source for this line. See [`DataSource`].
+	pub fn with_data_source(self, data_source: DataSource) -> Self {
+		self.line(data_source)
+	}
+
+	/// Sets [`DataSource::FieldValue`] data source for this line.
+	pub fn with_plot_field_line(self, guard: Option<String>, field: String) -> Self {
+		self.line(DataSource::new_plot_field(guard, field))
+	}
+
+	/// Sets [`DataSource::JsonField`] data source for this line.
+	pub fn with_json_field_line(self, guard: Option<String>, path: String) -> Self {
+		self.line(DataSource::new_json_field(guard, path))
+	}
+
+	/// Sets [`DataSource::LogfmtField`] data source for this line.
+	pub fn with_logfmt_field_line(self, guard: Option<String>, key: String) -> Self {
+		self.line(DataSource::new_logfmt_field(guard, key))
+	}
+
+	/// Sets [`DataSource::CsvFile`] data source for this line.
+	pub fn with_csv_file_line(
+		self,
+		csv_file: PathBuf,
+		time_column: String,
+		value_column: String,
+	) -> Self {
+		self.line(DataSource::new_csv_file(csv_file, time_column, value_column))
+	}
+
+	/// Sets [`DataSource::EventValue`] data source for this line.
+	pub fn with_event_value_line(
+		self,
+		guard: Option<String>,
+		pattern: String,
+		yvalue: f64,
+	) -> Self {
+		self.line(DataSource::new_event_value(guard, pattern, yvalue))
+	}
+
+	/// Sets [`DataSource::EventCount`] data source for this line.
+	pub fn with_event_count_line(self, guard: Option<String>, pattern: String) -> Self {
+		self.line(DataSource::new_event_count(guard, pattern))
+	}
+
+	/// Sets [`DataSource::EventDelta`] data source for this line.
+	pub fn with_event_delta_line(self, guard: Option<String>, pattern: String) -> Self {
+		self.line(DataSource::new_event_delta(guard, pattern))
+	}
+
+	/// Sets [`DataSource::Annotation`] data source for this line.
+	pub fn with_annotation_line(self, guard: Option<String>, pattern: String) -> Self {
+		self.line(DataSource::new_annotation(guard, pattern))
+	}
+
+	/// See: [`LineParams::file_name`]
+	pub fn with_file_name(self, file_name: PathBuf) -> Self {
+		self.apply_param(LineParam::InputFileName(file_name))
+	}
+
+	/// See: [`LineParams::file_id`]
+	pub fn with_file_id(self, file_id: usize) -> Self {
+		self.apply_param(LineParam::InputFileId(file_id))
+	}
+
+	/// See: [`LineParams::title`]
+	pub fn with_title(self, title: String) -> Self {
+		self.apply_param(LineParam::Title(title))
+	}
+
+	/// See: [`LineParams::style`]
+	pub fn with_style(self, style: PlotStyle) -> Self {
+		self.apply_param(LineParam::PlotStyle(style))
+	}
+
+	/// See: [`LineParams::line_width`]
+	pub fn with_line_width(self, line_width: LineWidth) -> Self {
+		self.apply_param(LineParam::LineWidth(line_width))
+	}
+
+	/// See: [`LineParams::line_color`]
+	pub fn with_line_color(self, color: Color) -> Self {
+		self.apply_param(LineParam::LineColor(color))
+	}
+
+	/// See: [`LineParams::dash_style`]
+	pub fn with_dash_style(self, dash_style: DashStyle) -> Self {
+		self.apply_param(LineParam::DashStyle(dash_style))
+	}
+
+	/// See: [`LineParams::yaxis`]
+	pub fn with_yaxis(self, yaxis: YAxis) -> Self {
+		self.apply_param(LineParam::YAxis(yaxis))
+	}
+
+	/// See: [`LineParams::marker_type`]
+	pub fn with_marker_type(self, marker_type: MarkerType) -> Self {
+		self.apply_param(LineParam::MarkerType(marker_type))
+	}
+
+	/// See: [`LineParams::marker_color`]
+	pub fn with_marker_color(self, color: Color) -> Self {
+		self.apply_param(LineParam::MarkerColor(color))
+	}
+
+	/// See: [`LineParams::marker_size`]
+	pub fn with_marker_size(self, marker_size: MarkerSize) -> Self {
+		self.apply_param(LineParam::MarkerSize(marker_size))
+	}
+
+	/// See: [`LineParams::gap_threshold`]
+	pub fn with_gap_threshold(self, gap_threshold: GapThreshold) -> Self {
+		self.apply_param(LineParam::GapThreshold(gap_threshold))
+	}
+
+	/// See: [`LineParams::alpha`]
+	pub fn with_alpha(self, alpha: Alpha) -> Self {
+		self.apply_param(LineParam::Alpha(alpha))
+	}
+
+	/// See: [`LineParams::smooth_style`]
+	pub fn with_smooth_style(self, smooth_style: SmoothStyle) -> Self {
+		self.apply_param(LineParam::SmoothStyle(smooth_style))
+	}
+
+	/// See: [`LineParams::z_order`]
+	pub fn with_z_order(self, z_order: i32) -> Self {
+		self.apply_param(LineParam::ZOrder(z_order))
+	}
+
+	/// See: [`LineParams::no_legend_entry`]
+	pub fn with_no_legend_entry(self, no_legend_entry: bool) -> Self {
+		self.apply_param(LineParam::NoLegendEntry(no_legend_entry))
+	}
+
+	/// See: [`LineParams::keep_text`]
+	pub fn with_keep_text(self, keep_text: MatchedText) -> Self {
+		self.apply_param(LineParam::KeepText(keep_text))
+	}
+
+	/// See: [`LineParams::value_units`]
+	pub fn with_value_units(self, value_units: ValueUnits) -> Self {
+		self.apply_param(LineParam::ValueUnits(value_units))
+	}
+
+	/// See: [`LineParams::value_map`]
+	pub fn with_value_map(self, value_map: ValueMap) -> Self {
+		self.apply_param(LineParam::ValueMap(value_map))
+	}
+
+	/// See: [`LineParams::baseline`]
+	pub fn with_baseline(self, baseline: &str) -> Self {
+		self.apply_param(LineParam::Baseline(baseline.to_string()))
+	}
+
+	/// See: [`LineParams::normalize`]
+	pub fn with_normalize(self, normalize: Normalize) -> Self {
+		self.apply_param(LineParam::Normalize(normalize))
+	}
+
+	/// See: [`LineParams::transforms`]
+	pub fn with_transforms(self, transforms: Vec<Transform>) -> Self {
+		self.apply_param(LineParam::Transforms(Transforms(transforms)))
+	}
+
+	/// See: [`LineParams::label_regex`]
+	pub fn with_label_regex(mut self, label_regex: String) -> Self {
+		self.params.label_regex = Some(label_regex);
+		self
+	}
+
+	/// See: [`LineParams::value_transform`]
+	pub fn with_value_transform(mut self, transform: impl ValueTransform + 'static) -> Self {
+		self.params.value_transform = Some(ValueTransformHook::new(transform));
+		self
+	}
+
 	/// Finalize and return the fully constructed [`Line`], if a data source was set.
 	///
-	/// Returns [`None`] if no [`DataSource`] was specified.
-	fn build(self) -> Result<Line, Error> {
-		if self.params.file_name.is_some() && self.params.file_id.is_some() {
+	/// Returns [`Error::MissingLineDataSource`] if no [`DataSource`] was specified, or
+	/// [`Error::InvalidLineSource`] if both file name and file id were set.
+	pub fn build(self) -> Result<Line, Error> {
+		if let (Some(file_name), Some(file_id)) = (&self.params.file_name, self.params.file_id) {
 			return Err(Error::InvalidLineSource(format!(
 				"file-name {} and file-id {} cannot be used together.",
-				self.params.file_name.unwrap().display(),
-				self.params.file_id.unwrap()
+				file_name.display(),
+				file_id
 			)));
 		}
 		self.line
@@ -156,7 +390,7 @@ impl Panel {
 
 impl PanelBuilder {
 	/// Create a new empty builder.
-	fn new() -> Self {
+	pub fn new() -> Self {
 		Self::default()
 	}
 
@@ -169,8 +403,17 @@ impl PanelBuilder {
 			PanelParam::PanelTitle(t) => self.params.panel_title = Some(t),
 			PanelParam::Height(h) => self.params.height = Some(h),
 			PanelParam::YAxisScale(ys) => self.params.yaxis_scale = Some(ys),
+			PanelParam::Y2Label(l) => self.params.y2label = Some(l),
+			PanelParam::Y2Range(r) => self.params.y2range = Some(r),
+			PanelParam::Y2AxisScale(ys) => self.params.y2axis_scale = Some(ys),
 			PanelParam::Legend(l) => self.params.legend = Some(l),
 			PanelParam::TimeRangeMode(r) => self.params.time_range_mode = Some(r),
+			PanelParam::RepeatFor(r) => self.params.repeat_for = Some(r),
+			PanelParam::RepeatLabels(l) => self.params.repeat_labels = Some(l),
+			PanelParam::StatsBox(s) => self.params.stats_box = Some(s),
+			PanelParam::HLine(v) => self.params.hline = Some(v),
+			PanelParam::HighlightBreaches(h) => self.params.highlight_breaches = Some(h),
+			PanelParam::Swimlanes(s) => self.params.swimlanes = Some(s),
 		}
 		self
 	}
@@ -181,12 +424,155 @@ impl PanelBuilder {
 		self
 	}
 
+	/// Appends the line to the lines contained within panel.
+	pub fn with_line(mut self, line: Line) -> Self {
+		self.lines.push(line);
+		self
+	}
+
+	/// See: [`PanelParams::panel_title`]
+	pub fn with_title(self, title: String) -> Self {
+		self.apply_param(PanelParam::PanelTitle(title))
+	}
+
+	/// See: [`PanelParams::height`]
+	pub fn with_height(self, height: f64) -> Self {
+		self.apply_param(PanelParam::Height(height))
+	}
+
+	/// See: [`PanelParams::yaxis_scale`]
+	pub fn with_yaxis_scale(self, scale: AxisScale) -> Self {
+		self.apply_param(PanelParam::YAxisScale(scale))
+	}
+
+	/// See: [`PanelParams::y2label`]
+	pub fn with_y2label(self, label: String) -> Self {
+		self.apply_param(PanelParam::Y2Label(label))
+	}
+
+	/// See: [`PanelParams::y2range`]
+	pub fn with_y2range(self, range: AxisRange) -> Self {
+		self.apply_param(PanelParam::Y2Range(range))
+	}
+
+	/// See: [`PanelParams::y2axis_scale`]
+	pub fn with_y2axis_scale(self, scale: AxisScale) -> Self {
+		self.apply_param(PanelParam::Y2AxisScale(scale))
+	}
+
+	/// See: [`PanelParams::legend`]
+	pub fn with_legend(self, legend: bool) -> Self {
+		self.apply_param(PanelParam::Legend(legend))
+	}
+
+	/// See: [`PanelParams::time_range_mode`]
+	pub fn with_time_range_mode(self, mode: PanelRangeMode) -> Self {
+		self.apply_param(PanelParam::TimeRangeMode(mode))
+	}
+
+	/// See: [`PanelParams::repeat_for`]
+	pub fn with_repeat_for(self, label_regex: String) -> Self {
+		self.apply_param(PanelParam::RepeatFor(label_regex))
+	}
+
+	/// See: [`PanelParams::repeat_labels`]
+	pub fn with_repeat_labels(self, labels: Vec<String>) -> Self {
+		self.apply_param(PanelParam::RepeatLabels(labels))
+	}
+
+	/// See: [`PanelParams::stats_box`]
+	pub fn with_stats_box(self, stats_box: bool) -> Self {
+		self.apply_param(PanelParam::StatsBox(stats_box))
+	}
+
+	/// See: [`PanelParams::hline`]
+	pub fn with_hline(self, value: f64) -> Self {
+		self.apply_param(PanelParam::HLine(value))
+	}
+
+	/// See: [`PanelParams::highlight_breaches`]
+	pub fn with_highlight_breaches(self, highlight_breaches: bool) -> Self {
+		self.apply_param(PanelParam::HighlightBreaches(highlight_breaches))
+	}
+
+	/// See: [`PanelParams::swimlanes`]
+	pub fn with_swimlanes(self, swimlanes: bool) -> Self {
+		self.apply_param(PanelParam::Swimlanes(swimlanes))
+	}
+
 	/// Finalize and return the constructed [`Panel`].
 	pub fn build(self) -> Panel {
 		Panel { lines: self.lines, params: self.params }
 	}
 }
 
+/// A builder for constructing a [`GraphConfig`] programmatically.
+///
+/// Lines are added to the current panel. A new panel is started with
+/// [`GraphConfigBuilder::with_panel`] or [`GraphConfigBuilder::with_default_panel`].
+///
+/// ```
+/// use plox::graph_config::{GraphConfig, Line, Panel};
+///
+/// let config = GraphConfig::builder()
+///     .with_line(Line::builder().with_plot_field_line(None, "duration".into()).build().unwrap())
+///     .with_panel(Panel::builder().with_title("events".into()).build())
+///     .with_line(Line::builder().with_event_count_line(None, "ERROR".into()).build().unwrap())
+///     .build();
+/// assert_eq!(config.panels.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct GraphConfigBuilder {
+	panels: Vec<Panel>,
+	current_panel: Option<Panel>,
+}
+
+impl GraphConfig {
+	pub fn builder() -> GraphConfigBuilder {
+		GraphConfigBuilder::new()
+	}
+}
+
+impl GraphConfigBuilder {
+	/// Create a new empty builder.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Starts a new panel. Subsequent lines are added to this panel.
+	pub fn with_panel(mut self, panel: Panel) -> Self {
+		if let Some(panel) = self.current_panel.take() {
+			self.panels.push(panel);
+		}
+		self.current_panel = Some(panel);
+		self
+	}
+
+	/// Starts a new panel with default parameters.
+	pub fn with_default_panel(self) -> Self {
+		self.with_panel(Panel { lines: Vec::new(), params: Default::default() })
+	}
+
+	/// Adds the line to the current panel. Panel with default parameters is started if there is
+	/// no current panel.
+	pub fn with_line(mut self, line: Line) -> Self {
+		if let Some(ref mut panel) = self.current_panel {
+			panel.lines.push(line);
+		} else {
+			self.current_panel = Some(Panel { lines: vec![line], params: Default::default() });
+		}
+		self
+	}
+
+	/// Finalize and return the constructed [`GraphConfig`].
+	pub fn build(mut self) -> GraphConfig {
+		if let Some(panel) = self.current_panel {
+			self.panels.push(panel);
+		}
+		GraphConfig { panels: self.panels }
+	}
+}
+
 #[derive(Debug)]
 enum Event {
 	NewPanel,
@@ -235,6 +621,39 @@ enum LineParam {
 
 	/// See: [`LineParams::marker_size`]
 	MarkerSize(MarkerSize),
+
+	/// See: [`LineParams::gap_threshold`]
+	GapThreshold(GapThreshold),
+
+	/// See: [`LineParams::z_order`]
+	ZOrder(i32),
+
+	/// See: [`LineParams::alpha`]
+	Alpha(Alpha),
+
+	/// See: [`LineParams::smooth_style`]
+	SmoothStyle(SmoothStyle),
+
+	/// See: [`LineParams::no_legend_entry`]
+	NoLegendEntry(bool),
+
+	/// See: [`LineParams::keep_text`]
+	KeepText(MatchedText),
+
+	/// See: [`LineParams::value_units`]
+	ValueUnits(ValueUnits),
+
+	/// See: [`LineParams::value_map`]
+	ValueMap(ValueMap),
+
+	/// See: [`LineParams::baseline`]
+	Baseline(String),
+
+	/// See: [`LineParams::normalize`]
+	Normalize(Normalize),
+
+	/// See: [`LineParams::transforms`]
+	Transforms(Transforms),
 }
 
 impl LineParam {
@@ -245,12 +664,25 @@ impl LineParam {
 			"file_id" => Self::InputFileId(val[0].parse::<usize>()?),
 			"style" => Self::PlotStyle(<PlotStyle as ValueEnum>::from_str(&val[0], false)?),
 			"line_width" => Self::LineWidth(LineWidth::from_str(&val[0])?),
-			"line_color" => Self::LineColor(<Color as ValueEnum>::from_str(&val[0], false)?),
+			"line_color" => Self::LineColor(Color::from_str(&val[0])?),
 			"dash_style" => Self::DashStyle(<DashStyle as ValueEnum>::from_str(&val[0], false)?),
 			"yaxis" => Self::YAxis(YAxis::from_str(&val[0], false)?),
 			"marker_type" => Self::MarkerType(<MarkerType as ValueEnum>::from_str(&val[0], false)?),
-			"marker_color" => Self::MarkerColor(<Color as ValueEnum>::from_str(&val[0], false)?),
+			"marker_color" => Self::MarkerColor(Color::from_str(&val[0])?),
 			"marker_size" => Self::MarkerSize(MarkerSize::from_str(&val[0])?),
+			"gap_threshold" => Self::GapThreshold(GapThreshold::from_str(&val[0])?),
+			"z_order" => Self::ZOrder(val[0].parse::<i32>()?),
+			"alpha" => Self::Alpha(Alpha::from_str(&val[0])?),
+			"smooth_style" => {
+				Self::SmoothStyle(<SmoothStyle as ValueEnum>::from_str(&val[0], false)?)
+			},
+			"no_legend_entry" => Self::NoLegendEntry(val[0].parse::<bool>()?),
+			"keep_text" => Self::KeepText(<MatchedText as ValueEnum>::from_str(&val[0], false)?),
+			"value_units" => Self::ValueUnits(<ValueUnits as ValueEnum>::from_str(&val[0], false)?),
+			"value_map" => Self::ValueMap(ValueMap::from_str(&val[0])?),
+			"baseline" => Self::Baseline(val[0].clone()),
+			"normalize" => Self::Normalize(<Normalize as ValueEnum>::from_str(&val[0], false)?),
+			"transforms" => Self::Transforms(Transforms::from_str(&val[0])?),
 			_ => Err(Error::UnknownLineParam(flag.to_string()))?,
 		})
 	}
@@ -267,11 +699,38 @@ enum PanelParam {
 	/// See: [`PanelParams::yaxis_scale`]
 	YAxisScale(AxisScale),
 
+	/// See: [`PanelParams::y2label`]
+	Y2Label(String),
+
+	/// See: [`PanelParams::y2range`]
+	Y2Range(AxisRange),
+
+	/// See: [`PanelParams::y2axis_scale`]
+	Y2AxisScale(AxisScale),
+
 	/// See: [`PanelParams::legend`]
 	Legend(bool),
 
 	/// See: [`PanelParams::time_range_mode`]
 	TimeRangeMode(PanelRangeMode),
+
+	/// See: [`PanelParams::repeat_for`]
+	RepeatFor(String),
+
+	/// See: [`PanelParams::repeat_labels`]
+	RepeatLabels(Vec<String>),
+
+	/// See: [`PanelParams::stats_box`]
+	StatsBox(bool),
+
+	/// See: [`PanelParams::hline`]
+	HLine(f64),
+
+	/// See: [`PanelParams::highlight_breaches`]
+	HighlightBreaches(bool),
+
+	/// See: [`PanelParams::swimlanes`]
+	Swimlanes(bool),
 }
 
 impl PanelParam {
@@ -280,8 +739,17 @@ impl PanelParam {
 			"panel_title" => Self::PanelTitle(val[0].to_string()),
 			"height" => Self::Height(val[0].parse::<f64>()?),
 			"yaxis_scale" => Self::YAxisScale(AxisScale::from_str(&val[0], false)?),
+			"y2label" => Self::Y2Label(val[0].to_string()),
+			"y2range" => Self::Y2Range(AxisRange::from_str(&val[0])?),
+			"y2axis_scale" => Self::Y2AxisScale(AxisScale::from_str(&val[0], false)?),
 			"legend" => Self::Legend(val[0].parse::<bool>()?),
 			"time_range_mode" => Self::TimeRangeMode(PanelRangeMode::from_str(&val[0], false)?),
+			"repeat_for" => Self::RepeatFor(val[0].to_string()),
+			"repeat_labels" => Self::RepeatLabels(val[0].split(',').map(Into::into).collect()),
+			"stats_box" => Self::StatsBox(val[0].parse::<bool>()?),
+			"hline" => Self::HLine(val[0].parse::<f64>()?),
+			"highlight_breaches" => Self::HighlightBreaches(val[0].parse::<bool>()?),
+			"swimlanes" => Self::Swimlanes(val[0].parse::<bool>()?),
 			_ => Err(Error::UnknownPanelParam(flag.to_string()))?,
 		})
 	}
@@ -583,10 +1051,13 @@ Supports:
 		);
 	const ENV_HELP: &str = color_print::cstr!(
 		r#"<bold><underline>Environment variables:</underline></bold>
-There are two environment variables controlling behaviour of graph command:
+The following environment variables control the behaviour of graph command:
 - `PLOX_IMAGE_VIEWER` - the name (or path) of the executable that will be used to display image generated by `gnuplot`.
 - `PLOX_BROWSER` - the name (or path) of the executable that will be used to display html generated by plotly backend.
 - `PLOX_SKIP_GNUPLOT` - if set, the gnuplot image generation will not be executed, only gnuplot script will be saved.
+- `PLOX_JOURNALCTL` - the name (or path) of the executable used to read `journal:<<UNIT>` inputs (default: `journalctl`).
+- `PLOX_KUBECTL` - the name (or path) of the executable used to read `k8s://<<NAMESPACE>/<<POD>` inputs (default: `kubectl`).
+- `PLOX_DOCKER` - the name (or path) of the executable used to read `docker://<<CONTAINER>` inputs (default: `docker`).
 "#
 	);
 	graph_config_cli.after_long_help(ENV_HELP.to_string() + EXTRA_HELP)
@@ -611,10 +1082,138 @@ pub fn build_from_matches(
 	} else {
 		GraphConfig::try_from_matches(matches)?
 	};
+	full_graph_context.resolve_time_window();
+	full_graph_context.resample().map_err(Error::GeneralCliParseError)?;
 
 	Ok((config, full_graph_context))
 }
 
+/// Loads the graph config (and the context stored with it) from the TOML file, as `--config`
+/// does.
+///
+/// Values already set in the given `context` (e.g. on the command line) take precedence over the
+/// ones read from the file.
+pub fn load_config_file(
+	config_path: &Path,
+	mut context: GraphFullContext,
+) -> Result<(GraphConfig, GraphFullContext), crate::error::Error> {
+	let GraphConfigWithContext { config, context: output_graph_ctx, input } =
+		GraphConfigWithContext::load_from_file(config_path)?;
+	context.merge_with_other(GraphFullContext { input_files_ctx: input, output_graph_ctx });
+	context.resolve_time_window();
+	Ok((config, context))
+}
+
+/// Returns the JSON Schema of the graph config file (pretty-printed).
+///
+/// Editors use it for the completion and validation of hand-written config files.
+pub fn config_json_schema() -> String {
+	let mut schema = schemars::schema_for!(GraphConfigWithContext);
+	schema.insert("title".into(), "plox graph config".into());
+	schema.insert("description".into(), "Graph config file (`plox graph --config`).".into());
+	serde_json::to_string_pretty(&schema).expect("schema serialization shall not fail")
+}
+
+/// Returns the arguments of `plox graph` command equivalent to the graph config file.
+///
+/// Context options (e.g. `timestamp_format`) are given as written in the file, only the panel and
+/// line params differing from the defaults are given.
+pub fn config_file_to_cli_args(config_path: &Path) -> Result<Vec<String>, crate::error::Error> {
+	let GraphConfigWithContext { config, .. } =
+		GraphConfigWithContext::load_from_file(config_path)?;
+	let content = std::fs::read_to_string(config_path)
+		.map_err(|e| crate::error::Error::IoError(format!("{}", config_path.display()), e))?;
+	let mut context: toml::Table = toml::from_str(&content)?;
+	context.remove("panels");
+	Ok(config_to_cli_args(&config, &context))
+}
+
+/// Returns the arguments of `plox graph` command building the `config`, preceded by the options
+/// given in the `context` table.
+fn config_to_cli_args(config: &GraphConfig, context: &toml::Table) -> Vec<String> {
+	let graph_cli = build_cli();
+	let mut args = vec![];
+	let context = context.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+	push_cli_args(&mut args, &graph_cli, context);
+	for (i, panel) in config.panels.iter().enumerate() {
+		if i > 0 {
+			args.push("--panel".into());
+		}
+		push_cli_args(&mut args, &graph_cli, non_default_fields(&panel.params));
+		for line in &panel.lines {
+			args.extend(line.data_source.cli_args());
+			push_cli_args(&mut args, &graph_cli, non_default_fields(&line.params));
+		}
+	}
+	args
+}
+
+/// Appends the options of `graph_cli` corresponding to the config fields, each followed by its
+/// value. Options are ordered as in the help, the value of flags is omitted if it is `true`.
+fn push_cli_args(
+	args: &mut Vec<String>,
+	graph_cli: &Command,
+	mut fields: Vec<(String, toml::Value)>,
+) {
+	let find_arg = |key: &str| graph_cli.get_arguments().position(|arg| arg.get_id() == key);
+	fields.sort_by_key(|(key, _)| find_arg(key));
+	for (key, value) in fields {
+		let Some((arg, long)) = find_arg(&key)
+			.and_then(|i| graph_cli.get_arguments().nth(i))
+			.and_then(|arg| arg.get_long().map(|long| (arg, long)))
+		else {
+			warn!("Config key '{key}' has no command line equivalent, skipped.");
+			continue;
+		};
+		args.push(format!("--{long}"));
+		let is_flag = arg.get_num_args().is_some_and(|num_args| num_args.min_values() == 0);
+		if value != toml::Value::Boolean(true) || !is_flag {
+			args.push(cli_value(&key, &value));
+		}
+	}
+}
+
+/// Formats the config value as accepted by the corresponding command line option.
+fn cli_value(key: &str, value: &toml::Value) -> String {
+	match value {
+		toml::Value::String(value) => value.clone(),
+		toml::Value::Array(values) => {
+			values.iter().map(|value| cli_value(key, value)).collect::<Vec<_>>().join(",")
+		},
+		toml::Value::Table(_) if key == "value_map" => value
+			.clone()
+			.try_into::<ValueMap>()
+			.map(|map| map.to_string())
+			.unwrap_or_default(),
+		toml::Value::Table(_) if key == "inputs" => value
+			.clone()
+			.try_into::<InputLabel>()
+			.map(|label| label.to_string())
+			.unwrap_or_default(),
+		toml::Value::Table(_) => value
+			.clone()
+			.try_into::<AxisRange>()
+			.map(|range| range.to_string())
+			.unwrap_or_default(),
+		value => value.to_string(),
+	}
+}
+
+/// Joins the arguments into the command line, quoting them for the shell where needed.
+pub fn shell_join(args: &[String]) -> String {
+	let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=,@%+".contains(c);
+	args.iter()
+		.map(|arg| {
+			if !arg.is_empty() && arg.chars().all(is_safe) {
+				arg.clone()
+			} else {
+				format!("'{}'", arg.replace('\'', r"'\''"))
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
 /// Intended to be used in test.
 #[cfg(test)]
 pub fn build_from_cli_args(
@@ -630,73 +1229,9 @@ mod tests {
 	use crate::logging::init_tracing_test;
 
 	use super::*;
+	use crate::graph_config::{ByteSize, FoldPeriod};
 	use std::path::Path;
-
-	pub struct GraphConfigBuilder {
-		panels: Vec<Panel>,
-		current_panel: Option<Panel>,
-	}
-
-	impl GraphConfigBuilder {
-		pub fn new() -> Self {
-			GraphConfigBuilder { panels: Vec::new(), current_panel: None }
-		}
-
-		pub fn with_panel(mut self, panel: Panel) -> Self {
-			if let Some(panel) = self.current_panel.take() {
-				self.panels.push(panel);
-			}
-			self.current_panel = Some(panel);
-			self
-		}
-
-		pub fn with_default_panel(mut self) -> Self {
-			if let Some(panel) = self.current_panel.take() {
-				self.panels.push(panel);
-			}
-			self.current_panel = Some(Panel { lines: Vec::new(), params: Default::default() });
-			self
-		}
-
-		pub fn with_line(mut self, line: Line) -> Self {
-			if let Some(ref mut panel) = self.current_panel {
-				panel.lines.push(line);
-			} else {
-				// If there's no current panel, start a new one and add the line
-				self.current_panel = Some(Panel { lines: vec![line], params: Default::default() });
-			}
-			self
-		}
-
-		pub fn build(mut self) -> GraphConfig {
-			if let Some(panel) = self.current_panel {
-				self.panels.push(panel);
-			}
-			GraphConfig { panels: self.panels }
-		}
-	}
-
-	impl LineBuilder {
-		pub fn with_event_count_line(mut self, guard: Option<String>, pattern: String) -> Self {
-			self.line = Some(DataSource::EventCount { guard, pattern });
-			self
-		}
-
-		pub fn with_event_value_line(
-			mut self,
-			guard: Option<String>,
-			pattern: String,
-			yvalue: f64,
-		) -> Self {
-			self.line = Some(DataSource::EventValue { guard, pattern, yvalue });
-			self
-		}
-
-		pub fn with_plot_field_line(mut self, guard: Option<String>, field: String) -> Self {
-			self.line = Some(DataSource::FieldValue(FieldCaptureSpec { guard, field }));
-			self
-		}
-	}
+	use strum::IntoEnumIterator;
 
 	#[test]
 	fn test_01() {
@@ -1132,6 +1667,645 @@ mod tests {
 		)
 	}
 
+	#[test]
+	fn test_15_y2axis() {
+		check_ok(
+			vec![
+				"--y2label",
+				"latency [ms]",
+				"--y2range",
+				"1,*",
+				"--y2axis-scale",
+				"log",
+				"--event",
+				"duration",
+				"666.0",
+			],
+			"tests/test-files/config15.toml",
+			GraphConfigBuilder::new()
+				.with_panel(
+					PanelBuilder::new()
+						.with_y2label("latency [ms]".into())
+						.with_y2range(AxisRange { min: Some(1.0), max: None })
+						.with_y2axis_scale(AxisScale::Log)
+						.build(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_event_value_line(None, "duration".into(), 666.0)
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_16_gap_threshold() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--gap-threshold", "2m", "--plot", "om_module", "y"],
+			"tests/test-files/config16.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_gap_threshold(GapThreshold(120.0))
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "y".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_17_value_units() {
+		check_ok(
+			vec![
+				"--plot",
+				"om_module",
+				"size",
+				"--value-units",
+				"original",
+				"--yaxis",
+				"y2",
+				"--plot",
+				"om_module",
+				"duration",
+			],
+			"tests/test-files/config17.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "size".into())
+						.with_value_units(ValueUnits::Original)
+						.with_yaxis(YAxis::Y2)
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "duration".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_18_value_map() {
+		check_ok(
+			vec!["--plot", "worker", "state", "--value-map", "idle=0,busy=1,error=2"],
+			"tests/test-files/config18.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("worker".into()), "state".into())
+						.with_value_map(ValueMap(BTreeMap::from([
+							("idle".into(), 0.0),
+							("busy".into(), 1.0),
+							("error".into(), 2.0),
+						])))
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_19_annotation() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--annotate", "startup", r"version=(\S+)"],
+			"tests/test-files/config19.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_annotation_line(Some("startup".into()), r"version=(\S+)".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_20_z_order() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--z-order", "-1", "--plot", "om_module", "y"],
+			"tests/test-files/config20.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_z_order(-1)
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "y".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_21_no_legend_entry() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--no-legend-entry", "--plot", "om_module", "y"],
+			"tests/test-files/config21.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_no_legend_entry(true)
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "y".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		)
+	}
+
+	#[test]
+	fn test_22_alpha() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--alpha", "0.25"],
+			"tests/test-files/config22.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_alpha(Alpha(0.25))
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+		assert!(Alpha::from_str("1.5").is_err());
+	}
+
+	#[test]
+	fn test_24_smooth_style() {
+		check_ok(
+			vec!["--plot", "om_module", "x", "--style", "lines", "--smooth-style", "csplines"],
+			"tests/test-files/config24.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_style(PlotStyle::Lines)
+						.with_smooth_style(SmoothStyle::Csplines)
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_25_stats_box() {
+		check_ok(
+			vec!["--stats-box", "--plot", "om_module", "x"],
+			"tests/test-files/config25.toml",
+			GraphConfigBuilder::new()
+				.with_panel(PanelBuilder::new().with_stats_box(true).build())
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_26_highlight_breaches() {
+		check_ok(
+			vec!["--hline", "-2.5", "--highlight-breaches", "--plot", "om_module", "x"],
+			"tests/test-files/config26.toml",
+			GraphConfigBuilder::new()
+				.with_panel(
+					PanelBuilder::new().with_hline(-2.5).with_highlight_breaches(true).build(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_29_baseline() {
+		check_ok(
+			vec![
+				"--plot",
+				"duration",
+				"--title",
+				"reference",
+				"--plot",
+				"duration",
+				"--file-id",
+				"1",
+				"--baseline",
+				"reference",
+			],
+			"tests/test-files/config29.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(None, "duration".into())
+						.with_title("reference".into())
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(None, "duration".into())
+						.with_file_id(1)
+						.with_baseline("reference")
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_30_normalize() {
+		check_ok(
+			vec!["--plot", "x", "--normalize", "max", "--plot", "y", "--normalize", "zscore"],
+			"tests/test-files/config30.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(None, "x".into())
+						.with_normalize(Normalize::Max)
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(None, "y".into())
+						.with_normalize(Normalize::Zscore)
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_31_transforms() {
+		check_ok(
+			vec!["--plot", "x", "--transforms", "smooth(5s),scale(0.001),clip(0,100)"],
+			"tests/test-files/config31.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(None, "x".into())
+						.with_transforms(vec![
+							Transform::Smooth(5.0),
+							Transform::Scale(0.001),
+							Transform::Clip(Some(0.0), Some(100.0)),
+						])
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_28_swimlanes() {
+		check_ok(
+			vec!["--swimlanes", "--event", "om_module", "started", "1"],
+			"tests/test-files/config28.toml",
+			GraphConfigBuilder::new()
+				.with_panel(PanelBuilder::new().with_swimlanes(true).build())
+				.with_line(
+					LineBuilder::new()
+						.with_event_value_line(Some("om_module".into()), "started".into(), 1.0)
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_27_named_data_source_params() {
+		check_ok(
+			vec![
+				"--plot",
+				"field=x",
+				"guard=om_module",
+				"--event",
+				"pattern=started",
+				"yvalue=2.5",
+				"--json-field",
+				"guard=level=INFO",
+				"path=fields.duration",
+				"--json-field",
+				"level=INFO",
+				"fields.duration",
+				"--csv-file",
+				"value-column=b",
+				"time_column=a",
+				"csv_file=some.csv",
+			],
+			"tests/test-files/config27.toml",
+			GraphConfigBuilder::new()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_event_value_line(None, "started".into(), 2.5)
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_json_field_line(Some("level=INFO".into()), "fields.duration".into())
+						.build()
+						.unwrap(),
+				)
+				// Not all values are named, so all are positional.
+				.with_line(
+					LineBuilder::new()
+						.with_json_field_line(Some("level=INFO".into()), "fields.duration".into())
+						.build()
+						.unwrap(),
+				)
+				.with_line(
+					LineBuilder::new()
+						.with_csv_file_line("some.csv".into(), "a".into(), "b".into())
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "Missing parameter 'field' for --plot.")]
+	fn test_e04_named_data_source_params_missing() {
+		check_err(vec!["--plot", "guard=om_module"])
+	}
+
+	#[test]
+	#[should_panic(expected = "Parameter 'guard' given more than once for --plot.")]
+	fn test_e05_named_data_source_params_duplicated() {
+		check_err(vec!["--plot", "guard=om_module", "guard=x"])
+	}
+
+	#[test]
+	fn test_23_rgb_color() {
+		check_ok(
+			vec![
+				"--plot",
+				"om_module",
+				"x",
+				"--line-color",
+				"#ff8800",
+				"--marker-color",
+				"steel-blue",
+			],
+			"tests/test-files/config23.toml",
+			GraphConfigBuilder::new()
+				.with_default_panel()
+				.with_line(
+					LineBuilder::new()
+						.with_plot_field_line(Some("om_module".into()), "x".into())
+						.with_line_color(Color::Rgb(0xff8800))
+						.with_marker_color(Color::SteelBlue)
+						.build()
+						.unwrap(),
+				)
+				.build(),
+		);
+	}
+
+	#[test]
+	fn test_color_parse() {
+		assert_eq!(Color::from_str("dark-green"), Ok(Color::DarkGreen));
+		assert_eq!(Color::from_str("#00FF7f"), Ok(Color::Rgb(0x00ff7f)));
+		assert_eq!(Color::Rgb(0x0000ff).to_string(), "#0000ff");
+		assert!(Color::from_str("#fff").is_err());
+		assert!(Color::from_str("#gg0000").is_err());
+		assert!(Color::from_str("no-such-color").is_err());
+		assert!(Color::iter().all(|color| !matches!(color, Color::Rgb(_))));
+	}
+
+	#[test]
+	fn test_value_map_parse() {
+		let map = ValueMap::from_str("true=1, false = 0").unwrap();
+		assert_eq!(map.get("true"), Some(1.0));
+		assert_eq!(map.get("false"), Some(0.0));
+		assert_eq!(map.get("maybe"), None);
+		assert_eq!(map.to_string(), "false=0,true=1");
+		assert!(ValueMap::from_str("true").is_err());
+		assert!(ValueMap::from_str("true=yes").is_err());
+		assert!(ValueMap::from_str("=1").is_err());
+	}
+
+	#[test]
+	fn test_concat_input() {
+		let input = "logs/app.log,logs/app.log.1,logs/app.log.2.gz";
+		let (_, context) =
+			build_from_cli_args(vec!["--input", input, "--concat", "--plot", "a", "b"]).unwrap();
+		let ctx = &context.input_files_ctx;
+		let virtual_input = PathBuf::from("logs/app.log.concat");
+		assert_eq!(ctx.logical_input(), vec![virtual_input.clone()]);
+		assert_eq!(ctx.input_parts(&virtual_input), *ctx.input());
+		assert_eq!(ctx.input_parts(Path::new("logs/app.log")), vec![PathBuf::from("logs/app.log")]);
+
+		let (_, context) = build_from_cli_args(vec!["--input", input, "--plot", "a", "b"]).unwrap();
+		assert_eq!(context.input_files_ctx.logical_input(), *context.input());
+		assert_eq!(context.input_files_ctx.concat_input_path(), None);
+	}
+
+	#[test]
+	fn test_minimal_config() {
+		let (config, _) = build_from_cli_args(vec![
+			"--input",
+			"some.log",
+			"--plot",
+			"om_module",
+			"x",
+			"--line-color",
+			"red",
+			"--panel-title",
+			"T",
+		])
+		.unwrap();
+		let toml = config.to_minimal_toml();
+		assert_eq!(toml::from_str::<GraphConfig>(&toml).unwrap(), config);
+		assert!(toml.contains("# Optional guard string"));
+		assert!(toml.contains("guard = \"om_module\""));
+		assert!(toml.contains("line_color = \"red\""));
+		assert!(toml.contains("panel_title = \"T\""));
+		assert!(!toml.contains("marker_size"));
+	}
+
+	#[test]
+	fn test_config_to_cli_args() {
+		let check_round_trip = |args: Vec<&'static str>| {
+			let (config, _) = build_from_cli_args(args).unwrap();
+			let args = config_to_cli_args(&config, &toml::Table::new());
+			let matches = build_cli()
+				.try_get_matches_from(["graph"].into_iter().map(String::from).chain(args.clone()))
+				.unwrap();
+			assert_eq!(build_from_matches(&matches).unwrap().0, config, "{args:?}");
+			args
+		};
+		check_round_trip(test_14_input());
+		check_round_trip(vec!["--plot", "c", "--y2range", "*,10", "--no-legend-entry"]);
+		let args = check_round_trip(vec![
+			"--plot",
+			"worker",
+			"state",
+			"--value-map",
+			"idle=0,busy=1",
+			"--panel",
+			"--repeat-for",
+			r"peer=(\w+)",
+			"--repeat-labels",
+			"a,b",
+			"--event-count",
+			"x y",
+		]);
+		assert_eq!(
+			shell_join(&args),
+			r"--plot worker state --value-map busy=1,idle=0 --panel --repeat-for 'peer=(\w+)' --repeat-labels a,b --event-count 'x y'"
+		);
+
+		let context = toml::from_str(
+			r#"
+			per_file_panels = true
+			timestamp_format = "%s"
+			inputs = [{ path = "0", label = "a" }, { path = "x.log", label = "b" }]
+			"#,
+		)
+		.unwrap();
+		let (config, _) = build_from_cli_args(vec!["--plot", "it's", "d"]).unwrap();
+		assert_eq!(
+			shell_join(&config_to_cli_args(&config, &context)),
+			r"--input-label 0=a,x.log=b --timestamp-format %s --per-file-panels --plot 'it'\''s' d"
+		);
+	}
+
+	#[test]
+	fn test_gap_threshold_parse() {
+		assert_eq!(GapThreshold::from_str("90"), Ok(GapThreshold(90.0)));
+		assert_eq!(GapThreshold::from_str("500ms"), Ok(GapThreshold(0.5)));
+		assert_eq!(GapThreshold::from_str("1.5h"), Ok(GapThreshold(5400.0)));
+		assert!(GapThreshold::from_str("0s").is_err());
+		assert!(GapThreshold::from_str("5d").is_err());
+	}
+
+	#[test]
+	fn test_fold_period_parse() {
+		assert_eq!(FoldPeriod::from_str("24h"), Ok(FoldPeriod(86400.0)));
+		assert_eq!(FoldPeriod::from_str("7d"), Ok(FoldPeriod(604800.0)));
+		assert_eq!(FoldPeriod::from_str("90"), Ok(FoldPeriod(90.0)));
+		assert!(FoldPeriod::from_str("0d").is_err());
+	}
+
+	#[test]
+	fn test_byte_size_parse() {
+		assert_eq!(ByteSize::from_str("2G"), Ok(ByteSize(2 << 30)));
+		assert_eq!(ByteSize::from_str("512MiB"), Ok(ByteSize(512 << 20)));
+		assert_eq!(ByteSize::from_str("1.5k"), Ok(ByteSize(1536)));
+		assert_eq!(ByteSize::from_str("100"), Ok(ByteSize(100)));
+		assert_eq!(ByteSize(2 << 30).to_string(), "2G");
+		assert_eq!(ByteSize(1536).to_string(), "1536");
+		assert!(ByteSize::from_str("2P").is_err());
+		assert!(ByteSize::from_str("-1G").is_err());
+		assert!(ByteSize::from_str("G").is_err());
+	}
+
+	#[test]
+	fn test_transforms_parse() {
+		let transforms = "smooth(1m), resample(500ms,ffill),offset(-2),clip(,5),normalize(zscore)";
+		let expected = Transforms(vec![
+			Transform::Smooth(60.0),
+			Transform::Resample(Resample { interval: 0.5, policy: ResamplePolicy::Ffill }),
+			Transform::Offset(-2.0),
+			Transform::Clip(None, Some(5.0)),
+			Transform::Normalize(Normalize::Zscore),
+		]);
+		assert_eq!(Transforms::from_str(transforms), Ok(expected.clone()));
+		assert_eq!(Transforms::from_str(&expected.to_string()), Ok(expected));
+		assert!(Transforms::from_str("smooth(0s)").is_err());
+		assert!(Transforms::from_str("scale(1,2)").is_err());
+		assert!(Transforms::from_str("square(2)").is_err());
+		assert!(Transforms::from_str("scale(2").is_err());
+	}
+
+	#[test]
+	fn test_resample_parse() {
+		let parse = |args: &[&str]| {
+			Resample::try_from(&args.iter().map(ToString::to_string).collect::<Vec<_>>()[..])
+		};
+		assert_eq!(parse(&["1s"]), Ok(Resample { interval: 1.0, policy: ResamplePolicy::Linear }));
+		assert_eq!(
+			parse(&["500ms", "ffill"]),
+			Ok(Resample { interval: 0.5, policy: ResamplePolicy::Ffill })
+		);
+		assert_eq!(
+			parse(&["1m", "drop"]),
+			Ok(Resample { interval: 60.0, policy: ResamplePolicy::Drop })
+		);
+		assert!(parse(&["1s", "nearest"]).is_err());
+		assert!(parse(&["ffill"]).is_err());
+	}
+
+	#[test]
+	#[should_panic(expected = "min (5) shall be less than max (1)")]
+	fn test_e03() {
+		check_err(vec!["--y2range", "5,1", "--event", "duration", "666.0"])
+	}
+
 	#[test]
 	#[should_panic(expected = "invalid value")]
 	fn test_e00() {
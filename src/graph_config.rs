@@ -4,19 +4,23 @@
 //! These configs, usually written in TOML (or provided as CLI options), describe panels, fields, and layout choices.
 //! This module handles parsing them into Rust types and preparing them for further processing.
 
-use crate::{error::Error, utils::common_path_ancestor};
+use crate::{backend::Backend, error::Error, utils::common_path_ancestor};
 use annotate_snippets::{Level, Renderer, Snippet};
 use chrono::NaiveDateTime;
 use clap::{Args, Subcommand, ValueEnum};
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::{
 	borrow::Cow,
+	collections::BTreeMap,
 	fmt::Display,
 	fs,
+	num::NonZeroUsize,
 	path::{Path, PathBuf},
 	str::FromStr,
+	sync::Arc,
 };
-use strum::EnumIter;
+use strum::{AsRefStr, EnumIter, IntoEnumIterator};
 use toml::de::Error as TomlError;
 use tracing::{error, info};
 
@@ -24,7 +28,7 @@ use tracing::{error, info};
 ///
 /// Each [`Panel`] is drawn horizontally in the final output, and each
 /// panel may contain multiple lines of data.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct GraphConfig {
 	/// The list of panels in this graph.
 	pub panels: Vec<Panel>,
@@ -37,11 +41,121 @@ pub const DEFAULT_TIMESTAMP_STR: &str = "%Y-%m-%d %H:%M:%S%.3f";
 pub const DEFAULT_TIMESTAMP_FORMAT: TimestampFormat =
 	TimestampFormat::DateTime(Cow::Borrowed(DEFAULT_TIMESTAMP_STR));
 
+/// Default format of the time tick labels.
+pub const DEFAULT_XTICS_FORMAT: &str = "%H:%M:%S";
+
+/// The format of the timestamp in the systemd journal entries (`journalctl -o short-iso-precise`)
+/// and in the container logs (`kubectl logs --timestamps`, `docker logs --timestamps`).
+///
+/// The UTC offset following the timestamp is not parsed, timestamps are kept as logged.
+pub const JOURNAL_TIMESTAMP_STR: &str = "%Y-%m-%dT%H:%M:%S%.f";
+pub const JOURNAL_TIMESTAMP_FORMAT: TimestampFormat =
+	TimestampFormat::DateTime(Cow::Borrowed(JOURNAL_TIMESTAMP_STR));
+
+/// Prefix of the input reading the systemd journal of the unit, e.g. `journal:nginx`.
+pub const JOURNAL_INPUT_PREFIX: &str = "journal:";
+
+/// Prefix of the input reading the logs of the Kubernetes pod, e.g. `k8s://default/nginx-7d9f`.
+pub const K8S_INPUT_PREFIX: &str = "k8s://";
+
+/// Prefix of the input reading the logs of the docker container, e.g. `docker://nginx`.
+pub const DOCKER_INPUT_PREFIX: &str = "docker://";
+
+/// Input which is not a file, but the output of an external command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandInput<'a> {
+	/// The systemd journal of the unit: `journal:<UNIT>`.
+	Journal { unit: &'a str },
+	/// The logs of the Kubernetes pod: `k8s://<NAMESPACE>/<POD>[/<CONTAINER>]`.
+	Kubernetes { namespace: &'a str, pod: &'a str, container: Option<&'a str> },
+	/// The logs of the docker container: `docker://<CONTAINER>`.
+	Docker { container: &'a str },
+}
+
+impl<'a> CommandInput<'a> {
+	/// Parses the input, returns `None` if it is a regular file.
+	pub fn parse(input: &'a Path) -> Option<Self> {
+		let input = input.to_str()?;
+		let non_empty = |s: &&str| !s.is_empty();
+		if let Some(unit) = input.strip_prefix(JOURNAL_INPUT_PREFIX).filter(non_empty) {
+			return Some(Self::Journal { unit });
+		}
+		if let Some(container) = input.strip_prefix(DOCKER_INPUT_PREFIX).filter(non_empty) {
+			return Some(Self::Docker { container });
+		}
+		let mut parts = input.strip_prefix(K8S_INPUT_PREFIX)?.split('/');
+		match (parts.next(), parts.next(), parts.next(), parts.next()) {
+			(Some(namespace), Some(pod), container, None)
+				if non_empty(&namespace)
+					&& non_empty(&pod)
+					&& container.is_none_or(|c| !c.is_empty()) =>
+			{
+				Some(Self::Kubernetes { namespace, pod, container })
+			},
+			_ => None,
+		}
+	}
+
+	/// The executable reading the input, and the environment variable overriding it.
+	pub fn program(&self) -> (&'static str, &'static str) {
+		match self {
+			Self::Journal { .. } => ("journalctl", "PLOX_JOURNALCTL"),
+			Self::Kubernetes { .. } => ("kubectl", "PLOX_KUBECTL"),
+			Self::Docker { .. } => ("docker", "PLOX_DOCKER"),
+		}
+	}
+
+	/// Arguments of the [`program`](Self::program) printing the timestamped log entries.
+	///
+	/// If `since` is given, only the entries newer than this duration (e.g. `2h`) are printed.
+	pub fn args(&self, since: Option<&str>) -> Vec<String> {
+		let mut args: Vec<String> = match self {
+			Self::Journal { unit } => ["--unit", unit, "--output", "short-iso-precise"]
+				.into_iter()
+				.chain(["--no-pager", "--quiet"])
+				.map(Into::into)
+				.collect(),
+			Self::Kubernetes { namespace, pod, container } => ["logs", "--namespace", namespace]
+				.into_iter()
+				.chain([*pod, "--timestamps"])
+				.chain(container.iter().flat_map(|c| ["--container", c]))
+				.map(Into::into)
+				.collect(),
+			Self::Docker { container } => {
+				["logs", "--timestamps", container].into_iter().map(Into::into).collect()
+			},
+		};
+		if let Some(since) = since {
+			args.push(match self {
+				// Relative time of `journalctl` is given as the negative offset.
+				Self::Journal { .. } => format!("--since=-{since}"),
+				_ => format!("--since={since}"),
+			});
+		}
+		args
+	}
+
+	/// The name of the input, which can be used as a file name (e.g. in the cache directory).
+	pub fn file_name(&self) -> String {
+		match self {
+			Self::Journal { unit } => format!("journal_{unit}"),
+			Self::Kubernetes { namespace, pod, container: None } => {
+				format!("k8s_{namespace}_{pod}")
+			},
+			Self::Kubernetes { namespace, pod, container: Some(container) } => {
+				format!("k8s_{namespace}_{pod}_{container}")
+			},
+			Self::Docker { container } => format!("docker_{container}"),
+		}
+	}
+}
+
 /// Represents user provided timestamp.
 ///
 /// Shall be compatible with chrono strftime format.
 /// For exact format specifiers refer to: <https://docs.rs/chrono/latest/chrono/format/strftime/index.html>
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, JsonSchema)]
+#[schemars(with = "String")]
 pub enum TimestampFormat {
 	/// Time stmap format contains date specifier
 	///
@@ -93,22 +207,257 @@ impl TimestampFormat {
 	}
 }
 
+/// Number format used in logs, controls the decimal and digit grouping separators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberLocale {
+	/// Dot as decimal separator, digits grouped with comma, apostrophe or space (e.g. `1,234.56`).
+	En,
+	/// Comma as decimal separator, digits grouped with dot, apostrophe or space (e.g. `1 234,56`).
+	Eu,
+}
+
+impl NumberLocale {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::En => "en",
+			Self::Eu => "eu",
+		}
+	}
+
+	/// Decimal separator of the locale.
+	pub fn decimal_separator(&self) -> char {
+		match self {
+			Self::En => '.',
+			Self::Eu => ',',
+		}
+	}
+
+	/// Tells if given character groups digits (regular, no-break and narrow no-break spaces are
+	/// accepted in both locales).
+	pub fn is_group_separator(&self, c: char) -> bool {
+		match c {
+			'\'' | ' ' | '\u{a0}' | '\u{202f}' => true,
+			',' => *self == Self::En,
+			'.' => *self == Self::Eu,
+			_ => false,
+		}
+	}
+
+	/// Regex matching a number formatted in the locale. Digits are only grouped by three.
+	pub fn number_pattern(&self) -> String {
+		let (group, decimal) = match self {
+			Self::En => (r"[,' \u{a0}\u{202f}]", r"\."),
+			Self::Eu => (r"[.' \u{a0}\u{202f}]", ","),
+		};
+		format!(r"\d{{1,3}}(?:{group}\d{{3}})+(?:{decimal}\d+)?|\d+(?:{decimal}\d+)?")
+	}
+
+	/// Converts the number formatted in the locale into the format accepted by [`str::parse`].
+	pub fn normalize(&self, value: &str) -> String {
+		value
+			.chars()
+			.filter(|c| !self.is_group_separator(*c))
+			.map(|c| if c == self.decimal_separator() { '.' } else { c })
+			.collect()
+	}
+}
+
+/// Sampling of the matches of every line, given with `--sample`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+	/// Every match is kept with given probability (`0.0 < fraction < 1.0`).
+	Fraction(f64),
+	/// Uniformly chosen subset of at most N matches is kept (reservoir sampling).
+	Count(usize),
+}
+
+impl Sampling {
+	/// Human readable description, displayed in the footer of the graph.
+	pub fn description(&self) -> String {
+		match self {
+			Self::Fraction(fraction) => {
+				format!("Sampled: {}% of matches per line", fraction * 100.0)
+			},
+			Self::Count(count) => format!("Sampled: at most {count} matches per line"),
+		}
+	}
+}
+
+impl Display for Sampling {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Fraction(fraction) => write!(f, "{fraction}"),
+			Self::Count(count) => write!(f, "{count}"),
+		}
+	}
+}
+
+impl FromStr for Sampling {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Ok(count) = s.parse::<usize>() {
+			return match count {
+				0 => Err("Sample size must be greater than 0".into()),
+				count => Ok(Self::Count(count)),
+			};
+		}
+		match s.parse::<f64>() {
+			Ok(fraction) if fraction > 0.0 && fraction < 1.0 => Ok(Self::Fraction(fraction)),
+			_ => {
+				Err(format!("Expected a fraction in (0.0, 1.0) or a number of matches, got '{s}'"))
+			},
+		}
+	}
+}
+
+/// Size in bytes, given with an optional binary unit: `k`, `M`, `G` or `T` (e.g. `512M`, `2G`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+impl Display for ByteSize {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (shift, unit) in [(40, "T"), (30, "G"), (20, "M"), (10, "k")] {
+			if self.0 != 0 && self.0.trailing_zeros() >= shift {
+				return write!(f, "{}{unit}", self.0 >> shift);
+			}
+		}
+		write!(f, "{}", self.0)
+	}
+}
+
+impl FromStr for ByteSize {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let error = || format!("Expected a size with an optional unit (k, M, G, T), got '{s}'");
+		let s = s.trim();
+		let number = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+		let shift = match s[number.len()..].to_ascii_lowercase().as_str() {
+			"" | "b" => 0,
+			"k" | "kb" | "kib" => 10,
+			"m" | "mb" | "mib" => 20,
+			"g" | "gb" | "gib" => 30,
+			"t" | "tb" | "tib" => 40,
+			_ => return Err(error()),
+		};
+		let value = number.trim().parse::<f64>().map_err(|_| error())?;
+		if !value.is_finite() || value < 0.0 {
+			return Err(error());
+		}
+		Ok(Self((value * (1u64 << shift) as f64) as u64))
+	}
+}
+
+/// Label displayed in titles instead of the file stem of the input file (see `--input-label`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputLabel {
+	/// Index of the input file in the `--input` list, or its path.
+	pub path: String,
+	/// Label of the input file.
+	pub label: String,
+}
+
+impl InputLabel {
+	/// Tells if the label refers to the `input` file of the `logical_input` list.
+	pub fn refers_to(&self, input: &Path, logical_input: &[PathBuf]) -> bool {
+		match self.path.parse::<usize>() {
+			Ok(id) => logical_input.get(id).is_some_and(|path| path == input),
+			Err(_) => Path::new(&self.path) == input,
+		}
+	}
+}
+
+impl Display for InputLabel {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}={}", self.path, self.label)
+	}
+}
+
+impl FromStr for InputLabel {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once('=') {
+			Some((path, label)) if !path.is_empty() && !label.is_empty() => {
+				Ok(Self { path: path.into(), label: label.into() })
+			},
+			_ => Err(format!("InputLabel: expected ID=NAME, got '{s}'")),
+		}
+	}
+}
+
+/// Extension of the virtual input combining all input files (see `--concat`).
+pub const CONCAT_INPUT_EXTENSION: &str = "concat";
+
 /// Input context for data sources, log parsing and plotting modules.
-#[derive(Args, Debug, Serialize, Deserialize, Default)]
+#[derive(Args, Clone, Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct InputFilesContext {
 	/// Input log files to be processed.
 	/// Comma-separated list of input log files to be processed.
+	///
+	/// Use `journal:<UNIT>` to read the systemd journal of the unit (with `journalctl`),
+	/// `k8s://<NAMESPACE>/<POD>[/<CONTAINER>]` to read the logs of the Kubernetes pod (with
+	/// `kubectl logs`) or `docker://<CONTAINER>` to read the logs of the docker container (with
+	/// `docker logs`). If all inputs are of these kinds, the timestamp prefix they are printed
+	/// with is used as the default timestamp format.
 	#[arg(long, short = 'i', value_delimiter = ',', help_heading = "Input files")]
 	#[serde(skip)]
 	input: Vec<PathBuf>,
 
+	/// Label displayed in line and panel titles instead of the file stem of the input file, e.g.
+	/// `--input-label 0=validator-3`.
+	///
+	/// The input file is given by its index in the `--input` list, or by its path. Comma-separated
+	/// list, can be given many times. In the config file given as
+	/// `inputs = [{ path = "node3.log", label = "validator-3" }]`.
+	#[arg(
+		long = "input-label",
+		value_name = "ID=NAME",
+		value_delimiter = ',',
+		help_heading = "Input files"
+	)]
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	inputs: Vec<InputLabel>,
+
+	/// Treat all input files as one logical log (e.g. rotated `app.log.2.gz`, `app.log.1` and
+	/// `app.log`).
+	///
+	/// Files are read one after another, ordered by their first timestamp, so every line is drawn
+	/// as a single continuous series instead of one series per file. Gzip compressed files
+	/// (`.gz`) are decompressed on the fly.
+	#[arg(long, default_value_t = false, help_heading = "Input files")]
+	#[serde(skip)]
+	concat: bool,
+
+	/// Drop repeated log lines (identical timestamp and content), e.g. events appearing in two
+	/// overlapping snapshots of the same log.
+	///
+	/// Useful with `--concat`, so counts and rates are not doubled. Lines without valid timestamp
+	/// (e.g. stack traces) are never dropped. Every part of the input shall be sorted by time, at
+	/// most a million of the latest lines are remembered.
+	#[arg(long, default_value_t = false, help_heading = "Input files")]
+	#[serde(skip)]
+	dedup: bool,
+
 	/// Directory to store parsed CSV cache files.
 	/// The full path of each log file is mirrored inside this directory to avoid name collisions.
 	/// If not set, a `.plox/` directory is created next to each log file to store its cache.
+	///
+	/// Use `auto` for the user cache directory: `$XDG_CACHE_HOME/plox` (`~/.cache/plox` by
+	/// default), e.g. for logs in read-only or NFS-mounted directories.
 	#[arg(long, value_name = "DIR", help_heading = "Output files")]
 	#[serde(skip)]
 	cache_dir: Option<PathBuf>,
 
+	/// Limit of the total size of the CSV cache files, e.g. `2G`.
+	///
+	/// Enforced after the logs are processed, by deleting the least recently used cache files
+	/// (access times are tracked in the cache manifests). The whole `--cache-dir` is pruned if
+	/// given, otherwise the `.plox/` directories used by the run. Files used by the run are never
+	/// deleted.
+	#[arg(long, value_name = "SIZE", help_heading = "Output files")]
+	#[serde(skip)]
+	cache_max_size: Option<ByteSize>,
+
 	/// The format of the timestamp which is used in logs.
 	///
 	/// For exact format specifiers refer to: <https://docs.rs/chrono/latest/chrono/format/strftime/index.html>
@@ -132,6 +481,71 @@ pub struct InputFilesContext {
 	#[arg(long, short = 't', default_value_t = false, help_heading = "Input files")]
 	#[serde(skip)]
 	ignore_invalid_timestamps: bool,
+
+	/// Fail if a matched value cannot be converted to a number.
+	///
+	/// By default records with values that cannot be converted (e.g. `duration=n/a`) are skipped,
+	/// and the number of skipped records is reported with a warning. Cached CSV files are not
+	/// checked, use `--force-csv-regen` to re-process the logs.
+	#[arg(long, default_value_t = false, help_heading = "Input files")]
+	#[serde(skip)]
+	strict: bool,
+
+	/// Sort the extracted records by timestamp.
+	///
+	/// Logs merged from multiple threads may contain non-monotonic timestamps, which are reported
+	/// with a warning. With this option records are sorted (and counts and deltas recomputed)
+	/// before the CSV file is written. Implies regeneration of the CSV cache.
+	#[arg(long, default_value_t = false, help_heading = "Input files")]
+	#[serde(skip)]
+	sort_timestamps: bool,
+
+	/// Number format of the values in logs.
+	///
+	/// By default values are expected in plain format (e.g. `1234.56`). With `en` values like
+	/// `1,234.56` are accepted, with `eu` values like `1 234,56` or `1.234,56`.
+	#[arg(long, value_name = "LOCALE", help_heading = "Input files")]
+	#[serde(skip)]
+	number_locale: Option<NumberLocale>,
+
+	/// Skip the first N lines of every input file.
+	///
+	/// Line numbers reported in warnings and stored in CSV files still refer to the whole file.
+	#[arg(long, value_name = "N", help_heading = "Input files")]
+	#[serde(skip)]
+	skip_lines: Option<usize>,
+
+	/// Process at most N lines of every input file (after the skipped ones).
+	///
+	/// Useful for quick iteration on the regexes, before processing the whole log.
+	#[arg(long, value_name = "N", help_heading = "Input files")]
+	#[serde(skip)]
+	max_lines: Option<usize>,
+
+	/// Read only the entries newer than given duration (e.g. `30m`, `2h`) from `journal:`,
+	/// `k8s://` and `docker://` inputs.
+	///
+	/// Passed to the `--since` option of the command reading the input.
+	#[arg(long, value_name = "DURATION", help_heading = "Input files")]
+	#[serde(skip)]
+	since: Option<String>,
+
+	/// Keep only a representative subset of the matches of every line.
+	///
+	/// Given as a fraction (e.g. `0.01` keeps about 1% of matches), or as a number of matches
+	/// (e.g. `10000` keeps a uniformly chosen subset of at most that many matches). Sampling is
+	/// deterministic, so repeated runs give the same subset. Counts and deltas are computed before
+	/// sampling. The sampling is noted in the footer of the graph.
+	#[arg(long, value_name = "FRACTION|N", help_heading = "Input files")]
+	#[serde(skip)]
+	sample: Option<Sampling>,
+
+	/// Absolute time window of interest, resolved from `--time-range`.
+	///
+	/// Log lines with timestamps outside the window are skipped before the regex matching.
+	#[arg(skip)]
+	#[serde(skip)]
+	time_window: Option<(NaiveDateTime, NaiveDateTime)>,
 }
 
 /// Global graph context shared across all panels and lines.
@@ -149,7 +563,7 @@ pub struct InputFilesContext {
 ///
 /// This context is injected when converting from a basic [`GraphConfig`] into a
 /// fully-resolved [`crate::resolved_graph_config::ResolvedGraphConfig`] with concrete log sources.
-#[derive(Args, Debug, Serialize, Deserialize, Default)]
+#[derive(Args, Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct GraphFullContext {
 	#[clap(flatten)]
 	#[serde(flatten)]
@@ -160,7 +574,7 @@ pub struct GraphFullContext {
 }
 
 /// Shared graph configuration, which does not include input files.
-#[derive(Args, Debug, Serialize, Deserialize, Default)]
+#[derive(Args, Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct OutputGraphContext {
 	/// When enabled, creates a separate panel for each input file.
 	///
@@ -172,6 +586,17 @@ pub struct OutputGraphContext {
 	#[arg(long, num_args(0..=1), default_value = None, help_heading = "Panels layout",  default_missing_value = "true")]
 	per_file_panels: Option<bool>,
 
+	/// When enabled, lines of all input files are overlaid in the same panel for direct
+	/// comparison.
+	///
+	/// Every line drawn for many input files keeps its own color, while the input files are
+	/// distinguished by dash style and marker type (e.g. `a.log` is always solid, `b.log` is
+	/// always dashed). Explicitly configured styles are kept.
+	///
+	/// Ignored if `--per-file-panels` is enabled.
+	#[arg(long, num_args(0..=1), default_value = None, help_heading = "Panels layout",  default_missing_value = "true")]
+	per_file_lines: Option<bool>,
+
 	/// Additionally writes the current graph configuration to a file in TOML format.
 	#[arg(
 		long = "write-config",
@@ -181,12 +606,27 @@ pub struct OutputGraphContext {
 	)]
 	output_config_path: Option<PathBuf>,
 
+	/// Additionally writes the minimal graph configuration to a file in TOML format.
+	///
+	/// Only the values differing from the defaults are written, each preceded by the comment
+	/// explaining it. Good starting point for editing the config.
+	#[arg(
+		long = "write-config-minimal",
+		value_name = "CONFIG-FILE",
+		help_heading = "Output files"
+	)]
+	output_minimal_config_path: Option<PathBuf>,
+
 	/// Path to the output PNG graph file.
 	///
 	/// The corresponding `.gnuplot` script will be written alongside it, using the same filename
 	/// with a different extension. Ignored if `--inline-output` is set.
 	///
 	/// If nothing is provided `graph.png` and `graph.gnuplot` in current directory will be stored.
+	///
+	/// Use `-` to write the image (or the html page) to stdout, e.g. `-o - | convert ...`. Log
+	/// messages are then written to stderr and info messages are suppressed. No gnuplot script is
+	/// kept, and the output is not displayed.
 	#[arg(long, short = 'o', value_name = "FILE", help_heading = "Output files")]
 	output: Option<PathBuf>,
 
@@ -208,12 +648,136 @@ pub struct OutputGraphContext {
 	)]
 	inline_output: Option<PathBuf>,
 
+	/// Do not overwrite existing output files.
+	///
+	/// Fails if the output image, gnuplot script or html file already exists.
+	#[arg(
+		long,
+		default_value_t = false,
+		conflicts_with = "auto_number",
+		help_heading = "Output files"
+	)]
+	#[serde(skip)]
+	no_clobber: bool,
+
+	/// Append the first free number to the output filename (e.g. `graph-003.png`).
+	///
+	/// Existing output files are never overwritten, every run of an exploration session is kept.
+	#[arg(long, default_value_t = false, help_heading = "Output files")]
+	#[serde(skip)]
+	auto_number: bool,
+
+	/// Render a separate image (or html page) for every input file, instead of a single one.
+	///
+	/// Every output contains only the lines of a single input file and is named after it, e.g.
+	/// `graph-node3.png` (or after its label given with `--input-label`). Useful for attaching to
+	/// per-node tickets. Ignored with `--output -`.
+	#[arg(long, default_value_t = false, help_heading = "Output files")]
+	#[serde(skip)]
+	per_file_output: bool,
+
+	/// Split the graph into many outputs (pages) containing at most N panels each.
+	///
+	/// Pages are numbered, e.g. `graph-1.png`, `graph-2.png`, instead of squeezing all panels
+	/// into unreadable strips. Particularly useful with `--per-file-panels` across many input
+	/// files. Ignored with `--output -`.
+	#[arg(long, value_name = "N", help_heading = "Output files")]
+	#[serde(skip)]
+	max_panels_per_page: Option<NonZeroUsize>,
+
+	/// Suffix appended to the output file name, set for the outputs of `--per-file-output` and
+	/// `--max-panels-per-page`.
+	#[arg(skip)]
+	#[serde(skip)]
+	output_suffix: Option<String>,
+
 	/// Strategy for aligning time ranges across all panels.
 	///
 	/// This determines how time-axis (x) ranges are handled when plotting.
 	#[arg(long, value_enum, conflicts_with = "time_range", help_heading = "Panels layout")]
 	panel_alignment_mode: Option<PanelAlignmentModeArg>,
 
+	/// Use identical Y-axis ranges in all panels duplicated with `--per-file-panels`.
+	///
+	/// The range spans the values of all files, so the panels can be compared visually. Explicit
+	/// `--y2range` takes precedence.
+	#[arg(long, default_value_t = false, help_heading = "Panels layout")]
+	#[serde(skip)]
+	shared_yrange: bool,
+
+	/// CSV file with external events (`timestamp,label` records) drawn on every panel.
+	///
+	/// Events (e.g. deployments, alerts) are drawn as vertical lines with labels, like the
+	/// matches of `--annotate`, so external context can be overlaid without being present in the
+	/// logs. Timestamps must be compatible with the `--timestamp-format`. The first record is
+	/// skipped if it is a header.
+	#[arg(long, value_name = "FILE", help_heading = "Panels layout")]
+	#[serde(skip)]
+	annotations: Option<PathBuf>,
+
+	/// Fold the time axis modulo the period (e.g. `24h` or `7d`), overlaying every period as a
+	/// separate line.
+	///
+	/// Every line is split into one line per period, shifted into the first period, so e.g. the
+	/// days of a long log can be compared to spot diurnal patterns. Periods start at midnight
+	/// (weeks on Monday). Accepts seconds with an optional unit: `ms`, `s`, `m`, `h` or `d`.
+	#[arg(
+		long,
+		value_name = "DURATION",
+		conflicts_with = "time_range",
+		help_heading = "Panels layout"
+	)]
+	#[serde(skip)]
+	fold_period: Option<FoldPeriod>,
+
+	/// Resample every line to evenly spaced samples of the interval (e.g. `1s`), optionally
+	/// followed by the policy of computing the samples: `linear` (default), `ffill` or `drop`.
+	///
+	/// Samples are aligned to the multiples of the interval, so the samples of all lines share
+	/// their timestamps. Accepts seconds with an optional unit: `ms`, `s`, `m` or `h`.
+	#[arg(
+		long,
+		num_args = 1..=2,
+		value_names = ["INTERVAL", "POLICY"],
+		help_heading = "Panels layout"
+	)]
+	#[serde(skip)]
+	resample: Option<Vec<String>>,
+
+	/// Show the time tick labels only on the bottom panel.
+	///
+	/// All panels share the same time axis, so repeating its labels on every panel only takes up
+	/// vertical space of tall graphs.
+	#[arg(long, default_value_t = false, help_heading = "Panels layout")]
+	#[serde(skip)]
+	xtics_bottom_only: bool,
+
+	/// Rotate the time tick labels by given angle (in degrees, counterclockwise), e.g. `45`.
+	#[arg(
+		long,
+		value_name = "DEGREES",
+		allow_negative_numbers = true,
+		help_heading = "Panels layout"
+	)]
+	#[serde(skip)]
+	xtics_rotate: Option<i32>,
+
+	/// Format of the time tick labels, e.g. `%d %H:%M`.
+	///
+	/// Defaults to `%H:%M:%S` for gnuplot, plotly adjusts the format to the zoom level. Common `strftime` specifiers (e.g. `%d`, `%b`, `%H`, `%M`, `%S`) are understood by both
+	/// backends.
+	#[arg(long, alias = "xtick-format", value_name = "FORMAT", help_heading = "Panels layout")]
+	#[serde(skip)]
+	xtics_format: Option<String>,
+
+	/// Interval between the major time ticks, e.g. `15m` or `2h`.
+	///
+	/// Accepts seconds with an optional unit: `ms`, `s`, `m` or `h`. By default the interval is
+	/// chosen automatically, which may be too dense for long captures.
+	#[arg(long, value_name = "DURATION", help_heading = "Panels layout")]
+	#[serde(skip)]
+	xtics: Option<TicsInterval>,
+
 	/// Optional override for the global time range used in the graph.
 	///
 	/// Can be specified as either:
@@ -222,6 +786,10 @@ pub struct OutputGraphContext {
 	///
 	/// Timestamp strings must be compatible with the `--timestamp-format`.
 	///
+	/// With timestamp strings, log lines outside the range are skipped before the regex matching,
+	/// and parsing stops once the timestamps are more than a minute past the range, if the log was
+	/// sorted by time so far (and `--sort-timestamps` is not given).
+	///
 	/// Conflicts with `--panel-alignment-mode`, and implies global alignment.
 	#[arg(
 		long,
@@ -247,10 +815,71 @@ pub struct OutputGraphContext {
 	#[serde(skip)]
 	pub do_not_display: bool,
 
+	/// Only write the gnuplot script (and the CSV cache files), do not execute gnuplot.
+	///
+	/// Useful in build pipelines which run gnuplot elsewhere. The graph is not displayed. With
+	/// `--output -` the script is written to stdout.
+	#[arg(
+		long,
+		default_value_t = false,
+		conflicts_with = "plotly_backend",
+		help_heading = "Output files"
+	)]
+	#[serde(skip)]
+	pub script_only: bool,
+
 	/// Use plotly backend, generated interactive self-contained html file.
 	#[arg(long, short = 'p', default_value_t = false, help_heading = "Backend")]
 	#[serde(skip)]
 	pub plotly_backend: bool,
+
+	/// Path to the gnuplot binary (default: `gnuplot` found in `PATH`).
+	#[arg(long, value_name = "PATH", help_heading = "Backend")]
+	#[serde(skip)]
+	gnuplot_bin: Option<PathBuf>,
+
+	/// File with gnuplot commands injected into the generated script before the plots.
+	///
+	/// Allows custom terminals or site-wide styling (e.g. `set terminal svg`, `set border lw 2`),
+	/// which override the ones set by plox.
+	#[arg(long, value_name = "FILE", help_heading = "Backend")]
+	#[serde(skip)]
+	gnuplot_extra: Option<PathBuf>,
+
+	/// Embed the data of every line into the gnuplot script as a datablock (`$DATA << EOD`).
+	///
+	/// The script becomes a single self-contained artifact, which can be shared and re-rendered
+	/// without the `.plox` cache directory.
+	#[arg(
+		long,
+		default_value_t = false,
+		conflicts_with = "plotly_backend",
+		help_heading = "Backend"
+	)]
+	#[serde(skip)]
+	inline_data: bool,
+
+	/// Write the paths in the gnuplot script relative to the script location.
+	///
+	/// The data files of the lines are copied into the `<NAME>-data` directory next to the script,
+	/// so the output directory can be moved (e.g. to another machine) and re-rendered with
+	/// `gnuplot <NAME>.gnuplot` executed within it.
+	#[arg(
+		long,
+		default_value_t = false,
+		conflicts_with = "plotly_backend",
+		help_heading = "Backend"
+	)]
+	#[serde(skip)]
+	portable_script: bool,
+
+	/// Record the SHA-256 hashes of the input files in the provenance of the generated output.
+	///
+	/// By default only the size and the modification time of the input files are recorded, as
+	/// hashing large logs on every render is slow.
+	#[arg(long, default_value_t = false, help_heading = "Backend")]
+	#[serde(skip)]
+	hash_inputs: bool,
 }
 
 impl InputFilesContext {
@@ -263,13 +892,79 @@ impl InputFilesContext {
 	}
 
 	pub fn timestamp_format(&self) -> &TimestampFormat {
-		self.timestamp_format.as_ref().unwrap_or(&DEFAULT_TIMESTAMP_FORMAT)
+		let only_commands = !self.input.is_empty()
+			&& self.input.iter().all(|input| CommandInput::parse(input).is_some());
+		self.timestamp_format.as_ref().unwrap_or(if only_commands {
+			&JOURNAL_TIMESTAMP_FORMAT
+		} else {
+			&DEFAULT_TIMESTAMP_FORMAT
+		})
+	}
+
+	/// Label of the input file (see `--input-label`), if given.
+	pub fn input_label(&self, input: &Path) -> Option<String> {
+		let logical_input = self.logical_input();
+		self.inputs
+			.iter()
+			.find(|label| label.refers_to(input, &logical_input))
+			.map(|label| label.label.clone())
+	}
+
+	/// Labels of the input files given with `--input-label`.
+	pub fn input_labels(&self) -> &[InputLabel] {
+		&self.inputs
+	}
+
+	/// Mutable labels of the input files, e.g. for expanding their paths.
+	pub(crate) fn input_labels_mut(&mut self) -> &mut [InputLabel] {
+		&mut self.inputs
 	}
 
 	pub fn input(&self) -> &Vec<PathBuf> {
 		&self.input
 	}
 
+	/// Inputs the lines are bound to: with `--concat` all input files form a single virtual input
+	/// (see [`Self::concat_input_path`]).
+	pub fn logical_input(&self) -> Vec<PathBuf> {
+		match self.concat_input_path() {
+			Some(path) => vec![path],
+			None => self.input.clone(),
+		}
+	}
+
+	/// Path of the virtual input combining all input files, if `--concat` is given.
+	///
+	/// The path does not exist. It is placed next to the first input file and named after the
+	/// common prefix of the input file names, e.g. `app.log.concat` for `app.log.1` and `app.log`.
+	pub fn concat_input_path(&self) -> Option<PathBuf> {
+		let first = self.input.first().filter(|_| self.concat)?;
+		let names = self
+			.input
+			.iter()
+			.map(|input| input.file_name().unwrap_or_default().to_string_lossy())
+			.collect::<Vec<_>>();
+		let prefix_len = names[0]
+			.char_indices()
+			.map(|(i, c)| i + c.len_utf8())
+			.take_while(|end| names.iter().all(|name| name.get(..*end) == names[0].get(..*end)))
+			.last()
+			.unwrap_or(0);
+		let prefix = names[0][..prefix_len].trim_end_matches(['.', '-', '_']);
+		let name = if prefix.is_empty() { "input" } else { prefix };
+		Some(first.with_file_name(format!("{name}.{CONCAT_INPUT_EXTENSION}")))
+	}
+
+	/// Files to be read for given input: all input files for the virtual input of `--concat`,
+	/// otherwise the input itself.
+	pub fn input_parts(&self, input: &Path) -> Vec<PathBuf> {
+		if self.concat_input_path().as_deref() == Some(input) {
+			self.input.clone()
+		} else {
+			vec![input.to_path_buf()]
+		}
+	}
+
 	pub fn force_csv_regen(&self) -> bool {
 		self.force_csv_regen
 	}
@@ -277,14 +972,99 @@ impl InputFilesContext {
 	pub fn ignore_invalid_timestamps(&self) -> bool {
 		self.ignore_invalid_timestamps
 	}
+
+	pub fn strict(&self) -> bool {
+		self.strict
+	}
+
+	pub fn sort_timestamps(&self) -> bool {
+		self.sort_timestamps
+	}
+
+	pub fn number_locale(&self) -> Option<NumberLocale> {
+		self.number_locale
+	}
+
+	pub fn time_window(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+		self.time_window
+	}
+
+	pub fn skip_lines(&self) -> Option<usize> {
+		self.skip_lines
+	}
+
+	pub fn max_lines(&self) -> Option<usize> {
+		self.max_lines
+	}
+
+	pub fn dedup(&self) -> bool {
+		self.dedup
+	}
+
+	pub fn since(&self) -> Option<&str> {
+		self.since.as_deref()
+	}
+
+	pub fn sample(&self) -> Option<Sampling> {
+		self.sample
+	}
+
+	pub fn cache_max_size(&self) -> Option<ByteSize> {
+		self.cache_max_size
+	}
 }
 
+/// The gnuplot command used if `--gnuplot-bin` is not given.
+pub const DEFAULT_GNUPLOT_BIN: &str = "gnuplot";
+
 /// Determines the output file paths, based on selected backend.
 pub enum OutputFilePaths {
 	/// Tuple containging the path to the image and the path to the gnuplot script
 	Gnuplot((PathBuf, PathBuf)),
 	/// The path to the HTML file
 	Plotly(PathBuf),
+	/// The output is written to stdout (`--output -`)
+	Stdout,
+}
+
+impl OutputFilePaths {
+	/// All the files which will be written.
+	pub fn files(&self) -> Vec<&Path> {
+		match self {
+			Self::Gnuplot((image_path, script_path)) => vec![image_path, script_path],
+			Self::Plotly(html_path) => vec![html_path],
+			Self::Stdout => vec![],
+		}
+	}
+
+	/// Returns the paths with the `suffix` appended to the file stems, e.g. `graph-node3.png` and
+	/// `graph-node3.gnuplot`.
+	fn suffixed(&self, suffix: &str) -> Self {
+		let suffixed = |path: &Path| {
+			let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+			let mut file_name = format!("{stem}-{suffix}");
+			if let Some(extension) = path.extension() {
+				file_name = format!("{file_name}.{}", extension.to_string_lossy());
+			}
+			path.with_file_name(file_name)
+		};
+		match self {
+			Self::Gnuplot((image_path, script_path)) => {
+				Self::Gnuplot((suffixed(image_path), suffixed(script_path)))
+			},
+			Self::Plotly(html_path) => Self::Plotly(suffixed(html_path)),
+			Self::Stdout => Self::Stdout,
+		}
+	}
+
+	/// Returns the paths with the first number for which none of the files exists, e.g.
+	/// `graph-001.png` and `graph-001.gnuplot`.
+	fn auto_numbered(&self) -> Self {
+		(1..)
+			.map(|n| self.suffixed(&format!("{n:03}")))
+			.find(|paths| paths.files().iter().all(|f| !f.exists()))
+			.expect("there is always a free number")
+	}
 }
 
 impl GraphFullContext {
@@ -299,8 +1079,31 @@ impl GraphFullContext {
 		}
 
 		set_if_none!(output_graph_ctx.per_file_panels);
+		set_if_none!(output_graph_ctx.per_file_lines);
 		set_if_none!(output_graph_ctx.inline_output);
+		set_if_none!(output_graph_ctx.output);
 		set_if_none!(input_files_ctx.timestamp_format);
+		if self.input_files_ctx.inputs.is_empty() {
+			self.input_files_ctx.inputs = other.input_files_ctx.inputs;
+		}
+	}
+
+	/// Resolves the absolute `--time-range` into the window used to prefilter log lines.
+	///
+	/// Relative ranges depend on the time range of the extracted data, so they cannot be used for
+	/// prefiltering. Invalid timestamps are ignored here, they are reported when panel ranges are
+	/// resolved.
+	pub fn resolve_time_window(&mut self) {
+		if let Some(time_range @ TimeRangeArg::AbsoluteDateTime(..)) =
+			&self.output_graph_ctx.time_range
+		{
+			// Total range is not used for absolute ranges.
+			let total_range = (NaiveDateTime::MIN, NaiveDateTime::MIN);
+			self.input_files_ctx.time_window = time_range
+				.resolve(total_range, self.timestamp_format())
+				.ok()
+				.filter(|(start, end)| start <= end);
+		}
 	}
 
 	pub fn new_with_input(input: Vec<PathBuf>) -> Self {
@@ -318,6 +1121,10 @@ impl GraphFullContext {
 		&self.input_files_ctx.input
 	}
 
+	pub fn logical_input(&self) -> Vec<PathBuf> {
+		self.input_files_ctx.logical_input()
+	}
+
 	pub fn cache_dir(&self) -> &Option<PathBuf> {
 		&self.input_files_ctx.cache_dir
 	}
@@ -331,11 +1138,129 @@ impl GraphFullContext {
 		self.output_graph_ctx.per_file_panels.unwrap_or(false)
 	}
 
-	/// Returns tuple containging the path to the image and the path to the gnuplot script
+	/// The backend selected for rendering the graph.
+	pub fn backend(&self) -> Backend {
+		Backend::from_plotly_flag(self.output_graph_ctx.plotly_backend)
+	}
+
+	/// Returns the gnuplot command (see `--gnuplot-bin`).
+	pub fn gnuplot_bin(&self) -> &Path {
+		self.output_graph_ctx
+			.gnuplot_bin
+			.as_deref()
+			.unwrap_or(Path::new(DEFAULT_GNUPLOT_BIN))
+	}
+
+	/// Returns the period the time axis is folded with (see `--fold-period`).
+	pub fn fold_period(&self) -> Option<FoldPeriod> {
+		self.output_graph_ctx.fold_period
+	}
+
+	/// Returns the resampling of the lines (see `--resample`).
+	pub fn resample(&self) -> Result<Option<Resample>, String> {
+		self.output_graph_ctx.resample.as_deref().map(Resample::try_from).transpose()
+	}
+
+	/// Returns the file with external events drawn on every panel (see `--annotations`).
+	pub fn annotations_file(&self) -> Option<&Path> {
+		self.output_graph_ctx.annotations.as_deref()
+	}
+
+	/// Tells if the data shall be embedded into the gnuplot script (see `--inline-data`).
+	pub fn inline_data(&self) -> bool {
+		self.output_graph_ctx.inline_data
+	}
+
+	/// Tells if the paths in the gnuplot script shall be relative to it (see `--portable-script`).
+	pub fn portable_script(&self) -> bool {
+		self.output_graph_ctx.portable_script
+	}
+
+	/// Tells if the input files shall be hashed for the provenance (see `--hash-inputs`).
+	pub fn hash_inputs(&self) -> bool {
+		self.output_graph_ctx.hash_inputs
+	}
+
+	/// Returns the file with extra gnuplot commands (see `--gnuplot-extra`).
+	pub fn gnuplot_extra(&self) -> Option<&Path> {
+		self.output_graph_ctx.gnuplot_extra.as_deref()
+	}
+
+	/// Tells if panels duplicated per input file shall share the Y-axis ranges.
+	pub fn shared_yrange(&self) -> bool {
+		self.output_graph_ctx.shared_yrange
+	}
+
+	/// Tells if the time tick labels shall be shown only on the bottom panel.
+	pub fn xtics_bottom_only(&self) -> bool {
+		self.output_graph_ctx.xtics_bottom_only
+	}
+
+	/// Rotation of the time tick labels (in degrees, counterclockwise).
+	pub fn xtics_rotate(&self) -> Option<i32> {
+		self.output_graph_ctx.xtics_rotate
+	}
+
+	/// Interval between the major time ticks, if explicitly given.
+	pub fn xtics(&self) -> Option<TicsInterval> {
+		self.output_graph_ctx.xtics
+	}
+
+	/// Format of the time tick labels, if explicitly given.
+	pub fn xtics_format(&self) -> Option<&str> {
+		self.output_graph_ctx.xtics_format.as_deref()
+	}
+
+	/// Tells if existing output files shall not be overwritten.
+	pub fn no_clobber(&self) -> bool {
+		self.output_graph_ctx.no_clobber
+	}
+
+	/// Tells if the output shall be written to stdout (`--output -`).
+	///
+	/// `--inline-output` takes precedence, as it overrides `--output`.
+	/// Tells if a separate output shall be rendered for every input file.
+	pub fn per_file_output(&self) -> bool {
+		self.output_graph_ctx.per_file_output && !self.output_to_stdout()
+	}
+
+	/// Maximal number of panels of a single output (see `--max-panels-per-page`).
+	pub fn max_panels_per_page(&self) -> Option<usize> {
+		self.output_graph_ctx
+			.max_panels_per_page
+			.filter(|_| !self.output_to_stdout())
+			.map(NonZeroUsize::get)
+	}
+
+	/// Sets the suffix of the output file name (see [`Self::per_file_output`]).
+	pub fn set_output_suffix(&mut self, suffix: Option<String>) {
+		self.output_graph_ctx.output_suffix = suffix;
+	}
+
+	pub fn output_to_stdout(&self) -> bool {
+		self.output_graph_ctx.inline_output.is_none()
+			&& self.output_graph_ctx.output.as_deref() == Some(Path::new("-"))
+	}
+
+	/// Returns the paths of the output files.
+	///
+	/// If `--auto-number` is set, the first free number is appended to the file names.
 	pub fn get_graph_output_path(&self) -> OutputFilePaths {
+		let mut paths = self.get_graph_output_path_inner();
+		if let Some(suffix) = &self.output_graph_ctx.output_suffix {
+			paths = paths.suffixed(suffix);
+		}
+		if self.output_graph_ctx.auto_number { paths.auto_numbered() } else { paths }
+	}
+
+	/// Returns tuple containging the path to the image and the path to the gnuplot script
+	fn get_graph_output_path_inner(&self) -> OutputFilePaths {
+		if self.output_to_stdout() {
+			return OutputFilePaths::Stdout;
+		}
 		let common_ancestor =
 			common_path_ancestor(self.input()).unwrap_or_else(|| PathBuf::from("./"));
-		if self.output_graph_ctx.plotly_backend {
+		if self.backend() == Backend::Plotly {
 			if let Some(ref output_file) = self.output_graph_ctx.inline_output {
 				let html_path = common_ancestor.join(output_file);
 				OutputFilePaths::Plotly(html_path.with_extension("html"))
@@ -362,6 +1287,10 @@ impl GraphFullContext {
 		&self.output_graph_ctx.output_config_path
 	}
 
+	pub fn output_minimal_config_path(&self) -> Option<&Path> {
+		self.output_graph_ctx.output_minimal_config_path.as_deref()
+	}
+
 	pub fn resolved_alignment_mode(
 		&self,
 		total_range: (NaiveDateTime, NaiveDateTime),
@@ -379,108 +1308,524 @@ impl GraphFullContext {
 	}
 }
 
-impl OutputGraphContext {
-	#[cfg(test)]
-	pub fn per_file_panels_option(&self) -> Option<bool> {
-		self.per_file_panels
-	}
+impl OutputGraphContext {
+	/// Mutable paths of the output files which can be given in the config file.
+	pub(crate) fn paths_mut(&mut self) -> impl Iterator<Item = &mut PathBuf> {
+		self.output.iter_mut()
+	}
+
+	#[cfg(test)]
+	pub fn per_file_panels_option(&self) -> Option<bool> {
+		self.per_file_panels
+	}
+
+	pub fn per_file_panels(&self) -> bool {
+		self.per_file_panels.unwrap_or(false)
+	}
+
+	pub fn per_file_lines(&self) -> bool {
+		self.per_file_lines.unwrap_or(false)
+	}
+}
+
+/// A panel that holds multiple [`Line`]s in the same horizontal space.
+///
+/// Panels are typically stacked vertically, so each panel is drawn on a separate row.
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Panel {
+	/// The list of lines to draw on this panel.
+	pub lines: Vec<Line>,
+
+	#[serde(flatten)]
+	pub params: PanelParams,
+}
+
+/// A single line (or data series) to be plotted on a panel.
+///
+/// It includes a [`DataSource`] to describe the data source (e.g. plotting a field vs
+/// an event pattern), as well as various styling and configuration details
+/// (e.g. color, axis).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Line {
+	/// The logical data source or type of this line.
+	#[serde(flatten)]
+	pub data_source: DataSource,
+
+	#[serde(flatten)]
+	pub params: LineParams,
+}
+
+impl Line {
+	pub fn new_with_data_source(data_source: DataSource) -> Self {
+		Line { data_source, params: LineParams::default() }
+	}
+
+	/// Text stored along with the values, see [`LineParams::keep_text`]. Annotations always keep
+	/// the captured text.
+	pub fn keep_text(&self) -> Option<MatchedText> {
+		if self.data_source.is_annotation() {
+			Some(MatchedText::Match)
+		} else {
+			self.params.keep_text
+		}
+	}
+}
+
+#[derive(Default, Clone, Args, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct LineParams {
+	/// Optionally overrides source log file.
+	///
+	/// Assigns a specific file to the line
+	#[arg(long)]
+	pub file_name: Option<PathBuf>,
+
+	/// Optionally specifies the index of input file.
+	///
+	/// Assigns the line to the nth input from `--input` (index starting at 0)
+	#[arg(long)]
+	pub file_id: Option<usize>,
+
+	/// Optional title of the line. Will be placed on legend.
+	///
+	/// Placeholders `{file}` (label or file stem of the input file), `{guard}`, `{pattern}` and
+	/// `{index}` (of the line within the panel) are expanded.
+	#[arg(long)]
+	pub title: Option<String>,
+
+	/// The style of the plotted line
+	#[arg(long, default_value = "points")]
+	#[serde(default)]
+	pub style: PlotStyle,
+
+	/// The width of the line
+	#[arg(long)]
+	pub line_width: Option<LineWidth>,
+
+	/// The color of the line: one of the predefined colors or hex RGB value (e.g. `#ff8800`).
+	///
+	/// Predefined colors: red, blue, dark-green, purple, cyan, goldenrod, brown, olive, navy,
+	/// violet, coral, salmon, steel-blue, dark-magenta, dark-cyan, dark-yellow, dark-turquoise,
+	/// yellow, black, magenta, orange, green, dark-orange.
+	#[arg(long, value_name = "COLOR")]
+	pub line_color: Option<Color>,
+
+	/// The dash type.
+	#[arg(long)]
+	pub dash_style: Option<DashStyle>,
+
+	/// Which Y-axis this line should use, if applicable (e.g. primary or secondary).
+	#[arg(long)]
+	pub yaxis: Option<YAxis>,
+
+	/// The marker type.
+	#[arg(long)]
+	pub marker_type: Option<MarkerType>,
+
+	/// The color of the marker (if markers are enabled), see `--line-color`.
+	#[arg(long, value_name = "COLOR")]
+	pub marker_color: Option<Color>,
+
+	/// Opacity of the line and markers, from `0` (invisible) to `1` (opaque, default).
+	///
+	/// Translucent points let the density of massive point clouds show through.
+	#[arg(long, value_name = "0..1")]
+	pub alpha: Option<Alpha>,
+
+	/// The size of the marker
+	#[arg(long, default_value_t = MarkerSize::default())]
+	#[serde(default = "MarkerSize::default")]
+	pub marker_size: MarkerSize,
+
+	/// Break the line if consecutive points are further apart than the given duration.
+	///
+	/// Avoids drawing a misleading straight segment across the gap in the log. Accepts seconds
+	/// with an optional unit: `ms`, `s`, `m` or `h` (e.g. `90`, `500ms`, `15m`).
+	#[arg(long, value_name = "DURATION")]
+	pub gap_threshold: Option<GapThreshold>,
+
+	/// Drawing order of the line within the panel (default: 0).
+	///
+	/// Lines with higher values are drawn on top of lines with lower ones, e.g. to keep an
+	/// important line visible over a dense scatter. Lines with equal values are drawn in the
+	/// order of declaration.
+	#[arg(long, value_name = "N", allow_negative_numbers = true)]
+	pub z_order: Option<i32>,
+
+	/// Draw the line smoothed with the given gnuplot algorithm.
+	///
+	/// Gives a quick visual trend without changing the underlying data. Best combined with the
+	/// `lines` style. Plotly backend renders all algorithms as a spline.
+	#[arg(long, value_name = "SMOOTH")]
+	pub smooth_style: Option<SmoothStyle>,
+
+	/// Do not show the line in the legend.
+	///
+	/// Useful for auxiliary lines (e.g. thresholds), which would only clutter the legend.
+	#[arg(long, num_args(0..=1), default_missing_value = "true")]
+	pub no_legend_entry: Option<bool>,
+
+	/// Store the matched text (`match`) or the whole log line (`line`) along with every value.
+	///
+	/// The text is shown in the plotly hover tooltips. Opt-in, as it increases the size of the CSV
+	/// cache files.
+	#[arg(long, value_name = "WHAT")]
+	pub keep_text: Option<MatchedText>,
+
+	/// Units of the plotted values.
+	///
+	/// By default captured time units (`s`, `ms`, `us`, `ns`) are converted to milliseconds. With
+	/// `original` values are kept as captured, the unit is stored in the `unit` column of the CSV
+	/// cache and shown on the tick labels of the line's axis.
+	#[arg(long, value_name = "UNITS")]
+	pub value_units: Option<ValueUnits>,
+
+	/// Translate captured strings to numbers, e.g. `true=1,false=0` or `idle=0,busy=1,error=2`.
+	///
+	/// Allows plotting of state flags which are not numeric in the log. Strings not present in
+	/// the map are skipped as values which cannot be converted. In the TOML config it is given as
+	/// a table, e.g. `value_map = { idle = 0, busy = 1 }`.
+	#[arg(long, value_name = "KEY=VALUE,...")]
+	pub value_map: Option<ValueMap>,
+
+	/// Title of the reference line subtracted from the values of this line.
+	///
+	/// The values of the reference line are linearly interpolated at the timestamps of this line,
+	/// so relative regressions between two runs or two nodes are visible directly. Values outside
+	/// of the time range of the reference line are skipped. If many lines have the given title, the
+	/// one drawn for the same input file is used.
+	#[arg(long, value_name = "TITLE")]
+	pub baseline: Option<String>,
+
+	/// Rescale the plotted values of the line.
+	///
+	/// Allows overlaying series of wildly different magnitudes on a single axis. Applied after the
+	/// baseline subtraction.
+	#[arg(long, value_name = "MODE")]
+	pub normalize: Option<Normalize>,
+
+	/// Transforms applied in order to the extracted values, e.g.
+	/// `smooth(5s),scale(0.001),clip(0,100)`.
+	///
+	/// Available transforms: `smooth(WINDOW)` (mean of the values within the preceding time
+	/// window), `scale(FACTOR)`, `offset(VALUE)`, `clip(MIN,MAX)` (either bound can be omitted),
+	/// `resample(INTERVAL[,POLICY])` (see `--resample`) and `normalize(MODE)` (see `--normalize`).
+	/// Applied before the baseline subtraction. In the TOML config it is given as a list, e.g.
+	/// `transforms = ["smooth(5s)", "scale(0.001)"]`.
+	#[arg(long, value_name = "TRANSFORM,...")]
+	pub transforms: Option<Transforms>,
+
+	/// Optional regex with a single capture group used to extract a label from matched lines.
+	///
+	/// The label is stored alongside each extracted value. Currently only used by `stat
+	/// --group-by`.
+	#[arg(skip)]
+	#[serde(skip)]
+	pub label_regex: Option<String>,
+
+	/// Optional hook post-processing the captured values before records are stored.
+	///
+	/// Only available for library users, applies to [`DataSource::FieldValue`] lines only.
+	#[arg(skip)]
+	#[serde(skip)]
+	pub value_transform: Option<ValueTransformHook>,
+
+	/// Optional filter restricting the line to log lines with the given label.
+	///
+	/// Set for the lines of panels repeated per label, see [`PanelParams::repeat_for`].
+	#[arg(skip)]
+	#[serde(skip)]
+	pub label_filter: Option<LabelFilter>,
+}
+
+/// Restricts the line to the log lines with given label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelFilter {
+	/// Regex with a single capture group extracting the label.
+	pub regex: String,
+	/// The label of the log lines to be kept.
+	pub label: String,
+}
+
+/// Post-processes the values captured from matched log lines, before records are stored.
+///
+/// Allows library users to plug in custom value parsing (e.g. custom units, JSON field decoding)
+/// in place of built-in unit normalization. Can be registered per line, see
+/// [`LineParams::value_transform`].
+///
+/// A `(name, closure)` pair, where closure is `Fn(&str, Option<&str>, Option<f64>) -> Option<f64>`,
+/// implements this trait.
+pub trait ValueTransform: Send + Sync {
+	/// Returns the value to be stored for the captured `value` and optional `unit`.
+	///
+	/// `normalized` is the value converted by built-in unit normalization (`None` if conversion
+	/// failed). Returning `None` drops the record.
+	fn transform(&self, value: &str, unit: Option<&str>, normalized: Option<f64>) -> Option<f64>;
+
+	/// Name of the transform, used in CSV cache file names.
+	///
+	/// Lines with the same data source and transform name share the CSV file, so distinct
+	/// transforms shall have distinct names.
+	fn name(&self) -> &str;
+}
+
+impl<N, F> ValueTransform for (N, F)
+where
+	N: AsRef<str> + Send + Sync,
+	F: Fn(&str, Option<&str>, Option<f64>) -> Option<f64> + Send + Sync,
+{
+	fn transform(&self, value: &str, unit: Option<&str>, normalized: Option<f64>) -> Option<f64> {
+		(self.1)(value, unit, normalized)
+	}
+
+	fn name(&self) -> &str {
+		self.0.as_ref()
+	}
+}
+
+/// Shared handle to the [`ValueTransform`], which can be stored in [`LineParams`].
+#[derive(Clone)]
+pub struct ValueTransformHook(pub Arc<dyn ValueTransform>);
+
+impl ValueTransformHook {
+	pub fn new(transform: impl ValueTransform + 'static) -> Self {
+		Self(Arc::new(transform))
+	}
+}
+
+impl std::fmt::Debug for ValueTransformHook {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("ValueTransformHook").field(&self.0.name()).finish()
+	}
+}
+
+impl PartialEq for ValueTransformHook {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LineWidth(pub f64);
 
-	pub fn per_file_panels(&self) -> bool {
-		self.per_file_panels.unwrap_or(false)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MarkerSize(pub f64);
+
+/// Opacity of the line, from `0.0` (invisible) to `1.0` (opaque).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Alpha(pub f64);
+
+/// Maximal distance (in seconds) between consecutive points of the line drawn as connected.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GapThreshold(pub f64);
+
+impl GapThreshold {
+	/// Tells if the line shall be broken between the points at given timestamps.
+	pub fn is_gap(&self, prev: NaiveDateTime, next: NaiveDateTime) -> bool {
+		(next - prev).num_milliseconds() as f64 / 1000.0 > self.0
 	}
 }
 
-/// A panel that holds multiple [`Line`]s in the same horizontal space.
-///
-/// Panels are typically stacked vertically, so each panel is drawn on a separate row.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
-pub struct Panel {
-	/// The list of lines to draw on this panel.
-	pub lines: Vec<Line>,
+impl Display for GapThreshold {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}s", self.0)
+	}
+}
 
-	#[serde(flatten)]
-	pub params: PanelParams,
+impl FromStr for GapThreshold {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_positive_seconds(s, "GapThreshold").map(Self)
+	}
 }
 
-/// A single line (or data series) to be plotted on a panel.
-///
-/// It includes a [`DataSource`] to describe the data source (e.g. plotting a field vs
-/// an event pattern), as well as various styling and configuration details
-/// (e.g. color, axis).
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct Line {
-	/// The logical data source or type of this line.
-	#[serde(flatten)]
-	pub data_source: DataSource,
+/// Interval (in seconds) between the major time ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TicsInterval(pub f64);
 
-	#[serde(flatten)]
-	pub params: LineParams,
+impl FromStr for TicsInterval {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_positive_seconds(s, "TicsInterval").map(Self)
+	}
 }
 
-impl Line {
-	pub fn new_with_data_source(data_source: DataSource) -> Self {
-		Line { data_source, params: LineParams::default() }
+/// Period (in seconds) the time axis is folded with (see `--fold-period`).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FoldPeriod(pub f64);
+
+impl FromStr for FoldPeriod {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		// Days are accepted additionally, periods are typically a day or a week.
+		match s.trim().strip_suffix('d') {
+			Some(days) => parse_positive_seconds(days, "FoldPeriod").map(|v| Self(v * 86400.0)),
+			None => parse_positive_seconds(s, "FoldPeriod").map(Self),
+		}
 	}
 }
 
-#[derive(Default, Clone, Args, Debug, Serialize, Deserialize, PartialEq)]
-pub struct LineParams {
-	/// Optionally overrides source log file.
-	///
-	/// Assigns a specific file to the line
-	#[arg(long)]
-	pub file_name: Option<PathBuf>,
-
-	/// Optionally specifies the index of input file.
-	///
-	/// Assigns the line to the nth input from `--input` (index starting at 0)
-	#[arg(long)]
-	pub file_id: Option<usize>,
+/// Policy of computing the samples of the resampled lines (see `--resample`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ResamplePolicy {
+	/// Value linearly interpolated between the neighbouring values.
+	#[default]
+	Linear,
+	/// The last value preceding the sample.
+	Ffill,
+	/// The last value within the interval preceding the sample. Samples without such value are
+	/// dropped.
+	Drop,
+}
 
-	/// Optional title of the line. Will be placed on legend.
-	#[arg(long)]
-	pub title: Option<String>,
+/// Resampling of the lines to the fixed interval (see `--resample`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resample {
+	/// Interval between the samples, in seconds.
+	pub interval: f64,
+	pub policy: ResamplePolicy,
+}
 
-	/// The style of the plotted line
-	#[arg(long, default_value = "points")]
-	#[serde(default)]
-	pub style: PlotStyle,
+impl TryFrom<&[String]> for Resample {
+	type Error = String;
+	fn try_from(args: &[String]) -> Result<Self, Self::Error> {
+		let (interval, policy) = match args {
+			[interval] => (interval, None),
+			[interval, policy] => (interval, Some(policy)),
+			_ => return Err("Resample: expected INTERVAL [POLICY]".to_string()),
+		};
+		let policy = policy
+			.map(|p| {
+				<ResamplePolicy as ValueEnum>::from_str(p, false)
+					.map_err(|e| format!("Resample: {e}"))
+			})
+			.transpose()?;
+		Ok(Self {
+			interval: parse_positive_seconds(interval, "Resample")?,
+			policy: policy.unwrap_or_default(),
+		})
+	}
+}
 
-	/// The width of the line
-	#[arg(long)]
-	pub line_width: Option<LineWidth>,
+/// Single transform of the line values (see [`LineParams::transforms`]).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "String", into = "String")]
+pub enum Transform {
+	/// `smooth(WINDOW)`: mean of the values within the preceding time window (in seconds).
+	Smooth(f64),
+	/// `scale(FACTOR)`: values multiplied by the factor.
+	Scale(f64),
+	/// `offset(VALUE)`: the value added to the values.
+	Offset(f64),
+	/// `clip(MIN,MAX)`: values clamped to the range, either bound can be omitted.
+	Clip(Option<f64>, Option<f64>),
+	/// `resample(INTERVAL[,POLICY])`: see `--resample`.
+	Resample(Resample),
+	/// `normalize(MODE)`: see [`LineParams::normalize`].
+	Normalize(Normalize),
+}
 
-	/// The color of the line.
-	#[arg(long)]
-	pub line_color: Option<Color>,
+impl FromStr for Transform {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Transform: invalid transform '{s}'");
+		let (name, args) =
+			s.trim().strip_suffix(')').and_then(|s| s.split_once('(')).ok_or_else(invalid)?;
+		let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+		let number = |s: &str| s.parse::<f64>().map_err(|e| format!("Transform: {s}: {e}"));
+		let bound = |s: &str| (!s.is_empty()).then(|| number(s)).transpose();
+		Ok(match (name.trim(), &args[..]) {
+			("smooth", [window]) => Self::Smooth(parse_positive_seconds(window, "Transform")?),
+			("scale", [factor]) => Self::Scale(number(factor)?),
+			("offset", [value]) => Self::Offset(number(value)?),
+			("clip", [min, max]) => Self::Clip(bound(min)?, bound(max)?),
+			("resample", args) => Self::Resample(Resample::try_from(
+				&args.iter().map(ToString::to_string).collect::<Vec<_>>()[..],
+			)?),
+			("normalize", [mode]) => Self::Normalize(
+				<Normalize as ValueEnum>::from_str(mode, false)
+					.map_err(|e| format!("Transform: {e}"))?,
+			),
+			_ => return Err(invalid()),
+		})
+	}
+}
 
-	/// The dash type.
-	#[arg(long)]
-	pub dash_style: Option<DashStyle>,
+impl Display for Transform {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let bound = |b: &Option<f64>| b.map(|b| b.to_string()).unwrap_or_default();
+		match self {
+			Self::Smooth(window) => write!(f, "smooth({window}s)"),
+			Self::Scale(factor) => write!(f, "scale({factor})"),
+			Self::Offset(value) => write!(f, "offset({value})"),
+			Self::Clip(min, max) => write!(f, "clip({},{})", bound(min), bound(max)),
+			Self::Resample(r) => {
+				write!(f, "resample({}s,{})", r.interval, value_enum_name(&r.policy))
+			},
+			Self::Normalize(mode) => write!(f, "normalize({})", value_enum_name(mode)),
+		}
+	}
+}
 
-	/// Which Y-axis this line should use, if applicable (e.g. primary or secondary).
-	#[arg(long)]
-	pub yaxis: Option<YAxis>,
+impl TryFrom<String> for Transform {
+	type Error = String;
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		s.parse()
+	}
+}
 
-	/// The marker type.
-	#[arg(long)]
-	pub marker_type: Option<MarkerType>,
+impl From<Transform> for String {
+	fn from(transform: Transform) -> Self {
+		transform.to_string()
+	}
+}
 
-	/// The color of the marker (if markers are enabled).
-	#[arg(long)]
-	pub marker_color: Option<Color>,
+/// Transforms of the line values applied in order (see [`LineParams::transforms`]).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Transforms(pub Vec<Transform>);
 
-	/// The size of the marker
-	#[arg(long, default_value_t = MarkerSize::default())]
-	#[serde(default = "MarkerSize::default")]
-	pub marker_size: MarkerSize,
+impl FromStr for Transforms {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		// Commas separate both the transforms and their arguments.
+		let mut transforms = vec![];
+		let (mut depth, mut start) = (0, 0);
+		for (i, c) in s.char_indices() {
+			match c {
+				'(' => depth += 1,
+				')' => depth -= 1,
+				',' if depth == 0 => {
+					transforms.push(s[start..i].parse()?);
+					start = i + 1;
+				},
+				_ => {},
+			}
+		}
+		transforms.push(s[start..].parse()?);
+		Ok(Self(transforms))
+	}
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub struct LineWidth(pub f64);
+impl Display for Transforms {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let transforms = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+		write!(f, "{}", transforms.join(","))
+	}
+}
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub struct MarkerSize(pub f64);
+/// Parses the positive duration given in seconds with an optional unit: `ms`, `s`, `m` or `h`.
+fn parse_positive_seconds(s: &str, name: &str) -> Result<f64, String> {
+	let s = s.trim();
+	let (value, multiplier) = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)]
+		.iter()
+		.find_map(|(unit, multiplier)| s.strip_suffix(unit).map(|v| (v, *multiplier)))
+		.unwrap_or((s, 1.0));
+	let value = value.trim().parse::<f64>().map_err(|e| format!("{name} parse error:{e}"))?;
+	if !(value > 0.0 && value.is_finite()) {
+		return Err(format!("{name}: invalid value {s}"));
+	}
+	Ok(value * multiplier)
+}
 
 impl Display for LineWidth {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -523,6 +1868,17 @@ impl FromStr for MarkerSize {
 	}
 }
 
+impl FromStr for Alpha {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let a = s.parse::<f64>().map_err(|e| format!("Alpha parse error:{}", e))?;
+		if !(0.0..=1.0).contains(&a) {
+			return Err(format!("Alpha: invalid value {a}, expected value in 0..1"));
+		}
+		Ok(Self(a))
+	}
+}
+
 impl FromStr for LineWidth {
 	type Err = String;
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -534,9 +1890,13 @@ impl FromStr for LineWidth {
 	}
 }
 
-#[derive(Default, Clone, Args, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Default, Clone, Args, Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct PanelParams {
 	/// Title displayed above the panel
+	///
+	/// Placeholders `{file}` (label or file stem of the input file), `{guard}`, `{pattern}` (of
+	/// the first line) and `{index}` (of the panel) are expanded, e.g. `--panel-title "{file}
+	/// latency"` for panels duplicated with `--per-file-panels`.
 	#[arg(long)]
 	pub panel_title: Option<String>,
 
@@ -548,6 +1908,20 @@ pub struct PanelParams {
 	#[arg(long)]
 	pub yaxis_scale: Option<AxisScale>,
 
+	/// Label of the secondary (right-hand) Y-axis.
+	#[arg(long)]
+	pub y2label: Option<String>,
+
+	/// Range of the secondary (right-hand) Y-axis: `MIN,MAX`.
+	///
+	/// Either bound can be left empty (or set to `*`) to be resolved automatically, e.g. `0,`.
+	#[arg(long)]
+	pub y2range: Option<AxisRange>,
+
+	/// Secondary (right-hand) Y-axis scale (linear or log)
+	#[arg(long)]
+	pub y2axis_scale: Option<AxisScale>,
+
 	/// Show legend.
 	///
 	/// Legend will be shown if not provided.
@@ -559,19 +1933,95 @@ pub struct PanelParams {
 	/// How panel time range shall be generated.
 	#[arg(long)]
 	pub time_range_mode: Option<PanelRangeMode>,
+
+	/// Repeat the panel once per label captured with the given regex (e.g. `peer=(\w+)`).
+	///
+	/// Regex shall contain exactly one capture group. Every instance of the panel shows only the
+	/// log lines with its label, and the label is appended to the panel title. Labels are
+	/// discovered in the input files, unless given with `--repeat-labels`.
+	#[arg(long, value_name = "REGEX")]
+	pub repeat_for: Option<String>,
+
+	/// Labels of the panel instances (comma separated), see `--repeat-for`.
+	///
+	/// Skips the discovery of labels in the input files, and keeps the given order.
+	#[arg(long, value_name = "LABEL,...", requires = "repeat_for")]
+	pub repeat_labels: Option<Vec<String>>,
+
+	/// Show a box with min/mean/p95/max of every line in the top-right corner of the panel.
+	///
+	/// Stats are computed from the extracted values within the panel time range, so the graph
+	/// answers the quantitative question without a separate `stat` run.
+	#[arg(long, num_args(0..=1), default_missing_value = "true")]
+	pub stats_box: Option<bool>,
+
+	/// Draw a horizontal threshold line at the given value of the primary Y-axis.
+	#[arg(long, value_name = "VALUE", allow_negative_numbers = true)]
+	pub hline: Option<f64>,
+
+	/// Highlight the values breaching the `--hline` threshold.
+	///
+	/// Values above the threshold of the lines drawn on the primary Y-axis are marked with red
+	/// points on top of the lines, making e.g. SLO violations pop out.
+	#[arg(long, num_args(0..=1), default_missing_value = "true", requires = "hline")]
+	pub highlight_breaches: Option<bool>,
+
+	/// Draw the events of every input file in its own row (swimlane) of the panel.
+	///
+	/// Matches of the lines are drawn as points at the row of their source file, with the name
+	/// of the file on the Y-axis, so the ordering of an event across the nodes is visible at a
+	/// glance. Extracted values are ignored. Best used with `--event` lines and multiple inputs.
+	#[arg(long, num_args(0..=1), default_missing_value = "true")]
+	pub swimlanes: Option<bool>,
 }
 
-#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum AxisScale {
 	Linear,
 	Log,
 }
 
+/// Range of the axis values. Missing bound is resolved automatically.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct AxisRange {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub min: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max: Option<f64>,
+}
+
+impl FromStr for AxisRange {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let Some((min, max)) = s.split_once(',') else {
+			return Err(format!("AxisRange: expected MIN,MAX, got '{s}'"));
+		};
+		let bound = |b: &str| match b.trim() {
+			"" | "*" => Ok(None),
+			b => b.parse::<f64>().map(Some).map_err(|e| format!("AxisRange parse error:{e}")),
+		};
+		let range = Self { min: bound(min)?, max: bound(max)? };
+		match (range.min, range.max) {
+			(Some(min), Some(max)) if min >= max => {
+				Err(format!("AxisRange: min ({min}) shall be less than max ({max})"))
+			},
+			_ => Ok(range),
+		}
+	}
+}
+
+impl Display for AxisRange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let bound = |b: Option<f64>| b.map_or("*".to_string(), |b| b.to_string());
+		write!(f, "{},{}", bound(self.min), bound(self.max))
+	}
+}
+
 /// Describes how to capture a numeric value from log lines using an optional guard and a field pattern.
 ///
 /// This specification is used by the data source to determine how to parse plotted values.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Args)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Args)]
 pub struct FieldCaptureSpec {
 	/// Optional guard string to quickly filter out log lines using `strcmp`
 	pub guard: Option<String>,
@@ -585,10 +2035,21 @@ pub struct FieldCaptureSpec {
 	// pub convert_to: Option<String>,
 }
 
+/// Describes the column of the existing CSV file plotted as a line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Args)]
+pub struct CsvFileSpec {
+	/// The CSV file starting with the header row (`.tsv` files are tab separated).
+	pub csv_file: PathBuf,
+	/// The name of the column containing timestamps, parsed with the timestamp format.
+	pub time_column: String,
+	/// The name of the column containing values. Values can be followed by the unit (e.g. `12ms`).
+	pub value_column: String,
+}
+
 /// Describes how to capture log events for calculating time deltas between consecutive matches.
 ///
 /// This specification is used by the data source to compute inter-event time differences.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Args)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Args)]
 pub struct EventDeltaSpec {
 	/// Optional guard string to quickly filter out log lines using `strcmp`
 	#[arg(required = false)]
@@ -598,7 +2059,7 @@ pub struct EventDeltaSpec {
 }
 
 /// Represents the different ways a line's data can be sourced from logs.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Subcommand)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Subcommand)]
 #[serde(tag = "data_source", rename_all = "snake_case")]
 pub enum DataSource {
 	/// Plot a fixed numerical value (`yvalue`) whenever `pattern` appears in logs.
@@ -623,12 +2084,57 @@ pub enum DataSource {
 	/// Plot the time delta between consecutive occurrences of `pattern`.
 	EventDelta(EventDeltaSpec),
 
+	/// Annotate the timeline with the text captured by `pattern` (e.g. version at startup).
+	///
+	/// Captured texts are drawn as labels at the time of every match, rather than as a series.
+	#[clap(name = "annotate")]
+	Annotation {
+		/// Optional guard string to quickly filter out log lines using `strcmp`
+		guard: Option<String>,
+		/// Regex pattern with a single capture group matching the annotation text.
+		pattern: String,
+	},
+
+	/// Plot a value of the JSON log line, extracted by the dotted `path` (e.g. `fields.duration`).
+	///
+	/// The JSON object starts at the first `{` of the log line, no regex is involved. Numbers,
+	/// booleans and strings (e.g. `12ms`) are plotted. If the timestamp is the first field of the
+	/// object, it can be matched with the timestamp format like
+	/// `{"timestamp":"%Y-%m-%dT%H:%M:%S%.fZ"`.
+	#[clap(name = "json-field")]
+	JsonField {
+		/// Optional guard on another field of the JSON object: `<PATH>=<VALUE>` (e.g.
+		/// `level=INFO`), or just `<PATH>` requiring the field to be present.
+		guard: Option<String>,
+		/// Dotted path to the value within the JSON object (e.g. `fields.duration`, `items.0`).
+		path: String,
+	},
+
+	/// Plot a value of the `key=value` pair of the logfmt structured log line.
+	///
+	/// Lines are tokenized (values can be quoted and contain escapes, e.g. `msg="a \"b\""
+	/// duration="-1.5ms"`), no regex is involved. Values can be followed by the unit.
+	#[clap(name = "logfmt")]
+	LogfmtField {
+		/// Optional guard string to quickly filter out log lines using `strcmp`
+		guard: Option<String>,
+		/// The key of the value (e.g. `duration`).
+		key: String,
+	},
+
 	/// Plot a numeric field from logs.
 	///
 	/// This is the most common data source type.
 	#[serde(untagged)]
 	#[clap(name = "plot")]
 	FieldValue(FieldCaptureSpec),
+
+	/// Plot a column of the existing CSV (or TSV) file, bypassing log parsing.
+	///
+	/// The line is always drawn from the given file, regardless of the input files.
+	#[serde(untagged)]
+	#[clap(name = "csv-file")]
+	CsvFile(CsvFileSpec),
 }
 
 impl DataSource {
@@ -644,9 +2150,30 @@ impl DataSource {
 		DataSource::EventDelta(EventDeltaSpec { guard, pattern })
 	}
 
+	pub fn new_annotation(guard: Option<String>, pattern: String) -> Self {
+		DataSource::Annotation { guard, pattern }
+	}
+
+	/// Tells if the data source is drawn as annotations instead of a plotted series.
+	pub fn is_annotation(&self) -> bool {
+		matches!(self, DataSource::Annotation { .. })
+	}
+
 	pub fn new_plot_field(guard: Option<String>, field: String) -> Self {
 		DataSource::FieldValue(FieldCaptureSpec { guard, field })
 	}
+
+	pub fn new_json_field(guard: Option<String>, path: String) -> Self {
+		DataSource::JsonField { guard, path }
+	}
+
+	pub fn new_logfmt_field(guard: Option<String>, key: String) -> Self {
+		DataSource::LogfmtField { guard, key }
+	}
+
+	pub fn new_csv_file(csv_file: PathBuf, time_column: String, value_column: String) -> Self {
+		DataSource::CsvFile(CsvFileSpec { csv_file, time_column, value_column })
+	}
 }
 
 /// Which Y-axis to plot a line against.
@@ -654,7 +2181,7 @@ impl DataSource {
 /// Typically, a graph can have two Y-axes:
 /// - The **primary** axis (left side) -> [`YAxis::Y`]
 /// - The **secondary** axis (right side) -> [`YAxis::Y2`]
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, ValueEnum)]
 #[serde(rename_all = "kebab-case")]
 pub enum YAxis {
 	/// Primary Y-axis (the left side).
@@ -663,9 +2190,129 @@ pub enum YAxis {
 	Y2,
 }
 
-/// Predefined set of colors for gnuplot lines and markers.
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, Serialize, EnumIter)]
+/// Text of the matched log line stored along with the value (see [`LineParams::keep_text`]).
+#[derive(
+	Clone,
+	Copy,
+	Debug,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	Serialize,
+	Deserialize,
+	JsonSchema,
+	ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchedText {
+	/// The substring matched by the data source regex.
+	Match,
+	/// The whole log line.
+	Line,
+}
+
+impl MatchedText {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Match => "match",
+			Self::Line => "line",
+		}
+	}
+}
+
+/// Mapping of captured strings to numbers (see [`LineParams::value_map`]).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct ValueMap(pub BTreeMap<String, f64>);
+
+impl ValueMap {
+	/// Returns the value the captured string is mapped to.
+	pub fn get(&self, key: &str) -> Option<f64> {
+		self.0.get(key).copied()
+	}
+}
+
+impl Display for ValueMap {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let entries = self.0.iter().map(|(key, value)| format!("{key}={value}"));
+		write!(f, "{}", entries.collect::<Vec<_>>().join(","))
+	}
+}
+
+impl FromStr for ValueMap {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let map = s
+			.split(',')
+			.map(|entry| {
+				let (key, value) = entry
+					.split_once('=')
+					.ok_or_else(|| format!("ValueMap: expected KEY=VALUE, got '{entry}'"))?;
+				let value = value
+					.trim()
+					.parse::<f64>()
+					.map_err(|e| format!("ValueMap parse error for '{entry}': {e}"))?;
+				Ok((key.trim().to_string(), value))
+			})
+			.collect::<Result<BTreeMap<_, _>, String>>()?;
+		if map.keys().any(|key| key.is_empty()) {
+			return Err(format!("ValueMap: empty key in '{s}'"));
+		}
+		Ok(Self(map))
+	}
+}
+
+/// Units of the values extracted by the field regex (see [`LineParams::value_units`]).
+#[derive(
+	Clone,
+	Copy,
+	Debug,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	Serialize,
+	Deserialize,
+	JsonSchema,
+	ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueUnits {
+	/// Time units converted to milliseconds.
+	Normalized,
+	/// Values kept in the captured unit.
+	Original,
+}
+
+/// Rescaling of the plotted values (see [`LineParams::normalize`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ValueEnum)]
 #[serde(rename_all = "kebab-case")]
+pub enum Normalize {
+	/// Percentage of the maximal absolute value of the line.
+	Max,
+	/// Percentage of the first value of the line.
+	First,
+	/// Number of standard deviations from the mean value of the line.
+	Zscore,
+}
+
+impl Normalize {
+	/// Suffix of the line title telling how the values were rescaled.
+	pub fn title_suffix(&self) -> &'static str {
+		match self {
+			Normalize::Max => "% of max",
+			Normalize::First => "% of first",
+			Normalize::Zscore => "z-score",
+		}
+	}
+}
+
+/// Color of lines and markers: one of the predefined set of colors (e.g. `red`, `dark-green`) or
+/// hex RGB value (e.g. `#ff8800`).
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, EnumIter, AsRefStr)]
+#[serde(try_from = "String", into = "String")]
+#[strum(serialize_all = "kebab-case")]
 pub enum Color {
 	Red,
 	Blue,
@@ -690,10 +2337,15 @@ pub enum Color {
 	Orange,
 	Green,
 	DarkOrange,
+	/// Custom RGB color, e.g. `0xff8800`. Not a part of the palette.
+	#[strum(disabled)]
+	Rgb(u32),
 }
 
 /// Predefined marker symbols for gnuplot plots.
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, Serialize, EnumIter)]
+#[derive(
+	ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, EnumIter,
+)]
 #[serde(rename_all = "kebab-case")]
 pub enum MarkerType {
 	Dot,
@@ -718,7 +2370,9 @@ impl FromStr for MarkerType {
 }
 
 /// Plot styles for gnuplot
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, Serialize, Default, EnumIter)]
+#[derive(
+	ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, Default, EnumIter,
+)]
 #[serde(rename_all = "kebab-case")]
 pub enum PlotStyle {
 	#[default]
@@ -728,6 +2382,17 @@ pub enum PlotStyle {
 	Lines,
 }
 
+/// Smoothing algorithms for gnuplot
+#[derive(
+	ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, EnumIter,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothStyle {
+	Bezier,
+	Csplines,
+	Sbezier,
+}
+
 impl FromStr for PlotStyle {
 	type Err = String;
 
@@ -737,7 +2402,9 @@ impl FromStr for PlotStyle {
 }
 
 /// Dash (line-type) styles for gnuplot
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, Serialize, Default, EnumIter)]
+#[derive(
+	ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, Default, EnumIter,
+)]
 #[serde(rename_all = "kebab-case")]
 pub enum DashStyle {
 	#[default]
@@ -756,11 +2423,57 @@ impl FromStr for DashStyle {
 	}
 }
 
+/// Returns the name of the value accepted on the command line (e.g. `lines-points`).
+pub fn value_enum_name<T: ValueEnum>(value: &T) -> String {
+	value.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
+
+/// Names of all predefined styles accepted by line options, grouped by kind (see `plox styles`).
+pub fn style_names() -> Vec<(&'static str, Vec<String>)> {
+	vec![
+		("colors", Color::iter().map(|c| c.to_string()).collect()),
+		("markers", MarkerType::iter().map(|m| value_enum_name(&m)).collect()),
+		("dash styles", DashStyle::iter().map(|d| value_enum_name(&d)).collect()),
+		("plot styles", PlotStyle::iter().map(|p| value_enum_name(&p)).collect()),
+	]
+}
+
 impl FromStr for Color {
 	type Err = String;
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		<Color as ValueEnum>::from_str(s, true).map_err(|_| format!("Bad Color: {}", s))
+		if let Some(hex) = s.strip_prefix('#') {
+			return (hex.len() == 6)
+				.then(|| u32::from_str_radix(hex, 16).ok())
+				.flatten()
+				.map(Color::Rgb)
+				.ok_or_else(|| format!("Bad Color: {s}, expected #rrggbb"));
+		}
+		Color::iter()
+			.find(|c| c.as_ref().eq_ignore_ascii_case(s))
+			.ok_or_else(|| format!("Bad Color: {}", s))
+	}
+}
+
+impl Display for Color {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Color::Rgb(rgb) => write!(f, "#{rgb:06x}"),
+			color => f.write_str(color.as_ref()),
+		}
+	}
+}
+
+impl TryFrom<String> for Color {
+	type Error = String;
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		s.parse()
+	}
+}
+
+impl From<Color> for String {
+	fn from(color: Color) -> Self {
+		color.to_string()
 	}
 }
 
@@ -788,7 +2501,7 @@ fn validate_standalone_filename(s: &str) -> Result<PathBuf, String> {
 ///
 /// This determines the `time_range` for every panel, based on the `time_range` values of the lines
 /// it contains.
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, Serialize, Default)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Deserialize, JsonSchema, Serialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub enum PanelRangeMode {
 	/// Use the full span of all line ranges (min start, max end).
@@ -834,7 +2547,7 @@ pub enum PanelAlignmentMode {
 }
 
 /// Clap wrapper for [`PanelAlignmentMode`]
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
 pub enum PanelAlignmentModeArg {
 	#[default]
 	SharedFull,
@@ -846,7 +2559,7 @@ pub enum PanelAlignmentModeArg {
 ///
 /// This can be used to zoom in or constrain the graph to a specific time window.
 /// The variant determines how to interpret the input:
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum TimeRangeArg {
 	/// Relative zoom: values between 0.0 and 1.0
 	Relative(f64, f64),
@@ -874,6 +2587,21 @@ impl TimeRangeArg {
 }
 
 impl GraphConfig {
+	/// Mutable paths of the files read by the lines: the source log files (see
+	/// [`LineParams::file_name`]) and the plotted CSV files.
+	pub(crate) fn paths_mut(&mut self) -> impl Iterator<Item = &mut PathBuf> {
+		self.panels
+			.iter_mut()
+			.flat_map(|panel| panel.lines.iter_mut())
+			.flat_map(|line| {
+				let csv_file = match &mut line.data_source {
+					DataSource::CsvFile(spec) => Some(&mut spec.csv_file),
+					_ => None,
+				};
+				line.params.file_name.iter_mut().chain(csv_file)
+			})
+	}
+
 	pub fn save_to_file(self: &GraphConfig, config_path: &Path) -> Result<(), Error> {
 		let toml_string = toml::to_string(self).expect("Failed to convert GraphConfig to TOML");
 		fs::write(config_path, toml_string)
@@ -881,6 +2609,53 @@ impl GraphConfig {
 			.map_err(|e| Error::IoError(format!("{:?}", config_path), e))
 	}
 
+	/// Writes the graph configuration without default values, with comments (see
+	/// [`Self::to_minimal_toml`]).
+	pub fn save_minimal_to_file(&self, config_path: &Path) -> Result<(), Error> {
+		fs::write(config_path, self.to_minimal_toml())
+			.map(|_| info!("Minimal config saved successfully: {:?}.", config_path))
+			.map_err(|e| Error::IoError(format!("{:?}", config_path), e))
+	}
+
+	/// Returns the TOML representation of the config containing only the values differing from
+	/// the defaults. Every value is preceded by the comment explaining it, taken from the help of
+	/// the corresponding CLI option.
+	pub fn to_minimal_toml(&self) -> String {
+		use clap::CommandFactory;
+		let args_help = |command: clap::Command| {
+			command
+				.get_arguments()
+				.map(|arg| (arg.get_id().to_string(), arg.get_help().map(ToString::to_string)))
+				.collect::<Vec<_>>()
+		};
+		let panel_help = args_help(PanelParams::augment_args(clap::Command::new("panel")));
+		let line_help = args_help(LineParams::augment_args(clap::Command::new("line")));
+		let data_sources = crate::data_source_cli_builder::DummyDataSourceSubcommand::command();
+
+		let mut toml = String::from(MINIMAL_CONFIG_HEADER);
+		for panel in &self.panels {
+			toml.push_str("\n[[panels]]\n");
+			push_commented_fields(&mut toml, non_default_fields(&panel.params), &panel_help);
+			for line in &panel.lines {
+				let cli_name = line.data_source.cli_args()[0].trim_start_matches("--").to_string();
+				let data_source = data_sources.find_subcommand(&cli_name);
+				let mut help = data_source.map(|sc| args_help(sc.clone())).unwrap_or_default();
+				help.insert(
+					0,
+					(
+						"data_source".into(),
+						data_source.and_then(|sc| sc.get_about()).map(ToString::to_string),
+					),
+				);
+				help.extend(line_help.iter().cloned());
+
+				toml.push_str("\n[[panels.lines]]\n");
+				push_commented_fields(&mut toml, non_default_fields(line), &help);
+			}
+		}
+		toml
+	}
+
 	pub fn load_from_file(path: &Path) -> Result<Self, Error> {
 		let content = fs::read_to_string(path).map_err(|error| {
 			error!(?error, "Reading toml error");
@@ -894,6 +2669,50 @@ impl GraphConfig {
 	}
 }
 
+/// Header of the config written by [`GraphConfig::to_minimal_toml`].
+const MINIMAL_CONFIG_HEADER: &str = "\
+# Graph config written with `plox graph --write-config-minimal`.
+# Every panel starts with [[panels]], followed by its lines ([[panels.lines]]).
+# Omitted fields have default values, refer to `plox graph --help` for all of them.
+";
+
+/// Returns the fields of the serialized `value` which are not restored to the same value when
+/// omitted, i.e. fields differing from the defaults.
+pub(crate) fn non_default_fields<T>(value: &T) -> Vec<(String, toml::Value)>
+where
+	T: Serialize + for<'de> Deserialize<'de> + PartialEq,
+{
+	let Ok(toml::Value::Table(table)) = toml::Value::try_from(value) else {
+		return vec![];
+	};
+	table
+		.iter()
+		.filter(|(key, _)| {
+			let mut without = table.clone();
+			without.remove(*key);
+			toml::Value::Table(without).try_into::<T>().ok().as_ref() != Some(value)
+		})
+		.map(|(key, value)| (key.clone(), value.clone()))
+		.collect()
+}
+
+/// Appends the `key = value` lines, each preceded by the comment with the help of the key.
+///
+/// Fields are ordered as in `help` (i.e. as the CLI options), fields without help go last.
+fn push_commented_fields(
+	toml: &mut String,
+	mut fields: Vec<(String, toml::Value)>,
+	help: &[(String, Option<String>)],
+) {
+	fields.sort_by_key(|(key, _)| help.iter().position(|(id, _)| id == key).unwrap_or(usize::MAX));
+	for (key, value) in fields {
+		if let Some((_, Some(help))) = help.iter().find(|(id, _)| *id == key) {
+			toml.push_str(&format!("# {help}\n"));
+		}
+		toml.push_str(&format!("{key} = {value}\n"));
+	}
+}
+
 pub fn annotate_toml_error(err: &TomlError, source: &str, filename: &str) -> String {
 	if let Some(span) = err.span() {
 		let snippet = Snippet::source(source)
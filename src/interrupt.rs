@@ -0,0 +1,37 @@
+//! Handling of the interruption of plox (Ctrl-C / `SIGINT`, `SIGTERM`).
+//!
+//! The signal handler only sets the flag, which is checked while reading logs. Reading of the
+//! interrupted log fails, so the processing is aborted in a regular way: partially written CSV
+//! caches and temporary scripts are removed, and no corrupted cache is used by subsequent runs.
+//! The second signal terminates the process immediately.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the handler of `SIGINT` and `SIGTERM` signals. Does nothing on non-unix platforms.
+pub fn install_handler() {
+	#[cfg(unix)]
+	{
+		let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		// SAFETY: the handler is async-signal-safe: it only touches the atomic flag and calls
+		// `_exit`.
+		unsafe {
+			libc::signal(libc::SIGINT, handler);
+			libc::signal(libc::SIGTERM, handler);
+		}
+	}
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+	if INTERRUPTED.swap(true, Ordering::SeqCst) {
+		// SAFETY: `_exit` is async-signal-safe.
+		unsafe { libc::_exit(128 + signal) };
+	}
+}
+
+/// Returns true if the interruption signal was received.
+pub fn is_interrupted() -> bool {
+	INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -13,15 +13,28 @@ docify::compile_markdown!("README.docify.md", "README.md");
 plox_macros::plox_process_doc!("README.md");
 
 pub mod align_ranges;
+pub mod backend;
+#[cfg(all(test, feature = "gnuplot", feature = "plotly"))]
+mod backend_conformance;
+pub mod baseline;
+mod cache_manifest;
 pub mod cli;
 pub mod data_source_cli_builder;
 pub mod error;
+#[cfg(feature = "gnuplot")]
 pub mod gnuplot;
 pub mod graph_cli_builder;
 pub mod graph_config;
+pub mod interrupt;
 pub mod logging;
 pub mod match_preview_cli_builder;
+pub mod period_fold;
+pub mod pipeline;
+#[cfg(feature = "plotly")]
 pub mod plotly_backend;
 pub mod process_log;
+pub mod provenance;
+pub mod report;
 pub mod resolved_graph_config;
+pub mod transforms;
 mod utils;
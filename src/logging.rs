@@ -1,22 +1,101 @@
 //! Sets up structured logging and tracing for the plox binary.
 
-use tracing_subscriber::{EnvFilter, fmt};
+use std::{
+	fmt::Debug,
+	fs::File,
+	io::{self, Write},
+	path::Path,
+	sync::Mutex,
+};
+use tracing::{Level, Subscriber, field::Field, info};
+use tracing_subscriber::{
+	EnvFilter, Layer, fmt, fmt::MakeWriter, fmt::writer::BoxMakeWriter, registry::LookupSpan,
+};
 
 use crate::process_log::MATCH_PREVIEW;
 
 /// Tracing target for verbose (-v -vv -vvv) cross-application messages.
 pub const APPV: &str = "appverbose";
 
+/// Tracing target for the machine-readable events emitted with `--message-format json`.
+///
+/// The message of the event is its `reason` (e.g. `cache-hit`), the fields of the event are copied
+/// into the JSON object. These events are never displayed in the human readable output.
+pub const MESSAGES: &str = "plox_messages";
+
+/// Format of the messages reported while running.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum MessageFormat {
+	/// Human readable log messages.
+	#[default]
+	Human,
+	/// One JSON object per line, e.g. `{"reason":"cache-hit","file":"..."}`.
+	Json,
+}
+
+/// Emits the `output` event (`--message-format json`) for the file written by plox.
+pub fn output_message(kind: &'static str, path: &Path) {
+	info!(target: MESSAGES, kind, path = %path.display(), "output");
+}
+
 pub fn init_tracing_test() {
 	use std::sync::Once;
 	static INIT: Once = Once::new();
 	INIT.call_once(|| {
-		init_tracing(true, 0);
+		init_tracing(true, 0, None).expect("no log file is created");
 	});
 }
 
-pub fn init_tracing(quiet: bool, verbosity: u8) {
+/// Initializes the tracing with messages written to stdout.
+///
+/// If `log_file` is given, the messages up to the level given by `verbosity` are written into that
+/// file, while the console is kept at the info level.
+pub fn init_tracing(quiet: bool, verbosity: u8, log_file: Option<&Path>) -> io::Result<()> {
+	init_tracing_with_writer(quiet, verbosity, BoxMakeWriter::new(std::io::stdout), log_file)
+}
+
+/// Initializes the tracing with all messages written to stderr, so stdout is left for the output
+/// data (e.g. `--output -`).
+pub fn init_tracing_on_stderr(
+	quiet: bool,
+	verbosity: u8,
+	log_file: Option<&Path>,
+) -> io::Result<()> {
+	init_tracing_with_writer(quiet, verbosity, BoxMakeWriter::new(std::io::stderr), log_file)
+}
+
+/// Returns the layer writing the messages up to the level given by `verbosity` into `log_file`.
+fn log_file_layer<S>(log_file: Option<File>, verbosity: u8) -> Option<impl Layer<S>>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	let level = match verbosity {
+		0 => "info",
+		1 => "debug",
+		_ => "trace",
+	};
+	log_file.map(|file| {
+		fmt::layer()
+			.with_ansi(false)
+			.with_target(true)
+			.with_writer(Mutex::new(file))
+			.with_filter(EnvFilter::new(format!("warn,{APPV}={level}")))
+	})
+}
+
+fn init_tracing_with_writer(
+	quiet: bool,
+	mut verbosity: u8,
+	writer: BoxMakeWriter,
+	log_file: Option<&Path>,
+) -> io::Result<()> {
 	use tracing_subscriber::prelude::*;
+	let log_file = log_file.map(File::create).transpose()?;
+	let log_file_verbosity = verbosity;
+	if log_file.is_some() {
+		// Verbose messages go to the log file only.
+		verbosity = 0;
+	}
 	if std::env::var("RUST_LOG").is_ok() {
 		let rust_log_env = std::env::var("RUST_LOG").unwrap_or_default();
 		let mut full_filter = EnvFilter::new(&rust_log_env);
@@ -28,6 +107,10 @@ pub fn init_tracing(quiet: bool, verbosity: u8) {
 			full_filter = full_filter.add_directive(directive.parse().unwrap());
 		}
 
+		if !rust_log_env.contains(MESSAGES) {
+			full_filter = full_filter.add_directive(format!("{MESSAGES}=off").parse().unwrap());
+		}
+
 		// We should still respect -vv, so enable APPV if not given
 		if !rust_log_env.contains(APPV) {
 			let level = match (quiet, verbosity) {
@@ -43,8 +126,8 @@ pub fn init_tracing(quiet: bool, verbosity: u8) {
 		}
 
 		let subscriber = tracing_subscriber::registry()
-			.with(fmt::layer().with_target(true))
-			.with(full_filter);
+			.with(fmt::layer().with_target(true).with_writer(writer).with_filter(full_filter))
+			.with(log_file_layer(log_file, log_file_verbosity));
 
 		tracing::subscriber::set_global_default(subscriber)
 			.expect("Failed to set tracing subscriber");
@@ -62,12 +145,102 @@ pub fn init_tracing(quiet: bool, verbosity: u8) {
 			EnvFilter::new("warn")
 		};
 
-		let fmt_layer = fmt::layer().without_time().with_target(false).with_level(true);
+		let fmt_layer = fmt::layer()
+			.without_time()
+			.with_target(false)
+			.with_level(true)
+			.with_writer(writer);
 
-		let subscriber = tracing_subscriber::registry().with(fmt_layer).with(env_filter);
+		let subscriber = tracing_subscriber::registry()
+			.with(fmt_layer.with_filter(env_filter))
+			.with(log_file_layer(log_file, log_file_verbosity));
 		tracing::subscriber::set_global_default(subscriber)
 			.expect("Failed to set tracing subscriber");
 	};
+	Ok(())
+}
+
+/// Initializes the tracing for `--message-format json`.
+///
+/// Only the [`MESSAGES`] events, warnings and errors are reported, each as a single line JSON
+/// object with the `reason` key (`warning` and `error` for warnings and errors). The `log_file`
+/// receives the human readable messages, as with [`init_tracing`].
+pub fn init_tracing_json(
+	on_stderr: bool,
+	verbosity: u8,
+	log_file: Option<&Path>,
+) -> io::Result<()> {
+	use tracing_subscriber::prelude::*;
+	let log_file = log_file.map(File::create).transpose()?;
+	let writer = if on_stderr {
+		BoxMakeWriter::new(std::io::stderr)
+	} else {
+		BoxMakeWriter::new(std::io::stdout)
+	};
+	let subscriber = tracing_subscriber::registry()
+		.with(
+			JsonLayer { writer }
+				.with_filter(EnvFilter::new(format!("warn,{MATCH_PREVIEW}=off,{MESSAGES}=info"))),
+		)
+		.with(log_file_layer(log_file, verbosity));
+	tracing::subscriber::set_global_default(subscriber).expect("Failed to set tracing subscriber");
+	Ok(())
+}
+
+/// Writes every event as a single line JSON object.
+struct JsonLayer {
+	writer: BoxMakeWriter,
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for JsonLayer {
+	fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+		let mut fields = JsonFields::default();
+		event.record(&mut fields);
+		let metadata = event.metadata();
+		let reason = if metadata.target() == MESSAGES {
+			fields.0.remove("message").unwrap_or_default()
+		} else {
+			match *metadata.level() {
+				Level::ERROR => "error",
+				Level::WARN => "warning",
+				_ => "message",
+			}
+			.into()
+		};
+		let mut object = serde_json::Map::from_iter([("reason".to_string(), reason)]);
+		object.extend(fields.0);
+		let _ = writeln!(self.writer.make_writer(), "{}", serde_json::Value::Object(object));
+	}
+}
+
+/// Fields of the event converted to JSON values.
+#[derive(Default)]
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl tracing::field::Visit for JsonFields {
+	fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+		self.0.insert(field.name().into(), format!("{value:?}").into());
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.insert(field.name().into(), value.into());
+	}
+
+	fn record_i64(&mut self, field: &Field, value: i64) {
+		self.0.insert(field.name().into(), value.into());
+	}
+
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		self.0.insert(field.name().into(), value.into());
+	}
+
+	fn record_f64(&mut self, field: &Field, value: f64) {
+		self.0.insert(field.name().into(), value.into());
+	}
+
+	fn record_bool(&mut self, field: &Field, value: bool) {
+		self.0.insert(field.name().into(), value.into());
+	}
 }
 
 // fn testing() {
@@ -32,11 +32,10 @@ impl MatchPreviewConfig {
 		let data_sources = DataSource::get_cli_ids();
 		let mut data_source = None;
 		for id in &data_sources {
-			if let Some(mut plot_values) = matches.get_occurrences::<String>(id) {
-				if let Some(plot_value) = plot_values.next() {
-					let args: Vec<_> = plot_value.collect();
-					data_source = Some(DataSource::try_from_flag(id, &args)?);
-				}
+			let plot_value = matches.get_occurrences::<String>(id).and_then(|mut v| v.next());
+			if let Some(plot_value) = plot_value {
+				let args: Vec<_> = plot_value.collect();
+				data_source = Some(DataSource::try_from_flag(id, &args)?);
 			}
 		}
 
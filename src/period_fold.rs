@@ -0,0 +1,187 @@
+//! Folding of the time axis modulo a period (see `--fold-period`).
+//!
+//! This is the transform stage between the log processing and the backends: the CSV file of
+//! every line is split into one CSV file per period, with the timestamps shifted into the first
+//! period of the graph. Every period becomes a separate line, so e.g. the days of a long log are
+//! overlaid for spotting diurnal patterns.
+
+use crate::{
+	graph_config::FoldPeriod,
+	logging::APPV,
+	process_log::{format_record_timestamp, parse_record_timestamp, record_derived_csvs},
+	resolved_graph_config::{ResolvedGraphConfig, UnresolvedCsvFileError},
+};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use std::{
+	collections::{BTreeMap, HashMap, hash_map::Entry},
+	fs, io,
+	path::{Path, PathBuf},
+};
+use tracing::debug;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("Error while accessing file '{0}': {1}")]
+	FileError(PathBuf, io::Error),
+	#[error("Malformed record in CSV file '{0}': '{1}' (this is bug)")]
+	MalformedRecord(PathBuf, String),
+	#[error(transparent)]
+	UnresolvedCsvFile(#[from] UnresolvedCsvFileError),
+}
+
+/// Records of the CSV file grouped by the start of their period.
+struct FoldedCsv {
+	header: String,
+	/// Offset of the record within its period, and the columns following the timestamp.
+	periods: BTreeMap<NaiveDateTime, Vec<(TimeDelta, String)>>,
+}
+
+/// Start of the period containing the timestamp.
+///
+/// Periods are counted from Monday 1970-01-05, so daily periods start at midnight and weekly
+/// ones on Monday.
+fn period_start(timestamp: NaiveDateTime, period: TimeDelta) -> NaiveDateTime {
+	let anchor: NaiveDateTime = NaiveDate::from_ymd_opt(1970, 1, 5).expect("date is valid").into();
+	let elapsed = (timestamp - anchor).num_milliseconds();
+	let period_ms = period.num_milliseconds();
+	anchor + TimeDelta::milliseconds(elapsed.div_euclid(period_ms) * period_ms)
+}
+
+/// Label of the period, distinguishing the lines of the folded graph.
+fn period_label(start: NaiveDateTime, period: TimeDelta) -> String {
+	let format = if period.num_milliseconds() % TimeDelta::days(1).num_milliseconds() == 0 {
+		"%Y-%m-%d"
+	} else if period.num_milliseconds() % TimeDelta::minutes(1).num_milliseconds() == 0 {
+		"%Y-%m-%d %H:%M"
+	} else {
+		"%Y-%m-%d %H:%M:%S%.3f"
+	};
+	start.format(format).to_string()
+}
+
+fn read_folded_csv(path: &Path, period: TimeDelta) -> Result<FoldedCsv, Error> {
+	let content = fs::read_to_string(path).map_err(|e| Error::FileError(path.to_path_buf(), e))?;
+	let mut lines = content.lines();
+	let header = lines.next().unwrap_or_default().to_string();
+	let mut periods: BTreeMap<_, Vec<_>> = BTreeMap::new();
+	for record in lines {
+		let malformed = || Error::MalformedRecord(path.to_path_buf(), record.to_string());
+		let mut columns = record.splitn(3, ',');
+		let (Some(date), Some(time), Some(rest)) = (columns.next(), columns.next(), columns.next())
+		else {
+			return Err(malformed());
+		};
+		let timestamp = parse_record_timestamp(date, time).ok_or_else(malformed)?;
+		let start = period_start(timestamp, period);
+		periods.entry(start).or_default().push((timestamp - start, rest.to_string()));
+	}
+	Ok(FoldedCsv { header, periods })
+}
+
+/// Path of the CSV file holding the records of the period starting at `start`.
+fn folded_csv_path(path: &Path, start: NaiveDateTime) -> PathBuf {
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	path.with_file_name(format!("{stem}.fold-{}.csv", start.format("%Y%m%dT%H%M%S%.3f")))
+}
+
+/// Replaces every line of the graph with one line per period of `period`, drawing the values of
+/// the period shifted into the first period of the graph.
+///
+/// Must be called after [`crate::process_log::process_inputs`], and before the time ranges are
+/// resolved. Empty lines are kept intact.
+pub fn fold_periods(config: &mut ResolvedGraphConfig, period: FoldPeriod) -> Result<(), Error> {
+	let period = TimeDelta::milliseconds(((period.0 * 1000.0) as i64).max(1));
+
+	let mut csv_files = HashMap::new();
+	for line in config.all_lines().filter(|line| !line.is_empty()) {
+		let path = line.resolved_csv_filename()?;
+		if let Entry::Vacant(entry) = csv_files.entry(path) {
+			let folded = read_folded_csv(entry.key(), period)?;
+			entry.insert(folded);
+		}
+	}
+	let Some(base) = csv_files.values().filter_map(|csv| csv.periods.keys().next()).min().copied()
+	else {
+		return Ok(());
+	};
+
+	let mut folded_csvs = vec![];
+	for (path, csv) in &csv_files {
+		for (start, records) in &csv.periods {
+			let mut content = csv.header.clone();
+			for (offset, rest) in records {
+				let (date, time) = format_record_timestamp(base + *offset);
+				content.push_str(&format!("\n{date},{time},{rest}"));
+			}
+			content.push('\n');
+			let folded_path = folded_csv_path(path, *start);
+			fs::write(&folded_path, content)
+				.map_err(|e| Error::FileError(folded_path.clone(), e))?;
+			let offsets = records.iter().map(|(offset, _)| *offset);
+			let range = offsets
+				.clone()
+				.min()
+				.zip(offsets.max())
+				.map(|(min, max)| (base + min, base + max));
+			folded_csvs.push((folded_path, (records.len(), range)));
+		}
+	}
+	record_derived_csvs(&folded_csvs);
+
+	for panel in &mut config.panels {
+		let mut lines = vec![];
+		for line in panel.lines.drain(..) {
+			let Some((path, csv)) = line
+				.shared_csv_filename()
+				.and_then(|path| csv_files.get(&path).map(|c| (path, c)))
+			else {
+				lines.push(line);
+				continue;
+			};
+			for (start, records) in &csv.periods {
+				let mut folded = line.clone();
+				folded.set_shared_csv_filename(&folded_csv_path(&path, *start));
+				folded.set_data_points_count(records.len());
+				let offsets = records.iter().map(|(offset, _)| *offset);
+				if let (Some(min), Some(max)) = (offsets.clone().min(), offsets.max()) {
+					folded.set_time_range(base + min, base + max);
+				}
+				folded.set_period_label(period_label(*start, period));
+				lines.push(folded);
+			}
+		}
+		panel.lines = lines;
+	}
+	debug!(target:APPV, "Lines folded into periods of {period}, base period: {base}");
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ts(s: &str) -> NaiveDateTime {
+		NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+	}
+
+	#[test]
+	fn test_period_start() {
+		let day = TimeDelta::days(1);
+		assert_eq!(period_start(ts("2020-01-01 13:45:00"), day), ts("2020-01-01 00:00:00"));
+		assert_eq!(period_start(ts("1960-06-01 01:00:00"), day), ts("1960-06-01 00:00:00"));
+		// 2020-01-01 is Wednesday.
+		let week = TimeDelta::days(7);
+		assert_eq!(period_start(ts("2020-01-01 13:45:00"), week), ts("2019-12-30 00:00:00"));
+		let hours = TimeDelta::hours(6);
+		assert_eq!(period_start(ts("2020-01-01 13:45:00"), hours), ts("2020-01-01 12:00:00"));
+	}
+
+	#[test]
+	fn test_period_label() {
+		let start = ts("2020-01-01 12:00:00");
+		assert_eq!(period_label(start, TimeDelta::days(1)), "2020-01-01");
+		assert_eq!(period_label(start, TimeDelta::hours(6)), "2020-01-01 12:00");
+		assert_eq!(period_label(start, TimeDelta::seconds(90)), "2020-01-01 12:00:00.000");
+	}
+}
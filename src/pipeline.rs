@@ -0,0 +1,271 @@
+//! Library entry point for the log extraction pipeline.
+//!
+//! [`Pipeline`] allows other tools to reuse the plox log parsing and value extraction without
+//! going through the CLI. Values are extracted directly from log files into memory, no CSV cache
+//! files are read or written.
+//!
+//! Values can be either collected at once ([`Pipeline::extract`]), or pulled lazily with
+//! [`Pipeline::iter`] (e.g. for embedding in TUIs or notebooks).
+//!
+//! Values of custom data source kinds can be extracted by implementing [`LineExtractor`] and
+//! iterating [`SeriesIter::from_extractor`] (the graph config and CSV cache only support
+//! [`DataSource`](crate::graph_config::DataSource)).
+//!
+//! ```no_run
+//! use plox::{
+//!     graph_config::{DataSource, GraphConfig, InputFilesContext, Line, Panel},
+//!     pipeline::Pipeline,
+//! };
+//!
+//! let line = Line::new_with_data_source(DataSource::new_plot_field(None, "duration".into()));
+//! let config = GraphConfig { panels: vec![Panel::builder().with_lines(vec![line]).build()] };
+//! let input = InputFilesContext::new_with_input(vec!["some.log".into()]);
+//!
+//! for series in Pipeline::new(config, input).extract()? {
+//!     println!("{}: {} points", series.input_file.display(), series.points.len());
+//! }
+//! # Ok::<(), plox::error::Error>(())
+//! ```
+
+pub use crate::process_log::{DataSourceExtractor, LineExtractor, SeriesIter};
+use crate::{
+	error::Error,
+	graph_config::{GraphConfig, InputFilesContext, Line},
+	process_log, resolved_graph_config,
+};
+use chrono::NaiveDateTime;
+use std::path::PathBuf;
+
+/// Single value extracted from a matching log line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataPoint {
+	/// Timestamp of the log line. For time-only timestamp formats the date is set to
+	/// `2025-01-01`.
+	pub timestamp: NaiveDateTime,
+	/// Extracted value (normalized to milliseconds for durations).
+	pub value: f64,
+	/// Number of occurrences so far (including this one).
+	pub count: u64,
+	/// Time delta (in milliseconds) from the previous occurrence.
+	pub delta: Option<f64>,
+	/// Number of the source log line (1-based).
+	pub line: u64,
+	/// Label extracted with the line's label regex (if configured).
+	pub label: Option<String>,
+}
+
+/// Values extracted for a single line of the graph config from a single input file.
+#[derive(Clone, Debug)]
+pub struct Series {
+	/// Line from the graph config the values were extracted for.
+	pub line: Line,
+	/// Log file the values were extracted from.
+	pub input_file: PathBuf,
+	/// Extracted values, in log order.
+	pub points: Vec<DataPoint>,
+}
+
+impl Series {
+	/// Returns the iterator over `(timestamp, value)` pairs.
+	pub fn values(&self) -> impl Iterator<Item = (NaiveDateTime, f64)> + '_ {
+		self.points.iter().map(|p| (p.timestamp, p.value))
+	}
+}
+
+/// Extracts values from log files according to the graph config.
+#[derive(Debug)]
+pub struct Pipeline {
+	config: GraphConfig,
+	input_files_ctx: InputFilesContext,
+}
+
+impl Pipeline {
+	pub fn new(config: GraphConfig, input_files_ctx: InputFilesContext) -> Self {
+		Self { config, input_files_ctx }
+	}
+
+	/// Extracts the values of every line in the config.
+	///
+	/// Lines without explicit file are applied to every input file, so one [`Series`] per line and
+	/// input file is returned. Every log file is read only once.
+	pub fn extract(&self) -> Result<Vec<Series>, Error> {
+		let resolved_config = resolved_graph_config::expand_graph_config(
+			&self.config,
+			&self.input_files_ctx.logical_input(),
+			false,
+		)?;
+		Ok(process_log::extract_series(&resolved_config, &self.input_files_ctx)?)
+	}
+
+	/// Returns the lazy iterators over the values of every line in the config.
+	///
+	/// One [`SeriesIter`] per line and input file is returned. Log files are read only while
+	/// iterating, every iterator reads its log file separately.
+	pub fn iter(&self) -> Result<Vec<SeriesIter>, Error> {
+		let resolved_config = resolved_graph_config::expand_graph_config(
+			&self.config,
+			&self.input_files_ctx.logical_input(),
+			false,
+		)?;
+		resolved_config
+			.all_lines()
+			.map(|line| {
+				Ok(SeriesIter::new(&line.line, line.source_file_name(), &self.input_files_ctx)?)
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		graph_config::{DataSource, Panel},
+		logging::init_tracing_test,
+	};
+	use chrono::NaiveDate;
+
+	#[test]
+	fn test_pipeline_extract() {
+		init_tracing_test();
+		let lines = vec![
+			Line::new_with_data_source(DataSource::new_plot_field(None, "x01".into())),
+			Line::new_with_data_source(DataSource::new_event_count(None, "foo_module".into())),
+		];
+		let config = GraphConfig { panels: vec![Panel::builder().with_lines(lines).build()] };
+		let input = InputFilesContext::new_with_input(vec!["tests/examples/default.log".into()]);
+
+		let series = Pipeline::new(config, input).extract().unwrap();
+		assert_eq!(series.len(), 2);
+		assert_eq!(series[0].input_file, PathBuf::from("tests/examples/default.log"));
+		assert_eq!(series[0].points.len(), 28);
+		assert_eq!(
+			series[0].points[1],
+			DataPoint {
+				timestamp: NaiveDate::from_ymd_opt(2020, 1, 1)
+					.unwrap()
+					.and_hms_opt(0, 0, 25)
+					.unwrap(),
+				value: 12.5,
+				count: 2,
+				delta: Some(13000.0),
+				line: 4,
+				label: None,
+			}
+		);
+		assert_eq!(series[1].points.len(), 90);
+		assert_eq!(series[1].points.last().unwrap().count, 90);
+	}
+
+	#[test]
+	fn test_pipeline_value_transform() {
+		init_tracing_test();
+		let transform = |value: &str, unit: Option<&str>, normalized: Option<f64>| {
+			assert_eq!(unit, None);
+			assert_eq!(value.parse::<f64>().ok(), normalized);
+			normalized.filter(|v| *v < 13.0).map(|v| v * 2.0)
+		};
+		let config = GraphConfig::builder()
+			.with_line(
+				Line::builder()
+					.with_plot_field_line(None, "x01".into())
+					.with_value_transform(("double", transform))
+					.build()
+					.unwrap(),
+			)
+			.build();
+		let input = InputFilesContext::new_with_input(vec!["tests/examples/default.log".into()]);
+
+		let series = Pipeline::new(config, input).extract().unwrap();
+		let values = series[0].points.iter().map(|p| p.value).collect::<Vec<_>>();
+		assert_eq!(values, [22.4, 25.0]);
+	}
+
+	#[test]
+	fn test_pipeline_distinct_value_transforms() {
+		init_tracing_test();
+		let line = |name: &'static str, factor: f64| {
+			Line::builder()
+				.with_plot_field_line(None, "x01".into())
+				.with_value_transform((name, move |_: &str, _: Option<&str>, v: Option<f64>| {
+					v.map(|v| v * factor)
+				}))
+				.build()
+				.unwrap()
+		};
+		let config = GraphConfig::builder()
+			.with_line(line("double", 2.0))
+			.with_line(line("negate", -1.0))
+			.build();
+		let input = InputFilesContext::new_with_input(vec!["tests/examples/default.log".into()]);
+
+		let series = Pipeline::new(config, input).extract().unwrap();
+		let values =
+			|i: usize| series[i].points.iter().map(|p| p.value).take(2).collect::<Vec<_>>();
+		assert_eq!(values(0), [22.4, 25.0]);
+		assert_eq!(values(1), [-11.2, -12.5]);
+	}
+
+	/// Sums all `xNN=` fields of the `x_module` line.
+	#[derive(Debug)]
+	struct SumExtractor;
+
+	impl LineExtractor for SumExtractor {
+		fn prefilter(&self, log_line: &str) -> bool {
+			log_line.contains("x_module")
+		}
+
+		fn extract(&mut self, remainder: &str) -> Option<f64> {
+			remainder
+				.split_whitespace()
+				.filter_map(|f| f.split_once('=').and_then(|(_, v)| v.parse::<f64>().ok()))
+				.reduce(|a, b| a + b)
+		}
+
+		fn describe(&self) -> String {
+			"sum of x_module fields".into()
+		}
+	}
+
+	#[test]
+	fn test_custom_extractor() {
+		init_tracing_test();
+		let input = InputFilesContext::new_with_input(vec![]);
+		let iter = SeriesIter::from_extractor(
+			Box::new(SumExtractor),
+			"tests/examples/default.log".as_ref(),
+			&input,
+		)
+		.unwrap();
+		assert!(iter.line().is_none());
+
+		let points = iter.collect::<Result<Vec<_>, _>>().unwrap();
+		assert_eq!(points.len(), 28);
+		assert!((points[0].value - 563.2).abs() < 1e-9);
+		assert_eq!((points[0].line, points[0].count), (2, 1));
+		assert_eq!((points[1].line, points[1].count), (4, 2));
+	}
+
+	#[test]
+	fn test_pipeline_iter() {
+		init_tracing_test();
+		let config = GraphConfig::builder()
+			.with_line(Line::builder().with_plot_field_line(None, "x01".into()).build().unwrap())
+			.build();
+		let input = InputFilesContext::new_with_input(vec![
+			"tests/examples/default.log".into(),
+			"tests/examples/default-other.log".into(),
+		]);
+		let pipeline = Pipeline::new(config, input);
+		let extracted = pipeline.extract().unwrap();
+
+		let iters = pipeline.iter().unwrap();
+		assert_eq!(iters.len(), 2);
+		for (iter, series) in iters.into_iter().zip(extracted) {
+			assert_eq!(iter.input_file(), series.input_file);
+			let values = iter.values().collect::<Result<Vec<_>, _>>().unwrap();
+			assert!(!values.is_empty());
+			assert_eq!(values, series.values().collect::<Vec<_>>());
+		}
+	}
+}
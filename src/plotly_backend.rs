@@ -1,19 +1,29 @@
 use crate::graph_config::{AxisScale, Color, DashStyle, MarkerSize, MarkerType, PlotStyle, YAxis};
 use crate::{
-	graph_config::{GraphFullContext, OutputFilePaths},
-	logging::APPV,
+	backend::EMPTY_GRAPH_MESSAGE,
+	graph_config::{AxisRange, GapThreshold, GraphFullContext, OutputFilePaths},
+	logging::{APPV, output_message},
+	process_log::{
+		self, HistoBucket, format_record_timestamp, format_stat_value, parse_record_timestamp,
+	},
+	provenance::Provenance,
+	report::ReportSection,
 	resolved_graph_config::{ResolvedGraphConfig, ResolvedLine},
 };
+use chrono::NaiveDateTime;
 use csv::ReaderBuilder;
 use plotly::{
 	Scatter,
-	common::{DashType, Line, LineShape, Marker, MarkerSymbol, Mode},
+	common::{DashType, Fill, Line, LineShape, Marker, MarkerSymbol, Mode},
 };
 use serde::Serialize;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
-use std::{fs::File, io};
+use std::{
+	fs::File,
+	io::{self, Write},
+};
 use std::{io::BufReader, num::ParseFloatError};
 use tracing::warn;
 use tracing::{debug, info};
@@ -28,6 +38,9 @@ use tracing::{debug, info};
 
 const LOG_TARGET: &str = "plotly";
 
+/// Height of the plot of the panel in pixels, if all panels have the same height ratio.
+const DEFAULT_PANEL_HEIGHT: f64 = 600.0;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("I/O error: {0}")]
@@ -44,11 +57,13 @@ pub enum Error {
 	SerdeJsonError(#[from] serde_json::Error),
 	#[error("Incorrect input files (this is bug).")]
 	IncorrectOutputFiles,
+	#[error("Error while reading CSV data: {0}")]
+	ParsingLogError(#[from] crate::process_log::Error),
 }
 
 impl Color {
-	pub fn to_plotly(&self) -> &'static str {
-		match self {
+	pub fn to_plotly(&self) -> String {
+		let name = match self {
 			Color::Red => "red",
 			Color::Blue => "blue",
 			Color::Green => "green",
@@ -72,7 +87,9 @@ impl Color {
 			Color::DarkTurquoise => "darkturquoise",
 			Color::Yellow => "yellow",
 			Color::Black => "black",
-		}
+			Color::Rgb(rgb) => return format!("#{rgb:06x}"),
+		};
+		name.into()
 	}
 }
 
@@ -113,26 +130,209 @@ impl From<MarkerSize> for usize {
 }
 
 #[derive(Serialize)]
-struct PanelTemplateInput {
+pub(crate) struct PanelTemplateInput {
 	id: String,
 	title: String,
 	traces_json: String,
 	yaxis_scale: String,
+	/// Overrides of the time axis layout (JSON object).
+	xaxis_json: String,
+	/// Overrides of the primary Y-axis layout (JSON object).
+	yaxis_json: String,
+	/// Overrides of the secondary Y-axis layout (JSON object).
+	yaxis2_json: String,
+	/// Annotations and their vertical lines (JSON object merged into the layout).
+	annotations_json: String,
+	/// Height of the plot in pixels, proportional to the height ratio of the panel.
+	height: u32,
+}
+
+/// Builds the layout overrides of the time axis. Tick labels are hidden if `show_labels` is
+/// false (see `--xtics-bottom-only`). The axis spans the `time_range` of the panel, as in the
+/// gnuplot backend.
+fn build_xaxis_layout(
+	context: &GraphFullContext,
+	show_labels: bool,
+	time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+) -> serde_json::Value {
+	let mut layout = serde_json::Map::new();
+	if let Some((start, end)) = time_range {
+		// Same format as the timestamps of the traces.
+		let format = |timestamp| {
+			let (date, time) = format_record_timestamp(timestamp);
+			format!("{date} {time}")
+		};
+		layout.insert("range".into(), serde_json::json!([format(start), format(end)]));
+	}
+	if let Some(format) = context.xtics_format() {
+		layout.insert("tickformat".into(), format.into());
+	}
+	if let Some(interval) = context.xtics() {
+		// Ticks of the date axis are given in milliseconds.
+		layout.insert("dtick".into(), (interval.0 * 1000.0).into());
+	}
+	if let Some(angle) = context.xtics_rotate() {
+		// Plotly rotates the labels clockwise.
+		layout.insert("tickangle".into(), (-angle).into());
+	}
+	if !show_labels {
+		layout.insert("showticklabels".into(), false.into());
+	}
+	serde_json::Value::Object(layout)
+}
+
+/// Builds the layout overrides of the Y-axis. Units (if given) are appended to the tick labels.
+fn build_yaxis_layout(
+	label: Option<&str>,
+	units: Option<&str>,
+	scale: Option<AxisScale>,
+	range: Option<AxisRange>,
+) -> serde_json::Value {
+	let log_scale = matches!(scale, Some(AxisScale::Log));
+	let mut layout = serde_json::Map::new();
+	if let Some(label) = label {
+		layout.insert("title".into(), label.into());
+	}
+	if let Some(units) = units {
+		layout.insert("ticksuffix".into(), format!(" {units}").into());
+	}
+	if scale.is_some() {
+		layout.insert("type".into(), if log_scale { "log" } else { "linear" }.into());
+	}
+	if let Some(range) = range {
+		// Range of the log axis is given in log10 units.
+		let bound = |b: Option<f64>| b.map(|b| if log_scale { b.log10() } else { b });
+		let autorange = match (range.min, range.max) {
+			(Some(_), Some(_)) => serde_json::json!(false),
+			(None, Some(_)) => serde_json::json!("min"),
+			(Some(_), None) => serde_json::json!("max"),
+			(None, None) => serde_json::json!(true),
+		};
+		layout.insert("range".into(), serde_json::json!([bound(range.min), bound(range.max)]));
+		layout.insert("autorange".into(), autorange);
+	}
+	serde_json::Value::Object(layout)
+}
+
+/// Builds the layout of the Y-axis of the swimlane panel (see
+/// [`crate::graph_config::PanelParams::swimlanes`]): the names of the files at their rows.
+fn build_swimlanes_yaxis_layout(swimlanes: &[(usize, String)]) -> serde_json::Value {
+	serde_json::json!({
+		"title": "",
+		"tickvals": swimlanes.iter().map(|(row, _)| row).collect::<Vec<_>>(),
+		"ticktext": swimlanes.iter().map(|(_, name)| name).collect::<Vec<_>>(),
+		"range": [-0.5, swimlanes.len() as f64 - 0.5],
+		"autorange": false,
+	})
+}
+
+/// Builds the layout annotations (with vertical lines at their times) from the `(color,
+/// timestamp, text)` entries, the stats box (see [`crate::graph_config::PanelParams::stats_box`])
+/// from its text lines and the horizontal threshold line (see
+/// [`crate::graph_config::PanelParams::hline`]).
+fn build_annotations_layout(
+	annotations: &[(Option<Color>, NaiveDateTime, String)],
+	stats_box: &[String],
+	hline: Option<f64>,
+) -> serde_json::Value {
+	if annotations.is_empty() && stats_box.is_empty() && hline.is_none() {
+		return serde_json::json!({});
+	}
+	let x = |timestamp: &NaiveDateTime| timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+	let color = |color: &Option<Color>| color.as_ref().map_or("gray".into(), Color::to_plotly);
+	serde_json::json!({
+		"annotations": annotations.iter().map(|(c, timestamp, text)| serde_json::json!({
+			"x": x(timestamp),
+			"y": 1,
+			"yref": "paper",
+			"text": html_escape(text),
+			"textangle": -90,
+			"xanchor": "right",
+			"yanchor": "top",
+			"showarrow": false,
+			"font": { "size": 10, "color": color(c) },
+		})).chain((!stats_box.is_empty()).then(|| serde_json::json!({
+			"x": 0.99,
+			"y": 0.97,
+			"xref": "paper",
+			"yref": "paper",
+			"text": stats_box.iter().map(|line| html_escape(line)).collect::<Vec<_>>().join("<br>"),
+			"xanchor": "right",
+			"yanchor": "top",
+			"align": "left",
+			"showarrow": false,
+			"bgcolor": "rgba(255,255,255,0.8)",
+			"bordercolor": "gray",
+			"font": { "size": 10 },
+		}))).collect::<Vec<_>>(),
+		"shapes": annotations.iter().map(|(c, timestamp, _)| serde_json::json!({
+			"type": "line",
+			"x0": x(timestamp),
+			"x1": x(timestamp),
+			"y0": 0,
+			"y1": 1,
+			"yref": "paper",
+			"line": { "dash": "dot", "width": 1, "color": color(c) },
+		})).chain(hline.map(|value| serde_json::json!({
+			"type": "line",
+			"x0": 0,
+			"x1": 1,
+			"xref": "paper",
+			"y0": value,
+			"y1": value,
+			"line": { "dash": "dash", "width": 1, "color": "red" },
+		}))).collect::<Vec<_>>(),
+	})
 }
 
 fn build_trace(
 	context: &GraphFullContext,
 	line: &ResolvedLine,
-) -> Result<Scatter<String, f64>, Error> {
+	swimlane_row: Option<usize>,
+) -> Result<Scatter<String, Option<f64>>, Error> {
 	let csv_path = line
 		.shared_csv_filename()
 		.ok_or(Error::CvsFilesResolutionError(Box::new(line.clone())))?;
 
-	let (timestamps, values) = read_csv(&csv_path, line.csv_data_column_for_plot())?;
+	let series =
+		read_csv(&csv_path, line.csv_data_column_for_plot(), line.line.params.gap_threshold)?;
+
+	// Source log line (not available in old cache files) and kept text of every point are
+	// displayed on hover.
+	let hover_texts = series.line_numbers.iter().any(Option::is_some).then(|| {
+		let file_name = line.source_file_name().display();
+		series
+			.line_numbers
+			.iter()
+			.zip(&series.texts)
+			.map(|(n, text)| {
+				let location = n.map(|n| format!("{file_name}:{n}")).unwrap_or_default();
+				match text {
+					Some(text) => format!("{location}<br>{}", html_escape(text)),
+					None => location,
+				}
+			})
+			.collect::<Vec<_>>()
+	});
 
-	let mut trace = Scatter::new(timestamps, values)
+	// Values are ignored in the swimlane panel, events are drawn at the row of the file.
+	let values = match swimlane_row {
+		Some(row) => series.values.into_iter().map(|v| v.map(|_| row as f64)).collect(),
+		None => series.values,
+	};
+	let mut trace = Scatter::new(series.timestamps, values)
 		.mode(plotly::common::Mode::Markers)
-		.name(line.title(context.input().len() > 1));
+		.name(line.title(context.logical_input().len() > 1));
+
+	if let Some(hover_texts) = hover_texts {
+		trace = trace.hover_text_array(hover_texts);
+	}
+	if line.line.params.no_legend_entry.unwrap_or(false) {
+		trace = trace.show_legend(false);
+	}
+	if let Some(alpha) = line.line.params.alpha {
+		trace = trace.opacity(alpha.0);
+	}
 
 	let style = &line.line.params.style;
 	trace = trace.mode(match style {
@@ -161,6 +361,8 @@ fn build_trace(
 
 	if matches!(style, PlotStyle::Steps) {
 		line_style = line_style.shape(LineShape::Hv); // horizontal-vertical steps
+	} else if line.line.params.smooth_style.is_some() {
+		line_style = line_style.shape(LineShape::Spline);
 	}
 
 	trace = trace.line(line_style);
@@ -172,7 +374,8 @@ fn build_trace(
 			marker = marker.symbol(mt.to_plotly());
 		}
 
-		if let Some(mc) = &line.line.params.marker_color {
+		// Markers take the color of the line unless given, as in the gnuplot backend.
+		if let Some(mc) = line.line.params.marker_color.or(line.line.params.line_color) {
 			marker = marker.color(mc.to_plotly());
 		}
 
@@ -187,29 +390,121 @@ fn build_trace(
 	Ok(*trace)
 }
 
+/// Builds the trace marking the values of the line above the threshold (see
+/// [`crate::graph_config::PanelParams::highlight_breaches`]).
+fn build_breach_trace(
+	line: &ResolvedLine,
+	threshold: f64,
+) -> Result<Scatter<String, Option<f64>>, Error> {
+	let csv_path = line
+		.shared_csv_filename()
+		.ok_or(Error::CvsFilesResolutionError(Box::new(line.clone())))?;
+	let series = read_csv(&csv_path, line.csv_data_column_for_plot(), None)?;
+	let values = series.values.into_iter().map(|v| v.filter(|v| *v > threshold)).collect();
+	let marker = Marker::new()
+		.size(Into::<usize>::into(line.line.params.marker_size))
+		.color("red");
+	let trace = Scatter::new(series.timestamps, values)
+		.mode(Mode::Markers)
+		.marker(marker)
+		.show_legend(false)
+		.y_axis("y");
+	Ok(*trace)
+}
+
+/// Writes the html page, returns its path. `None` is returned if the page was written to stdout
+/// (`--output -`).
 pub fn write_plotly_html_inner(
 	config: &ResolvedGraphConfig,
 	context: &GraphFullContext,
-) -> Result<PathBuf, Error> {
-	let OutputFilePaths::Plotly(html_path) = context.get_graph_output_path() else {
-		return Err(Error::IncorrectOutputFiles);
+) -> Result<Option<PathBuf>, Error> {
+	let html_path = match context.get_graph_output_path() {
+		OutputFilePaths::Plotly(html_path) => Some(html_path),
+		OutputFilePaths::Stdout => None,
+		OutputFilePaths::Gnuplot(_) => return Err(Error::IncorrectOutputFiles),
+	};
+
+	let panels = build_panels(config, context, "plot")?;
+
+	let raw_template = include_str!("../templates/plotly_template.html"); // relative to this Rust file
+	let rendered = minijinja::render!(raw_template,
+			panels => panels,
+			empty_message => EMPTY_GRAPH_MESSAGE,
+			provenance => html_escaped_provenance(context),
+			footer => context.input_files_ctx.sample().map(|s| html_escape(&s.description()))
+	);
+
+	let Some(html_path) = html_path else {
+		let mut stdout = io::stdout().lock();
+		stdout.write_all(rendered.as_bytes())?;
+		stdout.flush()?;
+		return Ok(None);
 	};
+	std::fs::write(&html_path, rendered)?;
+	info!(target:APPV,"HTML saved: {}", html_path.display());
+	output_message("html", &html_path);
 
+	Ok(Some(html_path))
+}
+
+/// Builds the template input of every non-empty panel. Ids of the panels are prefixed with
+/// `id_prefix`.
+pub(crate) fn build_panels(
+	config: &ResolvedGraphConfig,
+	context: &GraphFullContext,
+	id_prefix: &str,
+) -> Result<Vec<PanelTemplateInput>, Error> {
 	let mut panels = vec![];
+	let external_annotations = match context.annotations_file() {
+		Some(path) => process_log::read_annotations_file(path, context.timestamp_format())?,
+		None => vec![],
+	};
+	let bottom_panel_idx = config.panels.iter().rposition(|panel| !panel.is_empty());
+	// Equal ratios keep the default height of every panel.
+	let panel_heights = config.panel_heights();
+	let graph_height =
+		DEFAULT_PANEL_HEIGHT * panel_heights.iter().filter(|h| **h > 0.0).count() as f64;
 
 	for (panel_idx, panel) in config.panels.iter().enumerate() {
 		if panel.is_empty() {
 			continue;
 		}
-		let id = format!("plot{}", panel_idx);
+		let id = format!("{id_prefix}{panel_idx}");
 		debug!(target:LOG_TARGET,"drawing {id}: {:#?}",panel);
 		let mut traces = vec![];
-
-		for line in &panel.lines {
-			traces.push(build_trace(context, line)?);
+		let mut annotations = external_annotations
+			.iter()
+			.filter(|(timestamp, _)| {
+				panel.time_range.is_none_or(|(start, end)| (start..=end).contains(timestamp))
+			})
+			.map(|(timestamp, text)| (None, *timestamp, text.clone()))
+			.collect::<Vec<_>>();
+
+		for line in panel.lines_in_drawing_order() {
+			if line.line.data_source.is_annotation() {
+				annotations.extend(
+					line.annotations(panel.time_range)?
+						.into_iter()
+						.map(|(timestamp, text)| (line.line.params.line_color, timestamp, text)),
+				);
+			} else {
+				let swimlane_row = panel.swimlane_row(line);
+				traces.push(build_trace(context, line, swimlane_row)?);
+				let primary_axis = !matches!(line.line.params.yaxis, Some(YAxis::Y2));
+				if let Some(threshold) =
+					panel.breach_threshold().filter(|_| primary_axis && swimlane_row.is_none())
+				{
+					traces.push(build_breach_trace(line, threshold)?);
+				}
+			}
 		}
 
 		let traces_json = serde_json::to_string(&traces)?;
+		let stats_box = if panel.params.stats_box.unwrap_or(false) {
+			panel.stats_box(context.logical_input().len() > 1)?
+		} else {
+			vec![]
+		};
 		panels.push(PanelTemplateInput {
 			id,
 			traces_json,
@@ -218,28 +513,188 @@ pub fn write_plotly_html_inner(
 				Some(AxisScale::Linear) | None => "linear".to_string(),
 				Some(AxisScale::Log) => "log".to_string(),
 			},
+			xaxis_json: build_xaxis_layout(
+				context,
+				!context.xtics_bottom_only() || Some(panel_idx) == bottom_panel_idx,
+				panel.time_range,
+			)
+			.to_string(),
+			yaxis_json: match panel.swimlanes() {
+				swimlanes if swimlanes.is_empty() => build_yaxis_layout(
+					None,
+					panel.axis_units(YAxis::Y)?.as_deref(),
+					panel.params.yaxis_scale,
+					panel.shared_yrange,
+				),
+				swimlanes => build_swimlanes_yaxis_layout(&swimlanes),
+			}
+			.to_string(),
+			yaxis2_json: build_yaxis_layout(
+				panel.params.y2label.as_deref(),
+				panel.axis_units(YAxis::Y2)?.as_deref(),
+				panel.params.y2axis_scale,
+				panel.y2range(),
+			)
+			.to_string(),
+			annotations_json: build_annotations_layout(
+				&annotations,
+				&stats_box,
+				panel.params.hline,
+			)
+			.to_string(),
+			height: (graph_height * panel_heights[panel_idx]).round() as u32,
 		});
 	}
+	Ok(panels)
+}
 
-	let raw_template = include_str!("../templates/plotly_template.html"); // relative to this Rust file
+#[derive(Serialize)]
+struct ReportSectionTemplateInput {
+	title: String,
+	panels: Vec<PanelTemplateInput>,
+	/// Names of the metrics (table header).
+	stats_header: Vec<String>,
+	/// Line title and formatted metric values (table rows).
+	stats_rows: Vec<(String, Vec<String>)>,
+}
+
+/// Writes the html page of the report (see [`crate::report`]), containing the panels and the
+/// stats table of every section.
+pub fn write_report_html(
+	title: &str,
+	sections: &[ReportSection],
+	html_path: &Path,
+) -> Result<(), Error> {
+	let mut sections_input = vec![];
+	for (i, section) in sections.iter().enumerate() {
+		let stats_header = section
+			.stats
+			.first()
+			.map(|(_, metrics)| metrics.iter().map(|(name, _)| name.clone()).collect())
+			.unwrap_or_default();
+		let stats_rows = section
+			.stats
+			.iter()
+			.map(|(line, metrics)| {
+				let values = metrics.iter().map(|(_, value)| format_stat_value(*value)).collect();
+				(html_escape(line), values)
+			})
+			.collect();
+		sections_input.push(ReportSectionTemplateInput {
+			title: html_escape(&section.title),
+			panels: build_panels(&section.config, &section.context, &format!("s{i}_plot"))?,
+			stats_header,
+			stats_rows,
+		});
+	}
+
+	let provenance = sections.first().map(|s| html_escaped_provenance(&s.context));
+	let raw_template = include_str!("../templates/report_template.html");
 	let rendered = minijinja::render!(raw_template,
-			panels => panels
+			title => html_escape(title),
+			sections => sections_input,
+			empty_message => EMPTY_GRAPH_MESSAGE,
+			provenance => provenance.unwrap_or_default(),
 	);
 
-	std::fs::write(&html_path, rendered)?;
+	std::fs::write(html_path, rendered)?;
 	info!(target:APPV,"HTML saved: {}", html_path.display());
+	output_message("html", html_path);
 
-	Ok(html_path)
+	Ok(())
+}
+
+/// Escapes the text for use in html content and attributes.
+fn html_escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('"', "&quot;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+/// Provenance entries, with values escaped for use in html attributes.
+fn html_escaped_provenance(context: &GraphFullContext) -> Vec<(&'static str, String)> {
+	Provenance::collect(context.input(), context.hash_inputs())
+		.entries()
+		.into_iter()
+		.map(|(key, value)| (key, html_escape(&value)))
+		.collect()
 }
 
 pub fn write_plotly_html(
 	config: &ResolvedGraphConfig,
 	context: &GraphFullContext,
 ) -> Result<(), Error> {
-	let html_path = write_plotly_html_inner(config, context)?;
+	if let Some(html_path) = write_plotly_html_inner(config, context)? {
+		display_html(html_path, context.output_graph_ctx.do_not_display);
+	}
+	Ok(())
+}
+
+/// Writes the html page drawing the histogram of given buckets.
+///
+/// If `cdf` is set, the cumulative distribution is drawn against the secondary axis.
+pub fn write_histogram_html(
+	buckets: &[HistoBucket],
+	title: &str,
+	cdf: bool,
+	html_path: &Path,
+) -> Result<(), Error> {
+	// Buckets may have different widths (log buckets), so steps are used instead of bars.
+	let mut edges = buckets.iter().map(|b| b.start).collect::<Vec<_>>();
+	let mut counts = buckets.iter().map(|b| b.count as f64).collect::<Vec<_>>();
+	if let Some(last) = buckets.last() {
+		edges.push(last.end);
+		counts.push(last.count as f64);
+	}
 
-	let do_not_open =
-		context.output_graph_ctx.do_not_display || std::env::var("PLOX_DO_NOT_DISPLAY").is_ok();
+	let mut traces = vec![
+		*Scatter::new(edges, counts)
+			.name("count")
+			.mode(Mode::Lines)
+			.line(Line::new().shape(LineShape::Hv))
+			.fill(Fill::ToZeroY),
+	];
+
+	if cdf {
+		let total = buckets.iter().map(|b| b.count).sum::<u64>().max(1) as f64;
+		let ends = buckets.iter().map(|b| b.end).collect::<Vec<_>>();
+		let cumulative = buckets
+			.iter()
+			.scan(0, |acc, b| {
+				*acc += b.count;
+				Some(*acc as f64 / total)
+			})
+			.collect::<Vec<_>>();
+		traces.push(*Scatter::new(ends, cumulative).name("CDF").mode(Mode::Lines).y_axis("y2"));
+	}
+
+	let panels = vec![PanelTemplateInput {
+		id: "histogram".into(),
+		title: title.into(),
+		traces_json: serde_json::to_string(&traces)?,
+		yaxis_scale: "linear".into(),
+		xaxis_json: "{}".into(),
+		yaxis_json: "{}".into(),
+		yaxis2_json: "{}".into(),
+		annotations_json: "{}".into(),
+		height: DEFAULT_PANEL_HEIGHT as u32,
+	}];
+
+	let raw_template = include_str!("../templates/plotly_template.html");
+	let rendered = minijinja::render!(raw_template,
+			panels => panels
+	);
+
+	std::fs::write(html_path, rendered)?;
+	info!(target:APPV,"HTML saved: {}", html_path.display());
+	output_message("html", html_path);
+	Ok(())
+}
+
+/// Opens the html page in the browser (unless disabled).
+pub fn display_html(html_path: PathBuf, do_not_display: bool) {
+	let do_not_open = do_not_display || std::env::var("PLOX_DO_NOT_DISPLAY").is_ok();
 
 	if !do_not_open {
 		let cmd = if let Ok(viewer_cmd_path) = std::env::var("PLOX_BROWSER") {
@@ -264,11 +719,27 @@ pub fn write_plotly_html(
 	} else {
 		debug!(target:APPV,"Displaying html page disabled.");
 	}
+}
 
-	Ok(())
+/// Points of the single line read from the CSV file.
+struct CsvSeries {
+	timestamps: Vec<String>,
+	values: Vec<Option<f64>>,
+	/// Numbers of the source log lines.
+	line_numbers: Vec<Option<u64>>,
+	/// Matched texts (if kept, see [`crate::graph_config::LineParams::keep_text`]).
+	texts: Vec<Option<String>>,
 }
 
-fn read_csv(csv_path: &Path, value_column: &str) -> Result<(Vec<String>, Vec<f64>), Error> {
+/// Reads the timestamps, values and source log line numbers from the CSV file.
+///
+/// If `gap_threshold` is given, the `null` value (breaking the line) is inserted between
+/// consecutive records further apart than the threshold.
+fn read_csv(
+	csv_path: &Path,
+	value_column: &str,
+	gap_threshold: Option<GapThreshold>,
+) -> Result<CsvSeries, Error> {
 	let file = File::open(csv_path)?;
 	let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(BufReader::new(file));
 
@@ -276,9 +747,15 @@ fn read_csv(csv_path: &Path, value_column: &str) -> Result<(Vec<String>, Vec<f64
 	let date_idx = headers.iter().position(|h| h == "date").ok_or(Error::GeneralError)?;
 	let time_idx = headers.iter().position(|h| h == "time").ok_or(Error::GeneralError)?;
 	let value_idx = headers.iter().position(|h| h == value_column).ok_or(Error::GeneralError)?;
+	// Older cache files do not contain line numbers.
+	let line_idx = headers.iter().position(|h| h == "line");
+	let text_idx = headers.iter().position(|h| h == "text");
 
-	let mut timestamps = Vec::new();
+	let mut timestamps: Vec<String> = Vec::new();
 	let mut values = Vec::new();
+	let mut line_numbers = Vec::new();
+	let mut texts = Vec::new();
+	let mut prev = None;
 
 	for record in rdr.records() {
 		let record = record?;
@@ -286,10 +763,28 @@ fn read_csv(csv_path: &Path, value_column: &str) -> Result<(Vec<String>, Vec<f64
 		let t = record.get(time_idx).ok_or(Error::GeneralError)?.to_string();
 		let val_str = record.get(value_idx).ok_or(Error::GeneralError)?;
 		let val = val_str.parse::<f64>()?;
+		// Zero is written if the line number is not known.
+		let line_number = line_idx
+			.and_then(|i| record.get(i))
+			.and_then(|n| n.parse::<u64>().ok())
+			.filter(|n| *n > 0);
+
+		if let Some(gap_threshold) = gap_threshold {
+			let ts = parse_record_timestamp(&d, &t);
+			if matches!((prev, ts), (Some(prev), Some(ts)) if gap_threshold.is_gap(prev, ts)) {
+				timestamps.push(timestamps.last().cloned().unwrap_or_default());
+				values.push(None);
+				line_numbers.push(None);
+				texts.push(None);
+			}
+			prev = ts.or(prev);
+		}
 
 		timestamps.push(d + " " + &t);
-		values.push(val);
+		values.push(Some(val));
+		line_numbers.push(line_number);
+		texts.push(text_idx.and_then(|i| record.get(i)).map(ToString::to_string));
 	}
 
-	Ok((timestamps, values))
+	Ok(CsvSeries { timestamps, values, line_numbers, texts })
 }
@@ -3,23 +3,35 @@
 //! It supports value extraction, event counting, deltas, and outputs intermediate CSV caches.
 
 use crate::{
+	cache_manifest::{self, CacheManifest, CsvSummary},
 	graph_config::{
-		DataSource, EventDeltaSpec, FieldCaptureSpec, InputFilesContext, TimestampFormat, YAxis,
+		AxisRange, AxisScale, CommandInput, CsvFileSpec, DataSource, EventDeltaSpec,
+		FieldCaptureSpec, InputFilesContext, LabelFilter, Line, MatchedText, NumberLocale,
+		Sampling, TimestampFormat, ValueMap, ValueTransformHook, ValueUnits, YAxis,
 	},
-	logging::APPV,
+	interrupt,
+	logging::{APPV, MESSAGES},
 	match_preview_cli_builder::{MatchPreviewConfig, SharedMatchPreviewContext},
-	resolved_graph_config::{ResolvedGraphConfig, ResolvedLine},
+	pipeline::{DataPoint, Series},
+	resolved_graph_config::{
+		ResolvedGraphConfig, ResolvedLine, ResolvedPanel, UnresolvedCsvFileError,
+	},
 };
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, ParseError, TimeDelta};
+use flate2::read::MultiGzDecoder;
 use regex::Regex;
 use serde::Deserialize;
 use statrs::statistics::{Data, OrderStatistics, Statistics};
 use std::{
-	collections::HashMap,
+	cmp::Ordering,
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
 	fs::{self, File},
-	io::{self, BufRead, BufReader, Write},
+	io::{self, BufRead, BufReader, BufWriter, Write},
+	ops::Range,
 	path::{Path, PathBuf},
-	time::UNIX_EPOCH,
+	sync::Mutex,
+	thread,
+	time::{Duration, UNIX_EPOCH},
 };
 use tracing::{Level, debug, info, trace, warn};
 use tracing_subscriber::{EnvFilter, Layer, Registry, layer::SubscriberExt};
@@ -33,6 +45,19 @@ const RECORD_DATE_FORMAT: &str = "%Y-%m-%d";
 // Time format used to serialize record into CSV file
 const RECORD_TIME_FORMAT: &str = "%H:%M:%S%.3f";
 
+// Date written into CSV file if timestamps in log do not contain the date
+const TIME_ONLY_RECORD_DATE: &str = "2025-01-01";
+
+// Format of the time window bounds included in the CSV file name
+const WINDOW_FILENAME_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3f";
+
+// How often the followed log file is checked for new lines
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// How far past the end of the time window the timestamps of the (monotonic) log shall be, before
+// the rest of the log is skipped
+const TIME_WINDOW_SLACK: TimeDelta = TimeDelta::seconds(60);
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("Regex error: {0}")]
@@ -47,6 +72,12 @@ pub enum Error {
 	#[error("Field regex shall have 1 or 2 capture groups. Regex: {0}")]
 	RegexCapturesGroupsInvalidCount(String),
 
+	#[error("Label regex shall have exactly 1 capture group. Regex: {0}")]
+	LabelRegexCapturesGroupsInvalidCount(String),
+
+	#[error("Annotation regex shall have exactly 1 capture group. Regex: {0}")]
+	AnnotationRegexCapturesGroupsInvalidCount(String),
+
 	#[error("User provided time range parsing error: {0}")]
 	TimeRangeParsingError(#[from] ParseError),
 
@@ -58,12 +89,70 @@ pub enum Error {
 
 	#[error("Cat command supports only one input file.")]
 	CatCmdManyInputFiles,
+
+	#[error("Cat command output error: {0}")]
+	CatOutputError(#[from] csv::Error),
+
+	#[error("Cat command does not support follow mode for interval based data sources.")]
+	CatFollowUnsupportedDataSource,
+
+	#[error("Value conversion failed: file:'{0}' line:{1} value:'{2}'")]
+	ValueConversionFailure(PathBuf, u64, String),
+
+	#[error(transparent)]
+	UnresolvedCsvFile(#[from] UnresolvedCsvFileError),
+
+	#[error("Output CSV file of the processor for '{0}' is not set (this is bug)")]
+	MissingOutputPath(PathBuf),
+
+	#[error("No lines to process for input file '{0}' (this is bug)")]
+	NoCanonicalLine(PathBuf),
+
+	#[error("Output CSV file of the processor for '{0}' is not started (this is bug)")]
+	CsvNotStarted(PathBuf),
+
+	#[error("Column '{1}' not found in the header of CSV file '{0}'")]
+	CsvColumnNotFound(PathBuf, String),
+
+	#[error("Resampling with {1}s interval gives too many samples ({0}), use larger interval.")]
+	TooManySamples(usize, f64),
+
+	#[error("Counting within {1}s intervals gives too many intervals ({0}), use larger interval.")]
+	TooManyIntervals(usize, f64),
 }
 
 impl Error {
 	fn new_file_io_error(f: &Path, e: io::Error) -> Self {
 		Self::FileIoError(f.to_path_buf(), e)
 	}
+
+	/// Stable, machine-readable code of the error. See [`crate::error::Error::code`].
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::Regex(_) => "invalid-regex",
+			Self::FileIoError(..) => "input-io",
+			Self::InvalidInputFile(..) => "invalid-input-file",
+			Self::RegexCapturesGroupsInvalidCount(_) => "invalid-regex-capture-groups",
+			Self::LabelRegexCapturesGroupsInvalidCount(_) => "invalid-label-regex-capture-groups",
+			Self::AnnotationRegexCapturesGroupsInvalidCount(_) => {
+				"invalid-annotation-regex-capture-groups"
+			},
+			Self::TimeRangeParsingError(_) => "invalid-time-range",
+			Self::TimestampExtractionFailure(..) => "timestamp-extraction",
+			Self::CsvParseError(..) => "csv-parse",
+			Self::CatCmdManyInputFiles => "cat-many-input-files",
+			Self::CatOutputError(_) => "output-io",
+			Self::CatFollowUnsupportedDataSource => "cat-follow-unsupported",
+			Self::ValueConversionFailure(..) => "value-conversion",
+			Self::UnresolvedCsvFile(_) => "unresolved-csv-file",
+			Self::MissingOutputPath(_) => "missing-output-path",
+			Self::NoCanonicalLine(_) => "no-canonical-line",
+			Self::CsvNotStarted(_) => "csv-not-started",
+			Self::CsvColumnNotFound(..) => "csv-column-not-found",
+			Self::TooManySamples(..) => "too-many-samples",
+			Self::TooManyIntervals(..) => "too-many-intervals",
+		}
+	}
 }
 
 /// Stores match-specific state (e.g. counters or timestamps).
@@ -71,6 +160,14 @@ impl Error {
 struct ProcessingState {
 	count: u64,
 	last_timestamp: Option<ExtractedNaiveDateTime>,
+	/// Label extracted from the currently processed line (if label regex is configured).
+	current_label: Option<String>,
+	/// Text of the currently processed line (if text shall be kept).
+	current_text: Option<String>,
+	/// Unit of the value of the currently processed line (if unit shall be kept).
+	current_unit: Option<String>,
+	/// Number of the currently processed line of the input file.
+	line_number: u64,
 }
 
 /// Single record extracted from a matching log line, with some extra stats.
@@ -80,20 +177,557 @@ struct LogRecord {
 	pub time: String,
 	pub value: f64,
 	pub count: u64,
+	#[serde(rename = "delta")]
 	pub diff: Option<f64>,
+	/// Number of the source log line (1-based). Not available in cache files created by older
+	/// versions.
+	#[serde(default)]
+	pub line: Option<u64>,
+	#[serde(default)]
+	pub label: Option<String>,
+	/// Matched text (or whole log line), see [`crate::graph_config::LineParams::keep_text`].
+	#[serde(default)]
+	pub text: Option<String>,
+	/// Captured unit of the value, see [`crate::graph_config::LineParams::value_units`].
+	#[serde(default)]
+	pub unit: Option<String>,
+}
+
+/// Parses the timestamp stored in the `date` and `time` columns of the CSV file.
+///
+/// The `date` column is empty if timestamps in log do not contain the date.
+pub(crate) fn parse_record_timestamp(date: &str, time: &str) -> Option<NaiveDateTime> {
+	let date = if date.is_empty() { TIME_ONLY_RECORD_DATE } else { date };
+	Some(NaiveDateTime::new(
+		NaiveDate::parse_from_str(date, RECORD_DATE_FORMAT).ok()?,
+		NaiveTime::parse_from_str(time, RECORD_TIME_FORMAT).ok()?,
+	))
+}
+
+/// Formats the timestamp into the `date` and `time` columns of the CSV file.
+pub(crate) fn format_record_timestamp(timestamp: NaiveDateTime) -> (String, String) {
+	(
+		timestamp.format(RECORD_DATE_FORMAT).to_string(),
+		timestamp.format(RECORD_TIME_FORMAT).to_string(),
+	)
+}
+
+impl LogRecord {
+	/// Returns the timestamp of the record.
+	fn timestamp(&self) -> Result<NaiveDateTime, Error> {
+		//todo: clean up date
+		Ok(NaiveDateTime::new(
+			NaiveDate::parse_from_str(
+				self.date.as_deref().unwrap_or(TIME_ONLY_RECORD_DATE),
+				RECORD_DATE_FORMAT,
+			)?,
+			NaiveTime::parse_from_str(&self.time, RECORD_TIME_FORMAT)?,
+		))
+	}
+
+	fn into_data_point(self) -> Result<DataPoint, Error> {
+		Ok(DataPoint {
+			timestamp: self.timestamp()?,
+			value: self.value,
+			count: self.count,
+			delta: self.diff,
+			line: self.line.unwrap_or_default(),
+			label: self.label,
+		})
+	}
+
+	/// Appends the optional text columns (label, kept text and unit) formatted as trailing CSV
+	/// columns (including separators) to `line`.
+	///
+	/// Nothing is appended for columns the record does not have.
+	fn push_csv_text_columns(&self, line: &mut String) {
+		for column in [&self.label, &self.text, &self.unit] {
+			match column {
+				Some(text) if text.contains([',', '"', '\n', '\r']) => {
+					line.push_str(",\"");
+					line.push_str(&text.replace('"', "\"\""));
+					line.push('"');
+				},
+				Some(text) => {
+					line.push(',');
+					line.push_str(text);
+				},
+				None => {},
+			}
+		}
+	}
+}
+
+/// Extracts values from log lines.
+///
+/// The timestamp parsing, counting, time deltas, labels and storing of records are handled by the
+/// caller, the extractor only tells whether the line matches and what value it carries. Extractors
+/// may keep state between the lines.
+///
+/// Built-in data sources are implemented by [`DataSourceExtractor`]. Custom extractors (e.g.
+/// JSON-path, multi-line state machines provided by downstream crates) can only be used for the
+/// in-memory extraction with [`SeriesIter::from_extractor`]. Graphs, CSV cache files and the CLI
+/// commands are built from [`DataSource`] only.
+pub trait LineExtractor: std::fmt::Debug + Send {
+	/// Cheap check of the whole log line, done before the timestamp is parsed (e.g. guard
+	/// `strcmp`). Lines not passing the check are skipped.
+	fn prefilter(&self, _log_line: &str) -> bool {
+		true
+	}
+
+	/// Extracts the value from the log line remainder (log line without the timestamp prefix).
+	///
+	/// Returns `None` if the line does not match.
+	fn extract(&mut self, remainder: &str) -> Option<f64>;
+
+	/// Tells if [`Self::extract`] shall be given the whole log line instead of the remainder (e.g.
+	/// if the timestamp is a part of the structured log line).
+	fn extracts_whole_line(&self) -> bool {
+		false
+	}
+
+	/// Returns the byte range of the `remainder` matched by the last successful [`Self::extract`]
+	/// call. Used if the matched text shall be kept, the whole remainder is used if not provided.
+	fn matched_range(&self) -> Option<Range<usize>> {
+		None
+	}
+
+	/// Returns the unit captured by the last successful [`Self::extract`] call. Stored along with
+	/// the value if original units are kept.
+	fn matched_unit(&self) -> Option<&str> {
+		None
+	}
+
+	/// Returns the raw value which could not be converted to a number by the last
+	/// [`Self::extract`] call returning `None`.
+	///
+	/// Such lines are counted as skipped records (or reported as an error in strict mode).
+	fn take_conversion_failure(&mut self) -> Option<String> {
+		None
+	}
+
+	/// Sets the number format of the values in the log. Extractors not parsing numbers can ignore
+	/// it.
+	fn set_number_locale(&mut self, _locale: NumberLocale) {}
+
+	/// Short description of the extractor, used in logs (e.g. regex pattern).
+	fn describe(&self) -> String;
+}
+
+/// Compiled regex of the [`DataSource`].
+///
+/// Patterns are compiled with the fast `regex` engine. Patterns rejected by it (e.g. containing
+/// lookbehind, lookahead or backreferences) fall back to the backtracking `fancy-regex` engine.
+#[derive(Debug, Clone)]
+pub enum DataSourceRegex {
+	Fast(Regex),
+	Fancy(fancy_regex::Regex),
+}
+
+impl DataSourceRegex {
+	/// Compiles the pattern. If neither engine accepts it, the error of the `regex` engine is
+	/// returned.
+	pub fn new(pattern: &str) -> Result<Self, Error> {
+		match Regex::new(pattern) {
+			Ok(regex) => Ok(Self::Fast(regex)),
+			Err(e) => match fancy_regex::Regex::new(pattern) {
+				Ok(regex) => {
+					debug!(target: LOG_TARGET, "Using fancy-regex engine for pattern: {pattern}");
+					Ok(Self::Fancy(regex))
+				},
+				Err(_) => Err(e.into()),
+			},
+		}
+	}
+
+	/// Number of capture groups, including the implicit group of the whole match.
+	pub fn captures_len(&self) -> usize {
+		match self {
+			Self::Fast(regex) => regex.captures_len(),
+			Self::Fancy(regex) => regex.captures_len(),
+		}
+	}
+
+	/// Returns byte ranges of the capture groups (the whole match first), or `None` if the
+	/// `haystack` does not match.
+	///
+	/// Matching errors of the backtracking engine (e.g. exceeded backtrack limit) are treated as
+	/// no match.
+	pub fn captures_ranges(&self, haystack: &str) -> Option<Vec<Option<Range<usize>>>> {
+		match self {
+			Self::Fast(regex) => regex
+				.captures(haystack)
+				.map(|c| c.iter().map(|m| m.map(|m| m.range())).collect()),
+			Self::Fancy(regex) => regex
+				.captures(haystack)
+				.inspect_err(|e| debug!(target: LOG_TARGET, "Matching {haystack:?} failed: {e}"))
+				.ok()
+				.flatten()
+				.map(|c| c.iter().map(|m| m.map(|m| m.range())).collect()),
+		}
+	}
 }
 
+/// Value captured by the default field regex if the value map is used (keywords like `busy`).
+const KEYWORD_VALUE_PATTERN: &str = r"[^\s,;]+";
+
+/// [`LineExtractor`] of the built-in [`DataSource`] kinds.
 #[derive(Debug)]
-struct LineProcessor {
+pub struct DataSourceExtractor {
 	data_source: DataSource,
-	pub regex: Regex,
+	/// Compiled regex of the data source, not used for JSON fields.
+	regex: Option<DataSourceRegex>,
+	/// Optional hook post-processing captured values.
+	value_transform: Option<ValueTransformHook>,
+	/// Raw value which could not be converted by the last `extract` call.
+	conversion_failure: Option<String>,
+	/// Range of the remainder matched by the last successful `extract` call.
+	matched_range: Option<Range<usize>>,
+	/// Number format of the extracted values.
+	number_locale: Option<NumberLocale>,
+	/// Do not convert values to milliseconds.
+	original_units: bool,
+	/// Unit captured by the last successful `extract` call.
+	matched_unit: Option<String>,
+	/// Mapping of captured keywords to values.
+	value_map: Option<ValueMap>,
+}
+
+impl DataSourceExtractor {
+	pub fn new(
+		data_source: DataSource,
+		value_transform: Option<ValueTransformHook>,
+	) -> Result<Self, Error> {
+		let regex = match data_source {
+			DataSource::JsonField { .. }
+			| DataSource::LogfmtField { .. }
+			| DataSource::CsvFile(_) => None,
+			_ => Some(data_source.compile_regex()?),
+		};
+		Ok(Self {
+			data_source,
+			regex,
+			value_transform,
+			conversion_failure: None,
+			matched_range: None,
+			number_locale: None,
+			original_units: false,
+			matched_unit: None,
+			value_map: None,
+		})
+	}
+
+	/// Translates the captured strings to values with given map instead of parsing numbers.
+	pub fn with_value_map(mut self, value_map: Option<ValueMap>) -> Self {
+		self.value_map = value_map;
+		self.update_regex();
+		self
+	}
+
+	/// Pattern of the value captured by the default field regex.
+	fn value_pattern(&self) -> Option<String> {
+		match (&self.value_map, self.number_locale) {
+			(Some(_), _) => Some(KEYWORD_VALUE_PATTERN.to_string()),
+			(None, Some(locale)) => Some(locale.number_pattern()),
+			(None, None) => None,
+		}
+	}
+
+	/// Recompiles the regex after the value pattern has changed.
+	fn update_regex(&mut self) {
+		if self.regex.is_some() {
+			self.regex = Some(
+				DataSourceRegex::new(
+					&self.data_source.regex_pattern_with_value(self.value_pattern()),
+				)
+				.expect("pattern built from escaped field name is valid"),
+			);
+		}
+	}
+
+	/// Converts the raw value captured by the field (or extracted from JSON) into the number.
+	fn convert_field_value(&mut self, raw_val: &str, unit: Option<&str>) -> Option<f64> {
+		self.matched_unit = unit.map(Into::into);
+		let conversion_unit = if self.original_units { "" } else { unit.unwrap_or("") };
+		let normalized = match &self.value_map {
+			Some(value_map) => value_map.get(raw_val),
+			None => normalize_value(raw_val, conversion_unit, self.number_locale),
+		};
+		let value = match &self.value_transform {
+			Some(hook) => hook.0.transform(raw_val, unit, normalized),
+			None => normalized,
+		};
+		// Values dropped by the transform are not failures.
+		if value.is_none() && normalized.is_none() {
+			self.conversion_failure = Some(raw_val.to_string());
+		}
+		value
+	}
+
+	/// Keeps the values in the captured units instead of converting them to milliseconds.
+	pub fn with_original_units(mut self, original_units: bool) -> Self {
+		self.original_units = original_units;
+		self
+	}
+}
+
+impl LineExtractor for DataSourceExtractor {
+	fn prefilter(&self, log_line: &str) -> bool {
+		match &self.data_source {
+			// The guard of the JSON field is checked on the parsed object, only the looked up
+			// text is required here.
+			DataSource::JsonField { guard: Some(guard), .. } => {
+				log_line.contains(JsonFieldGuard::parse(guard).prefilter_text())
+			},
+			data_source => {
+				data_source.guard().as_ref().map(|g| log_line.contains(g)).unwrap_or(true)
+			},
+		}
+	}
+
+	fn extract(&mut self, remainder: &str) -> Option<f64> {
+		if let DataSource::JsonField { guard, path } = &self.data_source {
+			let (raw_val, unit) =
+				extract_json_field(remainder, guard.as_deref(), path, self.value_map.is_none())?;
+			debug!(target:MATCH_PREVIEW, "try_match: json field {path}={raw_val:?}");
+			self.matched_range = None;
+			return self.convert_field_value(&raw_val, unit.as_deref());
+		}
+		let value = match &self.data_source {
+			DataSource::LogfmtField { key, .. } => {
+				let value = logfmt_value(remainder, key);
+				debug!(target:MATCH_PREVIEW, "try_match: logfmt {key}={value:?}");
+				Some(value?)
+			},
+			// The value cell of the CSV file.
+			DataSource::CsvFile(_) => Some(remainder.to_string()),
+			_ => None,
+		};
+		if let Some(value) = value {
+			self.matched_range = None;
+			let (raw_val, unit) = match self.value_map {
+				Some(_) => (value, None),
+				None => split_value_unit(&value),
+			};
+			return self.convert_field_value(&raw_val, unit.as_deref());
+		}
+		let regex = self.regex.as_ref()?;
+		let captures = regex.captures_ranges(remainder);
+		let group =
+			|i: usize| captures.as_ref()?.get(i).cloned().flatten().map(|range| &remainder[range]);
+
+		if tracing::event_enabled!(Level::TRACE) {
+			trace!(target:MATCH_PREVIEW, "try_match remainder={remainder} regex={regex:#?} captures={captures:#?}");
+		} else {
+			debug!(target:MATCH_PREVIEW, "try_match: line remainder: \"{remainder}\"");
+			if captures.is_some() {
+				if let Some(c) = group(1) {
+					debug!(target:MATCH_PREVIEW, "try_match: (value) captures[1]={c:?}");
+				};
+				if let Some(c) = group(2) {
+					debug!(target:MATCH_PREVIEW, "try_match:  (unit) captures[2]={c:?}");
+				};
+			} else {
+				debug!(target:MATCH_PREVIEW, "try_match: no matches...");
+			}
+		}
+
+		// The text of annotations is their capture, not the whole match.
+		let matched_group = if self.data_source.is_annotation() { 1 } else { 0 };
+		self.matched_range = captures.as_ref()?.get(matched_group).cloned().flatten();
+		match &self.data_source {
+			DataSource::EventValue { yvalue, .. } => Some(*yvalue),
+			DataSource::EventCount { .. }
+			| DataSource::EventDelta { .. }
+			| DataSource::Annotation { .. } => Some(1.0),
+			DataSource::FieldValue { .. }
+			| DataSource::JsonField { .. }
+			| DataSource::LogfmtField { .. }
+			| DataSource::CsvFile(_) => self.convert_field_value(group(1).unwrap_or("0"), group(2)),
+		}
+	}
+
+	fn extracts_whole_line(&self) -> bool {
+		matches!(self.data_source, DataSource::JsonField { .. })
+	}
+
+	fn matched_range(&self) -> Option<Range<usize>> {
+		self.matched_range.clone()
+	}
+
+	fn matched_unit(&self) -> Option<&str> {
+		self.matched_unit.as_deref()
+	}
+
+	fn take_conversion_failure(&mut self) -> Option<String> {
+		self.conversion_failure.take()
+	}
+
+	fn set_number_locale(&mut self, locale: NumberLocale) {
+		self.number_locale = Some(locale);
+		self.update_regex();
+	}
+
+	fn describe(&self) -> String {
+		self.data_source.regex_pattern_with_value(self.value_pattern())
+	}
+}
+
+/// Output CSV file of the [`LineProcessor`] in streaming mode.
+#[derive(Debug)]
+struct CsvRecordWriter {
+	writer: BufWriter<File>,
+	/// Temporary file the records are written into, until all records are processed.
+	partial_path: PartialFile,
+	/// Reused buffer for formatting a single record.
+	line: String,
+}
+
+/// Temporary file, removed when dropped unless persisted (e.g. when processing was interrupted or
+/// failed).
+#[derive(Debug)]
+struct PartialFile(PathBuf);
+
+impl PartialFile {
+	/// Moves the file to its final `path`.
+	fn persist(self, path: &Path) -> io::Result<()> {
+		fs::rename(&self.0, path)?;
+		std::mem::forget(self);
+		Ok(())
+	}
+}
+
+impl std::ops::Deref for PartialFile {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for PartialFile {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.0);
+	}
+}
+
+/// Counters of the log lines processed by the [`LineProcessor`], used to diagnose lines with no
+/// matches.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MatchStats {
+	/// Number of read log lines.
+	lines: u64,
+	/// Number of log lines which passed the guard.
+	guard_passed: u64,
+	/// Number of log lines which passed the guard and had valid timestamp.
+	timestamp_parsed: u64,
+	/// Number of log lines with valid timestamp outside of the time window.
+	outside_time_window: u64,
+}
+
+/// Maximum number of records kept in memory by the [`LineProcessor`] in streaming mode.
+const RECORDS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Deterministic pseudo-random sampler of the records (see [`Sampling`]).
+///
+/// The fixed seed makes repeated runs (and so the cached CSV files) identical.
+#[derive(Debug)]
+struct Sampler {
+	sampling: Sampling,
+	/// State of the SplitMix64 generator.
+	state: u64,
+	/// Number of records offered so far.
+	offered: u64,
+}
+
+impl Sampler {
+	fn new(sampling: Sampling) -> Self {
+		Self { sampling, state: 0x706c_6f78, offered: 0 }
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Tells if all records are kept in memory until the processing is finished.
+	fn is_reservoir(&self) -> bool {
+		matches!(self.sampling, Sampling::Count(_))
+	}
+
+	/// Adds the record to `records`, if it is chosen.
+	///
+	/// In reservoir mode the chosen record may replace the previously chosen one, so `records`
+	/// shall contain only the sampled records.
+	fn offer(&mut self, records: &mut Vec<LogRecord>, record: LogRecord) {
+		self.offered += 1;
+		match self.sampling {
+			Sampling::Fraction(fraction) => {
+				let random = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+				if random < fraction {
+					records.push(record);
+				}
+			},
+			Sampling::Count(count) if records.len() < count => records.push(record),
+			Sampling::Count(count) => {
+				let index = (self.next_u64() % self.offered) as usize;
+				if index < count {
+					records[index] = record;
+				}
+			},
+		}
+	}
+}
+
+#[derive(Debug)]
+struct LineProcessor {
+	extractor: Box<dyn LineExtractor>,
+	/// Optional regex extracting the label of the matched line.
+	label_regex: Option<Regex>,
+	/// Optional regex extracting the label, along with the only label of lines to be kept.
+	label_filter: Option<(Regex, String)>,
+	/// Text of the matched line to be stored along with the value.
+	keep_text: Option<MatchedText>,
+	/// Store the captured unit along with the value.
+	keep_unit: bool,
 	pub state: ProcessingState,
 	pub records: Vec<LogRecord>,
+	/// Output CSV file writer, set in streaming mode (see [`LineProcessor::start_csv`]).
+	csv_writer: Option<CsvRecordWriter>,
+	/// Count and time range of the records already written to the CSV file.
+	summary: CsvSummary,
 	pub output_path: Option<PathBuf>,
 	pub timestamp_format: TimestampFormat,
 	timestamp_extraction_failure_count: usize,
 	input_file_name: PathBuf,
 	ignore_invalid_timestamps: bool,
+	/// Fail on values which cannot be converted, instead of skipping them.
+	strict: bool,
+	/// Number of matched lines skipped because of value conversion failure.
+	skipped_records_count: usize,
+	/// Line number and raw value of the first skipped record.
+	first_skipped_record: Option<(u64, String)>,
+	match_stats: MatchStats,
+	/// Sort the records by timestamp before writing the CSV file.
+	sort_timestamps: bool,
+	/// Number of records with timestamp earlier than the previous record.
+	out_of_order_count: usize,
+	/// Line number of the first out-of-order record.
+	first_out_of_order_line: Option<u64>,
+	/// Only lines with timestamps within this window are matched against the regex.
+	time_window: Option<(NaiveDateTime, NaiveDateTime)>,
+	/// Timestamp far after the end of the time window (see [`TIME_WINDOW_SLACK`]) was seen in the
+	/// log, and the log was monotonic so far.
+	past_time_window: bool,
+	/// Timestamp of the previous line passing the guard.
+	last_line_timestamp: Option<ExtractedNaiveDateTime>,
+	/// Tells if timestamps of the lines passing the guard were never going back in time.
+	monotonic: bool,
+	/// Keeps only a subset of the records, if sampling is requested.
+	sampler: Option<Sampler>,
 }
 
 impl LineProcessor {
@@ -104,18 +738,193 @@ impl LineProcessor {
 		input_file_name: PathBuf,
 		ignore_invalid_timestamps: bool,
 	) -> Result<Self, Error> {
-		let regex = data_source.compile_regex()?;
-		Ok(Self {
-			data_source,
-			regex,
+		Ok(Self::from_extractor(
+			Box::new(DataSourceExtractor::new(data_source, None)?),
+			output_path,
+			timestamp_format,
+			input_file_name,
+			ignore_invalid_timestamps,
+		))
+	}
+
+	/// Builds the processor for the line, including its label regex and value transform.
+	pub fn from_line(
+		line: &Line,
+		output_path: Option<PathBuf>,
+		timestamp_format: TimestampFormat,
+		input_file_name: PathBuf,
+		ignore_invalid_timestamps: bool,
+	) -> Result<Self, Error> {
+		let original_units = line.params.value_units == Some(ValueUnits::Original);
+		let extractor = DataSourceExtractor::new(
+			line.data_source.clone(),
+			line.params.value_transform.clone(),
+		)?
+		.with_original_units(original_units)
+		.with_value_map(line.params.value_map.clone());
+		Self::from_extractor(
+			Box::new(extractor),
+			output_path,
+			timestamp_format,
+			input_file_name,
+			ignore_invalid_timestamps,
+		)
+		.with_label_regex(line.params.label_regex.as_deref())
+		.and_then(|processor| processor.with_label_filter(line.params.label_filter.as_ref()))
+		.map(|processor| {
+			processor.with_keep_text(line.keep_text()).with_unit_column(original_units)
+		})
+	}
+
+	pub fn from_extractor(
+		extractor: Box<dyn LineExtractor>,
+		output_path: Option<PathBuf>,
+		timestamp_format: TimestampFormat,
+		input_file_name: PathBuf,
+		ignore_invalid_timestamps: bool,
+	) -> Self {
+		Self {
+			extractor,
+			label_regex: None,
+			label_filter: None,
+			keep_text: None,
+			keep_unit: false,
 			output_path,
 			timestamp_format,
 			state: ProcessingState::new(),
 			records: Vec::new(),
+			csv_writer: None,
+			summary: (0, None),
 			timestamp_extraction_failure_count: 0,
 			input_file_name,
 			ignore_invalid_timestamps,
-		})
+			strict: false,
+			skipped_records_count: 0,
+			first_skipped_record: None,
+			match_stats: MatchStats::default(),
+			sort_timestamps: false,
+			out_of_order_count: 0,
+			first_out_of_order_line: None,
+			time_window: None,
+			past_time_window: false,
+			last_line_timestamp: None,
+			monotonic: true,
+			sampler: None,
+		}
+	}
+
+	/// Makes value conversion failures errors, instead of skipping such records.
+	pub fn with_strict(mut self, strict: bool) -> Self {
+		self.strict = strict;
+		self
+	}
+
+	/// Sets the number format of the values in the log (see [`LineExtractor::set_number_locale`]).
+	pub fn with_number_locale(mut self, number_locale: Option<NumberLocale>) -> Self {
+		if let Some(locale) = number_locale {
+			self.extractor.set_number_locale(locale);
+		}
+		self
+	}
+
+	/// Makes the records sorted by timestamp before they are written to the CSV file.
+	///
+	/// All records are kept in memory until [`Self::finish_csv`] in this mode.
+	pub fn with_sorted_timestamps(mut self, sort_timestamps: bool) -> Self {
+		self.sort_timestamps = sort_timestamps;
+		self
+	}
+
+	/// Skips the lines with timestamps outside of the window, before they are matched.
+	///
+	/// Unless timestamps are sorted (see [`Self::with_sorted_timestamps`]), the log is assumed to
+	/// be monotonic, and the first timestamp after the end of the window marks the processor as
+	/// finished.
+	pub fn with_time_window(mut self, time_window: Option<(NaiveDateTime, NaiveDateTime)>) -> Self {
+		self.time_window = time_window;
+		self
+	}
+
+	/// Keeps only the subset of records chosen with given sampling.
+	///
+	/// Counts and deltas are computed for all records, before the sampling.
+	pub fn with_sampling(mut self, sampling: Option<Sampling>) -> Self {
+		self.sampler = sampling.map(Sampler::new);
+		self
+	}
+
+	/// Tells if the records can be written to the CSV file before all lines are processed.
+	fn is_streaming(&self) -> bool {
+		!self.sort_timestamps && !self.sampler.as_ref().is_some_and(Sampler::is_reservoir)
+	}
+
+	/// Tells where the timestamp lies relative to the time window.
+	fn time_window_position(&self, timestamp: &ExtractedNaiveDateTime) -> Ordering {
+		fn position<T: PartialOrd>(ts: T, start: T, end: T) -> Ordering {
+			if ts < start {
+				Ordering::Less
+			} else if ts > end {
+				Ordering::Greater
+			} else {
+				Ordering::Equal
+			}
+		}
+
+		let Some((start, end)) = self.time_window else {
+			return Ordering::Equal;
+		};
+		match timestamp {
+			ExtractedNaiveDateTime::DateTime(ts) => position(*ts, start, end),
+			ExtractedNaiveDateTime::Time(ts) => position(*ts, start.time(), end.time()),
+		}
+	}
+
+	/// Tells if the timestamp is later than the end of the time window by more than
+	/// [`TIME_WINDOW_SLACK`].
+	fn is_far_past_time_window(&self, timestamp: &ExtractedNaiveDateTime) -> bool {
+		let Some((_, end)) = self.time_window else {
+			return false;
+		};
+		match timestamp {
+			ExtractedNaiveDateTime::DateTime(ts) => {
+				end.checked_add_signed(TIME_WINDOW_SLACK).is_some_and(|end| *ts > end)
+			},
+			ExtractedNaiveDateTime::Time(ts) => {
+				let (end, wrapped) = end.time().overflowing_add_signed(TIME_WINDOW_SLACK);
+				wrapped == 0 && *ts > end
+			},
+		}
+	}
+
+	/// Makes the matched text (or the whole log line) stored along with every value.
+	pub fn with_keep_text(mut self, keep_text: Option<MatchedText>) -> Self {
+		self.keep_text = keep_text;
+		self
+	}
+
+	/// Makes the captured unit (see [`LineExtractor::matched_unit`]) stored along with every
+	/// value.
+	pub fn with_unit_column(mut self, keep_unit: bool) -> Self {
+		self.keep_unit = keep_unit;
+		self
+	}
+
+	/// Sets the regex used to extract the label of every matched line.
+	///
+	/// Regex shall contain exactly one capture group.
+	pub fn with_label_regex(mut self, label_regex: Option<&str>) -> Result<Self, Error> {
+		if let Some(label_regex) = label_regex {
+			self.label_regex = Some(compile_label_regex(label_regex)?);
+		}
+		Ok(self)
+	}
+
+	/// Sets the filter dropping matched lines with other labels (see [`LabelFilter`]).
+	pub fn with_label_filter(mut self, label_filter: Option<&LabelFilter>) -> Result<Self, Error> {
+		if let Some(LabelFilter { regex, label }) = label_filter {
+			self.label_filter = Some((compile_label_regex(regex)?, label.clone()));
+		}
+		Ok(self)
 	}
 
 	/// Parses timestamp prefix from the line.
@@ -149,37 +958,129 @@ impl LineProcessor {
 		}
 	}
 
-	pub fn guard_matches(&self, log_line: &str) -> bool {
-		self.data_source.guard().as_ref().map(|g| log_line.contains(g)).unwrap_or(true)
+	fn handle_conversion_failure(&mut self, raw_value: String) -> Result<(), Error> {
+		let line_number = self.state.line_number;
+		if self.strict {
+			return Err(Error::ValueConversionFailure(
+				self.input_file_name.clone(),
+				line_number,
+				raw_value,
+			));
+		}
+		debug!(target:MATCH_PREVIEW, "try_match: value conversion failed: {raw_value:?}");
+		self.skipped_records_count += 1;
+		self.first_skipped_record.get_or_insert((line_number, raw_value));
+		Ok(())
 	}
 
-	pub fn try_match<'a>(
-		&mut self,
-		line: &'a str,
-	) -> Result<(bool, Option<(regex::Captures<'a>, ExtractedNaiveDateTime)>), Error> {
-		if self.guard_matches(line) {
-			if tracing::event_enabled!(target:MATCH_PREVIEW, Level::TRACE) {
-				trace!(target:MATCH_PREVIEW, "try_match: line:\"{line}\"");
-			} else {
+	/// Warns about skipped and out-of-order records.
+	fn report_warnings(&self) {
+		self.report_skipped_records();
+		self.report_out_of_order_records();
+	}
+
+	fn report_out_of_order_records(&self) {
+		let Some(line_number) = self.first_out_of_order_line else { return };
+		let action = if self.sort_timestamps {
+			"records were sorted"
+		} else {
+			"use --sort-timestamps to sort records"
+		};
+		warn!(
+			target:APPV,
+			"Found {} out-of-order timestamps of '{}' in {} (first at line {}), {}.",
+			self.out_of_order_count,
+			self.extractor.describe(),
+			self.input_file_name.display(),
+			line_number,
+			action,
+		);
+	}
+
+	/// Warns about the records skipped because of value conversion failures.
+	fn report_skipped_records(&self) {
+		if let Some((line_number, raw_value)) = &self.first_skipped_record {
+			warn!(
+				target:APPV,
+				"Skipped {} records of '{}' in {}: value could not be converted (first at line {}: '{}'). Use --strict to fail instead.",
+				self.skipped_records_count,
+				self.extractor.describe(),
+				self.input_file_name.display(),
+				line_number,
+				raw_value,
+			);
+		}
+	}
+
+	pub fn guard_matches(&self, log_line: &str) -> bool {
+		self.extractor.prefilter(log_line)
+	}
+
+	/// Returns if the line passed the guard, and the extracted value with timestamp if the line
+	/// matched.
+	pub fn try_match(
+		&mut self,
+		line: &str,
+	) -> Result<(bool, Option<(f64, ExtractedNaiveDateTime)>), Error> {
+		self.state.line_number += 1;
+		self.match_stats.lines += 1;
+		if self.guard_matches(line) {
+			self.match_stats.guard_passed += 1;
+			if tracing::event_enabled!(target:MATCH_PREVIEW, Level::TRACE) {
+				trace!(target:MATCH_PREVIEW, "try_match: line:\"{line}\"");
+			} else {
 				info!(target:MATCH_PREVIEW, "try_match: line:\"{line}\"");
 			}
 			if let Ok((timestamp, remainder)) = self.extract_timestamp(line) {
-				let captures = self.regex.captures(remainder).map(|capture| (capture, timestamp));
+				self.match_stats.timestamp_parsed += 1;
+				if self.last_line_timestamp.is_some_and(|last| timestamp < last) {
+					self.monotonic = false;
+				}
+				self.last_line_timestamp = Some(timestamp);
+				match self.time_window_position(&timestamp) {
+					Ordering::Equal => {},
+					position => {
+						self.match_stats.outside_time_window += 1;
+						// The rest of the log can only be skipped if it is sorted by time.
+						self.past_time_window |= position == Ordering::Greater
+							&& !self.sort_timestamps
+							&& self.monotonic && self
+							.is_far_past_time_window(&timestamp);
+						return Ok((true, None));
+					},
+				}
+				let input = if self.extractor.extracts_whole_line() { line } else { remainder };
+				let captures = self.extractor.extract(input).map(|value| (value, timestamp));
+				if let (None, Some(raw_value)) =
+					(&captures, self.extractor.take_conversion_failure())
+				{
+					self.handle_conversion_failure(raw_value)?;
+				}
 
-				if tracing::event_enabled!(Level::TRACE) {
-					trace!(target:MATCH_PREVIEW, "try_match remainder={remainder} regex={:#?} captures={captures:#?}", self.regex);
-				} else {
-					debug!(target:MATCH_PREVIEW, "try_match: line remainder: \"{remainder}\"");
-					if let Some((captures, _)) = &captures {
-						if let Some(c) = captures.get(1) {
-							debug!(target:MATCH_PREVIEW, "try_match: (value) captures[1]={c:?}");
-						};
-						if let Some(c) = captures.get(2) {
-							debug!(target:MATCH_PREVIEW, "try_match:  (unit) captures[2]={c:?}");
-						};
-					} else {
-						debug!(target:MATCH_PREVIEW, "try_match: no matches...");
-					}
+				if captures.is_some()
+					&& self.label_filter.as_ref().is_some_and(|(regex, label)| {
+						extract_label(regex, remainder).is_none_or(|l| l != label)
+					}) {
+					return Ok((true, None));
+				}
+
+				if captures.is_some() {
+					self.state.current_label = self
+						.label_regex
+						.as_ref()
+						.map(|r| extract_label(r, remainder).unwrap_or_default().to_string());
+					self.state.current_text = self.keep_text.map(|keep_text| match keep_text {
+						MatchedText::Line => line.to_string(),
+						MatchedText::Match => self
+							.extractor
+							.matched_range()
+							.and_then(|range| input.get(range))
+							.unwrap_or(input)
+							.to_string(),
+					});
+					self.state.current_unit = self
+						.keep_unit
+						.then(|| self.extractor.matched_unit().unwrap_or_default().to_string());
 				}
 
 				Ok((true, captures))
@@ -192,79 +1093,220 @@ impl LineProcessor {
 		}
 	}
 
-	pub fn process(&mut self, caps: regex::Captures, timestamp: ExtractedNaiveDateTime) {
+	/// Feeds the processor with the rows of the CSV file, instead of the log lines.
+	///
+	/// Cells of the time column are parsed with the timestamp format, cells of the value column
+	/// are converted like captured field values. Rows with empty value are skipped.
+	fn process_csv_file(&mut self, spec: &CsvFileSpec) -> Result<(), Error> {
+		let csv_error = |e| Error::CsvParseError(spec.csv_file.clone(), e);
+		let delimiter =
+			if spec.csv_file.extension().is_some_and(|e| e == "tsv") { b'\t' } else { b',' };
+		let mut reader = csv::ReaderBuilder::new()
+			.delimiter(delimiter)
+			.flexible(true)
+			.from_path(&spec.csv_file)
+			.map_err(csv_error)?;
+		let headers = reader.headers().map_err(csv_error)?.clone();
+		let column = |name: &str| {
+			headers
+				.iter()
+				.position(|header| header.trim() == name)
+				.ok_or_else(|| Error::CsvColumnNotFound(spec.csv_file.clone(), name.to_string()))
+		};
+		let (time_column, value_column) = (column(&spec.time_column)?, column(&spec.value_column)?);
+
+		for record in reader.records() {
+			let record = record.map_err(csv_error)?;
+			self.state.line_number =
+				record.position().map_or(self.state.line_number + 1, |p| p.line());
+			self.match_stats.lines += 1;
+			self.match_stats.guard_passed += 1;
+			let (Some(time), Some(value)) = (record.get(time_column), record.get(value_column))
+			else {
+				continue;
+			};
+			let Ok((timestamp, _)) = self.extract_timestamp(time.trim()) else {
+				self.handle_timestamp_extraction_failure(
+					&record.iter().collect::<Vec<_>>().join(","),
+				)?;
+				continue;
+			};
+			self.match_stats.timestamp_parsed += 1;
+			if self.time_window_position(&timestamp) != Ordering::Equal {
+				self.match_stats.outside_time_window += 1;
+				continue;
+			}
+			if value.trim().is_empty() {
+				continue;
+			}
+			match self.extractor.extract(value.trim()) {
+				Some(value) => {
+					self.process(value, timestamp);
+					if self.is_streaming() && self.records.len() >= RECORDS_CHUNK_SIZE {
+						self.flush_records()?;
+					}
+				},
+				None => {
+					if let Some(raw_value) = self.extractor.take_conversion_failure() {
+						self.handle_conversion_failure(raw_value)?;
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Stores the record of the value extracted from the currently processed line.
+	pub fn process(&mut self, value: f64, timestamp: ExtractedNaiveDateTime) {
 		let date = timestamp.date().map(|d| d.format(RECORD_DATE_FORMAT).to_string());
 		let time = timestamp.time().format(RECORD_TIME_FORMAT).to_string();
 		let count = self.state.next_count();
 		let diff = self.state.compute_delta(timestamp);
+		let label = self.state.current_label.take();
+		let text = self.state.current_text.take();
+		let unit = self.state.current_unit.take();
+		let line = Some(self.state.line_number);
+		if diff.is_some_and(|d| d < 0.0) {
+			self.out_of_order_count += 1;
+			self.first_out_of_order_line.get_or_insert(self.state.line_number);
+		}
+
+		let record = LogRecord { date, time, value, count, diff, line, label, text, unit };
+		match self.sampler.as_mut() {
+			// Sorting recomputes counts and deltas, so records are sampled once sorted.
+			Some(sampler) if !self.sort_timestamps => sampler.offer(&mut self.records, record),
+			_ => self.records.push(record),
+		}
+	}
 
-		let mut value = 1.0;
+	/// Creates the output CSV file and switches the processor into streaming mode.
+	///
+	/// In streaming mode records are written to the CSV file with [`Self::flush_records`], so
+	/// memory usage does not depend on the number of matches. Counts and deltas are kept in the
+	/// processing state, so they are not affected.
+	///
+	/// Records are written into the temporary file, which is renamed to the output path in
+	/// [`Self::finish_csv`]. Interrupted processing never leaves a partial CSV file which would be
+	/// later used as cache.
+	fn start_csv(&mut self) -> Result<(), Error> {
+		let mut partial_path = self.output_path()?.clone().into_os_string();
+		partial_path.push(".partial");
+		let partial_path = PartialFile(PathBuf::from(partial_path));
+		let io_error = |e| Error::new_file_io_error(&partial_path, e);
+		let file = File::create(&*partial_path).map_err(io_error)?;
+		let mut writer = BufWriter::new(file);
+
+		let label_header = if self.label_regex.is_some() { ",label" } else { "" };
+		let text_header = if self.keep_text.is_some() { ",text" } else { "" };
+		let unit_header = if self.keep_unit { ",unit" } else { "" };
+		writeln!(
+			writer,
+			"date,time,value,count,delta,line{label_header}{text_header}{unit_header}"
+		)
+		.map_err(io_error)?;
 
-		match &self.data_source {
-			DataSource::EventValue { yvalue, .. } => value = *yvalue,
-			DataSource::EventCount { .. } | DataSource::EventDelta { .. } => (),
-			DataSource::FieldValue { .. } => {
-				let raw_val = caps.get(1).map(|m| m.as_str()).unwrap_or("0");
-				let unit = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-				value = match normalize_value(raw_val, unit) {
-					Some(v) => v,
-					None => {
-						//add conversion warning (if conversion enabled)
-						return;
-					},
-				};
-			},
+		self.csv_writer =
+			Some(CsvRecordWriter { writer, partial_path, line: String::with_capacity(128) });
+		Ok(())
+	}
+
+	/// Writes the records collected so far into the output CSV file and drops them.
+	///
+	/// Records are formatted into a reused line buffer (numbers with `ryu`/`itoa`) and written
+	/// through a buffered writer, as extraction of large logs is otherwise write-bound.
+	fn flush_records(&mut self) -> Result<(), Error> {
+		let Some(csv_writer) = self.csv_writer.as_mut() else {
+			return Err(Error::CsvNotStarted(self.input_file_name.clone()));
+		};
+		let io_error = |e| Error::new_file_io_error(&csv_writer.partial_path, e);
+
+		if let (Some(first), Some(last)) = (self.records.first(), self.records.last()) {
+			let start = self.summary.1.map_or(first.timestamp(), |(start, _)| Ok(start))?;
+			self.summary.1 = Some((start, last.timestamp()?));
 		}
+		self.summary.0 += self.records.len();
 
-		self.records.push(LogRecord { date, time, value, count, diff });
-	}
-
-	fn write_csv(&self) -> Result<(), Error> {
-		let filename = self.expect_output_path();
-		let mut file =
-			File::create(filename).map_err(|e| Error::FileIoError(filename.clone(), e))?;
-		match self.timestamp_format {
-			TimestampFormat::Time(_) => {
-				writeln!(file, "date,time,value,count,delta")
-					.map_err(|e| Error::FileIoError(filename.clone(), e))?;
-				for r in &self.records {
-					//todo: clean up date
-					writeln!(
-						file,
-						"2025-01-01,{},{},{},{}",
-						r.time,
-						r.value,
-						r.count,
-						r.diff.unwrap_or(0.0)
-					)
-					.map_err(|e| Error::new_file_io_error(filename, e))?;
-				}
-			},
-			TimestampFormat::DateTime(_) => {
-				writeln!(file, "date,time,value,count,delta")
-					.map_err(|e| Error::new_file_io_error(filename, e))?;
-				for r in &self.records {
-					writeln!(
-						file,
-						"{},{},{},{},{}",
-						r.date.as_ref().expect("date should be set"),
-						r.time,
-						r.value,
-						r.count,
-						r.diff.unwrap_or(0.0)
-					)
-					.map_err(|e| Error::new_file_io_error(filename, e))?;
+		let mut floats = ryu::Buffer::new();
+		let mut integers = itoa::Buffer::new();
+		let line = &mut csv_writer.line;
+		for r in self.records.drain(..) {
+			line.clear();
+			//todo: clean up date
+			line.push_str(match self.timestamp_format {
+				TimestampFormat::Time(_) => TIME_ONLY_RECORD_DATE,
+				TimestampFormat::DateTime(_) => r.date.as_ref().expect("date should be set"),
+			});
+			line.push(',');
+			line.push_str(&r.time);
+			line.push(',');
+			line.push_str(floats.format(r.value));
+			line.push(',');
+			line.push_str(integers.format(r.count));
+			line.push(',');
+			line.push_str(floats.format(r.diff.unwrap_or(0.0)));
+			line.push(',');
+			line.push_str(integers.format(r.line.unwrap_or(0)));
+			r.push_csv_text_columns(line);
+			line.push('\n');
+			csv_writer.writer.write_all(line.as_bytes()).map_err(io_error)?;
+		}
+		Ok(())
+	}
+
+	/// Writes the remaining records, closes the output CSV file and returns the count and time
+	/// range of all written records.
+	fn finish_csv(mut self) -> Result<CsvSummary, Error> {
+		if self.sort_timestamps {
+			if self.out_of_order_count > 0 {
+				self.sort_records()?;
+			}
+			if let Some(sampler) = self.sampler.as_mut() {
+				for record in std::mem::take(&mut self.records) {
+					sampler.offer(&mut self.records, record);
 				}
-			},
-		};
+				// Counts follow the order of sorted records.
+				self.records.sort_by_key(|r| r.count);
+			}
+		} else if self.sampler.as_ref().is_some_and(Sampler::is_reservoir) {
+			self.records.sort_by_key(|r| r.line);
+		}
+		self.flush_records()?;
+		let CsvRecordWriter { mut writer, partial_path, .. } = self
+			.csv_writer
+			.take()
+			.ok_or_else(|| Error::CsvNotStarted(self.input_file_name.clone()))?;
+		writer.flush().map_err(|e| Error::new_file_io_error(&partial_path, e))?;
+		drop(writer);
+		let filename = self.output_path()?;
+		partial_path
+			.persist(filename)
+			.map_err(|e| Error::new_file_io_error(filename, e))?;
+		Ok(self.summary)
+	}
 
+	/// Sorts the records by timestamp and recomputes their counts and deltas.
+	///
+	/// Records with equal timestamps keep the log order.
+	fn sort_records(&mut self) -> Result<(), Error> {
+		// Date and time formats of the records are sortable as strings.
+		self.records.sort_by(|a, b| (&a.date, &a.time).cmp(&(&b.date, &b.time)));
+		let mut previous = None;
+		for (i, record) in self.records.iter_mut().enumerate() {
+			let timestamp = record.timestamp()?;
+			record.count = i as u64 + 1;
+			record.diff = previous.map(|p: NaiveDateTime| {
+				timestamp.signed_duration_since(p).num_milliseconds() as f64
+			});
+			previous = Some(timestamp);
+		}
 		Ok(())
 	}
 
-	pub fn expect_output_path(&self) -> &PathBuf {
+	/// The path of the output CSV file.
+	pub fn output_path(&self) -> Result<&PathBuf, Error> {
 		self.output_path
 			.as_ref()
-			.expect("output_path is expected to be set (this is bug")
+			.ok_or_else(|| Error::MissingOutputPath(self.input_file_name.clone()))
 	}
 }
 
@@ -282,14 +1324,24 @@ impl ResolvedLine {
 	}
 
 	pub fn title(&self, multi_input_files: bool) -> String {
-		let file_stem = self
-			.source
-			.file_name()
-			.file_stem()
-			.expect("filename is validated at this point")
-			.to_string_lossy();
 		let title = self.line.params.title.clone().unwrap_or(self.line.data_source.title());
-		let title = if multi_input_files { format!("{} ({})", title, file_stem) } else { title };
+		let title = if multi_input_files && !self.file_in_title() {
+			format!("{} ({})", title, self.source_display_name())
+		} else {
+			title
+		};
+		let title = match self.period_label() {
+			Some(period) => format!("{title} [{period}]"),
+			None => title,
+		};
+		let title = match &self.line.params.baseline {
+			Some(baseline) => format!("{title} - {baseline}"),
+			None => title,
+		};
+		let title = match self.line.params.normalize {
+			Some(normalize) => format!("{title} ({})", normalize.title_suffix()),
+			None => title,
+		};
 		if self.line.params.yaxis == Some(YAxis::Y2) { format!("{} | y2", title) } else { title }
 	}
 
@@ -304,6 +1356,27 @@ impl ResolvedLine {
 	pub fn csv_data_column_for_plot(&self) -> &'static str {
 		self.line.data_source.csv_data_column_for_plot()
 	}
+
+	pub fn label_regex(&self) -> Option<&str> {
+		self.line.params.label_regex.as_deref()
+	}
+
+	pub fn value_transform(&self) -> Option<&ValueTransformHook> {
+		self.line.params.value_transform.as_ref()
+	}
+
+	pub fn keep_text(&self) -> Option<MatchedText> {
+		self.line.keep_text()
+	}
+
+	pub fn value_map(&self) -> Option<&ValueMap> {
+		self.line.params.value_map.as_ref()
+	}
+
+	/// Tells if the values are kept in the captured units.
+	pub fn original_units(&self) -> bool {
+		self.line.params.value_units == Some(ValueUnits::Original)
+	}
 }
 
 impl DataSource {
@@ -338,6 +1411,24 @@ impl DataSource {
 			DataSource::EventDelta(EventDeltaSpec { guard: None, .. }) => {
 				format!("delta {}", self.raw_pattern())
 			},
+			DataSource::Annotation { guard: Some(guard), .. } => {
+				format!("annotation {} {}", guard, self.raw_pattern())
+			},
+			DataSource::Annotation { guard: None, .. } => {
+				format!("annotation {}", self.raw_pattern())
+			},
+			DataSource::JsonField { guard: Some(guard), .. } => {
+				format!("value of {} {}", guard, self.raw_pattern())
+			},
+			DataSource::JsonField { guard: None, .. } => {
+				format!("value of {}", self.raw_pattern())
+			},
+			DataSource::LogfmtField { guard: Some(guard), .. } => {
+				format!("value of {} {}", guard, self.raw_pattern())
+			},
+			DataSource::LogfmtField { guard: None, .. } | DataSource::CsvFile(_) => {
+				format!("value of {}", self.raw_pattern())
+			},
 		}
 	}
 
@@ -347,25 +1438,59 @@ impl DataSource {
 			// DataSource::EventValue { pattern, yvalue, .. } => format!("{}_{}", pattern, yvalue),
 			DataSource::EventValue { pattern, .. }
 			| DataSource::EventCount { pattern, .. }
-			| DataSource::EventDelta(EventDeltaSpec { pattern, .. }) => pattern.clone(),
+			| DataSource::EventDelta(EventDeltaSpec { pattern, .. })
+			| DataSource::Annotation { pattern, .. } => pattern.clone(),
+			DataSource::JsonField { path, .. } => path.clone(),
+			DataSource::LogfmtField { key, .. } => key.clone(),
+			DataSource::CsvFile(CsvFileSpec { value_column, .. }) => value_column.clone(),
 			DataSource::FieldValue(FieldCaptureSpec { field, .. }) => field.clone(),
 		}
 	}
 
+	/// Returns the pattern identifying the data extracted by the data source, lines with the same
+	/// pattern can share the CSV file.
+	///
+	/// Field regexes equivalent to the default pattern of a field are reduced to the field name
+	/// (e.g. `\bduration=([\d\.]+)(\w+)?` to `duration`). Default patterns depend on the number
+	/// locale, so nothing is reduced if it is given.
+	fn canonical_pattern(&self, number_locale: Option<NumberLocale>) -> String {
+		let raw_pattern = self.raw_pattern();
+		let DataSource::FieldValue(FieldCaptureSpec { field, .. }) = self else {
+			return raw_pattern;
+		};
+		if number_locale.is_some() || !self.is_field_valid_regex() {
+			return raw_pattern;
+		}
+		let Some(name) = field.split_once('=').and_then(|(name, _)| name.strip_prefix(r"\b"))
+		else {
+			return raw_pattern;
+		};
+		if name.is_empty() || regex::escape(name) != name {
+			return raw_pattern;
+		}
+		let hir = |pattern: &str| regex_syntax::Parser::new().parse(pattern).ok();
+		let default_pattern = DataSource::new_plot_field(None, name.into()).regex_pattern();
+		match (hir(field), hir(&default_pattern)) {
+			(Some(hir), Some(default_hir)) if hir == default_hir => name.into(),
+			_ => raw_pattern,
+		}
+	}
+
 	/// Checks if regex pattern is valid.
 	///
 	/// For [`DataSource::FieldValue`] it checks if regex pattern contains a correct number of captures groups.
 	/// Otherwise no validation is performed and any pattern is assumed to be correct.
 	fn validate_field_regex(&self) -> Result<bool, Error> {
-		if let DataSource::FieldValue(FieldCaptureSpec { field, .. }) = &self {
-			if let Ok(regex) = Regex::new(field) {
-				let captures_len = regex.captures_len() - 1;
-				if (1..=2).contains(&captures_len) {
-					return Ok(true);
-				}
-				if captures_len > 2 {
-					return Err(Error::RegexCapturesGroupsInvalidCount(field.clone()));
-				}
+		let DataSource::FieldValue(FieldCaptureSpec { field, .. }) = &self else {
+			return Ok(false);
+		};
+		if let Ok(regex) = DataSourceRegex::new(field) {
+			let captures_len = regex.captures_len() - 1;
+			if (1..=2).contains(&captures_len) {
+				return Ok(true);
+			}
+			if captures_len > 2 {
+				return Err(Error::RegexCapturesGroupsInvalidCount(field.clone()));
 			}
 		}
 		Ok(false)
@@ -376,24 +1501,44 @@ impl DataSource {
 	}
 
 	/// Returns actual regex pattern that will be used for matching events and extracting values.
+	///
+	/// JSON and logfmt fields are not matched with regex, their path (or key) is returned. The
+	/// value column is returned for CSV files.
 	fn regex_pattern(&self) -> String {
+		self.regex_pattern_with_value(None)
+	}
+
+	/// Returns the regex pattern with the given pattern of the value (e.g. number formatted in the
+	/// locale). Only the default field pattern is affected, custom regexes are used as given.
+	fn regex_pattern_with_value(&self, value_pattern: Option<String>) -> String {
 		match &self {
 			DataSource::EventValue { pattern, .. }
 			| DataSource::EventCount { pattern, .. }
-			| DataSource::EventDelta(EventDeltaSpec { pattern, .. }) => pattern.clone(),
+			| DataSource::EventDelta(EventDeltaSpec { pattern, .. })
+			| DataSource::Annotation { pattern, .. } => pattern.clone(),
+			DataSource::JsonField { path, .. } => path.clone(),
+			DataSource::LogfmtField { key, .. } => key.clone(),
+			DataSource::CsvFile(CsvFileSpec { value_column, .. }) => value_column.clone(),
 			DataSource::FieldValue(FieldCaptureSpec { field, .. }) => {
 				if self.is_field_valid_regex() {
 					field.clone()
 				} else {
-					format!(r"\b{}=([\d\.]+)(\w+)?", regex::escape(field))
+					let value = value_pattern.unwrap_or_else(|| r"[\d\.]+".to_string());
+					format!(r"\b{}=({value})(\w+)?", regex::escape(field))
 				}
 			},
 		}
 	}
 
-	pub fn compile_regex(&self) -> Result<Regex, Error> {
+	pub fn compile_regex(&self) -> Result<DataSourceRegex, Error> {
 		self.validate_field_regex()?;
-		Regex::new(&self.regex_pattern()).map_err(Into::into)
+		let regex = DataSourceRegex::new(&self.regex_pattern())?;
+		match self {
+			DataSource::Annotation { pattern, .. } if regex.captures_len() != 2 => {
+				Err(Error::AnnotationRegexCapturesGroupsInvalidCount(pattern.clone()))
+			},
+			_ => Ok(regex),
+		}
 	}
 
 	pub fn guard(&self) -> &Option<String> {
@@ -401,13 +1546,22 @@ impl DataSource {
 			DataSource::EventValue { guard, .. }
 			| DataSource::EventCount { guard, .. }
 			| DataSource::EventDelta(EventDeltaSpec { guard, .. })
+			| DataSource::Annotation { guard, .. }
+			| DataSource::JsonField { guard, .. }
+			| DataSource::LogfmtField { guard, .. }
 			| DataSource::FieldValue(FieldCaptureSpec { guard, .. }) => guard,
+			DataSource::CsvFile(_) => &None,
 		}
 	}
 
 	pub fn csv_data_column_for_plot(&self) -> &'static str {
 		match &self {
-			DataSource::FieldValue { .. } | DataSource::EventValue { .. } => "value",
+			DataSource::FieldValue { .. }
+			| DataSource::JsonField { .. }
+			| DataSource::LogfmtField { .. }
+			| DataSource::CsvFile(_)
+			| DataSource::EventValue { .. }
+			| DataSource::Annotation { .. } => "value",
 			DataSource::EventCount { .. } => "count",
 			DataSource::EventDelta { .. } => "delta",
 		}
@@ -435,27 +1589,115 @@ impl ResolvedLine {
 	///
 	/// This naming strategy ensures that multiple lines using the same pattern and guard
 	/// will map to the same CSV file, enabling output reuse and avoiding redundant processing.
-	pub fn get_csv_filename(&self) -> PathBuf {
+	///
+	/// Values of field lines depend on the number locale, so it is included in their filename. CSV
+	/// files prefiltered with the time window contain only its records, so the window is included
+	/// too. The same applies to the lines range limited with `--skip-lines` and `--max-lines`, to
+	/// the sampling and to dropping of duplicated lines.
+	pub fn get_csv_filename(&self, input_context: &InputFilesContext) -> PathBuf {
 		let tag = self.regex_filename_tag();
 		let core = match &self.line.data_source {
 			DataSource::EventValue { yvalue, .. } => format!("value_{yvalue}_{tag}"),
 			DataSource::EventCount { .. } => format!("count_{tag}"),
 			DataSource::EventDelta { .. } => format!("delta_{tag}"),
+			DataSource::Annotation { .. } => format!("annotation_{tag}"),
+			DataSource::JsonField { .. } => format!("json_{tag}"),
+			DataSource::LogfmtField { .. } => format!("logfmt_{tag}"),
+			DataSource::CsvFile(CsvFileSpec { time_column, .. }) => {
+				format!("csv_{}_{tag}", urlencoding::encode(time_column))
+			},
 			DataSource::FieldValue { .. } => tag,
 		};
+		let core = if let Some(label_regex) = self.label_regex() {
+			format!("{core}__label_{}", urlencoding::encode(label_regex))
+		} else {
+			core
+		};
+		let core = if let Some(LabelFilter { regex, label }) = &self.line.params.label_filter {
+			format!("{core}__only_{}_{}", urlencoding::encode(regex), urlencoding::encode(label))
+		} else {
+			core
+		};
+		let core = if let Some(transform) = self.value_transform() {
+			format!("{core}__transform_{}", urlencoding::encode(transform.0.name()))
+		} else {
+			core
+		};
+		let core = if let Some(keep_text) = self.keep_text() {
+			format!("{core}__text_{}", keep_text.as_str())
+		} else {
+			core
+		};
+		let core = if self.original_units() { format!("{core}__units_original") } else { core };
+		let core = if let Some(value_map) = self.value_map() {
+			format!("{core}__map_{}", urlencoding::encode(&value_map.to_string()))
+		} else {
+			core
+		};
+		let core = match (input_context.number_locale(), &self.line.data_source) {
+			(
+				Some(locale),
+				DataSource::FieldValue { .. }
+				| DataSource::JsonField { .. }
+				| DataSource::LogfmtField { .. },
+			) => {
+				format!("{core}__locale_{}", locale.as_str())
+			},
+			_ => core,
+		};
+		let core = if let Some((start, end)) = input_context.time_window() {
+			let format =
+				|ts: NaiveDateTime| ts.format(WINDOW_FILENAME_TIMESTAMP_FORMAT).to_string();
+			format!("{core}__window_{}_{}", format(start), format(end))
+		} else {
+			core
+		};
+		let core = if input_context.dedup() { format!("{core}__dedup") } else { core };
+		let core = if let Some(skip_lines) = input_context.skip_lines() {
+			format!("{core}__skip_{skip_lines}")
+		} else {
+			core
+		};
+		let core = if let Some(max_lines) = input_context.max_lines() {
+			format!("{core}__max_{max_lines}")
+		} else {
+			core
+		};
+		let core = if let Some(sampling) = input_context.sample() {
+			format!("{core}__sample_{sampling}")
+		} else {
+			core
+		};
 
-		let log_name = self
-			.source_file_name()
-			.file_name()
-			.expect("file path shall be given")
-			.to_string_lossy();
+		let log_name = match CommandInput::parse(self.source_file_name()) {
+			Some(command_input) => command_input.file_name().into(),
+			None => self
+				.source_file_name()
+				.file_name()
+				.expect("file path shall be given")
+				.to_string_lossy(),
+		};
 
-		let ts = fs::metadata(self.source_file_name())
-			.and_then(|m| m.modified())
-			.map_err(|_| ())
-			.and_then(|t| t.duration_since(UNIX_EPOCH).map_err(|_| ()))
-			.map(|d| d.as_secs().to_string())
-			.unwrap_or_else(|_| "nots".to_string());
+		// The virtual input of `--concat` is as recent as its most recent part, the number of
+		// parts is included too.
+		let parts = input_context.input_parts(self.source_file_name());
+		let ts = parts
+			.iter()
+			.map(|part| {
+				fs::metadata(part)
+					.and_then(|m| m.modified())
+					.map_err(|_| ())
+					.and_then(|t| t.duration_since(UNIX_EPOCH).map_err(|_| ()))
+					.map(|d| d.as_secs())
+			})
+			.collect::<Result<Vec<_>, _>>()
+			.ok()
+			.and_then(|mtimes| mtimes.into_iter().max())
+			.map(|ts| match parts.len() {
+				1 => ts.to_string(),
+				len => format!("{ts}x{len}"),
+			})
+			.unwrap_or_else(|| "nots".to_string());
 
 		PathBuf::from(if let Some(guard) = self.line.data_source.guard() {
 			format!("{log_name}_{ts}__{guard}__{core}.csv")
@@ -467,60 +1709,110 @@ impl ResolvedLine {
 
 /// Result will contain exactly the lines that needs to be processed against the log.
 /// It will be deduplicated
+///
+/// Ordered maps are used, so the canonical lines (and the processing order) are the same on every
+/// run for the same config.
 fn propagate_shared_csv_files<F>(
 	config: &mut ResolvedGraphConfig,
 	inpput_files_context: &InputFilesContext,
 	get_cache_dir: F,
-) -> Result<HashMap<PathBuf, ResolvedLine>, Error>
+) -> Result<BTreeMap<PathBuf, ResolvedLine>, Error>
 where
 	F: Fn(&InputFilesContext, &PathBuf) -> Result<PathBuf, Error>,
 {
-	type MatchKey = (Option<String>, String, PathBuf);
-
-	let mut grouped_lines: HashMap<MatchKey, Vec<&mut ResolvedLine>> = HashMap::new();
+	type MatchKey = (
+		Option<String>,
+		String,
+		PathBuf,
+		Option<String>,
+		Option<String>,
+		Option<MatchedText>,
+		bool,
+		Option<String>,
+	);
+
+	let mut grouped_lines: BTreeMap<MatchKey, Vec<&mut ResolvedLine>> = BTreeMap::new();
 
 	for panel in &mut config.panels {
 		for line in &mut panel.lines {
 			let guard = line.guard().clone();
-			let token = line.raw_pattern();
+			// JSON paths and logfmt keys are not regexes, their matches cannot be shared with
+			// regex lines.
+			let token = match &line.line.data_source {
+				DataSource::JsonField { path, .. } => format!("json:{path}"),
+				DataSource::LogfmtField { key, .. } => format!("logfmt:{key}"),
+				data_source => data_source.canonical_pattern(inpput_files_context.number_locale()),
+			};
 			let input = line.source_file_name().clone();
-
-			grouped_lines.entry((guard, token, input)).or_default().push(line);
+			let label_regex = line.label_regex().map(Into::into);
+			let transform = line.value_transform().map(|t| t.0.name().to_string());
+			let keep_text = line.keep_text();
+			let original_units = line.original_units();
+			let value_map = line.value_map().map(ToString::to_string);
+
+			grouped_lines
+				.entry((
+					guard,
+					token,
+					input,
+					label_regex,
+					transform,
+					keep_text,
+					original_units,
+					value_map,
+				))
+				.or_default()
+				.push(line);
 		}
 	}
 
 	trace!(target: LOG_TARGET,  "propagete_shared_csv_files {:#?}", grouped_lines);
 
-	let mut canonicals: HashMap<PathBuf, ResolvedLine> = Default::default();
+	let mut canonicals: BTreeMap<PathBuf, ResolvedLine> = Default::default();
 
-	for ((_, _, input_filename), mut lines) in grouped_lines {
+	for ((_, _, input_filename, ..), mut lines) in grouped_lines {
 		for line in &mut lines {
 			let output_dir = get_cache_dir(inpput_files_context, &input_filename)?;
 
-			let csv_output_path = output_dir.join(line.get_csv_filename());
+			let csv_output_path = output_dir.join(line.get_csv_filename(inpput_files_context));
 			line.set_shared_csv_filename(&csv_output_path);
 		}
 
 		let canonical = lines
 			.iter()
-			.find(|l| matches!(l.line.data_source, DataSource::FieldValue { .. }))
+			.find(|l| {
+				matches!(
+					l.line.data_source,
+					DataSource::FieldValue { .. }
+						| DataSource::JsonField { .. }
+						| DataSource::LogfmtField { .. }
+				)
+			})
 			.or(lines
 				.iter()
 				.find(|l| matches!(l.line.data_source, DataSource::EventValue { .. })))
-			.unwrap_or(&lines[0]);
-
-		canonicals.insert((*canonical).expect_shared_csv_filename(), (*canonical).clone());
+			.or(lines.first())
+			.ok_or_else(|| Error::NoCanonicalLine(input_filename.clone()))?;
 
-		let shared_path = canonical.expect_shared_csv_filename();
+		let shared_path = canonical.resolved_csv_filename()?;
+		canonicals.insert(shared_path.clone(), (*canonical).clone());
+		let is_canonical_field = matches!(canonical.line.data_source, DataSource::FieldValue(_));
 
 		trace!(target: LOG_TARGET,  "propagete_shared_csv_files canonical {:#?}", shared_path);
 
 		for line in lines {
-			if line.can_csv_file_be_shared() {
+			// Field regexes reduced to the field name are equivalent to the default pattern of the
+			// field, so they extract the same values as the canonical field line.
+			let data_source = &line.line.data_source;
+			let is_equivalent_field = is_canonical_field
+				&& matches!(data_source, DataSource::FieldValue(_))
+				&& data_source.canonical_pattern(inpput_files_context.number_locale())
+					!= data_source.raw_pattern();
+			if line.can_csv_file_be_shared() || is_equivalent_field {
 				line.set_shared_csv_filename(&shared_path);
 			} else {
 				canonicals
-					.entry(line.expect_shared_csv_filename())
+					.entry(line.resolved_csv_filename()?)
 					.or_insert_with(|| (*line).clone());
 			}
 		}
@@ -544,10 +1836,23 @@ pub fn process_inputs(
 	trace!(target: LOG_TARGET,  "after propagete_shared_csv_files {:#?}", config);
 
 	// input_log_file ->  map( output_path -> processor)
-	let mut processors: HashMap<PathBuf, HashMap<PathBuf, LineProcessor>> = Default::default();
+	let mut processors: BTreeMap<PathBuf, BTreeMap<PathBuf, LineProcessor>> = Default::default();
+
+	// Records count and time range of every CSV file, so they are not re-read.
+	let mut summaries: HashMap<PathBuf, CsvSummary> = Default::default();
+
+	// Counters of processed log lines of every freshly written CSV file.
+	let mut match_stats: HashMap<PathBuf, MatchStats> = Default::default();
+
+	// cache_dir -> manifest
+	let mut manifests: HashMap<PathBuf, CacheManifest> = Default::default();
+
+	// output_path -> (processor, CSV file) of the lines drawn from CSV files
+	let mut csv_file_processors: BTreeMap<PathBuf, (LineProcessor, CsvFileSpec)> =
+		Default::default();
 
 	for line in config.all_lines() {
-		let csv_output_path = line.expect_shared_csv_filename();
+		let csv_output_path = line.resolved_csv_filename()?;
 
 		let output_dir: PathBuf = csv_output_path
 			.parent()
@@ -558,153 +1863,752 @@ pub fn process_inputs(
 				.map_err(|e| Error::new_file_io_error(&output_dir, e))?;
 		}
 
-		if !input_context.force_csv_regen() && Path::new(&csv_output_path).exists() {
+		// Transform implementation may change between runs, so cache is not used. Cached files
+		// may be unsorted. Journals and container logs change all the time.
+		let regenerate = input_context.force_csv_regen()
+			|| input_context.sort_timestamps()
+			|| line.value_transform().is_some()
+			|| input_context
+				.input_parts(line.source_file_name())
+				.iter()
+				.any(|part| CommandInput::parse(part).is_some());
+		if !regenerate && Path::new(&csv_output_path).exists() {
 			debug!(
 				target: APPV,
 				"Using cached file for regex: {} file: {}",
 				line.line.data_source.regex_pattern(),
 				csv_output_path.display(),
 			);
+			info!(
+				target: MESSAGES,
+				file = %csv_output_path.display(),
+				input = %line.source_file_name().display(),
+				"cache-hit"
+			);
+			let manifest = manifests
+				.entry(output_dir.clone())
+				.or_insert_with(|| CacheManifest::load(&output_dir));
+			manifest.touch(&csv_output_path);
+			if let Some(summary) = manifest.summary(&csv_output_path) {
+				summaries.insert(csv_output_path, summary);
+			}
 			continue;
 		}
 
 		if let Some(canonical_line) = canonical_lines.remove(&csv_output_path) {
-			let processor = LineProcessor::from_data_source(
-				canonical_line.line.data_source.clone(),
-				Some(csv_output_path),
+			info!(
+				target: MESSAGES,
+				file = %csv_output_path.display(),
+				input = %canonical_line.source_file_name().display(),
+				"cache-miss"
+			);
+			let processor = LineProcessor::from_line(
+				&canonical_line.line,
+				Some(csv_output_path.clone()),
 				input_context.timestamp_format().clone(),
 				canonical_line.source_file_name().clone(),
 				input_context.ignore_invalid_timestamps(),
-			)?;
-
+			)?
+			.with_strict(input_context.strict())
+			.with_number_locale(input_context.number_locale())
+			.with_sorted_timestamps(input_context.sort_timestamps())
+			.with_time_window(input_context.time_window())
+			.with_sampling(input_context.sample());
+
+			if let DataSource::CsvFile(spec) = &canonical_line.line.data_source {
+				csv_file_processors.insert(csv_output_path, (processor, spec.clone()));
+				continue;
+			}
 			processors
 				.entry(canonical_line.source_file_name().clone())
 				.or_default()
-				.entry(processor.expect_output_path().clone())
+				.entry(csv_output_path)
 				.or_insert(processor);
 		}
 	}
 
 	trace!(target: LOG_TARGET,  "process_inputs readers: {:#?}", processors);
 
+	let mut finish_processor = |output_path: PathBuf, processor: LineProcessor| {
+		processor.report_warnings();
+		match_stats.insert(output_path.clone(), processor.match_stats);
+		let description = processor.extractor.describe();
+		let input_file_name = processor.input_file_name.clone();
+		let summary = processor.finish_csv()?;
+		if summary.0 > 0 {
+			debug!(
+				target:APPV,
+				"Processed input file: {}, regex: {}, matched {}, cache file: {}",
+				input_file_name.display(),
+				description,
+				summary.0,
+				output_path.display()
+			);
+		}
+
+		let output_dir = output_path.parent().expect("checked above").to_path_buf();
+		manifests
+			.entry(output_dir.clone())
+			.or_insert_with(|| CacheManifest::load(&output_dir))
+			.insert(&output_path, summary);
+		summaries.insert(output_path, summary);
+		Ok::<_, Error>(())
+	};
+
 	// Iterate over log lines
-	for (log_file_name, mut processors) in processors {
-		if !log_file_name.is_file() {
-			return Err(Error::InvalidInputFile(log_file_name, "Not a regular file".to_string()));
-		}
-		let input_file =
-			File::open(&log_file_name).map_err(|e| Error::new_file_io_error(&log_file_name, e))?;
-		let reader = BufReader::new(input_file);
-		for line in reader.lines().map_while(Result::ok) {
-			for processor in &mut processors.values_mut() {
+	let total = processors.len();
+	for (index, (log_file_name, mut processors)) in processors.into_iter().enumerate() {
+		let mut log_lines = LogLines::open_input(&log_file_name, input_context)?;
+		let skip_lines = input_context.skip_lines().unwrap_or(0);
+		for processor in processors.values_mut() {
+			processor.start_csv()?;
+			processor.state.line_number = skip_lines as u64;
+		}
+		let max_lines = input_context.max_lines().unwrap_or(usize::MAX);
+		let mut deduplicator = input_context.dedup().then(LineDeduplicator::default);
+		for line in log_lines.by_ref().skip(skip_lines).take(max_lines) {
+			let line = line.map_err(|e| Error::new_file_io_error(&log_file_name, e))?;
+			if deduplicator
+				.as_mut()
+				.is_some_and(|d| d.is_duplicate(&line, input_context.timestamp_format()))
+			{
+				// Line numbers still refer to the whole input.
+				processors.values_mut().for_each(|p| p.state.line_number += 1);
+				continue;
+			}
+			for processor in processors.values_mut().filter(|p| !p.past_time_window) {
 				if let (_, Some((captures, timestamp))) = processor.try_match(&line)? {
 					processor.process(captures, timestamp);
+					if processor.is_streaming() && processor.records.len() >= RECORDS_CHUNK_SIZE {
+						processor.flush_records()?;
+					}
 				}
 			}
+			if processors.values().all(|p| p.past_time_window) {
+				warn!(
+					target:APPV,
+					"End of the time window reached at line {}, skipping the rest of: {} (use --sort-timestamps if the log is not sorted by time)",
+					processors.values().map(|p| p.state.line_number).max().unwrap_or_default(),
+					log_file_name.display()
+				);
+				break;
+			}
 		}
-		// Write all output files
-		for (_, processor) in processors {
-			assert_eq!(log_file_name, processor.input_file_name);
-			if !processor.records.is_empty() {
-				debug!(
+		log_lines.report_lossy_lines(&log_file_name);
+		if let Some(deduplicator) = deduplicator {
+			if deduplicator.dropped > 0 {
+				info!(
 					target:APPV,
-					"Processed input file: {}, regex: {}, matched {}, cache file: {}",
-					log_file_name.display(),
-					processor.data_source.regex_pattern(),
-					processor.records.len(),
-					processor.expect_output_path().display()
+					"Dropped {} duplicated lines of: {}",
+					deduplicator.dropped,
+					log_file_name.display()
+				);
+			}
+			if deduplicator.forgotten > 0 {
+				warn!(
+					target:APPV,
+					"Only the latest {MAX_DEDUP_LINES} lines were remembered for --dedup, duplicates of {} older lines may be kept in: {}",
+					deduplicator.forgotten,
+					log_file_name.display()
 				);
 			}
-
-			processor.write_csv()?;
 		}
+		// Finish all output files
+		for (output_path, processor) in processors {
+			assert_eq!(log_file_name, processor.input_file_name);
+			finish_processor(output_path, processor)?;
+		}
+		info!(
+			target: MESSAGES,
+			file = %log_file_name.display(),
+			index = index + 1,
+			total,
+			"file-processed"
+		);
 	}
 
-	config.resolve_data_points_count()?;
+	// Lines drawn from CSV files are fed with the rows of the file, no log is read.
+	for (output_path, (mut processor, spec)) in csv_file_processors {
+		processor.start_csv()?;
+		processor.process_csv_file(&spec)?;
+		finish_processor(output_path, processor)?;
+	}
 
-	Ok(())
-}
+	manifests.values().for_each(CacheManifest::save);
 
-pub fn regex_match_preview(
-	config: MatchPreviewConfig,
-	context: SharedMatchPreviewContext,
-	verbose_level: u8,
-) -> Result<(), Error> {
-	let env_filter = if verbose_level == 2 {
-		EnvFilter::new(format!("warn,{}=trace", MATCH_PREVIEW))
-	} else {
-		EnvFilter::new(format!("warn,{}=debug", MATCH_PREVIEW))
-	};
+	if let Some(max_size) = input_context.cache_max_size() {
+		let cache_dirs = match input_context.get_cache_root() {
+			Some(root) => vec![root],
+			None => manifests.into_keys().collect(),
+		};
+		prune_cache(config, &cache_dirs, max_size.0)?;
+	}
 
-	let preview_layer = tracing_subscriber::fmt::layer()
-		.without_time()
-		.with_target(false)
-		.with_level(true);
-	let preview_subscriber = Registry::default().with(preview_layer.with_filter(env_filter));
+	config.resolve_data_points_count(&summaries, &match_stats, input_context.timestamp_format())?;
 
-	tracing::subscriber::with_default(preview_subscriber, || {
-		regex_match_preview_inner(config, context)
-	})
+	Ok(())
 }
 
-pub fn regex_match_preview_inner(
-	config: MatchPreviewConfig,
-	context: SharedMatchPreviewContext,
+/// CSV files used by the graphs processed so far, which are still needed for rendering (e.g. all
+/// sections of the report are rendered at once).
+static CACHE_FILES_IN_USE: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// Records the CSV files derived from the cached CSV files (e.g. by transforms) in the cache
+/// manifests, so they are pruned like other cached files once they are not in use anymore.
+pub(crate) fn record_derived_csvs(csv_files: &[(PathBuf, CsvSummary)]) {
+	let mut in_use = CACHE_FILES_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+	in_use
+		.get_or_insert_with(HashSet::new)
+		.extend(csv_files.iter().map(|(path, _)| path.clone()));
+	cache_manifest::record_derived_csvs(
+		csv_files.iter().map(|(path, summary)| (path.as_path(), *summary)),
+	);
+}
+
+/// Deletes the least recently used CSV files from the cache directories, so the cache does not
+/// exceed `max_size` bytes (see `--cache-max-size`).
+fn prune_cache(
+	config: &ResolvedGraphConfig,
+	cache_dirs: &[PathBuf],
+	max_size: u64,
 ) -> Result<(), Error> {
-	let mut processor = LineProcessor::from_data_source(
-		config.data_source.clone(),
-		None,
-		context.timestamp_format().clone(),
-		context.input.clone(),
-		false,
-	)?;
+	let mut in_use = CACHE_FILES_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+	let in_use = in_use.get_or_insert_with(HashSet::new);
+	for line in config.all_lines() {
+		in_use.insert(line.resolved_csv_filename()?);
+	}
+	let stats =
+		cache_manifest::prune_cache(cache_dirs.iter().map(PathBuf::as_path), max_size, in_use);
+	if stats.removed > 0 {
+		info!(target:APPV, "Cache pruned: {} files deleted, {} bytes freed, {} bytes left.",
+			stats.removed, stats.freed, stats.remaining);
+	}
+	if stats.remaining > max_size {
+		warn!(target:APPV, "Cache size ({} bytes) exceeds the limit, the files in use are kept.",
+			stats.remaining);
+	}
+	Ok(())
+}
 
-	let input_file =
-		File::open(&context.input).map_err(|e| Error::FileIoError(context.input.clone(), e))?;
-	let reader = BufReader::new(input_file);
-	let mut matched_count = 0;
+/// Compiles the regex extracting labels. Regex shall contain exactly one capture group.
+fn compile_label_regex(label_regex: &str) -> Result<Regex, Error> {
+	let regex = Regex::new(label_regex)?;
+	if regex.captures_len() != 2 {
+		return Err(Error::LabelRegexCapturesGroupsInvalidCount(label_regex.to_string()));
+	}
+	Ok(regex)
+}
 
-	info!(target:MATCH_PREVIEW, "input file: {}", context.input.display());
-	if let Some(guard) = config.data_source.guard().as_ref() {
-		info!(target:MATCH_PREVIEW, "guard: {guard}")
-	};
-	info!(target:MATCH_PREVIEW, "regex pattern: {}", config.data_source.regex_pattern());
-	info!(target:MATCH_PREVIEW, "timestamp pattern: {:?}", context.timestamp_format);
+/// Returns the text captured with the label regex.
+fn extract_label<'a>(regex: &Regex, text: &'a str) -> Option<&'a str> {
+	regex.captures(text).and_then(|c| c.get(1)).map(|m| m.as_str())
+}
 
-	for line in reader.lines().map_while(Result::ok) {
-		let (guard_matched, captured) = processor.try_match(&line)?;
-		if guard_matched {
-			if let Some((captures, timestamp)) = captured {
-				processor.process(captures, timestamp);
-				info!(target:MATCH_PREVIEW, "matched: {:#?}", processor.records.last());
+/// Returns the labels captured with the regex in all input files (sorted and deduplicated).
+///
+/// Used to instantiate panels repeated per label (see [`crate::graph_config::PanelParams::repeat_for`]).
+pub fn discover_labels(
+	label_regex: &str,
+	input_context: &InputFilesContext,
+) -> Result<Vec<String>, Error> {
+	let regex = compile_label_regex(label_regex)?;
+	let mut labels = BTreeSet::new();
+	for input in input_context.logical_input() {
+		for line in LogLines::open_input(&input, input_context)? {
+			let line = line.map_err(|e| Error::new_file_io_error(&input, e))?;
+			if let Some(label) = extract_label(&regex, &line) {
+				labels.insert(label.to_string());
 			}
-
-			matched_count += 1;
-		}
-		if matched_count >= context.count {
-			break;
 		}
 	}
+	Ok(labels.into_iter().collect())
+}
 
-	if matched_count == 0 {
-		if let Some(guard) = config.data_source.guard() {
-			warn!(target:MATCH_PREVIEW, "No lines matched against guard: '{:?}'", guard);
-			warn!(target:MATCH_PREVIEW, "Is it correctly configured?");
+/// Maximal number of log lines remembered by [`LineDeduplicator`], the lines with the oldest
+/// timestamps are forgotten first.
+const MAX_DEDUP_LINES: usize = 1_000_000;
+
+/// Maximal number of intervals (of all labels) the records are counted within, see
+/// [`StatAggregation`].
+const MAX_INTERVALS: usize = 1 << 24;
+
+/// Drops the repeated log lines (see `--dedup`).
+///
+/// Parts of the input are expected to be sorted by time, repeated lines appear when the input goes
+/// back in time (e.g. the next part is a snapshot overlapping the previous one). Within such
+/// overlap the lines older than the current one cannot be repeated anymore, so they are
+/// forgotten. At most [`MAX_DEDUP_LINES`] lines are remembered.
+#[derive(Debug, Default)]
+struct LineDeduplicator {
+	/// Lines seen so far, by their timestamp.
+	seen: BTreeMap<ExtractedNaiveDateTime, HashSet<String>>,
+	/// Number of lines in `seen`.
+	len: usize,
+	/// The latest timestamp seen so far.
+	latest: Option<ExtractedNaiveDateTime>,
+	/// Number of dropped lines.
+	dropped: usize,
+	/// Number of lines forgotten due to [`MAX_DEDUP_LINES`] limit.
+	forgotten: usize,
+}
+
+impl LineDeduplicator {
+	/// Tells if the same line was already seen. Lines without valid timestamp are never
+	/// duplicates.
+	fn is_duplicate(&mut self, line: &str, timestamp_format: &TimestampFormat) -> bool {
+		let Ok((timestamp, _)) = timestamp_format.extract_timestamp(line) else {
+			return false;
+		};
+		if self.latest.is_some_and(|latest| timestamp < latest) {
+			let newer = self.seen.split_off(&timestamp);
+			self.len -= self.seen.values().map(HashSet::len).sum::<usize>();
+			self.seen = newer;
+		}
+		self.latest = self.latest.max(Some(timestamp));
+
+		let duplicate = !self.seen.entry(timestamp).or_default().insert(line.to_string());
+		if duplicate {
+			self.dropped += 1;
+		} else {
+			self.len += 1;
 		}
+		while self.len > MAX_DEDUP_LINES {
+			let Some((_, oldest)) = self.seen.pop_first() else { break };
+			self.len -= oldest.len();
+			self.forgotten += oldest.len();
+		}
+		duplicate
 	}
-	Ok(())
+}
+
+/// Iterator over the lines of the log file.
+///
+/// Unlike [`BufRead::lines`], invalid UTF-8 sequences do not end the iteration: they are replaced
+/// with `U+FFFD` and the number of such lines is counted (see [`Self::report_lossy_lines`]).
+///
+/// Parts of the concatenated input (see `--concat`) are read one after another.
+#[derive(Debug)]
+struct LogLines<R> {
+	reader: R,
+	/// Readers of the remaining parts of the concatenated input.
+	next_readers: VecDeque<R>,
+	buf: Vec<u8>,
+	/// Number of lines which contained invalid UTF-8 sequences.
+	lossy_lines_count: u64,
+}
+
+impl LogLines<InputReader> {
+	fn open(log_file_name: &Path) -> Result<Self, Error> {
+		Ok(Self::new(InputReader::open(log_file_name, None)?))
+	}
+
+	/// Opens the input, which can also be the virtual input of `--concat`.
+	///
+	/// Parts of the virtual input are read in the order of their first timestamps.
+	fn open_input(input: &Path, input_context: &InputFilesContext) -> Result<Self, Error> {
+		let parts = input_context.input_parts(input);
+		if let Some(part) =
+			parts.iter().find(|part| !part.is_file() && CommandInput::parse(part).is_none())
+		{
+			return Err(Error::InvalidInputFile(part.clone(), "Not a regular file".to_string()));
+		}
+		let parts = if parts.len() > 1 {
+			let parts = order_by_first_timestamp(parts, input_context)?;
+			debug!(target:APPV, "Parts of {} in order: {parts:?}", input.display());
+			parts
+		} else {
+			parts
+		};
+		let mut readers = parts
+			.iter()
+			.map(|part| InputReader::open(part, input_context.since()))
+			.collect::<Result<VecDeque<_>, _>>()?;
+		let reader = readers.pop_front().expect("input has at least one part");
+		Ok(Self { next_readers: readers, ..Self::new(reader) })
+	}
+}
+
+impl<R: BufRead> LogLines<R> {
+	fn new(reader: R) -> Self {
+		Self { reader, next_readers: VecDeque::new(), buf: vec![], lossy_lines_count: 0 }
+	}
+
+	/// Warns if any of the lines read so far contained invalid UTF-8 sequences.
+	fn report_lossy_lines(&self, log_file_name: &Path) {
+		if self.lossy_lines_count > 0 {
+			warn!(
+				target:APPV,
+				"{} line(s) of '{}' contained invalid UTF-8 sequences, they were replaced with U+FFFD.",
+				self.lossy_lines_count,
+				log_file_name.display()
+			);
+		}
+	}
+}
+
+impl<R: BufRead> Iterator for LogLines<R> {
+	type Item = io::Result<String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if interrupt::is_interrupted() {
+			return Some(Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted by signal")));
+		}
+		self.buf.clear();
+		loop {
+			match self.reader.read_until(b'\n', &mut self.buf) {
+				Ok(0) => self.reader = self.next_readers.pop_front()?,
+				Ok(_) => break,
+				Err(e) => return Some(Err(e)),
+			}
+		}
+		if self.buf.ends_with(b"\n") {
+			self.buf.pop();
+			if self.buf.ends_with(b"\r") {
+				self.buf.pop();
+			}
+		}
+		let line = match std::str::from_utf8(&self.buf) {
+			Ok(line) => line.to_string(),
+			Err(_) => {
+				self.lossy_lines_count += 1;
+				String::from_utf8_lossy(&self.buf).into_owned()
+			},
+		};
+		Some(Ok(line))
+	}
+}
+
+/// Reader of the log file. Gzip compressed files (`.gz`) are decompressed on the fly.
+///
+/// The [`CommandInput`] (e.g. journal of the systemd unit) is read from the output of the command
+/// (the executable can be overridden with the environment variable, e.g. `PLOX_JOURNALCTL`).
+struct InputReader(Box<dyn BufRead + Send>);
+
+impl InputReader {
+	fn open(log_file_name: &Path, since: Option<&str>) -> Result<Self, Error> {
+		if let Some(command_input) = CommandInput::parse(log_file_name) {
+			let (program, env_var) = command_input.program();
+			let program = std::env::var(env_var).unwrap_or_else(|_| program.to_string());
+			let stdout = std::process::Command::new(&program)
+				.args(command_input.args(since))
+				.stdout(std::process::Stdio::piped())
+				.spawn()
+				.and_then(|child| child.stdout.ok_or_else(|| io::Error::other("no stdout")))
+				.map_err(|e| {
+					Error::InvalidInputFile(
+						log_file_name.to_path_buf(),
+						format!("{program} could not be started: {e}"),
+					)
+				})?;
+			return Ok(Self(Box::new(BufReader::new(stdout))));
+		}
+		let file =
+			File::open(log_file_name).map_err(|e| Error::new_file_io_error(log_file_name, e))?;
+		Ok(Self(if log_file_name.extension().is_some_and(|e| e == "gz") {
+			Box::new(BufReader::new(MultiGzDecoder::new(file)))
+		} else {
+			Box::new(BufReader::new(file))
+		}))
+	}
+}
+
+impl std::fmt::Debug for InputReader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("InputReader")
+	}
+}
+
+impl io::Read for InputReader {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read(buf)
+	}
+}
+
+impl BufRead for InputReader {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		self.0.fill_buf()
+	}
+
+	fn consume(&mut self, amt: usize) {
+		self.0.consume(amt)
+	}
+}
+
+/// Orders the parts of the concatenated input by their first timestamp.
+///
+/// Parts without any valid timestamp are placed at the end, in the given order.
+fn order_by_first_timestamp(
+	parts: Vec<PathBuf>,
+	input_context: &InputFilesContext,
+) -> Result<Vec<PathBuf>, Error> {
+	let timestamp_format = input_context.timestamp_format();
+	let mut parts = parts
+		.into_iter()
+		.map(|part| {
+			let first_timestamp = LogLines::new(InputReader::open(&part, input_context.since())?)
+				.map_while(Result::ok)
+				.find_map(|line| timestamp_format.extract_timestamp(&line).ok().map(|(ts, _)| ts));
+			Ok((first_timestamp.is_none(), first_timestamp, part))
+		})
+		.collect::<Result<Vec<_>, Error>>()?;
+	parts.sort_by_key(|(no_timestamp, first_timestamp, _)| (*no_timestamp, *first_timestamp));
+	Ok(parts.into_iter().map(|(.., part)| part).collect())
+}
+
+/// Lazily extracts the values of a single line from the log file, line by line.
+///
+/// Created with [`SeriesIter::new`], yields the [`DataPoint`] for every matching log line. No CSV
+/// cache files are involved.
+#[derive(Debug)]
+pub struct SeriesIter {
+	line: Option<Line>,
+	processor: LineProcessor,
+	log_lines: LogLines<InputReader>,
+}
+
+impl SeriesIter {
+	/// Opens the `log_file_name` and prepares the extraction for the given `line`.
+	///
+	/// Line's own file name / file id are ignored.
+	pub fn new(
+		line: &Line,
+		log_file_name: &Path,
+		input_context: &InputFilesContext,
+	) -> Result<Self, Error> {
+		let log_lines = LogLines::open_input(log_file_name, input_context)?;
+		let processor = LineProcessor::from_line(
+			line,
+			None,
+			input_context.timestamp_format().clone(),
+			log_file_name.to_path_buf(),
+			input_context.ignore_invalid_timestamps(),
+		)?
+		.with_strict(input_context.strict())
+		.with_number_locale(input_context.number_locale());
+		Ok(Self { line: Some(line.clone()), processor, log_lines })
+	}
+
+	/// Opens the `log_file_name` and prepares the extraction with custom [`LineExtractor`].
+	pub fn from_extractor(
+		extractor: Box<dyn LineExtractor>,
+		log_file_name: &Path,
+		input_context: &InputFilesContext,
+	) -> Result<Self, Error> {
+		let log_lines = LogLines::open_input(log_file_name, input_context)?;
+		let processor = LineProcessor::from_extractor(
+			extractor,
+			None,
+			input_context.timestamp_format().clone(),
+			log_file_name.to_path_buf(),
+			input_context.ignore_invalid_timestamps(),
+		)
+		.with_strict(input_context.strict())
+		.with_number_locale(input_context.number_locale());
+		Ok(Self { line: None, processor, log_lines })
+	}
+
+	/// Line from the graph config the values are extracted for. Not available for custom
+	/// extractors.
+	pub fn line(&self) -> Option<&Line> {
+		self.line.as_ref()
+	}
+
+	/// Log file the values are extracted from.
+	pub fn input_file(&self) -> &Path {
+		&self.processor.input_file_name
+	}
+
+	/// Returns the iterator over `(timestamp, value)` pairs only.
+	pub fn values(self) -> impl Iterator<Item = Result<(NaiveDateTime, f64), Error>> {
+		self.map(|p| p.map(|p| (p.timestamp, p.value)))
+	}
+}
+
+impl Iterator for SeriesIter {
+	type Item = Result<DataPoint, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		for log_line in self.log_lines.by_ref() {
+			let log_line = match log_line {
+				Ok(l) => l,
+				Err(e) => {
+					return Some(Err(Error::new_file_io_error(&self.processor.input_file_name, e)));
+				},
+			};
+			match self.processor.try_match(&log_line) {
+				Ok((_, Some((captures, timestamp)))) => {
+					self.processor.process(captures, timestamp);
+					if let Some(record) = self.processor.records.pop() {
+						return Some(record.into_data_point());
+					}
+				},
+				Ok((_, None)) => {},
+				Err(e) => return Some(Err(e)),
+			}
+		}
+		self.processor.report_warnings();
+		self.log_lines.report_lossy_lines(&self.processor.input_file_name);
+		None
+	}
+}
+
+/// Extracts the values of every line in the config directly from log files, without involving
+/// CSV cache files.
+///
+/// One [`Series`] is returned for every line, in config order.
+pub fn extract_series(
+	config: &ResolvedGraphConfig,
+	input_context: &InputFilesContext,
+) -> Result<Vec<Series>, Error> {
+	// input_log_file -> (line index, processor)
+	let mut processors: BTreeMap<PathBuf, Vec<(usize, LineProcessor)>> = Default::default();
+	for (i, line) in config.all_lines().enumerate() {
+		let processor = LineProcessor::from_line(
+			&line.line,
+			None,
+			input_context.timestamp_format().clone(),
+			line.source_file_name().clone(),
+			input_context.ignore_invalid_timestamps(),
+		)?
+		.with_strict(input_context.strict())
+		.with_number_locale(input_context.number_locale());
+		processors
+			.entry(line.source_file_name().clone())
+			.or_default()
+			.push((i, processor));
+	}
+
+	let mut records: HashMap<usize, Vec<LogRecord>> = HashMap::new();
+	for (log_file_name, mut processors) in processors {
+		let mut log_lines = LogLines::open_input(&log_file_name, input_context)?;
+		for line in log_lines.by_ref() {
+			let line = line.map_err(|e| Error::new_file_io_error(&log_file_name, e))?;
+			for (_, processor) in &mut processors {
+				if let (_, Some((captures, timestamp))) = processor.try_match(&line)? {
+					processor.process(captures, timestamp);
+				}
+			}
+		}
+		processors.iter().for_each(|(_, p)| p.report_warnings());
+		log_lines.report_lossy_lines(&log_file_name);
+		records.extend(processors.into_iter().map(|(i, p)| (i, p.records)));
+	}
+
+	config
+		.all_lines()
+		.enumerate()
+		.map(|(i, line)| {
+			let points = records
+				.remove(&i)
+				.unwrap_or_default()
+				.into_iter()
+				.map(LogRecord::into_data_point)
+				.collect::<Result<Vec<_>, Error>>()?;
+			Ok(Series {
+				line: line.line.clone(),
+				input_file: line.source_file_name().clone(),
+				points,
+			})
+		})
+		.collect()
+}
+
+pub fn regex_match_preview(
+	config: MatchPreviewConfig,
+	context: SharedMatchPreviewContext,
+	verbose_level: u8,
+) -> Result<(), Error> {
+	let env_filter = if verbose_level == 2 {
+		EnvFilter::new(format!("warn,{}=trace", MATCH_PREVIEW))
+	} else {
+		EnvFilter::new(format!("warn,{}=debug", MATCH_PREVIEW))
+	};
+
+	let preview_layer = tracing_subscriber::fmt::layer()
+		.without_time()
+		.with_target(false)
+		.with_level(true);
+	let preview_subscriber = Registry::default().with(preview_layer.with_filter(env_filter));
+
+	tracing::subscriber::with_default(preview_subscriber, || {
+		regex_match_preview_inner(config, context)
+	})
+}
+
+pub fn regex_match_preview_inner(
+	config: MatchPreviewConfig,
+	context: SharedMatchPreviewContext,
+) -> Result<(), Error> {
+	let mut processor = LineProcessor::from_data_source(
+		config.data_source.clone(),
+		None,
+		context.timestamp_format().clone(),
+		context.input.clone(),
+		false,
+	)?;
+
+	let mut log_lines = LogLines::open(&context.input)?;
+	let mut matched_count = 0;
+
+	info!(target:MATCH_PREVIEW, "input file: {}", context.input.display());
+	if let Some(guard) = config.data_source.guard().as_ref() {
+		info!(target:MATCH_PREVIEW, "guard: {guard}")
+	};
+	info!(target:MATCH_PREVIEW, "regex pattern: {}", config.data_source.regex_pattern());
+	info!(target:MATCH_PREVIEW, "timestamp pattern: {:?}", context.timestamp_format);
+
+	for line in log_lines.by_ref() {
+		let line = line.map_err(|e| Error::new_file_io_error(&context.input, e))?;
+		let (guard_matched, captured) = processor.try_match(&line)?;
+		if guard_matched {
+			if let Some((captures, timestamp)) = captured {
+				processor.process(captures, timestamp);
+				info!(target:MATCH_PREVIEW, "matched: {:#?}", processor.records.last());
+			}
+
+			matched_count += 1;
+		}
+		if matched_count >= context.count {
+			break;
+		}
+	}
+
+	log_lines.report_lossy_lines(&context.input);
+
+	if let (0, Some(guard)) = (matched_count, config.data_source.guard()) {
+		warn!(target:MATCH_PREVIEW, "No lines matched against guard: '{:?}'", guard);
+		warn!(target:MATCH_PREVIEW, "Is it correctly configured?");
+	}
+	Ok(())
 }
 
 impl ResolvedGraphConfig {
-	pub fn resolve_data_points_count(&mut self) -> Result<(), Error> {
+	/// Sets the data points count of every line.
+	///
+	/// Count (and time range) is taken from `summaries` of freshly written (or cached, if recorded
+	/// in the cache manifest) CSV files, other CSV files are read.
+	///
+	/// Lines with no matches are reported, with diagnostics based on `match_stats` (if the logs
+	/// were processed in this run).
+	fn resolve_data_points_count(
+		&mut self,
+		summaries: &HashMap<PathBuf, CsvSummary>,
+		match_stats: &HashMap<PathBuf, MatchStats>,
+		timestamp_format: &TimestampFormat,
+	) -> Result<(), Error> {
 		for panel in &mut self.panels {
 			for line in &mut panel.lines {
-				let file_path = line.expect_shared_csv_filename();
-				let file =
-					File::open(&file_path).map_err(|e| Error::new_file_io_error(&file_path, e))?;
-				let reader = io::BufReader::new(file);
-
-				let data_points_count = reader.lines().count() - 1;
+				let file_path = line.resolved_csv_filename()?;
+				let data_points_count = if let Some((count, range)) = summaries.get(&file_path) {
+					if let Some((start, end)) = range {
+						line.set_time_range(*start, *end);
+					}
+					*count
+				} else {
+					let file = File::open(&file_path)
+						.map_err(|e| Error::new_file_io_error(&file_path, e))?;
+					io::BufReader::new(file).lines().count() - 1
+				};
 				line.set_data_points_count(data_points_count);
 
 				let log_file_name = line.source_file_name();
@@ -716,6 +2620,9 @@ impl ResolvedGraphConfig {
 						regex = line.regex_pattern(),
 						"No matches."
 					);
+					if let Some(stats) = match_stats.get(&file_path) {
+						report_no_matches(line, stats, timestamp_format);
+					}
 				} else {
 					debug!(
 						target:APPV,
@@ -725,7 +2632,7 @@ impl ResolvedGraphConfig {
 						line.guard().clone().map(|v| format!(", guard: {v}")).unwrap_or_default(),
 						line.raw_pattern(),
 						line.regex_pattern(),
-						line.expect_shared_csv_filename().display()
+						line.resolved_csv_filename()?.display()
 					);
 				}
 			}
@@ -734,9 +2641,189 @@ impl ResolvedGraphConfig {
 	}
 }
 
-/// Converts value+unit to milliseconds.
-fn normalize_value(value: &str, unit: &str) -> Option<f64> {
-	let base: f64 = value.parse().ok()?;
+/// Explains why the line has no matches, based on the counters of processed log lines.
+///
+/// Points to the first failing stage (guard, timestamp, regex) and suggests the `match-preview`
+/// invocation reproducing the problem.
+fn report_no_matches(line: &ResolvedLine, stats: &MatchStats, timestamp_format: &TimestampFormat) {
+	let guard = line.guard().as_ref();
+	match guard {
+		Some(guard) => warn!(
+			target:APPV,
+			"  {} lines read, {} contained the guard '{guard}', {} of them had a valid timestamp.",
+			stats.lines,
+			stats.guard_passed,
+			stats.timestamp_parsed
+		),
+		None => warn!(
+			target:APPV,
+			"  {} lines read, {} had a valid timestamp.",
+			stats.lines,
+			stats.timestamp_parsed
+		),
+	}
+
+	let hint = if stats.lines == 0 {
+		"The input file is empty.".to_string()
+	} else if stats.guard_passed == 0 {
+		"Check the guard, it is matched as a plain (case-sensitive) substring.".to_string()
+	} else if stats.timestamp_parsed == 0 {
+		format!(
+			"Check the timestamp format ('{}') or use '--ignore-invalid-timestamps'.",
+			timestamp_format.as_str()
+		)
+	} else if stats.timestamp_parsed == stats.outside_time_window {
+		"No line with a valid timestamp is within the '--time-range' window.".to_string()
+	} else if let DataSource::JsonField { path, .. } = &line.line.data_source {
+		format!("Check the path, no JSON log line contained the value at '{path}'.")
+	} else if let DataSource::LogfmtField { key, .. } = &line.line.data_source {
+		format!("Check the key, no logfmt log line contained the '{key}=' pair.")
+	} else {
+		format!("Check the pattern, no line matched the regex '{}'.", line.regex_pattern())
+	};
+	warn!(target:APPV, "  {hint}");
+
+	let args = ["match-preview".to_string(), "--input".to_string()]
+		.into_iter()
+		.chain([line.source_file_name().display().to_string()])
+		.chain(["--timestamp-format".to_string(), timestamp_format.as_str().to_string()])
+		.chain(line.line.data_source.cli_args())
+		.map(|arg| shell_quote(&arg))
+		.collect::<Vec<_>>();
+	warn!(target:APPV, "  Try: plox {} --verbose", args.join(" "));
+}
+
+/// Quotes the argument for the shell, if needed.
+pub(crate) fn shell_quote(arg: &str) -> String {
+	let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:%".contains(c);
+	if !arg.is_empty() && arg.chars().all(is_safe) {
+		arg.to_string()
+	} else {
+		format!("'{}'", arg.replace('\'', r"'\''"))
+	}
+}
+
+/// Guard of the JSON field: `<PATH>=<VALUE>` or `<PATH>` (see [`DataSource::JsonField`]).
+struct JsonFieldGuard<'a> {
+	path: &'a str,
+	value: Option<&'a str>,
+}
+
+impl<'a> JsonFieldGuard<'a> {
+	fn parse(guard: &'a str) -> Self {
+		match guard.split_once('=') {
+			Some((path, value)) => Self { path, value: Some(value) },
+			None => Self { path: guard, value: None },
+		}
+	}
+
+	/// Text which must be contained in the log line for the guard to match.
+	fn prefilter_text(&self) -> &'a str {
+		self.value.unwrap_or_else(|| self.path.rsplit('.').next().unwrap_or(self.path))
+	}
+
+	fn matches(&self, object: &serde_json::Value) -> bool {
+		match (json_value_at(object, self.path), self.value) {
+			(None | Some(serde_json::Value::Null), _) => false,
+			(Some(_), None) => true,
+			(Some(serde_json::Value::String(actual)), Some(expected)) => actual == expected,
+			(Some(actual), Some(expected)) => {
+				expected.parse::<serde_json::Value>().is_ok_and(|expected| *actual == expected)
+			},
+		}
+	}
+}
+
+/// Returns the value at the dotted path (e.g. `fields.duration`, `items.0`) of the JSON value.
+fn json_value_at<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+	path.split('.').try_fold(value, |value, key| match value {
+		serde_json::Value::Object(map) => map.get(key),
+		serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+		_ => None,
+	})
+}
+
+/// Extracts the raw value at the dotted path of the JSON object starting at the first `{` of the
+/// log line.
+///
+/// Numbers in strings can be followed by the unit (e.g. `"12ms"`), which is returned separately if
+/// `split_unit` is set. Returns `None` if the line is not JSON, the guard does not match or the
+/// value is missing.
+fn extract_json_field(
+	line: &str,
+	guard: Option<&str>,
+	path: &str,
+	split_unit: bool,
+) -> Option<(String, Option<String>)> {
+	let json = &line[line.find('{')?..];
+	let object = serde_json::Deserializer::from_str(json)
+		.into_iter::<serde_json::Value>()
+		.next()?
+		.ok()?;
+	if guard.is_some_and(|guard| !JsonFieldGuard::parse(guard).matches(&object)) {
+		return None;
+	}
+	match json_value_at(&object, path)? {
+		serde_json::Value::Null => None,
+		serde_json::Value::Bool(value) => Some((u8::from(*value).to_string(), None)),
+		serde_json::Value::String(value) if split_unit => Some(split_value_unit(value)),
+		serde_json::Value::String(value) => Some((value.clone(), None)),
+		value => Some((value.to_string(), None)),
+	}
+}
+
+/// Splits the textual value into the number and the unit following it (e.g. `12ms`).
+fn split_value_unit(value: &str) -> (String, Option<String>) {
+	let number = value.trim_end_matches(char::is_alphabetic);
+	let unit = &value[number.len()..];
+	(number.trim().to_string(), (!unit.is_empty()).then(|| unit.to_string()))
+}
+
+/// Returns the (unquoted and unescaped) value of the `key=value` pair of the logfmt line.
+///
+/// Values can be quoted with `"`, quoted values can contain `\"`, `\\`, `\n` and `\t` escapes.
+/// Tokens which are not pairs (e.g. plain words) are skipped. Returns `None` if the key is not
+/// present.
+fn logfmt_value(line: &str, key: &str) -> Option<String> {
+	let mut chars = line.chars().peekable();
+	loop {
+		while chars.next_if(|c| c.is_whitespace()).is_some() {}
+		chars.peek()?;
+		let token_key = std::iter::from_fn(|| chars.next_if(|c| *c != '=' && !c.is_whitespace()))
+			.collect::<String>();
+		if chars.next_if_eq(&'=').is_none() {
+			continue;
+		}
+		let value = if chars.next_if_eq(&'"').is_some() {
+			let mut value = String::new();
+			while let Some(c) = chars.next() {
+				match c {
+					'"' => break,
+					'\\' => match chars.next() {
+						Some('n') => value.push('\n'),
+						Some('t') => value.push('\t'),
+						Some(c) => value.push(c),
+						None => break,
+					},
+					c => value.push(c),
+				}
+			}
+			value
+		} else {
+			std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect()
+		};
+		if token_key == key {
+			return Some(value);
+		}
+	}
+}
+
+/// Converts value+unit to milliseconds. Values formatted in `number_locale` are converted first.
+fn normalize_value(value: &str, unit: &str, number_locale: Option<NumberLocale>) -> Option<f64> {
+	let base: f64 = match number_locale {
+		Some(locale) => locale.normalize(value).parse().ok()?,
+		None => value.parse().ok()?,
+	};
 	match unit {
 		"s" => Some(base * 1000.0),
 		"ms" => Some(base),
@@ -749,7 +2836,14 @@ fn normalize_value(value: &str, unit: &str) -> Option<f64> {
 
 impl ProcessingState {
 	fn new() -> Self {
-		Self { count: 0, last_timestamp: None }
+		Self {
+			count: 0,
+			last_timestamp: None,
+			current_label: None,
+			current_text: None,
+			current_unit: None,
+			line_number: 0,
+		}
 	}
 
 	fn next_count(&mut self) -> u64 {
@@ -766,7 +2860,7 @@ impl ProcessingState {
 	}
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ExtractedNaiveDateTime {
 	DateTime(NaiveDateTime),
 	Time(NaiveTime),
@@ -834,13 +2928,75 @@ impl TimestampFormat {
 	}
 }
 
+/// Reads the external events (see `--annotations`) from the CSV file of `timestamp,label`
+/// records.
+///
+/// Timestamps are parsed with the given format, the first record is skipped if its timestamp
+/// cannot be parsed (header).
+pub fn read_annotations_file(
+	path: &Path,
+	format: &TimestampFormat,
+) -> Result<Vec<(NaiveDateTime, String)>, Error> {
+	let file = File::open(path).map_err(|e| Error::new_file_io_error(path, e))?;
+	let mut rdr = csv::ReaderBuilder::new()
+		.has_headers(false)
+		.flexible(true)
+		.trim(csv::Trim::All)
+		.from_reader(file);
+	let time_only_date = NaiveDate::parse_from_str(TIME_ONLY_RECORD_DATE, RECORD_DATE_FORMAT)
+		.expect("date constant is valid");
+	let mut annotations = vec![];
+	for (i, record) in rdr.records().enumerate() {
+		let record = record.map_err(|e| Error::CsvParseError(path.to_path_buf(), e))?;
+		let timestamp = match format.extract_timestamp(record.get(0).unwrap_or_default()) {
+			Ok((ExtractedNaiveDateTime::DateTime(timestamp), _)) => timestamp,
+			Ok((ExtractedNaiveDateTime::Time(time), _)) => time_only_date.and_time(time),
+			Err(_) if i == 0 => continue,
+			Err(_) => {
+				return Err(Error::TimestampExtractionFailure(
+					path.to_path_buf(),
+					format.clone(),
+					record.iter().collect::<Vec<_>>().join(","),
+				));
+			},
+		};
+		annotations.push((timestamp, record.iter().skip(1).collect::<Vec<_>>().join(",")));
+	}
+	Ok(annotations)
+}
+
+/// Value of `--cache-dir` selecting the user cache directory (see [`auto_cache_root`]).
+pub const AUTO_CACHE_DIR: &str = "auto";
+
+/// Root of the cache files in the user cache directory: `$XDG_CACHE_HOME/plox`, or
+/// `$HOME/.cache/plox` if `XDG_CACHE_HOME` is not set (or is not an absolute path, as required by
+/// the XDG Base Directory specification).
+///
+/// `None` is returned if neither of the variables is set.
+fn auto_cache_root(var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+	let xdg_cache_home = var("XDG_CACHE_HOME").map(PathBuf::from).filter(|p| p.is_absolute());
+	let cache_home =
+		xdg_cache_home.or_else(|| var("HOME").map(|h| Path::new(&h).join(".cache")))?;
+	Some(cache_home.join("plox"))
+}
+
 impl InputFilesContext {
 	/// Returns the configured root directory for storing cache files, if provided by the user.
 	///
-	/// This corresponds to the `--cache-dir` CLI option. If `None`, per-log `.plox/` directories
-	/// will be used instead. The returned path does not include any log-specific subdirectories.
-	fn get_cache_root(&self) -> &Option<PathBuf> {
-		self.cache_dir()
+	/// This corresponds to the `--cache-dir` CLI option, `auto` is resolved into the user cache
+	/// directory. If `None`, per-log `.plox/` directories will be used instead. The returned path
+	/// does not include any log-specific subdirectories.
+	fn get_cache_root(&self) -> Option<PathBuf> {
+		match self.cache_dir() {
+			Some(dir) if dir.as_os_str() == AUTO_CACHE_DIR => {
+				let root = auto_cache_root(|name| std::env::var(name).ok());
+				if root.is_none() {
+					warn!(target:APPV, "Neither XDG_CACHE_HOME nor HOME is set, using .plox/ cache directories.");
+				}
+				root
+			},
+			dir => dir.clone(),
+		}
 	}
 
 	/// Returns the directory where the cache file for a given log file should be stored.
@@ -855,60 +3011,210 @@ impl InputFilesContext {
 	///   log: `./logs/debug.log`
 	///   result: `./logs/.plox/`
 	///
+	/// If the `.plox/` directory cannot be written (e.g. read-only mount), the path of the log file
+	/// is reproduced inside the fallback cache root instead (with a warning), see
+	/// [`fallback_cache_root`].
+	///
 	/// The log file must exist and be canonicalizable; otherwise this function returns an error.
 	pub fn get_cache_dir(&self, log_file: &Path) -> Result<PathBuf, Error> {
-		let log_file_path =
-			log_file.canonicalize().map_err(|e| Error::new_file_io_error(log_file, e))?; // fails if file doesn't exist
+		// The virtual input of `--concat` does not exist, its cache is placed next to the first
+		// part (in the same directory as the virtual input).
+		let log_file = &self.input_parts(log_file)[0];
+		// Command inputs (e.g. the journal) are not files, their cache is placed in the current
+		// directory.
+		let log_file_path = match CommandInput::parse(log_file) {
+			Some(command_input) => {
+				Path::new(".").canonicalize().map(|cwd| cwd.join(command_input.file_name()))
+			},
+			None => log_file.canonicalize(), // fails if file doesn't exist
+		}
+		.map_err(|e| Error::new_file_io_error(log_file, e))?;
 		self.get_cache_dir_inner(&log_file_path)
 	}
 
 	fn get_cache_dir_inner(&self, log_file_path: &Path) -> Result<PathBuf, Error> {
 		assert!(log_file_path.is_absolute());
 		if let Some(root) = self.get_cache_root() {
-			// Strip leading `/` to build a relative path under the root
-			let relative = log_file_path.strip_prefix("/").unwrap_or(log_file_path);
-			Ok(root.join(relative).parent().unwrap_or(root).to_path_buf())
+			Ok(mirrored_cache_dir(&root, log_file_path))
 		} else {
 			let log_dir = log_file_path.parent().unwrap_or_else(|| Path::new("."));
-			Ok(log_dir.join(".plox"))
+			let cache_dir = log_dir.join(".plox");
+			let mut checked = CHECKED_CACHE_DIRS.lock().unwrap_or_else(|e| e.into_inner());
+			let (writable, first_check) = match checked.get(&cache_dir) {
+				Some(writable) => (*writable, false),
+				None => {
+					let writable = is_writable_dir(&cache_dir);
+					checked.insert(cache_dir.clone(), writable);
+					(writable, true)
+				},
+			};
+			if writable {
+				return Ok(cache_dir);
+			}
+			let fallback = mirrored_cache_dir(&fallback_cache_root()?, log_file_path);
+			if first_check {
+				warn!(target:APPV, "Cache directory {} is not writable, using {} instead.",
+					cache_dir.display(), fallback.display());
+			}
+			Ok(fallback)
 		}
 	}
 }
 
+/// `.plox/` directories already probed for writability, so each directory is probed (and the
+/// fallback reported) only once.
+static CHECKED_CACHE_DIRS: Mutex<BTreeMap<PathBuf, bool>> = Mutex::new(BTreeMap::new());
+
+/// Root of the cache directories used if `.plox/` next to the log file is not writable.
+///
+/// This is the user cache directory (as for `--cache-dir auto`), or the per-user `plox-<uid>`
+/// directory of the system temp directory, accessible only by the user.
+fn fallback_cache_root() -> Result<PathBuf, Error> {
+	match auto_cache_root(|name| std::env::var(name).ok()) {
+		Some(root) => Ok(root),
+		None => private_temp_dir(),
+	}
+}
+
+/// Returns the `plox-<uid>` directory of the system temp directory, creating it with `0700` mode.
+///
+/// An existing directory is only used if it is owned by the user and not accessible by others.
+#[cfg(unix)]
+fn private_temp_dir() -> Result<PathBuf, Error> {
+	use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+	// SAFETY: `getuid` is always successful and has no side effects.
+	let uid = unsafe { libc::getuid() };
+	let dir = std::env::temp_dir().join(format!("plox-{uid}"));
+	match fs::DirBuilder::new().mode(0o700).create(&dir) {
+		Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+			return Err(Error::new_file_io_error(&dir, e));
+		},
+		_ => {},
+	}
+	let metadata = fs::symlink_metadata(&dir).map_err(|e| Error::new_file_io_error(&dir, e))?;
+	if !metadata.is_dir() || metadata.uid() != uid || metadata.permissions().mode() & 0o077 != 0 {
+		let e = io::Error::new(
+			io::ErrorKind::PermissionDenied,
+			"not a directory accessible only by the current user",
+		);
+		return Err(Error::new_file_io_error(&dir, e));
+	}
+	Ok(dir)
+}
+
+#[cfg(not(unix))]
+fn private_temp_dir() -> Result<PathBuf, Error> {
+	Ok(std::env::temp_dir().join("plox"))
+}
+
+/// Directory of the cache files of the log file, reproducing the (absolute) path of the log file
+/// inside the `root` directory.
+fn mirrored_cache_dir(root: &Path, log_file_path: &Path) -> PathBuf {
+	// Strip leading `/` to build a relative path under the root
+	let relative = log_file_path.strip_prefix("/").unwrap_or(log_file_path);
+	root.join(relative).parent().unwrap_or(root).to_path_buf()
+}
+
+/// Tells if files can be created in the directory. The directory is created if missing.
+fn is_writable_dir(dir: &Path) -> bool {
+	if fs::create_dir_all(dir).is_err() {
+		return false;
+	}
+	let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+	let writable = File::create(&probe).is_ok();
+	let _ = fs::remove_file(&probe);
+	writable
+}
+
+/// Single histogram bucket covering `[start, end)` range.
+#[derive(Debug, PartialEq)]
+pub struct HistoBucket {
+	pub start: f64,
+	pub end: f64,
+	pub count: u64,
+}
+
 struct PloxHisto {
-	histogram: histo_fp::Histogram,
+	buckets: Vec<HistoBucket>,
 	width: Option<usize>,
 	precision: Option<usize>,
 }
 
 impl PloxHisto {
+	/// Builds the histogram with equal-width buckets.
 	pub fn with_buckets(
+		values: &[f64],
 		num_buckets: u64,
 		width: Option<usize>,
 		precision: Option<usize>,
 	) -> PloxHisto {
-		PloxHisto {
-			histogram: histo_fp::Histogram::with_buckets(num_buckets, None),
-			width,
-			precision,
-		}
+		let mut histogram = histo_fp::Histogram::with_buckets(num_buckets, None);
+		values.iter().for_each(|x| histogram.add(*x));
+		let buckets = histogram
+			.buckets()
+			.map(|b| HistoBucket { start: b.start(), end: b.end(), count: b.count() })
+			.collect();
+		PloxHisto { buckets, width, precision }
 	}
-}
 
-use std::cmp;
-use std::fmt;
-impl fmt::Display for PloxHisto {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		use std::fmt::Write;
-
-		let width = self.width.unwrap_or(10);
-		let precision = self.precision.unwrap_or(4);
+	/// Builds the histogram with exponentially growing buckets.
+	///
+	/// Bucket edges are spread geometrically between the smallest positive value and the
+	/// maximal value. Non-positive values (if any) are counted in an extra leading bucket. If
+	/// there are no positive values, equal-width buckets are used.
+	pub fn with_log_buckets(
+		values: &[f64],
+		num_buckets: u64,
+		width: Option<usize>,
+		precision: Option<usize>,
+	) -> PloxHisto {
+		let positive = values.iter().copied().filter(|v| *v > 0.0);
+		let (Some(low), Some(high)) =
+			(positive.clone().reduce(f64::min), positive.clone().reduce(f64::max))
+		else {
+			return Self::with_buckets(values, num_buckets, width, precision);
+		};
+
+		let num_buckets = num_buckets.max(1) as usize;
+		let ratio = (high / low).powf(1.0 / num_buckets as f64);
+		let mut buckets = (0..num_buckets)
+			.map(|i| HistoBucket {
+				start: low * ratio.powi(i as i32),
+				end: low * ratio.powi(i as i32 + 1),
+				count: 0,
+			})
+			.collect::<Vec<_>>();
+		for v in positive {
+			let index = if ratio > 1.0 { ((v / low).ln() / ratio.ln()) as usize } else { 0 };
+			buckets[index.min(num_buckets - 1)].count += 1;
+		}
+
+		let non_positive = values.iter().copied().filter(|v| *v <= 0.0);
+		if let Some(min) = non_positive.clone().reduce(f64::min) {
+			buckets.insert(
+				0,
+				HistoBucket { start: min, end: low, count: non_positive.count() as u64 },
+			);
+		}
 
-		if self.histogram.buckets().next().is_none() {
+		PloxHisto { buckets, width, precision }
+	}
+}
+
+use std::cmp;
+use std::fmt;
+impl fmt::Display for PloxHisto {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		use std::fmt::Write;
+
+		let width = self.width.unwrap_or(10);
+		let precision = self.precision.unwrap_or(4);
+
+		if self.buckets.is_empty() {
 			return Ok(());
 		}
 
-		let max_bucket_count = self.histogram.buckets().map(|b| b.count()).fold(0, cmp::max);
+		let max_bucket_count = self.buckets.iter().map(|b| b.count).fold(0, cmp::max);
 
 		const WIDTH: u64 = 50;
 		let count_per_char = cmp::max(max_bucket_count / WIDTH, 1);
@@ -918,19 +3224,19 @@ impl fmt::Display for PloxHisto {
 
 		let mut count_str = String::new();
 
-		let widest_count = self.histogram.buckets().fold(0, |n, b| {
+		let widest_count = self.buckets.iter().fold(0, |n, b| {
 			count_str.clear();
-			write!(&mut count_str, "{}", b.count()).unwrap();
+			write!(&mut count_str, "{}", b.count).unwrap();
 			cmp::max(n, count_str.len())
 		});
 
 		let mut end_str = String::new();
-		let widest_range = self.histogram.buckets().fold(0, |n, b| {
+		let widest_range = self.buckets.iter().fold(0, |n, b| {
 			end_str.clear();
 			write!(
 				&mut end_str,
 				"{:width$.precision$}",
-				b.end(),
+				b.end,
 				width = width,
 				precision = precision
 			)
@@ -940,12 +3246,12 @@ impl fmt::Display for PloxHisto {
 
 		let mut start_str = String::with_capacity(widest_range);
 
-		for bucket in self.histogram.buckets() {
+		for bucket in &self.buckets {
 			start_str.clear();
 			write!(
 				&mut start_str,
 				"{:width$.precision$}",
-				bucket.start(),
+				bucket.start,
 				width = width,
 				precision = precision
 			)
@@ -958,7 +3264,7 @@ impl fmt::Display for PloxHisto {
 			write!(
 				&mut end_str,
 				"{:width$.precision$}",
-				bucket.end(),
+				bucket.end,
 				width = width,
 				precision = precision,
 			)
@@ -968,13 +3274,13 @@ impl fmt::Display for PloxHisto {
 			}
 
 			count_str.clear();
-			write!(&mut count_str, "{}", bucket.count()).unwrap();
+			write!(&mut count_str, "{}", bucket.count).unwrap();
 			for _ in 0..widest_count - count_str.len() {
 				count_str.insert(0, ' ');
 			}
 
 			write!(f, "{} - {} [ {} ]: ", start_str, end_str, count_str)?;
-			for _ in 0..bucket.count() / count_per_char {
+			for _ in 0..bucket.count / count_per_char {
 				write!(f, "∎")?;
 			}
 			writeln!(f)?;
@@ -990,21 +3296,14 @@ impl ResolvedLine {
 		start: NaiveDateTime,
 		end: NaiveDateTime,
 	) -> Result<bool, Error> {
-		let filename = self.expect_shared_csv_filename();
+		let filename = self.resolved_csv_filename()?;
 		let mut rdr = csv::Reader::from_path(&filename)
 			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
 		for result in rdr.deserialize() {
 			let record: LogRecord =
 				result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
 
-			//todo: clean up date
-			let record_ts = NaiveDateTime::new(
-				NaiveDate::parse_from_str(
-					&record.date.expect("date is always written into csv"),
-					RECORD_DATE_FORMAT,
-				)?,
-				NaiveTime::parse_from_str(&record.time, RECORD_TIME_FORMAT)?,
-			);
+			let record_ts = record.timestamp()?;
 
 			if record_ts >= start && record_ts < end {
 				return Ok(true);
@@ -1016,39 +3315,358 @@ impl ResolvedLine {
 		}
 		Ok(false)
 	}
-}
 
-pub fn display_stats(
-	config: &ResolvedGraphConfig,
-	buckets_count: u64,
-	width: Option<usize>,
-	precision: Option<usize>,
-) -> Result<(), Error> {
-	let lines_count = config.all_lines_count();
+	/// Returns the unit of the values stored in the CSV file, if the line keeps the original units.
+	///
+	/// The unit of the first record is used, `None` is returned if no unit was captured.
+	pub fn values_unit(&self) -> Result<Option<String>, Error> {
+		if !self.original_units() {
+			return Ok(None);
+		}
+		let filename = self.resolved_csv_filename()?;
+		let mut rdr = csv::Reader::from_path(&filename)
+			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+		let record: Option<LogRecord> = rdr
+			.deserialize()
+			.next()
+			.transpose()
+			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+		Ok(record.and_then(|r| r.unit).filter(|unit| !unit.is_empty()))
+	}
 
-	for (i, line) in config.all_lines().enumerate() {
-		let filename = line.expect_shared_csv_filename();
+	/// Returns the timestamps and texts of the annotations (optionally limited to the given time
+	/// range), see [`DataSource::Annotation`].
+	pub fn annotations(
+		&self,
+		time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+	) -> Result<Vec<(NaiveDateTime, String)>, Error> {
+		let filename = self.resolved_csv_filename()?;
 		let mut rdr = csv::Reader::from_path(&filename)
 			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
-		let mut values: Vec<f64> = vec![];
+		let mut annotations = Vec::new();
 		for result in rdr.deserialize() {
 			let record: LogRecord =
 				result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+			let record_ts = record.timestamp()?;
+			if time_range.is_some_and(|(start, end)| record_ts < start || record_ts > end) {
+				continue;
+			}
+			annotations.push((record_ts, record.text.unwrap_or_default()));
+		}
+		Ok(annotations)
+	}
 
-			match &line.line.data_source {
-				DataSource::FieldValue { .. } => values.push(record.value),
-				DataSource::EventDelta { .. } => {
-					record.diff.inspect(|v| values.push(*v));
-				},
-				_ => {
-					unreachable!("this is bug.");
-				},
+	/// Returns the range of the plotted values (optionally limited to the given time range).
+	///
+	/// If `positive_only` is set, non-positive values (which cannot be drawn on the log scale) are
+	/// skipped.
+	pub fn plotted_values_range(
+		&self,
+		time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+		positive_only: bool,
+	) -> Result<Option<(f64, f64)>, Error> {
+		Ok(self
+			.plotted_values(time_range)?
+			.into_iter()
+			.filter(|v| !positive_only || *v > 0.0)
+			.fold(None, |range, value| {
+				Some(range.map_or((value, value), |(min, max)| (min.min(value), max.max(value))))
+			}))
+	}
+
+	/// Returns the min, mean, p95 and max of the plotted values (optionally limited to the given
+	/// time range), or `None` if there are no values.
+	pub fn plotted_values_summary(
+		&self,
+		time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+	) -> Result<Option<[(&'static str, f64); 4]>, Error> {
+		let values = self.plotted_values(time_range)?;
+		if values.is_empty() {
+			return Ok(None);
+		}
+		let p95 = Data::new(values.clone()).quantile(0.95);
+		Ok(Some([
+			("min", Statistics::min(&values)),
+			("mean", Statistics::mean(&values)),
+			("p95", p95),
+			("max", Statistics::max(&values)),
+		]))
+	}
+
+	/// Returns the selected `stat` metrics (see [`compute_metrics`]) of the plotted values
+	/// (optionally limited to the given time range), or `None` if there are no values.
+	pub fn plotted_values_stats(
+		&self,
+		time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+		metrics: &[StatMetric],
+		percentiles: &[f64],
+	) -> Result<Option<Vec<(String, f64)>>, Error> {
+		let values = self.plotted_values(time_range)?;
+		Ok((!values.is_empty()).then(|| compute_metrics(&values, metrics, percentiles)))
+	}
+
+	/// Returns the finite plotted values (optionally limited to the given time range).
+	fn plotted_values(
+		&self,
+		time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+	) -> Result<Vec<f64>, Error> {
+		let filename = self.resolved_csv_filename()?;
+		let mut rdr = csv::Reader::from_path(&filename)
+			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+		let mut values = Vec::new();
+		for result in rdr.deserialize() {
+			let record: LogRecord =
+				result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+			if let Some((start, end)) = time_range {
+				let record_ts = record.timestamp()?;
+				if record_ts < start || record_ts > end {
+					continue;
+				}
+			}
+			let value = match self.line.data_source {
+				DataSource::EventCount { .. } => Some(record.count as f64),
+				DataSource::EventDelta(_) => record.diff,
+				DataSource::FieldValue(_)
+				| DataSource::JsonField { .. }
+				| DataSource::LogfmtField { .. }
+				| DataSource::CsvFile(_)
+				| DataSource::EventValue { .. } => Some(record.value),
+				// Annotations are drawn as labels, their values are not plotted.
+				DataSource::Annotation { .. } => None,
 			};
+			values.extend(value.filter(|v| v.is_finite()));
 		}
-		let mut h = PloxHisto::with_buckets(buckets_count, width, precision);
-		values.iter().for_each(|x| {
-			h.histogram.add(*x);
-		});
+		Ok(values)
+	}
+}
+
+impl ResolvedPanel {
+	/// Returns the units of the lines drawn on given axis which keep the original units (see
+	/// [`crate::graph_config::LineParams::value_units`]), e.g. `ms` or `B, ms`.
+	pub fn axis_units(&self, axis: YAxis) -> Result<Option<String>, Error> {
+		let mut units = Vec::new();
+		for line in &self.lines {
+			if line.line.params.yaxis.unwrap_or(YAxis::Y) != axis {
+				continue;
+			}
+			match line.values_unit()? {
+				Some(unit) if !units.contains(&unit) => units.push(unit),
+				_ => {},
+			}
+		}
+		Ok((!units.is_empty()).then(|| units.join(", ")))
+	}
+
+	/// Returns the text lines of the stats box (see
+	/// [`crate::graph_config::PanelParams::stats_box`]): the summary of
+	/// plotted values of every line, e.g. `x: min 1 mean 2.5 p95 3.9 max 4`.
+	///
+	/// Lines without data points in the panel time range are skipped.
+	pub fn stats_box(&self, has_multiple_input_files: bool) -> Result<Vec<String>, Error> {
+		let mut text_lines = Vec::new();
+		for line in &self.lines {
+			if line.line.data_source.is_annotation() {
+				continue;
+			}
+			let Some(summary) = line.plotted_values_summary(self.time_range)? else {
+				continue;
+			};
+			let summary = summary
+				.iter()
+				.map(|(name, value)| format!("{name} {}", format_stat_value(*value)))
+				.collect::<Vec<_>>()
+				.join(" ");
+			text_lines.push(format!("{}: {summary}", line.title(has_multiple_input_files)));
+		}
+		Ok(text_lines)
+	}
+}
+
+/// Formats the value with up to 3 decimal places, without trailing zeros.
+pub(crate) fn format_stat_value(value: f64) -> String {
+	let formatted = format!("{value:.3}");
+	let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+	if formatted == "-0" { "0".into() } else { formatted.into() }
+}
+
+/// Makes all panels duplicated from the same config panel (see `--per-file-panels`) use identical
+/// Y-axis ranges, spanning the values of all the duplicates.
+///
+/// Requires the panels time ranges to be resolved. Panels which were not duplicated are not
+/// affected.
+pub fn resolve_shared_y_ranges(config: &mut ResolvedGraphConfig) -> Result<(), Error> {
+	// (source panel index, is secondary axis) -> range
+	let mut ranges: BTreeMap<(usize, bool), (f64, f64)> = BTreeMap::new();
+	for panel in &config.panels {
+		let Some(source_panel) = panel.source_panel() else { continue };
+		for line in panel.lines.iter().filter(|line| !line.is_empty()) {
+			let y2 = matches!(line.line.params.yaxis, Some(YAxis::Y2));
+			let scale = if y2 { panel.params.y2axis_scale } else { panel.params.yaxis_scale };
+			let log_scale = matches!(scale, Some(AxisScale::Log));
+			if let Some((min, max)) = line.plotted_values_range(panel.time_range, log_scale)? {
+				ranges
+					.entry((source_panel, y2))
+					.and_modify(|range| *range = (range.0.min(min), range.1.max(max)))
+					.or_insert((min, max));
+			}
+		}
+	}
+	debug!(target: LOG_TARGET, "Shared Y-axis ranges: {ranges:?}");
+
+	for panel in &mut config.panels {
+		let Some(source_panel) = panel.source_panel() else { continue };
+		// Single value gives empty range, autoscaling is the same for all duplicates then.
+		let shared_range = |y2| {
+			ranges
+				.get(&(source_panel, y2))
+				.filter(|(min, max)| min < max)
+				.map(|&(min, max)| AxisRange { min: Some(min), max: Some(max) })
+		};
+		panel.shared_yrange = shared_range(false);
+		panel.shared_y2range = shared_range(true);
+	}
+	Ok(())
+}
+
+/// Metric displayed by `stat` command.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum StatMetric {
+	/// Number of values.
+	Count,
+	/// Minimal value.
+	Min,
+	/// Maximal value.
+	Max,
+	/// Arithmetic mean.
+	Mean,
+	/// Sample standard deviation.
+	Stddev,
+	/// Sample variance.
+	Variance,
+	/// Total sum of values.
+	Sum,
+	/// The most frequent value (the smallest one if there are many).
+	Mode,
+	/// Percentiles selected with `--percentiles`.
+	Percentiles,
+}
+
+/// Metrics displayed by `stat` command if not provided by user.
+pub const DEFAULT_STAT_METRICS: [StatMetric; 5] = [
+	StatMetric::Count,
+	StatMetric::Min,
+	StatMetric::Max,
+	StatMetric::Mean,
+	StatMetric::Percentiles,
+];
+
+/// Percentiles displayed by `stat` command if not provided by user.
+pub const DEFAULT_PERCENTILES: [f64; 5] = [50.0, 75.0, 90.0, 95.0, 99.0];
+
+/// Options controlling the output of `stat` command.
+#[derive(Clone, Debug)]
+pub struct StatOptions {
+	/// Histogram buckets count.
+	pub buckets_count: u64,
+	/// Use exponentially growing histogram buckets.
+	pub log_buckets: bool,
+	/// Width of the float printed in histogram bucket range.
+	pub width: Option<usize>,
+	/// Precision of the float printed in histogram bucket range (and grouped stats table).
+	pub precision: Option<usize>,
+	/// Percentiles to be displayed (within `[0, 100]` range).
+	pub percentiles: Vec<f64>,
+	/// Metrics to be displayed, in given order.
+	pub metrics: Vec<StatMetric>,
+	/// Aggregation applied to extracted records.
+	pub aggregation: StatAggregation,
+	/// Number of largest/smallest values to be displayed with their log lines.
+	pub outliers_count: Option<usize>,
+}
+
+/// Computes the selected metrics for given (non-empty) values.
+///
+/// Returns the list of metric labels and values in the order of `metrics`.
+fn compute_metrics(
+	values: &[f64],
+	metrics: &[StatMetric],
+	percentiles: &[f64],
+) -> Vec<(String, f64)> {
+	let mut data = Data::new(values.to_vec());
+	let mut result = vec![];
+	for metric in metrics {
+		match metric {
+			StatMetric::Count => result.push(("count".to_string(), values.len() as f64)),
+			StatMetric::Min => result.push(("min".to_string(), Statistics::min(values))),
+			StatMetric::Max => result.push(("max".to_string(), Statistics::max(values))),
+			StatMetric::Mean => result.push(("mean".to_string(), Statistics::mean(values))),
+			StatMetric::Stddev => result.push(("stddev".to_string(), Statistics::std_dev(values))),
+			StatMetric::Variance => result.push(("var".to_string(), Statistics::variance(values))),
+			StatMetric::Sum => result.push(("sum".to_string(), values.iter().sum())),
+			StatMetric::Mode => result.push(("mode".to_string(), mode(values))),
+			StatMetric::Percentiles => {
+				for p in percentiles {
+					let label = if *p == 50.0 { "median".to_string() } else { format!("q{p}") };
+					result.push((label, data.quantile(p / 100.0)));
+				}
+			},
+		}
+	}
+	result
+}
+
+/// Returns the most frequent value. If there are many, the smallest one is returned.
+fn mode(values: &[f64]) -> f64 {
+	let mut counts: HashMap<u64, usize> = HashMap::new();
+	values.iter().for_each(|v| *counts.entry(v.to_bits()).or_default() += 1);
+	counts
+		.into_iter()
+		.map(|(bits, count)| (f64::from_bits(bits), count))
+		.max_by(|(v0, c0), (v1, c1)| c0.cmp(c1).then(v1.total_cmp(v0)))
+		.map(|(v, _)| v)
+		.unwrap_or(f64::NAN)
+}
+
+pub fn display_stats(config: &ResolvedGraphConfig, options: &StatOptions) -> Result<(), Error> {
+	let lines_count = config.all_lines_count();
+
+	for (i, line) in config.all_lines().enumerate() {
+		let stat_values = read_stat_values(line, options.aggregation)?;
+		let values: Vec<f64> = stat_values.iter().map(|v| v.value).collect();
+
+		if line.label_regex().is_some() {
+			let mut groups: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+			for v in &stat_values {
+				groups.entry(v.label.clone().unwrap_or_default()).or_default().push(v.value);
+			}
+			if i > 0 {
+				println!("-------------------------");
+			}
+			if lines_count > 1 {
+				println!("file: {}", line.source.file_name().display());
+			}
+			display_grouped_stats(&groups, options);
+			if let Some(n) = options.outliers_count {
+				display_outliers(line, stat_values, n)?;
+			}
+			continue;
+		}
+
+		let h = if options.log_buckets {
+			PloxHisto::with_log_buckets(
+				&values,
+				options.buckets_count,
+				options.width,
+				options.precision,
+			)
+		} else {
+			PloxHisto::with_buckets(
+				&values,
+				options.buckets_count,
+				options.width,
+				options.precision,
+			)
+		};
 		if i > 0 {
 			println!("-------------------------");
 		}
@@ -1056,51 +3674,952 @@ pub fn display_stats(
 		if lines_count > 1 {
 			println!("file: {}", line.source.file_name().display());
 		}
-		println!(" count: {}", values.len());
 		if values.is_empty() {
+			println!(" count: 0");
 			continue;
 		}
-		println!("   min: {}", Statistics::min(&values));
-		println!("   max: {}", Statistics::max(&values));
-		println!("  mean: {}", Statistics::mean(&values));
 
-		let mut data = Data::new(values);
-		println!("median: {}", data.percentile(50));
-		println!("   q75: {}", data.percentile(75));
-		println!("   q90: {}", data.percentile(90));
-		println!("   q95: {}", data.percentile(95));
-		println!("   q99: {}", data.percentile(99));
+		for (label, value) in compute_metrics(&values, &options.metrics, &options.percentiles) {
+			println!("{label:>6}: {value}");
+		}
+		if let Some(n) = options.outliers_count {
+			display_outliers(line, stat_values, n)?;
+		}
 		println!("\n{h}");
 	}
 
 	Ok(())
 }
 
-pub fn display_values(config: &ResolvedGraphConfig) -> Result<(), Error> {
+/// Computes the histogram buckets for the values of the (single) line in the config.
+pub fn histogram_buckets(
+	config: &ResolvedGraphConfig,
+	aggregation: StatAggregation,
+	buckets_count: u64,
+	log_buckets: bool,
+) -> Result<Vec<HistoBucket>, Error> {
+	let mut values = vec![];
+	for line in config.all_lines() {
+		values.extend(read_stat_values(line, aggregation)?.into_iter().map(|v| v.value));
+	}
+	let h = if log_buckets {
+		PloxHisto::with_log_buckets(&values, buckets_count, None, None)
+	} else {
+		PloxHisto::with_buckets(&values, buckets_count, None, None)
+	};
+	Ok(h.buckets)
+}
+
+/// Strategy of aligning two series in time before computing their correlation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum CorrelationAlignment {
+	/// Pair every value of the first series with the nearest (in time) value of the second one.
+	///
+	/// Values further apart than the alignment interval are not paired.
+	#[default]
+	Nearest,
+	/// Split both series into consecutive time intervals and pair the means of values within
+	/// every interval. Intervals missing values of any series are skipped.
+	Bucket,
+}
+
+/// Reads the values of two data sources and aligns them in time.
+///
+/// Values of all lines in every config are merged. Returned pairs are sorted by time.
+pub fn correlated_values(
+	(x_config, x_aggregation): (&ResolvedGraphConfig, StatAggregation),
+	(y_config, y_aggregation): (&ResolvedGraphConfig, StatAggregation),
+	alignment: CorrelationAlignment,
+	interval: f64,
+) -> Result<Vec<(f64, f64)>, Error> {
+	let read = |config: &ResolvedGraphConfig, aggregation| -> Result<_, Error> {
+		let mut values = vec![];
+		for line in config.all_lines() {
+			values.extend(
+				read_stat_values(line, aggregation)?.into_iter().map(|v| (v.timestamp, v.value)),
+			);
+		}
+		values.sort_by_key(|(ts, _)| *ts);
+		Ok(values)
+	};
+	let xs = read(x_config, x_aggregation)?;
+	let ys = read(y_config, y_aggregation)?;
+	let interval = TimeDelta::microseconds((interval * 1_000_000.0).round() as i64);
+	Ok(match alignment {
+		CorrelationAlignment::Nearest => align_nearest(&xs, &ys, interval),
+		CorrelationAlignment::Bucket => align_buckets(&xs, &ys, interval),
+	})
+}
+
+fn align_nearest(
+	xs: &[(NaiveDateTime, f64)],
+	ys: &[(NaiveDateTime, f64)],
+	max_distance: TimeDelta,
+) -> Vec<(f64, f64)> {
+	xs.iter()
+		.filter_map(|(ts, x)| {
+			let i = ys.partition_point(|(t, _)| t < ts);
+			let before = i.checked_sub(1).map(|i| ys[i]);
+			let after = ys.get(i).copied();
+			[before, after]
+				.into_iter()
+				.flatten()
+				.map(|(t, y)| ((t - *ts).abs(), y))
+				.filter(|(d, _)| *d <= max_distance)
+				.min_by_key(|(d, _)| *d)
+				.map(|(_, y)| (*x, y))
+		})
+		.collect()
+}
+
+fn align_buckets(
+	xs: &[(NaiveDateTime, f64)],
+	ys: &[(NaiveDateTime, f64)],
+	interval: TimeDelta,
+) -> Vec<(f64, f64)> {
+	let Some(start) = xs.iter().chain(ys).map(|(ts, _)| *ts).min() else {
+		return vec![];
+	};
+	let interval = interval.num_microseconds().unwrap_or(1).max(1);
+	let bucket_means = |values: &[(NaiveDateTime, f64)]| {
+		let mut buckets: BTreeMap<i64, (f64, usize)> = BTreeMap::new();
+		for (ts, v) in values {
+			let index = (*ts - start).num_microseconds().unwrap_or(i64::MAX) / interval;
+			let bucket = buckets.entry(index).or_default();
+			bucket.0 += v;
+			bucket.1 += 1;
+		}
+		buckets
+			.into_iter()
+			.map(|(i, (sum, n))| (i, sum / n as f64))
+			.collect::<BTreeMap<_, _>>()
+	};
+	let x_means = bucket_means(xs);
+	let y_means = bucket_means(ys);
+	x_means.iter().filter_map(|(i, x)| y_means.get(i).map(|y| (*x, *y))).collect()
+}
+
+/// Pearson correlation coefficient. `NaN` is returned if it is not defined (e.g. less than two
+/// pairs or constant series).
+fn pearson(pairs: &[(f64, f64)]) -> f64 {
+	let n = pairs.len() as f64;
+	if pairs.len() < 2 {
+		return f64::NAN;
+	}
+	let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+	let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+	let (cov, var_x, var_y) = pairs.iter().fold((0.0, 0.0, 0.0), |(c, vx, vy), (x, y)| {
+		let (dx, dy) = (x - mean_x, y - mean_y);
+		(c + dx * dy, vx + dx * dx, vy + dy * dy)
+	});
+	cov / (var_x * var_y).sqrt()
+}
+
+/// Returns ranks (1-based) of the values. Tied values get the average of their ranks.
+fn ranks(values: &[f64]) -> Vec<f64> {
+	let mut order = (0..values.len()).collect::<Vec<_>>();
+	order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+	let mut ranks = vec![0.0; values.len()];
+	let mut i = 0;
+	while i < order.len() {
+		let mut j = i;
+		while j + 1 < order.len() && values[order[j + 1]] == values[order[i]] {
+			j += 1;
+		}
+		let rank = (i + j) as f64 / 2.0 + 1.0;
+		order[i..=j].iter().for_each(|k| ranks[*k] = rank);
+		i = j + 1;
+	}
+	ranks
+}
+
+/// Spearman rank correlation coefficient.
+fn spearman(pairs: &[(f64, f64)]) -> f64 {
+	let x_ranks = ranks(&pairs.iter().map(|(x, _)| *x).collect::<Vec<_>>());
+	let y_ranks = ranks(&pairs.iter().map(|(_, y)| *y).collect::<Vec<_>>());
+	pearson(&x_ranks.into_iter().zip(y_ranks).collect::<Vec<_>>())
+}
+
+/// Prints the count of aligned pairs and their Pearson and Spearman correlation coefficients.
+pub fn display_correlation(pairs: &[(f64, f64)]) {
+	println!("{:>8}: {}", "pairs", pairs.len());
+	println!("{:>8}: {}", "pearson", pearson(pairs));
+	println!("{:>8}: {}", "spearman", spearman(pairs));
+}
+
+/// Maximal number of samples of the resampled series analyzed by [`periodogram`].
+const MAX_PERIODOGRAM_SAMPLES: usize = 1 << 24;
+
+/// Number of the strongest peaks of the spectrum considered by [`periodogram`].
+const MAX_PERIODOGRAM_PEAKS: usize = 100;
+
+/// Result of the periodicity analysis (see [`periodogram`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Periodogram {
+	/// Number of samples of the resampled series.
+	pub samples: usize,
+	/// Resampling interval in seconds.
+	pub interval: f64,
+	/// Dominant periods, strongest first.
+	pub periods: Vec<DominantPeriod>,
+}
+
+/// Frequency standing out in the spectrum of the series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DominantPeriod {
+	/// Period in seconds.
+	pub period: f64,
+	/// Frequency in Hz.
+	pub frequency: f64,
+	/// Share of the power of the whole spectrum (except the mean), including the harmonics of the
+	/// frequency.
+	pub power_share: f64,
+}
+
+/// Resamples the values of all lines in the config to the fixed interval (in seconds), and
+/// returns at most `top` frequencies with the highest power.
+///
+/// Every sample is the mean of the values within the interval, intervals without values are
+/// filled with the mean of the whole series. Only local maxima of the spectrum are reported, so
+/// the neighbours of a strong frequency do not crowd out other periods, and harmonics are
+/// reported along with their fundamental frequency.
+pub fn periodogram(
+	config: &ResolvedGraphConfig,
+	aggregation: StatAggregation,
+	interval: f64,
+	top: usize,
+) -> Result<Periodogram, Error> {
+	let mut values = vec![];
+	for line in config.all_lines() {
+		values.extend(
+			read_stat_values(line, aggregation)?.into_iter().map(|v| (v.timestamp, v.value)),
+		);
+	}
+	let samples = resample(&values, interval)?;
+	Ok(Periodogram {
+		samples: samples.len(),
+		interval,
+		periods: dominant_periods(&samples, interval, top),
+	})
+}
+
+/// Means of the values within consecutive intervals, gaps are filled with the mean of all values.
+fn resample(values: &[(NaiveDateTime, f64)], interval: f64) -> Result<Vec<f64>, Error> {
+	let (Some(start), Some(end)) =
+		(values.iter().map(|(ts, _)| *ts).min(), values.iter().map(|(ts, _)| *ts).max())
+	else {
+		return Ok(vec![]);
+	};
+	let index =
+		|ts: NaiveDateTime| ((ts - start).num_milliseconds() as f64 / 1000.0 / interval) as usize;
+	let count = index(end) + 1;
+	if count > MAX_PERIODOGRAM_SAMPLES {
+		return Err(Error::TooManySamples(count, interval));
+	}
+	let mut buckets = vec![(0.0, 0usize); count];
+	for (ts, v) in values {
+		let bucket = &mut buckets[index(*ts)];
+		bucket.0 += v;
+		bucket.1 += 1;
+	}
+	let mean = values.iter().map(|(_, v)| v).sum::<f64>() / values.len() as f64;
+	Ok(buckets
+		.into_iter()
+		.map(|(sum, n)| if n > 0 { sum / n as f64 } else { mean })
+		.collect())
+}
+
+/// Finds the strongest periods of the evenly sampled series.
+fn dominant_periods(samples: &[f64], interval: f64, top: usize) -> Vec<DominantPeriod> {
+	let n = samples.len();
+	if n < 4 {
+		return vec![];
+	}
+	let mean = samples.iter().sum::<f64>() / n as f64;
+	let mut spectrum = samples
+		.iter()
+		.map(|v| rustfft::num_complex::Complex::new(v - mean, 0.0))
+		.collect::<Vec<_>>();
+	rustfft::FftPlanner::new().plan_fft_forward(n).process(&mut spectrum);
+
+	// Spectrum of the real series is symmetric, the mean (k = 0) is skipped.
+	let power = spectrum[..=n / 2].iter().map(|c| c.norm_sqr()).collect::<Vec<_>>();
+	let total = power[1..].iter().sum::<f64>();
+	if total <= 0.0 {
+		return vec![];
+	}
+	// Peaks negligible compared to the whole spectrum are rounding errors of the transform.
+	let mut peaks = (1..power.len())
+		.filter(|k| power[k - 1] <= power[*k] && power.get(k + 1).is_none_or(|p| *p <= power[*k]))
+		.filter(|k| power[*k] > total * 1e-12)
+		.collect::<Vec<_>>();
+	peaks.sort_by(|a, b| power[*b].total_cmp(&power[*a]));
+	peaks.truncate(MAX_PERIODOGRAM_PEAKS);
+
+	// Harmonics of the periodic spikes are as strong as the fundamental frequency: their power is
+	// added to the (comparably strong) peak of the fundamental frequency, which is reported.
+	let fundamental = |k: usize| {
+		peaks
+			.iter()
+			.copied()
+			.filter(|j| *j >= 3 && *j < k && power[*j] >= power[k] / 2.0)
+			.filter(|j| {
+				let m = (k as f64 / *j as f64).round() as usize;
+				m >= 2 && k.abs_diff(m * j) <= 1
+			})
+			.min()
+	};
+	let mut groups: Vec<(usize, f64)> = vec![];
+	for k in &peaks {
+		let mut root = *k;
+		while let Some(j) = fundamental(root) {
+			root = j;
+		}
+		match groups.iter_mut().find(|(j, _)| *j == root) {
+			Some(group) => group.1 += power[*k],
+			None => groups.push((root, power[*k])),
+		}
+	}
+	groups.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+	groups
+		.into_iter()
+		.take(top)
+		.map(|(k, power)| {
+			let frequency = k as f64 / (n as f64 * interval);
+			DominantPeriod { period: 1.0 / frequency, frequency, power_share: power / total }
+		})
+		.collect()
+}
+
+/// Prints the dominant periods found by [`periodogram`].
+pub fn display_periodogram(periodogram: &Periodogram) {
+	println!("samples: {} (interval: {}s)", periodogram.samples, periodogram.interval);
+	if periodogram.periods.is_empty() {
+		println!("no periodicity found");
+		return;
+	}
+	println!("{:>12} {:>12} {:>7}", "period[s]", "freq[Hz]", "power");
+	for p in &periodogram.periods {
+		println!("{:>12.3} {:>12.6} {:>6.1}%", p.period, p.frequency, p.power_share * 100.0);
+	}
+}
+
+/// Label displayed for values extracted from lines not matching the label regex.
+const UNLABELED_GROUP: &str = "-";
+
+/// Ordering of labels displayed by `top` command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum TopOrder {
+	/// Number of occurrences of the label.
+	#[default]
+	Count,
+	/// Sum of values extracted for the label.
+	Sum,
+}
+
+/// Prints the ranking of labels captured with label regex.
+///
+/// Values of all lines in the config are grouped by label, and `top` labels with the highest
+/// count (or sum of values) are displayed.
+pub fn display_top(
+	config: &ResolvedGraphConfig,
+	aggregation: StatAggregation,
+	top: usize,
+	order: TopOrder,
+	precision: usize,
+) -> Result<(), Error> {
+	let mut groups: HashMap<String, (usize, f64)> = HashMap::new();
+	for line in config.all_lines() {
+		for v in read_stat_values(line, aggregation)? {
+			let label = v.label.unwrap_or_else(|| UNLABELED_GROUP.to_string());
+			let entry = groups.entry(label).or_default();
+			entry.0 += 1;
+			entry.1 += v.value;
+		}
+	}
+
+	let mut ranking = groups.into_iter().collect::<Vec<_>>();
+	ranking.sort_by(|(l0, (c0, s0)), (l1, (c1, s1))| {
+		let ord = match order {
+			TopOrder::Count => c1.cmp(c0).then(s1.total_cmp(s0)),
+			TopOrder::Sum => s1.total_cmp(s0).then(c1.cmp(c0)),
+		};
+		ord.then(l0.cmp(l1))
+	});
+	ranking.truncate(top);
+
+	let label_width = ranking.iter().map(|(l, _)| l.len()).max().unwrap_or(0).max("label".len());
+	println!("{:>4} {:<label_width$} {:>8} {:>12}", "rank", "label", "count", "sum");
+	for (i, (label, (count, sum))) in ranking.iter().enumerate() {
+		println!("{:>4} {label:<label_width$} {count:>8} {sum:>12.precision$}", i + 1);
+	}
+	Ok(())
+}
+
+/// Prints a table containing a stats row for every group of values.
+///
+/// The count of values is always displayed.
+fn display_grouped_stats(groups: &BTreeMap<String, Vec<f64>>, options: &StatOptions) {
+	let precision = options.precision.unwrap_or(4);
+	let metrics = options
+		.metrics
+		.iter()
+		.copied()
+		.filter(|m| *m != StatMetric::Count)
+		.collect::<Vec<_>>();
+	let label_width = groups.keys().map(|k| k.len()).max().unwrap_or(0).max("label".len());
+	let column_width = 12;
+
+	for (i, (label, values)) in groups.iter().enumerate() {
+		let columns = compute_metrics(values, &metrics, &options.percentiles);
+		if i == 0 {
+			let mut header = format!("{:<label_width$} {:>8}", "label", "count");
+			for (name, _) in &columns {
+				header.push_str(&format!(" {name:>column_width$}"));
+			}
+			println!("{header}");
+		}
+
+		let label = if label.is_empty() { UNLABELED_GROUP } else { label };
+		let mut row = format!("{label:<label_width$} {:>8}", values.len());
+		for (_, c) in columns {
+			row.push_str(&format!(" {c:>column_width$.precision$}"));
+		}
+		println!("{row}");
+	}
+}
+
+/// Column displayed by `cat` command.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum CatColumn {
+	/// Timestamp of the log line (or start of the interval for aggregated values).
+	Ts,
+	/// Extracted value.
+	Value,
+	/// Number of occurrences so far.
+	Count,
+	/// Time delta (in milliseconds) from the previous occurrence.
+	Delta,
+	/// Number of the source log line.
+	Line,
+	/// Label extracted with label regex.
+	Label,
+	/// Matched text (or whole log line) kept with `--keep-text`.
+	Text,
+}
+
+/// Output format of `cat` command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum CatFormat {
+	/// Space separated columns, no header.
+	#[default]
+	Plain,
+	/// Comma separated values with header.
+	Csv,
+	/// Tab separated values with header.
+	Tsv,
+	/// JSON object per line.
+	Json,
+}
+
+pub fn display_values(
+	config: &ResolvedGraphConfig,
+	aggregation: StatAggregation,
+	columns: &[CatColumn],
+	format: CatFormat,
+) -> Result<(), Error> {
 	if config.all_lines_count() > 1 {
 		return Err(Error::CatCmdManyInputFiles);
 	}
 
+	let printed = ValuesPrinter::new(columns, format).and_then(|mut printer| {
+		for line in config.all_lines() {
+			for v in read_stat_values(line, aggregation)? {
+				printer.print(&v)?;
+			}
+		}
+		printer.flush()
+	});
+	ignore_broken_pipe(printed)
+}
+
+/// Prints values extracted from the log file, and keeps printing new values as lines are
+/// appended to the file.
+///
+/// The existing content of the file is processed first. Cache files are not used. Returns only on
+/// error.
+pub fn follow_values(
+	config: &ResolvedGraphConfig,
+	input_context: &InputFilesContext,
+	aggregation: StatAggregation,
+	columns: &[CatColumn],
+	format: CatFormat,
+) -> Result<(), Error> {
+	if config.all_lines_count() > 1 {
+		return Err(Error::CatCmdManyInputFiles);
+	}
+	if aggregation != StatAggregation::None {
+		return Err(Error::CatFollowUnsupportedDataSource);
+	}
+	let Some(line) = config.all_lines().next() else {
+		return Ok(());
+	};
+	ignore_broken_pipe(follow_line_values(line, input_context, columns, format))
+}
+
+/// Keeps printing the values of the line, see [`follow_values`].
+fn follow_line_values(
+	line: &ResolvedLine,
+	input_context: &InputFilesContext,
+	columns: &[CatColumn],
+	format: CatFormat,
+) -> Result<(), Error> {
+	let new_processor = || {
+		LineProcessor::from_line(
+			&line.line,
+			None,
+			input_context.timestamp_format().clone(),
+			line.source_file_name().clone(),
+			input_context.ignore_invalid_timestamps(),
+		)
+		.map(|p| {
+			p.with_strict(input_context.strict())
+				.with_number_locale(input_context.number_locale())
+		})
+	};
+	let mut processor = new_processor()?;
+	let mut printer = ValuesPrinter::new(columns, format)?;
+	let mut follower = LogFollower::new(line.source_file_name())?;
+
+	// Following ends with Ctrl-C.
+	while !interrupt::is_interrupted() {
+		let log_line = match follower.next_line()? {
+			FollowedLine::Line(log_line) => log_line,
+			FollowedLine::Pending => {
+				printer.flush()?;
+				thread::sleep(FOLLOW_POLL_INTERVAL);
+				continue;
+			},
+			FollowedLine::Truncated => {
+				processor = new_processor()?;
+				continue;
+			},
+		};
+		if let (_, Some((captures, timestamp))) = processor.try_match(&log_line)? {
+			processor.process(captures, timestamp);
+		}
+		for record in processor.records.drain(..) {
+			if let Some(v) = StatValue::from_record(record, &line.line.data_source)? {
+				printer.print(&v)?;
+			}
+		}
+	}
+	printer.flush()
+}
+
+/// Treats the output closed by the reader (e.g. `plox cat ... | head`) as the end of printing.
+fn ignore_broken_pipe(result: Result<(), Error>) -> Result<(), Error> {
+	match result {
+		Err(Error::CatOutputError(e)) if matches!(e.kind(), csv::ErrorKind::Io(e) if e.kind() == io::ErrorKind::BrokenPipe) =>
+		{
+			debug!(target: APPV, "Output closed, printing stopped.");
+			Ok(())
+		},
+		result => result,
+	}
+}
+
+/// Prints the values in the format requested for `cat` command.
+struct ValuesPrinter<'a> {
+	writer: csv::Writer<io::Stdout>,
+	columns: &'a [CatColumn],
+	format: CatFormat,
+}
+
+impl<'a> ValuesPrinter<'a> {
+	/// Creates the printer, the header is printed for formats that have one.
+	fn new(columns: &'a [CatColumn], format: CatFormat) -> Result<Self, Error> {
+		let delimiter = match format {
+			CatFormat::Plain => b' ',
+			CatFormat::Tsv => b'\t',
+			_ => b',',
+		};
+		let quote_style = if format == CatFormat::Plain {
+			csv::QuoteStyle::Never
+		} else {
+			csv::QuoteStyle::Necessary
+		};
+		let mut writer = csv::WriterBuilder::new()
+			.delimiter(delimiter)
+			.quote_style(quote_style)
+			.from_writer(io::stdout());
+		if matches!(format, CatFormat::Csv | CatFormat::Tsv) {
+			writer.write_record(columns.iter().map(|c| c.to_string()))?;
+		}
+		Ok(Self { writer, columns, format })
+	}
+
+	fn print(&mut self, v: &StatValue) -> Result<(), Error> {
+		if self.format == CatFormat::Json {
+			// Built manually to keep the order of columns.
+			let fields = self
+				.columns
+				.iter()
+				.map(|c| format!("\"{c}\":{}", v.json_column(*c)))
+				.collect::<Vec<_>>();
+			self.writer.flush().map_err(|e| Error::CatOutputError(e.into()))?;
+			writeln!(self.writer.get_ref(), "{{{}}}", fields.join(","))
+				.map_err(|e| Error::CatOutputError(e.into()))?;
+		} else {
+			self.writer.write_record(self.columns.iter().map(|c| v.column(*c)))?;
+		}
+		Ok(())
+	}
+
+	fn flush(&mut self) -> Result<(), Error> {
+		self.writer.flush().map_err(|e| Error::CatOutputError(e.into()))
+	}
+}
+
+/// Result of reading the followed log file.
+#[derive(Debug, PartialEq)]
+enum FollowedLine {
+	/// Next complete line.
+	Line(String),
+	/// No complete line is available yet.
+	Pending,
+	/// File was truncated, reading restarts from the beginning.
+	Truncated,
+}
+
+/// Reads lines appended to the log file.
+///
+/// If the file gets truncated (e.g. rotated with `copytruncate`), reading restarts from the
+/// beginning of the file.
+struct LogFollower {
+	path: PathBuf,
+	reader: BufReader<File>,
+	position: u64,
+	/// Incomplete line read so far.
+	partial: Vec<u8>,
+}
+
+impl LogFollower {
+	fn new(path: &PathBuf) -> Result<Self, Error> {
+		let file = File::open(path).map_err(|e| Error::new_file_io_error(path, e))?;
+		Ok(Self { path: path.clone(), reader: BufReader::new(file), position: 0, partial: vec![] })
+	}
+
+	fn next_line(&mut self) -> Result<FollowedLine, Error> {
+		let read = self
+			.reader
+			.read_until(b'\n', &mut self.partial)
+			.map_err(|e| Error::new_file_io_error(&self.path, e))?;
+		self.position += read as u64;
+
+		if self.partial.ends_with(b"\n") {
+			let line = self.partial.strip_suffix(b"\n").unwrap_or_default();
+			let line = line.strip_suffix(b"\r").unwrap_or(line);
+			let line = String::from_utf8_lossy(line).into_owned();
+			self.partial.clear();
+			return Ok(FollowedLine::Line(line));
+		}
+
+		if read == 0 {
+			let len = fs::metadata(&self.path)
+				.map_err(|e| Error::new_file_io_error(&self.path, e))?
+				.len();
+			if len < self.position {
+				debug!(target: APPV, "Followed file truncated: {}", self.path.display());
+				*self = Self::new(&self.path)?;
+				return Ok(FollowedLine::Truncated);
+			}
+		}
+		Ok(FollowedLine::Pending)
+	}
+}
+
+/// Defines how records extracted from the log are turned into values used by `stat` and `cat`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatAggregation {
+	/// Values (or deltas) of the records are used as they are.
+	None,
+	/// Number of records within consecutive intervals of given length (in seconds).
+	CountPerInterval(f64),
+	/// Number of records per second within consecutive intervals of given length (in seconds).
+	RatePerInterval(f64),
+}
+
+/// Single value used by `stat` and `cat` commands.
+#[derive(Debug)]
+struct StatValue {
+	/// Label of the value (if label regex was provided).
+	label: Option<String>,
+	/// Matched text (if it was kept). Not available for aggregated values.
+	text: Option<String>,
+	value: f64,
+	/// Number of the source log line. Not available for aggregated values.
+	line: Option<u64>,
+	/// Timestamp of the record (or start of the interval for aggregated values).
+	timestamp: NaiveDateTime,
+	/// Occurrences count. Not available for aggregated values.
+	count: Option<u64>,
+	/// Time delta from the previous occurrence. Not available for aggregated values.
+	delta: Option<f64>,
+}
+
+impl StatValue {
+	/// Builds the value from the record extracted for given data source.
+	///
+	/// Returns `None` if the record carries no value (e.g. the first event for delta).
+	fn from_record(record: LogRecord, data_source: &DataSource) -> Result<Option<Self>, Error> {
+		let value = match data_source {
+			DataSource::FieldValue { .. }
+			| DataSource::JsonField { .. }
+			| DataSource::LogfmtField { .. }
+			| DataSource::CsvFile(_) => Some(record.value),
+			DataSource::EventDelta { .. } => record.diff,
+			_ => {
+				unreachable!("this is bug.");
+			},
+		};
+		let Some(value) = value else {
+			return Ok(None);
+		};
+		Ok(Some(StatValue {
+			timestamp: record.timestamp()?,
+			count: Some(record.count),
+			delta: record.diff,
+			label: record.label,
+			text: record.text,
+			value,
+			line: record.line,
+		}))
+	}
+
+	/// Returns the column formatted for plain and CSV outputs. Missing values are empty.
+	fn column(&self, column: CatColumn) -> String {
+		match column {
+			CatColumn::Ts => self.timestamp.format(CAT_TIMESTAMP_FORMAT).to_string(),
+			CatColumn::Value => format!("{:?}", self.value),
+			CatColumn::Count => self.count.map(|c| c.to_string()).unwrap_or_default(),
+			CatColumn::Delta => self.delta.map(|d| format!("{d:?}")).unwrap_or_default(),
+			CatColumn::Line => self.line.map(|l| l.to_string()).unwrap_or_default(),
+			CatColumn::Label => self.label.clone().unwrap_or_default(),
+			CatColumn::Text => self.text.clone().unwrap_or_default(),
+		}
+	}
+
+	/// Returns the column as JSON value. Missing values are `null`.
+	fn json_column(&self, column: CatColumn) -> serde_json::Value {
+		match column {
+			CatColumn::Ts => self.column(column).into(),
+			CatColumn::Value => self.value.into(),
+			CatColumn::Count => self.count.into(),
+			CatColumn::Delta => self.delta.into(),
+			CatColumn::Line => self.line.into(),
+			CatColumn::Label => self.label.clone().into(),
+			CatColumn::Text => self.text.clone().into(),
+		}
+	}
+}
+
+/// Timestamp format used in `cat` command output.
+const CAT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Prints `count` largest and smallest values together with the log lines they were extracted from.
+fn display_outliers(
+	line: &ResolvedLine,
+	mut values: Vec<StatValue>,
+	count: usize,
+) -> Result<(), Error> {
+	values.sort_by(|a, b| b.value.total_cmp(&a.value));
+	let count = count.min(values.len());
+	let largest = &values[..count];
+	let smallest = &values[values.len() - count..];
+
+	// Log lines are not available if stats are computed directly from the cache file (or the
+	// source is the virtual input of `--concat`).
+	let log_lines = if !line.is_from_cache() && line.source_file_name().is_file() {
+		let line_numbers = largest.iter().chain(smallest).filter_map(|v| v.line).collect();
+		read_log_lines(line.source_file_name(), &line_numbers)?
+	} else {
+		HashMap::default()
+	};
+
+	let print = |v: &StatValue| {
+		let log_line = v.line.and_then(|n| log_lines.get(&n).map(|l| (n, l)));
+		match (log_line, v.line) {
+			(Some((n, l)), _) => println!("{:>12} [line {n}]: {l}", v.value),
+			(None, Some(n)) if n > 0 => println!("{:>12} [line {n}]", v.value),
+			(None, _) => println!("{:>12}", v.value),
+		}
+	};
+	println!("\nlargest values:");
+	largest.iter().for_each(print);
+	println!("\nsmallest values:");
+	smallest.iter().rev().for_each(print);
+	Ok(())
+}
+
+/// Prints the log lines matched by every line's data source, annotated with extracted value and
+/// delta (in milliseconds) from the previous match.
+///
+/// If `time_range` is given, only lines with timestamps within the range (inclusive) are printed.
+/// Source file name is prepended to every printed line if there are many input files.
+pub fn display_matched_lines(
+	config: &ResolvedGraphConfig,
+	input_context: &InputFilesContext,
+	time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+) -> Result<(), Error> {
+	let multi_input_files = config.all_lines_count() > 1;
+
 	for line in config.all_lines() {
-		let filename = line.expect_shared_csv_filename();
+		let filename = line.resolved_csv_filename()?;
 		let mut rdr = csv::Reader::from_path(&filename)
 			.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+
+		let mut records = vec![];
 		for result in rdr.deserialize() {
 			let record: LogRecord =
 				result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+			let ts = record.timestamp()?;
+			if time_range.is_none_or(|(start, end)| ts >= start && ts <= end) {
+				records.push((ts, record));
+			}
+		}
+
+		// Records are not sorted by line number if timestamps were sorted (or records sampled),
+		// all needed log lines are read at once.
+		let log_file_name = line.source_file_name();
+		let line_numbers = records.iter().filter_map(|(_, r)| r.line).collect();
+		let log_lines = select_log_lines(
+			LogLines::open_input(log_file_name, input_context)?.map_while(Result::ok),
+			&line_numbers,
+		);
+
+		for (ts, record) in records {
+			let prefix = if multi_input_files {
+				format!("{}:", log_file_name.display())
+			} else {
+				String::new()
+			};
+			let delta = record.diff.map(|d| format!("{d:?}")).unwrap_or("-".to_string());
+			let value = match line.line.data_source {
+				DataSource::EventCount { .. } => Some(record.count as f64),
+				DataSource::EventDelta(_) => record.diff,
+				_ => Some(record.value),
+			};
+			let value = value.map(|v| format!("{v:?}")).unwrap_or("-".to_string());
+			let annotation = format!("[value={value} delta={delta}]");
+
+			let log_line = record.line.and_then(|n| log_lines.get(&n).map(|l| (n, l)));
+			match log_line {
+				Some((n, l)) => println!("{prefix}{n}: {annotation} {l}"),
+				None => println!("{prefix}{annotation} {}", ts.format(CAT_TIMESTAMP_FORMAT)),
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Reads the lines with given numbers (1-based) from the log file.
+fn read_log_lines(
+	log_file_name: &Path,
+	line_numbers: &BTreeSet<u64>,
+) -> Result<HashMap<u64, String>, Error> {
+	if line_numbers.is_empty() {
+		return Ok(HashMap::default());
+	}
+	Ok(select_log_lines(LogLines::open(log_file_name)?.map_while(Result::ok), line_numbers))
+}
+
+/// Picks the lines with given numbers (1-based) from the log lines.
+fn select_log_lines(
+	log_lines: impl Iterator<Item = String>,
+	line_numbers: &BTreeSet<u64>,
+) -> HashMap<u64, String> {
+	let last = line_numbers.last().copied().unwrap_or_default();
+	log_lines
+		.zip(1u64..=last)
+		.filter(|(_, n)| line_numbers.contains(n))
+		.map(|(l, n)| (n, l))
+		.collect()
+}
+
+/// Reads the values (along with optional labels) from the line's CSV file.
+///
+/// For interval based aggregations, the intervals span the whole range between the first and the
+/// last record, so intervals with no records are reported as zeros. If records are labeled,
+/// intervals are computed separately for every label.
+fn read_stat_values(
+	line: &ResolvedLine,
+	aggregation: StatAggregation,
+) -> Result<Vec<StatValue>, Error> {
+	let filename = line.resolved_csv_filename()?;
+	let mut rdr =
+		csv::Reader::from_path(&filename).map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+
+	let interval = match aggregation {
+		StatAggregation::CountPerInterval(interval)
+		| StatAggregation::RatePerInterval(interval) => interval,
+		StatAggregation::None => {
+			let mut values = vec![];
+			for result in rdr.deserialize() {
+				let record: LogRecord =
+					result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+				values.extend(StatValue::from_record(record, &line.line.data_source)?);
+			}
+			return Ok(values);
+		},
+	};
+
+	let mut timestamps: BTreeMap<Option<String>, Vec<NaiveDateTime>> = BTreeMap::new();
+	let mut range: Option<(NaiveDateTime, NaiveDateTime)> = None;
+	for result in rdr.deserialize() {
+		let record: LogRecord = result.map_err(|e| Error::CsvParseError(filename.clone(), e))?;
+		let ts = record.timestamp()?;
+		range = Some(range.map_or((ts, ts), |(start, end)| (start.min(ts), end.max(ts))));
+		timestamps.entry(record.label).or_default().push(ts);
+	}
+
+	let Some((start, end)) = range else {
+		return Ok(vec![]);
+	};
 
-			match &line.line.data_source {
-				DataSource::FieldValue { .. } => println!("{:?}", record.value),
-				DataSource::EventDelta { .. } => {
-					record.diff.inspect(|v| println!("{:?}", v));
-				},
-				_ => {
-					unreachable!("this is bug.");
-				},
+	let interval_index =
+		|ts: NaiveDateTime| ((ts - start).num_milliseconds() as f64 / 1000.0 / interval) as usize;
+	let intervals_count = interval_index(end).saturating_add(1);
+	let total = intervals_count.saturating_mul(timestamps.len());
+	if total > MAX_INTERVALS {
+		return Err(Error::TooManyIntervals(total, interval));
+	}
+
+	let mut values = vec![];
+	for (label, timestamps) in timestamps {
+		let mut counts = vec![0u64; intervals_count];
+		timestamps.into_iter().for_each(|ts| counts[interval_index(ts)] += 1);
+		values.extend(counts.into_iter().enumerate().map(|(i, count)| {
+			let value = match aggregation {
+				StatAggregation::RatePerInterval(_) => count as f64 / interval,
+				_ => count as f64,
 			};
-		}
+			let timestamp =
+				start + TimeDelta::milliseconds((i as f64 * interval * 1000.0).round() as i64);
+			StatValue {
+				label: label.clone(),
+				text: None,
+				value,
+				line: None,
+				timestamp,
+				count: None,
+				delta: None,
+			}
+		}));
 	}
-	Ok(())
+	Ok(values)
 }
 
 #[cfg(test)]
@@ -1179,7 +4698,7 @@ mod tests {
 
 	fn check_output_and_config(
 		config: ResolvedGraphConfig,
-		output: HashMap<PathBuf, ResolvedLine>,
+		output: BTreeMap<PathBuf, ResolvedLine>,
 		expected_output_len: usize,
 		allow_shared_lines_in_output: bool,
 	) {
@@ -1193,11 +4712,11 @@ mod tests {
 		);
 		// Make sure all shared names are set in config
 		for value in config.all_lines() {
-			value.expect_shared_csv_filename();
+			value.resolved_csv_filename().unwrap();
 		}
 		// Make sure all keys are matched to the files in values.
 		for (output_file_name, canonical) in &output {
-			assert_eq!(*output_file_name, canonical.expect_shared_csv_filename());
+			assert_eq!(*output_file_name, canonical.resolved_csv_filename().unwrap());
 			if !allow_shared_lines_in_output {
 				assert!(!canonical.can_csv_file_be_shared(), "no shared lines allowed in output");
 			}
@@ -1205,7 +4724,7 @@ mod tests {
 		// Ensure no duplicate values from method: get_shared_csv_filename()
 		let mut seen_filenames = std::collections::HashSet::new();
 		for value in output.values() {
-			let filename = value.expect_shared_csv_filename();
+			let filename = value.resolved_csv_filename().unwrap();
 			assert!(
 				seen_filenames.insert(filename.clone()),
 				"Duplicate filename detected: {}",
@@ -1223,11 +4742,11 @@ mod tests {
 					allowed_canonical_names.push(output_file_name.clone());
 				}
 			}
-			assert!(allowed_canonical_names.contains(&line.expect_shared_csv_filename()));
+			assert!(allowed_canonical_names.contains(&line.resolved_csv_filename().unwrap()));
 		}
 		//make sure that all shared files from config are in output
 		for line in config.all_lines() {
-			let shared_csv_file = line.expect_shared_csv_filename();
+			let shared_csv_file = line.resolved_csv_filename().unwrap();
 			assert!(
 				output.contains_key(&shared_csv_file),
 				"Output should contain shared_csv_file: {}",
@@ -1237,7 +4756,7 @@ mod tests {
 	}
 	fn call_propagate_shared_csv_files(
 		config: &mut ResolvedGraphConfig,
-	) -> Result<HashMap<PathBuf, ResolvedLine>, Error> {
+	) -> Result<BTreeMap<PathBuf, ResolvedLine>, Error> {
 		let input_context = InputFilesContext::new_with_input(vec![PathBuf::from("input.log")]);
 		propagate_shared_csv_files(config, &input_context, |_, _| {
 			Ok(PathBuf::from("/some/out/dir"))
@@ -1322,152 +4841,770 @@ mod tests {
 	}
 
 	#[test]
-	fn test_csv_resolution_04() {
+	fn test_csv_resolution_04() {
+		init_tracing_test();
+
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input.log", Some("guard0"), "duration"),
+			event_line("input.log", Some("guard1"), "duration", 100.0),
+			event_count_line("input.log", Some("guard1"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 2, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_05() {
+		init_tracing_test();
+
+		let mut config = build_resolved_graph_config(vec![event_count_line(
+			"input.log",
+			Some("guard1"),
+			"duration",
+		)]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 1, true);
+	}
+
+	#[test]
+	fn test_csv_resolution_06() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			event_count_line("input.log", Some("guard1"), "duration1"),
+			event_delta_line("input.log", Some("guard1"), "duration2"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 2, true);
+	}
+
+	#[test]
+	fn test_csv_resolution_07() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input.log", Some("guard1"), "duration"),
+			event_line("input.log", Some("guard1"), "duration", 100.0),
+			event_count_line("input.log", Some("guard1"), "duration"),
+			event_delta_line("input.log", Some("guard1"), "duration"),
+			plot_line("input.log", Some("guard2"), "duration"),
+			event_line("input.log", Some("guard2"), "duration", 100.0),
+			event_count_line("input.log", Some("guard2"), "duration"),
+			event_delta_line("input.log", Some("guard2"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 4, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_08() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input1.log", Some("guard"), "duration"),
+			event_line("input1.log", Some("guard"), "duration", 100.0),
+			event_count_line("input1.log", Some("guard"), "duration"),
+			event_delta_line("input1.log", Some("guard"), "duration"),
+			plot_line("input2.log", Some("guard"), "duration"),
+			event_line("input2.log", Some("guard"), "duration", 100.0),
+			event_count_line("input2.log", Some("guard"), "duration"),
+			event_delta_line("input2.log", Some("guard"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 4, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_09() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			event_count_line("input1.log", Some("guard1"), "duration"),
+			event_delta_line("input1.log", Some("guard2"), "duration"),
+			event_count_line("input2.log", Some("guard1"), "duration"),
+			event_delta_line("input2.log", Some("guard2"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 4, true);
+	}
+
+	#[test]
+	fn test_csv_resolution_10() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input.log", Some("guard"), "duration"),
+			plot_line("input.log", Some("guard"), "duration"),
+			plot_line("input.log", Some("guard"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 1, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_11() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			event_line("input.log", Some("guard"), "duration", 1.0),
+			event_line("input.log", Some("guard"), "duration", 2.0),
+			event_line("input.log", Some("guard"), "duration", 3.0),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 3, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_12() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			event_count_line("input.log", Some("guard"), "duration"),
+			event_count_line("input.log", Some("guard"), "duration"),
+			event_count_line("input.log", Some("guard"), "duration"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 1, true);
+	}
+
+	#[test]
+	fn test_csv_resolution_label_regex() {
+		init_tracing_test();
+		let mut labeled = plot_line("input.log", Some("guard"), "duration");
+		labeled.line.params.label_regex = Some(r"peer=(\w+)".into());
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input.log", Some("guard"), "duration"),
+			labeled,
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		check_output_and_config(config, output, 2, false);
+	}
+
+	#[test]
+	fn test_csv_resolution_equivalent_regex() {
+		init_tracing_test();
+		let mut config = build_resolved_graph_config(vec![
+			plot_line("input.log", Some("guard"), "duration"),
+			plot_line("input.log", Some("guard"), r"duration=([\d\.]+)(\w+)?"),
+			plot_line("input.log", Some("guard"), r"\bduration=([\d.]+)(\w+)?"),
+			plot_line("input.log", Some("guard"), r"duration=(\d+)(\w+)?"),
+		]);
+		let output = call_propagate_shared_csv_files(&mut config).unwrap();
+		assert_eq!(output.len(), 3);
+		let shared = config
+			.all_lines()
+			.map(|l| l.resolved_csv_filename().unwrap())
+			.collect::<Vec<_>>();
+		// Without the word boundary the regex also matches e.g. `max_duration=`.
+		assert_ne!(shared[0], shared[1]);
+		assert_eq!(shared[0], shared[2]);
+		assert_ne!(shared[0], shared[3]);
+		assert_ne!(shared[1], shared[3]);
+
+		let data_source = DataSource::new_plot_field(None, r"\bduration=([\d\.]+)(\w+)?".into());
+		assert_eq!(data_source.canonical_pattern(None), "duration");
+		assert_eq!(
+			data_source.canonical_pattern(Some(NumberLocale::Eu)),
+			r"\bduration=([\d\.]+)(\w+)?"
+		);
+		let data_source = DataSource::new_plot_field(None, r"duration=([\d\.]+)(\w+)?".into());
+		assert_eq!(data_source.canonical_pattern(None), r"duration=([\d\.]+)(\w+)?");
+		let data_source = DataSource::new_plot_field(None, r"a.b=([\d\.]+)(\w+)?".into());
+		assert_eq!(data_source.canonical_pattern(None), r"a.b=([\d\.]+)(\w+)?");
+	}
+
+	#[test]
+	fn test_csv_resolution_deterministic() {
+		init_tracing_test();
+		let build = || {
+			build_resolved_graph_config(vec![
+				event_count_line("input.log", Some("guard"), "duration"),
+				plot_line("other.log", None, "x"),
+				event_line("input.log", Some("guard"), "duration", 1.0),
+				plot_line("input.log", Some("guard"), "duration"),
+				event_count_line("other.log", Some("guard2"), "y"),
+			])
+		};
+		let resolve = || {
+			let mut config = build();
+			let output = call_propagate_shared_csv_files(&mut config).unwrap();
+			let shared = config
+				.all_lines()
+				.map(|l| l.resolved_csv_filename().unwrap())
+				.collect::<Vec<_>>();
+			let canonicals = output
+				.into_iter()
+				.map(|(path, line)| (path, line.line.data_source.title()))
+				.collect::<Vec<_>>();
+			(shared, canonicals)
+		};
+		let (shared, canonicals) = resolve();
+		for _ in 0..5 {
+			assert_eq!(resolve(), (shared.clone(), canonicals.clone()));
+		}
+		assert!(canonicals.is_sorted_by(|a, b| a.0 < b.0));
+	}
+
+	#[test]
+	fn test_log_lines_invalid_utf8() {
+		let input: &[u8] = b"first\r\nbad \xff\xfe value\nthird\n\nlast";
+		let mut lines = LogLines::new(input);
+		let read = lines.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+		assert_eq!(read, ["first", "bad \u{fffd}\u{fffd} value", "third", "", "last"]);
+		assert_eq!(lines.lossy_lines_count, 1);
+	}
+
+	#[test]
+	fn test_mode() {
+		assert_eq!(mode(&[1.0, 2.0, 2.0, 3.0]), 2.0);
+		assert_eq!(mode(&[3.0, 1.0, 2.0]), 1.0);
+		assert_eq!(mode(&[5.0, 4.0, 5.0, 4.0]), 4.0);
+		assert!(mode(&[]).is_nan());
+	}
+
+	#[test]
+	fn test_compute_metrics() {
+		let metrics = compute_metrics(
+			&[1.0, 2.0, 3.0, 4.0],
+			&[StatMetric::Sum, StatMetric::Variance, StatMetric::Percentiles, StatMetric::Count],
+			&[50.0, 99.0],
+		);
+		let labels = metrics.iter().map(|(l, _)| l.as_str()).collect::<Vec<_>>();
+		assert_eq!(labels, ["sum", "var", "median", "q99", "count"]);
+		assert_eq!(metrics[0].1, 10.0);
+		assert!((metrics[1].1 - 5.0 / 3.0).abs() < 1e-9);
+		assert_eq!(metrics[4].1, 4.0);
+	}
+
+	#[test]
+	fn test_correlation() {
+		let pairs = [(1.0, 2.0), (2.0, 4.0), (3.0, 6.0), (4.0, 8.0)];
+		assert!((pearson(&pairs) - 1.0).abs() < 1e-9);
+		assert!((spearman(&pairs) - 1.0).abs() < 1e-9);
+
+		// monotonic, but not linear
+		let pairs = [(1.0, 1.0), (2.0, 8.0), (3.0, 27.0), (4.0, 1000.0)];
+		assert!(pearson(&pairs) < 0.9);
+		assert!((spearman(&pairs) - 1.0).abs() < 1e-9);
+
+		let pairs = [(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)];
+		assert!((pearson(&pairs) + 1.0).abs() < 1e-9);
+		assert!(pearson(&[(1.0, 1.0)]).is_nan());
+		assert_eq!(ranks(&[10.0, 20.0, 10.0, 5.0]), [2.5, 4.0, 2.5, 1.0]);
+	}
+
+	#[test]
+	fn test_correlation_alignment() {
+		let ts = |ms: i64| {
+			NaiveDate::from_ymd_opt(2025, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+				+ TimeDelta::milliseconds(ms)
+		};
+		let xs = [(ts(0), 1.0), (ts(1000), 2.0), (ts(5000), 3.0)];
+		let ys = [(ts(100), 10.0), (ts(900), 20.0), (ts(1500), 30.0), (ts(1600), 40.0)];
+
+		let pairs = align_nearest(&xs, &ys, TimeDelta::milliseconds(500));
+		assert_eq!(pairs, [(1.0, 10.0), (2.0, 20.0)]);
+
+		let pairs = align_buckets(&xs, &ys, TimeDelta::seconds(1));
+		assert_eq!(pairs, [(1.0, 15.0), (2.0, 35.0)]);
+	}
+
+	#[test]
+	fn test_log_buckets() {
+		let h = PloxHisto::with_log_buckets(&[1.0, 5.0, 10.0, 50.0, 100.0, 1000.0], 3, None, None);
+		let counts = h.buckets.iter().map(|b| b.count).collect::<Vec<_>>();
+		assert_eq!(counts, [2, 2, 2]);
+		assert_eq!(h.buckets[0].start, 1.0);
+		assert!((h.buckets[0].end - 10.0).abs() < 1e-9);
+		assert!((h.buckets[2].end - 1000.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_log_buckets_non_positive_values() {
+		let h = PloxHisto::with_log_buckets(&[-1.0, 0.0, 2.0, 8.0], 2, None, None);
+		assert_eq!(h.buckets[0], HistoBucket { start: -1.0, end: 2.0, count: 2 });
+		assert_eq!(h.buckets.iter().map(|b| b.count).collect::<Vec<_>>(), [2, 1, 1]);
+
+		let h = PloxHisto::with_log_buckets(&[-1.0, 0.0], 2, None, None);
+		assert_eq!(h.buckets.iter().map(|b| b.count).sum::<u64>(), 2);
+	}
+
+	#[test]
+	fn test_partial_file() {
+		let dir = std::env::temp_dir().join(format!("plox-partial-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		let dropped = PartialFile(dir.join("a.csv.partial"));
+		fs::write(&*dropped, "date,time").unwrap();
+		drop(dropped);
+		assert!(!dir.join("a.csv.partial").exists());
+
+		let persisted = PartialFile(dir.join("b.csv.partial"));
+		fs::write(&*persisted, "date,time").unwrap();
+		persisted.persist(&dir.join("b.csv")).unwrap();
+		assert_eq!(fs::read_to_string(dir.join("b.csv")).unwrap(), "date,time");
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_line_deduplicator() {
+		let format = TimestampFormat::from("%H:%M:%S");
+		let mut deduplicator = LineDeduplicator::default();
+		let duplicates = |deduplicator: &mut LineDeduplicator, lines: &[&str]| {
+			lines.iter().map(|l| deduplicator.is_duplicate(l, &format)).collect::<Vec<_>>()
+		};
+		// Distinct lines sharing the timestamp, line without timestamp.
+		assert_eq!(
+			duplicates(
+				&mut deduplicator,
+				&["00:00:00 a", "00:00:00 b", "00:00:10 c", "00:00:20 d", "trace", "trace"]
+			),
+			[false, false, false, false, false, false]
+		);
+		// Overlapping snapshot.
+		assert_eq!(
+			duplicates(
+				&mut deduplicator,
+				&["00:00:10 c", "00:00:20 e", "00:00:20 d", "00:00:30 f", "00:00:30 f"]
+			),
+			[true, false, true, false, true]
+		);
+		assert_eq!(deduplicator.dropped, 3);
+		// Lines older than the overlap were forgotten.
+		assert_eq!(deduplicator.seen.keys().count(), 3);
+		assert_eq!(deduplicator.len, 4);
+		assert_eq!(duplicates(&mut deduplicator, &["00:00:00 a"]), [false]);
+	}
+
+	#[test]
+	fn test_log_follower() {
+		let path = std::env::temp_dir().join(format!("plox-follow-{}.log", std::process::id()));
+		let append = |content: &str| {
+			fs::OpenOptions::new()
+				.append(true)
+				.create(true)
+				.open(&path)
+				.unwrap()
+				.write_all(content.as_bytes())
+				.unwrap()
+		};
+		let line = |l: &str| FollowedLine::Line(l.to_string());
+
+		append("a \nb");
+		let mut follower = LogFollower::new(&path).unwrap();
+		assert_eq!(follower.next_line().unwrap(), line("a "));
+		assert_eq!(follower.next_line().unwrap(), FollowedLine::Pending);
+		append("c\r\nd\n");
+		assert_eq!(follower.next_line().unwrap(), line("bc"));
+		assert_eq!(follower.next_line().unwrap(), line("d"));
+		assert_eq!(follower.next_line().unwrap(), FollowedLine::Pending);
+
+		fs::write(&path, "e\n").unwrap();
+		assert_eq!(follower.next_line().unwrap(), FollowedLine::Truncated);
+		assert_eq!(follower.next_line().unwrap(), line("e"));
+
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_line_processing_00() {
+		init_tracing_test();
+		let log_line = "2025-04-03 11:32:48.027 INFO main: operation duration=12.5ms";
+
+		let resolved_line = plot_line("input.log", Some("operation"), "duration");
+
+		let mut processor = LineProcessor::from_data_source(
+			resolved_line.line.data_source,
+			Some(PathBuf::from("output.csv")),
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap();
+
+		assert!(processor.guard_matches(log_line));
+		let (g, matched) = processor.try_match(log_line).unwrap();
+		let (captures, timestamp) = matched.unwrap();
+		assert!(g);
+		processor.process(captures, timestamp);
+
+		assert_eq!(processor.records.len(), 1);
+		let record = &processor.records[0];
+		assert_eq!(record.value, 12.5);
+		assert_eq!(record.count, 1);
+		assert_eq!(record.diff, None);
+	}
+
+	#[test]
+	fn test_line_processing_label() {
+		init_tracing_test();
+		let log_line = "2025-04-03 11:32:48.027 INFO main: operation peer=alice duration=12.5ms";
+
+		let resolved_line = plot_line("input.log", Some("operation"), "duration");
+
+		let mut processor = LineProcessor::from_data_source(
+			resolved_line.line.data_source,
+			Some(PathBuf::from("output.csv")),
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap()
+		.with_label_regex(Some(r"peer=(\w+)"))
+		.unwrap();
+
+		let (_, matched) = processor.try_match(log_line).unwrap();
+		let (captures, timestamp) = matched.unwrap();
+		processor.process(captures, timestamp);
+
+		assert_eq!(processor.records.len(), 1);
+		assert_eq!(processor.records[0].value, 12.5);
+		assert_eq!(processor.records[0].label.as_deref(), Some("alice"));
+	}
+
+	#[test]
+	fn test_line_processing_keep_text() {
+		init_tracing_test();
+		let log_line = "2025-04-03 11:32:48.027 INFO main: operation peer=alice, duration=12.5ms";
+
+		for (keep_text, expected) in [
+			(None, None),
+			(Some(MatchedText::Match), Some("duration=12.5ms")),
+			(Some(MatchedText::Line), Some(log_line)),
+		] {
+			let mut line = plot_line("input.log", Some("operation"), "duration").line;
+			line.params.keep_text = keep_text;
+			let mut processor = LineProcessor::from_line(
+				&line,
+				Some(PathBuf::from("output.csv")),
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap();
+
+			let (_, matched) = processor.try_match(log_line).unwrap();
+			let (captures, timestamp) = matched.unwrap();
+			processor.process(captures, timestamp);
+
+			assert_eq!(processor.records[0].value, 12.5);
+			assert_eq!(processor.records[0].text.as_deref(), expected);
+		}
+
+		// Texts containing separators are quoted.
+		let mut csv_line = String::new();
+		let record = LogRecord {
+			date: None,
+			time: "11:32:48.027".into(),
+			value: 1.0,
+			count: 1,
+			diff: None,
+			line: Some(1),
+			label: Some("alice".into()),
+			text: Some("a, \"b\"".into()),
+			unit: None,
+		};
+		record.push_csv_text_columns(&mut csv_line);
+		assert_eq!(csv_line, ",alice,\"a, \"\"b\"\"\"");
+	}
+
+	#[test]
+	fn test_line_processing_number_locale() {
 		init_tracing_test();
-
-		let mut config = build_resolved_graph_config(vec![
-			plot_line("input.log", Some("guard0"), "duration"),
-			event_line("input.log", Some("guard1"), "duration", 100.0),
-			event_count_line("input.log", Some("guard1"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 2, false);
+		for (locale, value, expected) in [
+			(None, "1234.5ms", Some(1234.5)),
+			// Without the locale only the digits before the grouping separator are matched.
+			(None, "1,234.5ms", Some(1.0)),
+			(Some(NumberLocale::Eu), "n/a", None),
+			(Some(NumberLocale::En), "1,234.5ms", Some(1234.5)),
+			(Some(NumberLocale::En), "1234.5s", Some(1234500.0)),
+			(Some(NumberLocale::En), "12,345,678", Some(12345678.0)),
+			(Some(NumberLocale::Eu), "1 234,5ms", Some(1234.5)),
+			(Some(NumberLocale::Eu), "1.234,5ms", Some(1234.5)),
+			(Some(NumberLocale::Eu), "1\u{202f}234", Some(1234.0)),
+			(Some(NumberLocale::Eu), "0,25s", Some(250.0)),
+		] {
+			let line = plot_line("input.log", Some("operation"), "duration").line;
+			let mut processor = LineProcessor::from_line(
+				&line,
+				None,
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap()
+			.with_number_locale(locale);
+
+			let log_line =
+				format!("2025-04-03 11:32:48.027 INFO main: operation duration={value} peer=alice");
+			let (_, matched) = processor.try_match(&log_line).unwrap();
+			match expected {
+				Some(expected) => {
+					let (captures, timestamp) = matched.unwrap();
+					processor.process(captures, timestamp);
+					assert_eq!(processor.records[0].value, expected, "{value} {locale:?}");
+				},
+				None => assert!(matched.is_none(), "{value} {locale:?}"),
+			}
+		}
 	}
 
 	#[test]
-	fn test_csv_resolution_05() {
+	fn test_line_processing_time_window() {
 		init_tracing_test();
+		let ts = |s| NaiveDate::from_ymd_opt(2025, 4, 3).unwrap().and_hms_opt(11, 32, s).unwrap();
+		let log_lines = [
+			"2025-04-03 11:32:10.000 INFO main: operation duration=1",
+			"2025-04-03 11:32:20.000 INFO main: operation duration=2",
+			"2025-04-03 11:32:30.000 INFO main: operation duration=3",
+			"2025-04-03 11:32:40.000 INFO main: operation duration=4",
+			"2025-04-03 11:34:40.000 INFO main: operation duration=5",
+		];
 
-		let mut config = build_resolved_graph_config(vec![event_count_line(
-			"input.log",
-			Some("guard1"),
-			"duration",
-		)]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 1, true);
+		for (sort_timestamps, expected_past_window) in [(false, true), (true, false)] {
+			let line = plot_line("input.log", Some("operation"), "duration").line;
+			let mut processor = LineProcessor::from_line(
+				&line,
+				None,
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap()
+			.with_sorted_timestamps(sort_timestamps)
+			.with_time_window(Some((ts(15), ts(30))));
+
+			for log_line in log_lines {
+				if let (_, Some((value, timestamp))) = processor.try_match(log_line).unwrap() {
+					processor.process(value, timestamp);
+				}
+			}
+			let values = processor.records.iter().map(|r| r.value).collect::<Vec<_>>();
+			assert_eq!(values, [2.0, 3.0]);
+			assert_eq!(processor.match_stats.outside_time_window, 3);
+			assert_eq!(processor.past_time_window, expected_past_window);
+		}
+
+		// Lines shortly past the window, or past the window in the log which is not monotonic, do
+		// not end the processing.
+		for (log_lines, expected_values) in
+			[(vec![10, 40, 20, 25], vec![20.0, 25.0]), (vec![20, 10, 200, 25], vec![20.0, 25.0])]
+		{
+			let line = plot_line("input.log", Some("operation"), "duration").line;
+			let mut processor = LineProcessor::from_line(
+				&line,
+				None,
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap()
+			.with_time_window(Some((ts(15), ts(30))));
+			for s in log_lines {
+				let log_line = format!(
+					"2025-04-03 11:{:02}:{:02}.000 INFO main: operation duration={s}",
+					32 + s / 60,
+					s % 60
+				);
+				let (_, matched) = processor.try_match(&log_line).unwrap();
+				assert!(!processor.past_time_window);
+				if let Some((value, timestamp)) = matched {
+					processor.process(value, timestamp);
+				}
+			}
+			let values = processor.records.iter().map(|r| r.value).collect::<Vec<_>>();
+			assert_eq!(values, expected_values);
+		}
 	}
 
 	#[test]
-	fn test_csv_resolution_06() {
-		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			event_count_line("input.log", Some("guard1"), "duration1"),
-			event_delta_line("input.log", Some("guard1"), "duration2"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 2, true);
+	fn test_extract_json_field() {
+		let line =
+			r#"2025-04-03 11:32:48.027 {"level":"INFO","ok":true,"fields":{"d":"12ms","q":[4,5]}}"#;
+		let extract = |guard, path| extract_json_field(line, guard, path, true);
+		assert_eq!(extract(None, "fields.d"), Some(("12".into(), Some("ms".into()))));
+		assert_eq!(extract(None, "fields.q.1"), Some(("5".into(), None)));
+		assert_eq!(extract(None, "ok"), Some(("1".into(), None)));
+		assert_eq!(extract(None, "fields.x"), None);
+		assert_eq!(extract(Some("level=INFO"), "fields.q.0"), Some(("4".into(), None)));
+		assert_eq!(extract(Some("level=WARN"), "fields.q.0"), None);
+		assert_eq!(extract(Some("ok=true"), "fields.q.0"), Some(("4".into(), None)));
+		assert_eq!(extract(Some("fields.q"), "ok"), Some(("1".into(), None)));
+		assert_eq!(extract(Some("fields.x"), "ok"), None);
+		assert_eq!(extract_json_field(line, None, "fields.d", false), Some(("12ms".into(), None)));
+		assert_eq!(extract_json_field("no json", None, "ok", true), None);
 	}
 
 	#[test]
-	fn test_csv_resolution_07() {
-		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			plot_line("input.log", Some("guard1"), "duration"),
-			event_line("input.log", Some("guard1"), "duration", 100.0),
-			event_count_line("input.log", Some("guard1"), "duration"),
-			event_delta_line("input.log", Some("guard1"), "duration"),
-			plot_line("input.log", Some("guard2"), "duration"),
-			event_line("input.log", Some("guard2"), "duration", 100.0),
-			event_count_line("input.log", Some("guard2"), "duration"),
-			event_delta_line("input.log", Some("guard2"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 4, false);
+	fn test_logfmt_value() {
+		let line = r#"level=info msg="a \"quoted\" duration=1s" plain duration=-12.5ms empty="""#;
+		assert_eq!(logfmt_value(line, "level").as_deref(), Some("info"));
+		assert_eq!(logfmt_value(line, "msg").as_deref(), Some(r#"a "quoted" duration=1s"#));
+		assert_eq!(logfmt_value(line, "duration").as_deref(), Some("-12.5ms"));
+		assert_eq!(logfmt_value(line, "empty").as_deref(), Some(""));
+		assert_eq!(logfmt_value(line, "plain"), None);
+		assert_eq!(split_value_unit("-12.5ms"), ("-12.5".to_string(), Some("ms".to_string())));
 	}
 
 	#[test]
-	fn test_csv_resolution_08() {
-		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			plot_line("input1.log", Some("guard"), "duration"),
-			event_line("input1.log", Some("guard"), "duration", 100.0),
-			event_count_line("input1.log", Some("guard"), "duration"),
-			event_delta_line("input1.log", Some("guard"), "duration"),
-			plot_line("input2.log", Some("guard"), "duration"),
-			event_line("input2.log", Some("guard"), "duration", 100.0),
-			event_count_line("input2.log", Some("guard"), "duration"),
-			event_delta_line("input2.log", Some("guard"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 4, false);
+	fn test_sampling() {
+		assert_eq!("0.25".parse(), Ok(Sampling::Fraction(0.25)));
+		assert_eq!("1000".parse(), Ok(Sampling::Count(1000)));
+		assert!("0".parse::<Sampling>().is_err());
+		assert!("1.5".parse::<Sampling>().is_err());
+		assert!("abc".parse::<Sampling>().is_err());
+
+		let sample = |sampling| {
+			let mut sampler = Sampler::new(sampling);
+			let mut records = vec![];
+			for i in 1..=1000 {
+				let record = LogRecord {
+					date: None,
+					time: String::new(),
+					value: i as f64,
+					count: i,
+					diff: None,
+					line: Some(i),
+					label: None,
+					text: None,
+					unit: None,
+				};
+				sampler.offer(&mut records, record);
+			}
+			records.iter().map(|r| r.count).collect::<Vec<_>>()
+		};
+
+		let fraction = sample(Sampling::Fraction(0.1));
+		assert!((50..150).contains(&fraction.len()), "{}", fraction.len());
+		assert!(fraction.is_sorted());
+		// Sampling is deterministic.
+		assert_eq!(fraction, sample(Sampling::Fraction(0.1)));
+
+		let reservoir = sample(Sampling::Count(10));
+		assert_eq!(reservoir.len(), 10);
+		// Not only the first records are kept.
+		assert!(reservoir.iter().any(|count| *count > 10));
+		assert_eq!(reservoir, sample(Sampling::Count(10)));
 	}
 
 	#[test]
-	fn test_csv_resolution_09() {
+	fn test_line_processing_original_units() {
 		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			event_count_line("input1.log", Some("guard1"), "duration"),
-			event_delta_line("input1.log", Some("guard2"), "duration"),
-			event_count_line("input2.log", Some("guard1"), "duration"),
-			event_delta_line("input2.log", Some("guard2"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 4, true);
+		let log_line = "2025-04-03 11:32:48.027 INFO main: operation duration=12.5s";
+
+		for (value_units, expected_value, expected_unit) in [
+			(None, 12500.0, None),
+			(Some(ValueUnits::Normalized), 12500.0, None),
+			(Some(ValueUnits::Original), 12.5, Some("s")),
+		] {
+			let mut line = plot_line("input.log", Some("operation"), "duration").line;
+			line.params.value_units = value_units;
+			let mut processor = LineProcessor::from_line(
+				&line,
+				None,
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap();
+
+			let (_, matched) = processor.try_match(log_line).unwrap();
+			let (captures, timestamp) = matched.unwrap();
+			processor.process(captures, timestamp);
+
+			assert_eq!(processor.records[0].value, expected_value);
+			assert_eq!(processor.records[0].unit.as_deref(), expected_unit);
+		}
 	}
 
 	#[test]
-	fn test_csv_resolution_10() {
+	fn test_line_processing_value_map() {
 		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			plot_line("input.log", Some("guard"), "duration"),
-			plot_line("input.log", Some("guard"), "duration"),
-			plot_line("input.log", Some("guard"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 1, false);
+		let mut line = plot_line("input.log", Some("worker"), "state").line;
+		line.params.value_map = Some("idle=0,busy=1".parse().unwrap());
+		let mut processor = LineProcessor::from_line(
+			&line,
+			None,
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap();
+
+		for (state, expected) in [("busy", Some(1.0)), ("idle,", Some(0.0)), ("error", None)] {
+			let log_line = format!("2025-04-03 11:32:48.027 worker state={state} id=7");
+			let (_, matched) = processor.try_match(&log_line).unwrap();
+			assert_eq!(matched.map(|(value, _)| value), expected, "{state}");
+		}
+		assert_eq!(processor.skipped_records_count, 1);
 	}
 
 	#[test]
-	fn test_csv_resolution_11() {
+	fn test_annotation_processing() {
 		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			event_line("input.log", Some("guard"), "duration", 1.0),
-			event_line("input.log", Some("guard"), "duration", 2.0),
-			event_line("input.log", Some("guard"), "duration", 3.0),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 3, false);
+		let line = Line::new_with_data_source(DataSource::new_annotation(
+			Some("startup".into()),
+			r"version=(\S+)".into(),
+		));
+		let mut processor = LineProcessor::from_line(
+			&line,
+			None,
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap();
+		let (_, matched) = processor
+			.try_match("2025-04-03 11:32:48.027 INFO startup version=1.2.3 ok")
+			.unwrap();
+		let (value, timestamp) = matched.unwrap();
+		processor.process(value, timestamp);
+		assert_eq!(processor.records[0].text.as_deref(), Some("1.2.3"));
+
+		let no_capture = DataSource::new_annotation(None, "startup".into());
+		assert!(matches!(
+			no_capture.compile_regex(),
+			Err(Error::AnnotationRegexCapturesGroupsInvalidCount(_))
+		));
 	}
 
 	#[test]
-	fn test_csv_resolution_12() {
+	fn test_field_regex_fancy_fallback() {
 		init_tracing_test();
-		let mut config = build_resolved_graph_config(vec![
-			event_count_line("input.log", Some("guard"), "duration"),
-			event_count_line("input.log", Some("guard"), "duration"),
-			event_count_line("input.log", Some("guard"), "duration"),
-		]);
-		let output = call_propagate_shared_csv_files(&mut config).unwrap();
-		check_output_and_config(config, output, 1, true);
+		assert!(matches!(DataSourceRegex::new(r"lat=(\d+)"), Ok(DataSourceRegex::Fast(_))));
+		assert!(matches!(DataSourceRegex::new(r"(?<!x)lat=(\d+)"), Ok(DataSourceRegex::Fancy(_))));
+		assert!(matches!(DataSourceRegex::new(r"lat=(\d+"), Err(Error::Regex(_))));
+
+		let line = plot_line("input.log", None, r"(?<=peer=bob )lat=(\d+)(?=ms)").line;
+		let mut processor = LineProcessor::from_line(
+			&line,
+			None,
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap();
+		for (log_line, expected) in [
+			("2025-04-03 11:32:48.027 peer=alice lat=5ms", None),
+			("2025-04-03 11:32:48.027 peer=bob lat=7s", None),
+			("2025-04-03 11:32:48.027 peer=bob lat=7ms", Some(7.0)),
+		] {
+			let (_, matched) = processor.try_match(log_line).unwrap();
+			let value = matched.map(|(captures, timestamp)| {
+				processor.process(captures, timestamp);
+				processor.records.last().unwrap().value
+			});
+			assert_eq!(value, expected, "{log_line}");
+		}
 	}
 
 	#[test]
-	fn test_line_processing_00() {
-		init_tracing_test();
-		let log_line = "2025-04-03 11:32:48.027 INFO main: operation duration=12.5ms";
-
+	fn test_label_regex_invalid_captures_count() {
 		let resolved_line = plot_line("input.log", Some("operation"), "duration");
-
-		let mut processor = LineProcessor::from_data_source(
+		let processor = LineProcessor::from_data_source(
 			resolved_line.line.data_source,
-			Some(PathBuf::from("output.csv")),
+			None,
 			DEFAULT_TIMESTAMP_FORMAT,
 			"input.log".into(),
 			false,
 		)
-		.unwrap();
-
-		assert!(processor.guard_matches(log_line));
-		let (g, matched) = processor.try_match(log_line).unwrap();
-		let (captures, timestamp) = matched.unwrap();
-		assert!(g);
-		processor.process(captures, timestamp);
-
-		assert_eq!(processor.records.len(), 1);
-		let record = &processor.records[0];
-		assert_eq!(record.value, 12.5);
-		assert_eq!(record.count, 1);
-		assert_eq!(record.diff, None);
+		.unwrap()
+		.with_label_regex(Some(r"peer=\w+"));
+		assert!(matches!(processor, Err(Error::LabelRegexCapturesGroupsInvalidCount(_))));
 	}
 
 	#[test]
@@ -1505,6 +5642,64 @@ mod tests {
 		assert_eq!(record.diff, None);
 	}
 
+	#[test]
+	fn test_match_preview_args() {
+		let data_sources = [
+			DataSource::new_plot_field(Some("guard".into()), r"x(\d+)".into()),
+			DataSource::new_event_value(None, "started".into(), 2.5),
+			DataSource::new_event_count(Some("g".into()), "it's".into()),
+			DataSource::new_event_delta(None, "tick".into()),
+		];
+		for data_source in data_sources {
+			let args = data_source.cli_args();
+			let id = args[0].trim_start_matches("--");
+			let values = args[1..].iter().collect::<Vec<_>>();
+			assert_eq!(DataSource::try_from_flag(id, &values).unwrap(), data_source);
+		}
+		assert_eq!(shell_quote("x_module"), "x_module");
+		assert_eq!(shell_quote(r"x(\d+)"), r"'x(\d+)'");
+		assert_eq!(shell_quote("it's"), r"'it'\''s'");
+	}
+
+	#[test]
+	fn test_line_processing_conversion_failure() {
+		init_tracing_test();
+		let log_lines = [
+			"2025-04-03 11:32:48.027 INFO main: operation duration:n/a",
+			"2025-04-03 11:32:48.054 INFO main: operation duration:2.5",
+			"2025-04-03 11:32:48.055 INFO main: operation duration:n/a",
+		];
+		let resolved_line = plot_line("input.log", Some("operation"), r"duration:([\w\./]+)");
+		let new_processor = || {
+			LineProcessor::from_data_source(
+				resolved_line.line.data_source.clone(),
+				None,
+				DEFAULT_TIMESTAMP_FORMAT,
+				"input.log".into(),
+				false,
+			)
+			.unwrap()
+		};
+
+		let mut processor = new_processor();
+		for log_line in log_lines {
+			if let (_, Some((value, timestamp))) = processor.try_match(log_line).unwrap() {
+				processor.process(value, timestamp);
+			}
+		}
+		assert_eq!(processor.records.len(), 1);
+		assert_eq!(processor.records[0].value, 2.5);
+		assert_eq!(processor.records[0].count, 1);
+		assert_eq!(processor.skipped_records_count, 2);
+		assert_eq!(processor.first_skipped_record, Some((1, "n/a".to_string())));
+
+		let mut processor = new_processor().with_strict(true);
+		assert!(matches!(
+			processor.try_match(log_lines[0]),
+			Err(Error::ValueConversionFailure(_, 1, v)) if v == "n/a"
+		));
+	}
+
 	#[test]
 	fn test_line_processing_date_format_no_year2() {
 		init_tracing_test();
@@ -1821,6 +6016,154 @@ mod tests {
 		assert_eq!(record.diff.unwrap(), 86400000.0);
 	}
 
+	#[test]
+	fn test_line_processing_streaming() {
+		init_tracing_test();
+		let log_lines = [
+			"2025-04-03 11:32:48.027 INFO main: operation duration:1.5",
+			"2025-04-03 11:32:48.054 INFO main: operation duration:2.5",
+			"2025-04-03 11:32:49.054 INFO main: operation duration:3.5",
+		];
+		let output = std::env::temp_dir().join(format!("plox-stream-{}.csv", std::process::id()));
+		let resolved_line = plot_line("input.log", Some("operation"), r"duration:([\d\.]+)");
+		let mut processor = LineProcessor::from_data_source(
+			resolved_line.line.data_source,
+			Some(output.clone()),
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap();
+
+		processor.start_csv().unwrap();
+		for (i, log_line) in log_lines.iter().enumerate() {
+			let (_, matched) = processor.try_match(log_line).unwrap();
+			let (value, timestamp) = matched.unwrap();
+			processor.process(value, timestamp);
+			if i == 1 {
+				processor.flush_records().unwrap();
+				assert!(processor.records.is_empty());
+				assert!(!output.exists());
+			}
+		}
+
+		let (count, range) = processor.finish_csv().unwrap();
+		assert_eq!(count, 3);
+		let (start, end) = range.unwrap();
+		assert_eq!(start.to_string(), "2025-04-03 11:32:48.027");
+		assert_eq!(end.to_string(), "2025-04-03 11:32:49.054");
+
+		let csv = fs::read_to_string(&output).unwrap();
+		fs::remove_file(&output).unwrap();
+		assert_eq!(
+			csv.lines().collect::<Vec<_>>(),
+			[
+				"date,time,value,count,delta,line",
+				"2025-04-03,11:32:48.027,1.5,1,0.0,1",
+				"2025-04-03,11:32:48.054,2.5,2,27.0,2",
+				"2025-04-03,11:32:49.054,3.5,3,1000.0,3",
+			]
+		);
+	}
+
+	#[test]
+	fn test_line_processing_sort_timestamps() {
+		init_tracing_test();
+		let log_lines = [
+			"2025-04-03 11:32:48.027 INFO main: operation duration:1.5",
+			"2025-04-03 11:32:49.054 INFO main: operation duration:3.5",
+			"2025-04-03 11:32:48.054 INFO main: operation duration:2.5",
+		];
+		let output = std::env::temp_dir().join(format!("plox-sort-{}.csv", std::process::id()));
+		let resolved_line = plot_line("input.log", Some("operation"), r"duration:([\d\.]+)");
+		let mut processor = LineProcessor::from_data_source(
+			resolved_line.line.data_source,
+			Some(output.clone()),
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap()
+		.with_sorted_timestamps(true);
+
+		processor.start_csv().unwrap();
+		for log_line in log_lines {
+			let (_, matched) = processor.try_match(log_line).unwrap();
+			let (value, timestamp) = matched.unwrap();
+			processor.process(value, timestamp);
+		}
+		assert_eq!(processor.out_of_order_count, 1);
+		assert_eq!(processor.first_out_of_order_line, Some(3));
+		assert_eq!(processor.records[2].diff, Some(-1000.0));
+
+		let (count, range) = processor.finish_csv().unwrap();
+		assert_eq!(count, 3);
+		assert_eq!(range.unwrap().1.to_string(), "2025-04-03 11:32:49.054");
+
+		let csv = fs::read_to_string(&output).unwrap();
+		fs::remove_file(&output).unwrap();
+		assert_eq!(
+			csv.lines().collect::<Vec<_>>(),
+			[
+				"date,time,value,count,delta,line",
+				"2025-04-03,11:32:48.027,1.5,1,0.0,1",
+				"2025-04-03,11:32:48.054,2.5,2,27.0,3",
+				"2025-04-03,11:32:49.054,3.5,3,1000.0,2",
+			]
+		);
+	}
+
+	#[test]
+	fn test_line_processing_sample_sort_timestamps() {
+		init_tracing_test();
+		let output =
+			std::env::temp_dir().join(format!("plox-sample-sort-{}.csv", std::process::id()));
+		let resolved_line = plot_line("input.log", Some("operation"), r"duration:([\d\.]+)");
+		let mut processor = LineProcessor::from_data_source(
+			resolved_line.line.data_source,
+			Some(output.clone()),
+			DEFAULT_TIMESTAMP_FORMAT,
+			"input.log".into(),
+			false,
+		)
+		.unwrap()
+		.with_sorted_timestamps(true)
+		.with_sampling(Some(Sampling::Count(10)));
+
+		processor.start_csv().unwrap();
+		// Every pair of lines is swapped, the value is the position of the line once sorted.
+		for i in 0..100u32 {
+			let position = i ^ 1;
+			let log_line = format!(
+				"2025-04-03 11:{:02}:{:02}.000 INFO main: operation duration:{}",
+				position / 60,
+				position % 60,
+				position + 1
+			);
+			let (_, matched) = processor.try_match(&log_line).unwrap();
+			let (value, timestamp) = matched.unwrap();
+			processor.process(value, timestamp);
+		}
+		let (count, _) = processor.finish_csv().unwrap();
+		assert_eq!(count, 10);
+
+		let csv = fs::read_to_string(&output).unwrap();
+		fs::remove_file(&output).unwrap();
+		let records = csv
+			.lines()
+			.skip(1)
+			.map(|r| r.split(',').map(String::from).collect::<Vec<_>>())
+			.collect::<Vec<_>>();
+		assert_eq!(records.len(), 10);
+		assert!(records.is_sorted_by_key(|r| r[1].clone()), "{csv}");
+		// Counts and deltas are computed from all sorted records, before sampling.
+		for record in records {
+			assert_eq!(record[2].parse::<f64>(), record[3].parse::<f64>(), "{csv}");
+			let delta = if record[3] == "1" { "0.0" } else { "1000.0" };
+			assert_eq!(record[4], delta, "{csv}");
+		}
+	}
+
 	#[test]
 	fn test_line_processing_bad_regex() {
 		//3 captures group are incorrect
@@ -1843,4 +6186,112 @@ mod tests {
 			panic!("incorrect error value");
 		}
 	}
+
+	#[test]
+	fn test_read_annotations_file() {
+		let path =
+			std::env::temp_dir().join(format!("plox-annotations-{}.csv", std::process::id()));
+		fs::write(&path, "time,label\n11:32:48.027, deploy v1\n11:33:00.000,\"alert: a, b\"\n")
+			.unwrap();
+		let annotations =
+			read_annotations_file(&path, &TimestampFormat::from("%H:%M:%S%.3f")).unwrap();
+		let ts = |s| {
+			NaiveDate::from_ymd_opt(2025, 1, 1)
+				.unwrap()
+				.and_time(NaiveTime::parse_from_str(s, "%H:%M:%S%.3f").unwrap())
+		};
+		assert_eq!(
+			annotations,
+			[
+				(ts("11:32:48.027"), "deploy v1".to_string()),
+				(ts("11:33:00.000"), "alert: a, b".to_string())
+			]
+		);
+
+		fs::write(&path, "11:32:48.027,deploy\nyesterday,alert\n").unwrap();
+		let result = read_annotations_file(&path, &TimestampFormat::from("%H:%M:%S%.3f"));
+		fs::remove_file(&path).unwrap();
+		assert!(matches!(result, Err(Error::TimestampExtractionFailure(..))), "{result:?}");
+	}
+
+	#[test]
+	fn test_dominant_periods() {
+		// Spike every 90 seconds on top of a noise-free baseline, sampled every second.
+		let samples =
+			(0..1800).map(|t| if t % 90 == 0 { 900.0 } else { 100.0 }).collect::<Vec<_>>();
+		let periods = dominant_periods(&samples, 1.0, 3);
+		assert_eq!(periods.len(), 1, "{periods:?}");
+		assert!((periods[0].period - 90.0).abs() < 1e-6, "{periods:?}");
+		assert!((periods[0].power_share - 1.0).abs() < 1e-6, "{periods:?}");
+
+		// Two sine waves, the stronger one first.
+		let samples = (0..1000)
+			.map(|t| {
+				let t = t as f64 * std::f64::consts::TAU;
+				3.0 * (t / 50.0).sin() + (t / 8.0).sin()
+			})
+			.collect::<Vec<_>>();
+		let periods = dominant_periods(&samples, 0.5, 5);
+		assert_eq!(periods.len(), 2, "{periods:?}");
+		assert!((periods[0].period - 25.0).abs() < 1e-6, "{periods:?}");
+		assert!((periods[1].period - 4.0).abs() < 1e-6, "{periods:?}");
+		assert!((periods[0].power_share - 0.9).abs() < 1e-6, "{periods:?}");
+
+		assert!(dominant_periods(&[1.0; 100], 1.0, 3).is_empty());
+		assert!(dominant_periods(&[1.0, 2.0], 1.0, 3).is_empty());
+	}
+
+	#[test]
+	fn test_resample() {
+		let ts = |s| NaiveDate::from_ymd_opt(2025, 1, 1).unwrap().and_hms_opt(0, 0, s).unwrap();
+		let values = [(ts(0), 1.0), (ts(1), 3.0), (ts(4), 5.0), (ts(5), 7.0)];
+		assert_eq!(resample(&values, 2.0).unwrap(), [2.0, 4.0, 6.0]);
+		assert_eq!(resample(&values, 1.0).unwrap(), [1.0, 3.0, 4.0, 4.0, 5.0, 7.0]);
+		assert!(matches!(resample(&values, 1e-9), Err(Error::TooManySamples(..))));
+	}
+
+	#[test]
+	fn test_auto_cache_root() {
+		let vars = |xdg: Option<&'static str>, home: Option<&'static str>| {
+			move |name: &str| match name {
+				"XDG_CACHE_HOME" => xdg.map(String::from),
+				"HOME" => home.map(String::from),
+				_ => None,
+			}
+		};
+		let root = |xdg, home| auto_cache_root(vars(xdg, home));
+		assert_eq!(root(Some("/xdg"), Some("/home/a")), Some("/xdg/plox".into()));
+		assert_eq!(root(None, Some("/home/a")), Some("/home/a/.cache/plox".into()));
+		assert_eq!(root(Some("xdg"), Some("/home/a")), Some("/home/a/.cache/plox".into()));
+		assert_eq!(root(None, None), None);
+	}
+
+	#[test]
+	fn test_cache_dir_fallback() {
+		fs::create_dir_all("tests/.output").unwrap();
+		let output = Path::new("tests/.output").canonicalize().unwrap();
+		let ctx = InputFilesContext::default();
+		let log_file = output.join("cache-dir-fallback.log");
+		assert_eq!(ctx.get_cache_dir_inner(&log_file).unwrap(), output.join(".plox"));
+
+		// `.plox/` cannot be created under the regular file.
+		let file = output.join("cache-dir-fallback");
+		fs::write(&file, "").unwrap();
+		let log_file = file.join("a.log");
+		assert_eq!(
+			ctx.get_cache_dir_inner(&log_file).unwrap(),
+			mirrored_cache_dir(&fallback_cache_root().unwrap(), &log_file)
+		);
+		assert!(!file.join(".plox").exists());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_private_temp_dir() {
+		use std::os::unix::fs::PermissionsExt;
+		let dir = private_temp_dir().unwrap();
+		assert!(dir.file_name().unwrap().to_string_lossy().starts_with("plox-"));
+		assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+		assert_eq!(private_temp_dir().unwrap(), dir);
+	}
 }
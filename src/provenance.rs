@@ -0,0 +1,134 @@
+//! Provenance of the generated graphs.
+//!
+//! Generated gnuplot scripts and plotly html pages contain the plox version, the full command
+//! line, the size and modification time of the input files (or their SHA-256 hashes with
+//! `--hash-inputs`) and the generation time, so an old graph can be reproduced (and its inputs
+//! verified) without remembering the exact invocation.
+//!
+//! If the `SOURCE_DATE_EPOCH` environment variable is set, it is used as the generation time, so
+//! repeated runs produce byte-identical outputs.
+
+use chrono::{DateTime, Local, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+	fs::File,
+	io,
+	path::{Path, PathBuf},
+};
+
+/// Provenance of the generated output.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+	/// Version of plox.
+	pub version: &'static str,
+	/// Full command line (quoted for the shell).
+	pub command_line: String,
+	/// Input files along with their identity: size and modification time, or SHA-256 hash of their
+	/// content (if they could be read).
+	pub inputs: Vec<(PathBuf, Option<String>)>,
+	/// Generation time.
+	pub generated: String,
+}
+
+impl Provenance {
+	/// Collects the provenance of the output generated from given input files.
+	///
+	/// Input files are hashed only if `hash_inputs` is set.
+	pub fn collect(inputs: &[PathBuf], hash_inputs: bool) -> Self {
+		// Only the name of the executable is kept, its location is not relevant.
+		let command_line = std::env::args()
+			.enumerate()
+			.map(|(i, arg)| match Path::new(&arg).file_name() {
+				Some(name) if i == 0 => name.to_string_lossy().into_owned(),
+				_ => crate::process_log::shell_quote(&arg),
+			})
+			.collect::<Vec<_>>()
+			.join(" ");
+		let generated = std::env::var("SOURCE_DATE_EPOCH")
+			.ok()
+			.and_then(|epoch| epoch.parse().ok())
+			.and_then(|epoch| DateTime::from_timestamp(epoch, 0))
+			.map(|t| t.format("%Y-%m-%d %H:%M:%S %z").to_string())
+			.unwrap_or_else(|| Local::now().format("%Y-%m-%d %H:%M:%S %z").to_string());
+		Self {
+			version: env!("CARGO_PKG_VERSION"),
+			command_line,
+			inputs: inputs
+				.iter()
+				.map(|i| {
+					let identity = if hash_inputs {
+						sha256_file(i).map(|h| format!("sha256:{h}"))
+					} else {
+						file_stamp(i)
+					};
+					(i.clone(), identity.ok())
+				})
+				.collect(),
+			generated,
+		}
+	}
+
+	/// Provenance as `(key, value)` entries, one per input file.
+	pub fn entries(&self) -> Vec<(&'static str, String)> {
+		let mut entries = vec![
+			("version", format!("plox {}", self.version)),
+			("command", self.command_line.clone()),
+		];
+		entries.extend(self.inputs.iter().map(|(path, identity)| {
+			(
+				"input",
+				format!("{} {}", path.display(), identity.as_deref().unwrap_or("unavailable")),
+			)
+		}));
+		entries.push(("generated", self.generated.clone()));
+		entries
+	}
+}
+
+/// Returns the size and the modification time (UTC) of the file.
+fn file_stamp(path: &Path) -> io::Result<String> {
+	let metadata = std::fs::metadata(path)?;
+	let modified = DateTime::<Utc>::from(metadata.modified()?);
+	Ok(format!("size:{} mtime:{}", metadata.len(), modified.format("%Y-%m-%dT%H:%M:%S%.fZ")))
+}
+
+/// Returns the hex encoded SHA-256 hash of the file content.
+fn sha256_file(path: &Path) -> io::Result<String> {
+	let mut hasher = Sha256::new();
+	io::copy(&mut File::open(path)?, &mut hasher)?;
+	Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_provenance_entries() {
+		let path = std::env::temp_dir().join(format!("plox-provenance-{}", std::process::id()));
+		std::fs::write(&path, "abc").unwrap();
+		let inputs = [path.clone(), PathBuf::from("no-such-file.log")];
+		let provenance = Provenance::collect(&inputs, true);
+		let stamped = Provenance::collect(&inputs, false);
+		std::fs::remove_file(&path).unwrap();
+
+		let entries = provenance.entries();
+		assert_eq!(entries[0], ("version", format!("plox {}", env!("CARGO_PKG_VERSION"))));
+		assert_eq!(
+			entries[2],
+			(
+				"input",
+				format!(
+					"{} sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+					path.display()
+				)
+			)
+		);
+		assert_eq!(entries[3], ("input", "no-such-file.log unavailable".to_string()));
+		assert_eq!(entries[4].0, "generated");
+
+		let entries = stamped.entries();
+		assert!(entries[2].1.starts_with(&format!("{} size:3 mtime:", path.display())));
+		assert_eq!(entries[3], ("input", "no-such-file.log unavailable".to_string()));
+	}
+}
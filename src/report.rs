@@ -0,0 +1,75 @@
+//! Assembling of the HTML report (`plox report`) from many graph configs.
+//!
+//! Every graph config is processed exactly as with `plox graph --config`, and becomes a
+//! [`ReportSection`]: its resolved panels together with the stats of every line. Sections are
+//! rendered into a single page by [`crate::backend::render_report`].
+
+use crate::{
+	error::Error,
+	graph_cli_builder,
+	graph_config::{GraphFullContext, InputFilesContext},
+	logging::APPV,
+	process_log::{self, DEFAULT_PERCENTILES, DEFAULT_STAT_METRICS},
+	resolved_graph_config::{self, ResolvedGraphConfig},
+};
+use std::path::Path;
+use tracing::warn;
+
+/// Single section of the report, rendered from one graph config.
+#[derive(Debug)]
+pub struct ReportSection {
+	/// Title displayed above the section.
+	pub title: String,
+	/// Graph config with extracted data and resolved ranges.
+	pub config: ResolvedGraphConfig,
+	/// Context the graph config was resolved with.
+	pub context: GraphFullContext,
+	/// Stats of every non-empty line: title of the line and `(metric, value)` pairs.
+	pub stats: Vec<(String, Vec<(String, f64)>)>,
+}
+
+impl ReportSection {
+	/// Processes the input files for the graph config read from `config_path`.
+	///
+	/// The name of the config file is used as the title, unless `title` is given.
+	pub fn build(
+		config_path: &Path,
+		title: Option<String>,
+		input_files_ctx: &InputFilesContext,
+	) -> Result<Self, Error> {
+		let context =
+			GraphFullContext { input_files_ctx: input_files_ctx.clone(), ..Default::default() };
+		let (config, context) = graph_cli_builder::load_config_file(config_path, context)?;
+		let title = title.unwrap_or_else(|| {
+			config_path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+		});
+
+		let mut config = resolved_graph_config::expand_graph_config_with_ctx(&config, &context)?;
+		process_log::process_inputs(&mut config, &context.input_files_ctx)?;
+
+		if config.is_empty() {
+			warn!(target:APPV, section = title, "No data for any line of the report section.");
+			return Ok(Self { title, config, context, stats: vec![] });
+		}
+
+		resolved_graph_config::prepare_for_render(&mut config, &context)?;
+		let has_multiple_input_files = context.logical_input().len() > 1;
+
+		let mut stats = vec![];
+		for panel in &config.panels {
+			for line in panel.lines.iter().filter(|line| !line.line.data_source.is_annotation()) {
+				let Some(line_stats) = line.plotted_values_stats(
+					panel.time_range,
+					&DEFAULT_STAT_METRICS,
+					&DEFAULT_PERCENTILES,
+				)?
+				else {
+					continue;
+				};
+				stats.push((line.title(has_multiple_input_files), line_stats));
+			}
+		}
+
+		Ok(Self { title, config, context, stats })
+	}
+}
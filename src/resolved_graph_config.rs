@@ -10,22 +10,34 @@
 #![allow(private_interfaces)]
 #![allow(unused_variables)]
 use crate::{
+	align_ranges, baseline,
 	error::Error,
+	graph_cli_builder,
 	graph_config::{
-		DataSource, GraphConfig, GraphFullContext, Line, LineParams, OutputGraphContext,
-		PanelParams,
+		AxisRange, Color, DashStyle, DataSource, GraphConfig, GraphFullContext, InputFilesContext,
+		LabelFilter, Line, LineParams, MarkerType, OutputGraphContext, Panel, PanelParams,
 	},
+	logging::APPV,
+	period_fold, process_log, transforms,
 };
 use chrono::NaiveDateTime;
 use clap::Args;
 use serde::{Deserialize, Serialize};
 use std::{
+	collections::HashMap,
 	fmt::Display,
 	fs,
 	path::{Path, PathBuf},
 	str::FromStr,
+	time::Instant,
 };
-use tracing::info;
+use strum::IntoEnumIterator;
+use tracing::{debug, info, warn};
+
+/// The CSV file of the line was accessed before it was resolved.
+#[derive(Debug, thiserror::Error)]
+#[error("CSV file of line '{0}' (input file: '{1}') is not resolved (this is bug)")]
+pub struct UnresolvedCsvFileError(String, PathBuf);
 
 #[derive(Debug)]
 pub struct ResolvedGraphConfig {
@@ -38,12 +50,231 @@ impl ResolvedGraphConfig {
 		self.panels.iter().flat_map(|panel| panel.lines.iter())
 	}
 
+	/// Sets the labels of the input files (see `--input-label`) displayed in titles.
+	///
+	/// Labels referring to no input file are reported.
+	pub fn assign_input_labels(&mut self, ctx: &InputFilesContext) {
+		let logical_input = ctx.logical_input();
+		for label in ctx.input_labels() {
+			if !logical_input.iter().any(|input| label.refers_to(input, &logical_input)) {
+				warn!("Input label '{label}' does not refer to any input file.");
+			}
+		}
+		for panel in &mut self.panels {
+			panel.input_label =
+				panel.input_file_name.as_ref().and_then(|input| ctx.input_label(input));
+			for line in &mut panel.lines {
+				line.source_label = ctx.input_label(line.source_file_name());
+			}
+		}
+	}
+
+	/// Splits the config into the configs containing only the lines of a single source file (see
+	/// `--per-file-output`), together with the name of the file used for the output file name.
+	///
+	/// Panels with no lines of the file are skipped, files with no data are reported and skipped.
+	pub fn split_per_file(&self) -> Vec<(String, ResolvedGraphConfig)> {
+		let mut files: Vec<&PathBuf> = vec![];
+		for line in self.all_lines() {
+			if !files.contains(&line.source_file_name()) {
+				files.push(line.source_file_name());
+			}
+		}
+		files
+			.into_iter()
+			.filter_map(|file| {
+				let panels = self
+					.panels
+					.iter()
+					.filter_map(|panel| {
+						let mut panel = panel.clone();
+						panel.lines.retain(|line| line.source_file_name() == file);
+						(!panel.lines.is_empty()).then_some(panel)
+					})
+					.collect::<Vec<_>>();
+				let config = ResolvedGraphConfig { panels };
+				if config.is_empty() {
+					warn!("No data for any line of '{}', no output is rendered.", file.display());
+					return None;
+				}
+				let name = config.all_lines().next()?.source_display_name().replace('/', "_");
+				Some((name, config))
+			})
+			.collect()
+	}
+
+	/// Splits the config into the pages containing at most `max_panels` panels each (see
+	/// `--max-panels-per-page`).
+	pub fn into_pages(self, max_panels: usize) -> Vec<ResolvedGraphConfig> {
+		self.panels
+			.chunks(max_panels)
+			.map(|panels| ResolvedGraphConfig { panels: panels.to_vec() })
+			.collect()
+	}
+
+	/// Splits the config into the configs rendered as separate outputs, per input file (see
+	/// [`Self::split_per_file`]) and into pages (see [`Self::into_pages`]).
+	///
+	/// Every config comes with the suffix of the output file name, e.g. `node3-2` for the second
+	/// page of `node3` input file. No suffix is given if the config is not split.
+	pub fn into_outputs(
+		self,
+		ctx: &GraphFullContext,
+	) -> Vec<(Option<String>, ResolvedGraphConfig)> {
+		let outputs = if ctx.per_file_output() {
+			let configs = self.split_per_file();
+			configs.into_iter().map(|(name, config)| (Some(name), config)).collect()
+		} else {
+			vec![(None, self)]
+		};
+		let Some(max_panels) = ctx.max_panels_per_page() else {
+			return outputs;
+		};
+		outputs
+			.into_iter()
+			.flat_map(|(name, config)| {
+				let pages = config.into_pages(max_panels);
+				let paged = pages.len() > 1;
+				pages.into_iter().enumerate().map(move |(i, page)| {
+					let suffix = match (&name, paged) {
+						(Some(name), true) => Some(format!("{name}-{}", i + 1)),
+						(None, true) => Some((i + 1).to_string()),
+						(name, false) => name.clone(),
+					};
+					(suffix, page)
+				})
+			})
+			.collect()
+	}
+
+	/// Share of the graph height taken by every panel, given by the height ratios of the panels
+	/// (see [`PanelParams::height`]). Missing ratios count as `1`.
+	///
+	/// Empty panels are not drawn and take no height, so the shares of the drawn panels sum up to
+	/// `1`. Both backends lay out the panels with these shares.
+	pub fn panel_heights(&self) -> Vec<f64> {
+		height_fractions(
+			self.panels
+				.iter()
+				.map(|panel| (!panel.is_empty()).then(|| panel.params.height.unwrap_or(1.0))),
+		)
+	}
+
+	/// Expands the placeholders of the panel and line titles:
+	/// - `{file}`: the label (see `--input-label`) or the file stem of the input file,
+	/// - `{guard}`, `{pattern}`: the guard and the pattern (or field) of the line,
+	/// - `{index}`: the index of the panel, or the index of the line within its panel.
+	///
+	/// Panel titles use the guard and pattern of the first line of the panel, and its input file
+	/// unless the panel was duplicated per input file. Titles containing `{file}` are not
+	/// followed by the file stem.
+	pub fn expand_title_placeholders(&mut self) {
+		for (panel_index, panel) in self.panels.iter_mut().enumerate() {
+			if let Some(panel_title) = &panel.params.panel_title {
+				let first_line = panel.lines.first();
+				let file = match &panel.input_file_name {
+					Some(_) => panel.input_display_name(),
+					None => first_line.map(ResolvedLine::source_display_name),
+				};
+				panel.file_in_title = panel_title.contains("{file}");
+				panel.params.panel_title = Some(expand_placeholders(
+					panel_title,
+					file.unwrap_or_default(),
+					first_line,
+					panel_index,
+				));
+			}
+			for (line_index, line) in panel.lines.iter_mut().enumerate() {
+				if let Some(title) = &line.line.params.title {
+					line.file_in_title = title.contains("{file}");
+					let title = expand_placeholders(
+						title,
+						line.source_display_name(),
+						Some(line),
+						line_index,
+					);
+					line.line.params.title = Some(title);
+				}
+			}
+		}
+	}
+
+	/// Assigns every input file a stable color, so the lines of the same file are drawn with the
+	/// same color in every panel. Lines of the same file within the panel get subsequent dash
+	/// styles.
+	///
+	/// Explicitly configured colors and dash styles are kept.
+	pub fn assign_per_file_styles(&mut self, input: &[PathBuf]) {
+		let colors = Color::iter().collect::<Vec<_>>();
+		let dashes = DashStyle::iter().collect::<Vec<_>>();
+		let mut files = input.to_vec();
+		for panel in &mut self.panels {
+			let mut lines_per_file: HashMap<usize, usize> = HashMap::new();
+			for line in &mut panel.lines {
+				let file = line.source_file_name();
+				let file_index = files.iter().position(|f| f == file).unwrap_or_else(|| {
+					files.push(file.clone());
+					files.len() - 1
+				});
+				let nth = lines_per_file.entry(file_index).or_default();
+				let params = &mut line.line.params;
+				params.line_color.get_or_insert(colors[file_index % colors.len()]);
+				params.dash_style.get_or_insert(dashes[*nth % dashes.len()]);
+				*nth += 1;
+			}
+		}
+	}
+
+	/// Makes the lines drawn for many input files (see `--per-file-lines`) distinguishable within
+	/// the shared panel: every such line gets its own color, and every input file gets a stable
+	/// dash style and marker type.
+	///
+	/// Explicitly configured colors, dash styles and marker types are kept.
+	pub fn assign_per_file_line_styles(&mut self) {
+		let colors = Color::iter().collect::<Vec<_>>();
+		let dashes = DashStyle::iter().collect::<Vec<_>>();
+		let markers = MarkerType::iter().collect::<Vec<_>>();
+		for panel in &mut self.panels {
+			// Lines populated to all inputs are expanded to consecutive lines, starting with the
+			// first input file.
+			let mut logical_line = 0;
+			for (i, line) in panel.lines.iter_mut().enumerate() {
+				let file_index = match line.source {
+					ResolvedSource::PopulatedInput { index, .. } => index,
+					_ => 0,
+				};
+				if i > 0 && file_index == 0 {
+					logical_line += 1;
+				}
+				let params = &mut line.line.params;
+				params.line_color.get_or_insert(colors[logical_line % colors.len()]);
+				params.dash_style.get_or_insert(dashes[file_index % dashes.len()]);
+				params.marker_type.get_or_insert(markers[file_index % markers.len()]);
+			}
+		}
+	}
+
+	/// Tells if there is no data for any line of the graph.
+	pub fn is_empty(&self) -> bool {
+		self.panels.iter().all(ResolvedPanel::is_empty)
+	}
+
 	pub fn all_lines_count(&self) -> usize {
 		self.panels.iter().map(|panel| panel.lines.len()).sum()
 	}
+
+	/// Builds the single line config reading the data directly from the existing CSV cache file.
+	///
+	/// No log file is involved, the cache file is also used as the line's source.
+	pub fn new_from_csv_file(line: Line, csv_file: &Path) -> Self {
+		let mut line = ResolvedLine::from_explicit_name(line, csv_file.to_path_buf());
+		line.set_shared_csv_filename(csv_file);
+		line.from_cache = true;
+		Self { panels: vec![ResolvedPanel::new_with_lines(vec![line])] }
+	}
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ResolvedPanel {
 	pub lines: Vec<ResolvedLine>,
 	pub params: PanelParams,
@@ -51,6 +282,17 @@ pub struct ResolvedPanel {
 	/// If panel was duplicated due to per-file-panels this will be set to the source file name.
 	input_file_name: Option<PathBuf>,
 
+	/// Label of the source file displayed instead of its file stem (see `--input-label`).
+	input_label: Option<String>,
+
+	/// Tells if the panel title contains the name of the input file (see
+	/// [`ResolvedGraphConfig::expand_title_placeholders`]).
+	file_in_title: bool,
+
+	/// If panel was duplicated due to per-file-panels this will be set to the index of the
+	/// source panel in the graph config.
+	source_panel: Option<usize>,
+
 	/// Final time range to use for this panel when plotting.
 	///
 	/// This is derived from the time ranges of all its lines, using the
@@ -60,6 +302,12 @@ pub struct ResolvedPanel {
 	/// Used to generate the `set xrange [...]` directive for Gnuplot.
 	//todo: do something with pub
 	pub(crate) time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+
+	/// Primary Y-axis range shared by all duplicates of the panel (see `--shared-yrange`).
+	pub(crate) shared_yrange: Option<AxisRange>,
+
+	/// Secondary Y-axis range shared by all duplicates of the panel (see `--shared-yrange`).
+	pub(crate) shared_y2range: Option<AxisRange>,
 }
 
 impl ResolvedPanel {
@@ -75,23 +323,80 @@ impl ResolvedPanel {
 		&self.input_file_name
 	}
 
+	/// Lines in the order they shall be drawn (see [`LineParams::z_order`]).
+	pub fn lines_in_drawing_order(&self) -> Vec<&ResolvedLine> {
+		let mut lines = self.lines.iter().collect::<Vec<_>>();
+		lines.sort_by_key(|line| line.line.params.z_order.unwrap_or_default());
+		lines
+	}
+
+	/// Threshold above which the values of the lines drawn on the primary Y-axis are highlighted
+	/// (see [`PanelParams::highlight_breaches`]).
+	pub fn breach_threshold(&self) -> Option<f64> {
+		self.params.hline.filter(|_| self.params.highlight_breaches.unwrap_or(false))
+	}
+
+	/// First line of every source file drawn in the swimlane panel (see
+	/// [`PanelParams::swimlanes`]), in order of appearance. Empty for regular panels.
+	fn swimlane_lines(&self) -> Vec<&ResolvedLine> {
+		let mut rows: Vec<&ResolvedLine> = vec![];
+		if self.params.swimlanes.unwrap_or(false) {
+			for line in self.lines.iter().filter(|line| !line.line.data_source.is_annotation()) {
+				if !rows.iter().any(|row| row.source_file_name() == line.source_file_name()) {
+					rows.push(line);
+				}
+			}
+		}
+		rows
+	}
+
+	/// Rows of the swimlane panel: the Y-value and the name of every source file. Rows are laid
+	/// out top-down, so the first file gets the highest value. Empty for regular panels.
+	pub fn swimlanes(&self) -> Vec<(usize, String)> {
+		let rows = self.swimlane_lines();
+		let count = rows.len();
+		rows.into_iter()
+			.enumerate()
+			.map(|(i, line)| (count - 1 - i, line.source_display_name()))
+			.collect()
+	}
+
+	/// Y-value of the swimlane row the line is drawn at, `None` for regular panels.
+	pub fn swimlane_row(&self, line: &ResolvedLine) -> Option<usize> {
+		let rows = self.swimlane_lines();
+		let i = rows.iter().position(|row| row.source_file_name() == line.source_file_name())?;
+		Some(rows.len() - 1 - i)
+	}
+
+	/// Index of the graph config panel this panel was duplicated from (if it was duplicated).
+	pub fn source_panel(&self) -> Option<usize> {
+		self.source_panel
+	}
+
+	/// Range of the secondary Y-axis: explicitly configured one, or shared across duplicates.
+	pub fn y2range(&self) -> Option<AxisRange> {
+		self.params.y2range.or(self.shared_y2range)
+	}
+
+	/// The label (see `--input-label`) or the file stem of the input file the panel was
+	/// duplicated for.
+	fn input_display_name(&self) -> Option<String> {
+		let input_file_name = self.input_file_name.as_ref()?;
+		Some(self.input_label.clone().unwrap_or_else(|| {
+			input_file_name
+				.file_stem()
+				.expect("filename is validated at this point")
+				.to_string_lossy()
+				.into_owned()
+		}))
+	}
+
 	pub fn title(&self) -> Vec<String> {
-		match (&self.params.panel_title, &self.input_file_name) {
-			(Some(panel_title), Some(input_file_name)) => {
-				let file_stem = input_file_name
-					.file_stem()
-					.expect("filename is validated at this point")
-					.to_string_lossy();
-				vec![panel_title.clone(), format!("[{}]", file_stem)]
-			},
+		match (&self.params.panel_title, self.input_display_name()) {
+			(Some(panel_title), Some(_)) if self.file_in_title => vec![panel_title.clone()],
+			(Some(panel_title), Some(input)) => vec![panel_title.clone(), format!("[{input}]")],
 			(Some(panel_title), None) => vec![panel_title.clone()],
-			(None, Some(input_file_name)) => {
-				let file_stem = input_file_name
-					.file_stem()
-					.expect("filename is validated at this point")
-					.to_string_lossy();
-				vec![format!("[{}]", file_stem)]
-			},
+			(None, Some(input)) => vec![format!("[{input}]")],
 			(None, None) => Default::default(),
 		}
 	}
@@ -132,6 +437,20 @@ pub struct ResolvedLine {
 	/// This is used for panel-level range calculations and alignment.
 	//todo pub
 	time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+
+	/// Label of the source file displayed instead of its file stem (see `--input-label`).
+	source_label: Option<String>,
+
+	/// Tells if the line title contains the name of the input file (see
+	/// [`ResolvedGraphConfig::expand_title_placeholders`]).
+	file_in_title: bool,
+
+	/// Label of the period the line was folded into (see [`crate::period_fold`]).
+	period_label: Option<String>,
+
+	/// Tells if the values are read from the existing CSV cache file (`--from-cache`) instead of
+	/// the log file. The cache file is the source of the line then.
+	from_cache: bool,
 }
 
 impl ResolvedLine {
@@ -139,6 +458,22 @@ impl ResolvedLine {
 		self.data_points_count == 0
 	}
 
+	/// The label (see `--input-label`) or the file stem of the source file.
+	pub fn source_display_name(&self) -> String {
+		self.source_label.clone().unwrap_or_else(|| {
+			self.source_file_name()
+				.file_stem()
+				.expect("filename is validated at this point")
+				.to_string_lossy()
+				.into_owned()
+		})
+	}
+
+	/// Tells if the line title contains the name of the source file.
+	pub fn file_in_title(&self) -> bool {
+		self.file_in_title
+	}
+
 	pub fn from_explicit_name(line: Line, file_name: PathBuf) -> Self {
 		Self {
 			line,
@@ -146,6 +481,10 @@ impl ResolvedLine {
 			shared_csv_file: None,
 			data_points_count: 0,
 			time_range: None,
+			source_label: None,
+			file_in_title: false,
+			period_label: None,
+			from_cache: false,
 		}
 	}
 
@@ -160,6 +499,10 @@ impl ResolvedLine {
 				shared_csv_file: None,
 				data_points_count: 0,
 				time_range: None,
+				source_label: None,
+				file_in_title: false,
+				period_label: None,
+				from_cache: false,
 			}),
 			Some((file_id, file_name)) => {
 				ResolvedSource::try_match_input(line.source(), file_id, file_name).map(|source| {
@@ -169,6 +512,10 @@ impl ResolvedLine {
 						shared_csv_file: None,
 						data_points_count: 0,
 						time_range: None,
+						source_label: None,
+						file_in_title: false,
+						period_label: None,
+						from_cache: false,
 					}
 				})
 			},
@@ -180,8 +527,12 @@ impl ResolvedLine {
 		self.shared_csv_file.clone()
 	}
 
-	pub fn expect_shared_csv_filename(&self) -> PathBuf {
-		self.shared_csv_filename().expect("All shared CVS are resolved at this point.")
+	/// The name of final csv file to be used. Fails if the file was not resolved yet (i.e. the
+	/// line was not processed with [`crate::process_log::process_inputs`]).
+	pub fn resolved_csv_filename(&self) -> Result<PathBuf, UnresolvedCsvFileError> {
+		self.shared_csv_filename().ok_or_else(|| {
+			UnresolvedCsvFileError(self.line.data_source.title(), self.source_file_name().clone())
+		})
 	}
 
 	/// Set the name of final csv file to be used.
@@ -193,6 +544,21 @@ impl ResolvedLine {
 		self.data_points_count = count;
 	}
 
+	/// Label of the period the line was folded into (see [`crate::period_fold`]).
+	pub fn period_label(&self) -> Option<&str> {
+		self.period_label.as_deref()
+	}
+
+	pub fn set_period_label(&mut self, label: String) {
+		self.period_label = Some(label);
+	}
+
+	/// Tells if the values are read from the existing CSV cache file (`--from-cache`), no log
+	/// file is available.
+	pub fn is_from_cache(&self) -> bool {
+		self.from_cache
+	}
+
 	pub fn set_time_range(&mut self, start: NaiveDateTime, end: NaiveDateTime) {
 		self.time_range = Some((start, end));
 	}
@@ -284,6 +650,9 @@ enum LineSource {
 
 impl Line {
 	fn source(&self) -> LineSource {
+		if let DataSource::CsvFile(spec) = &self.data_source {
+			return LineSource::FileName(spec.csv_file.clone());
+		}
 		match (&self.params.file_name, self.params.file_id) {
 			(Some(path), _) => LineSource::FileName(PathBuf::from(path)),
 			(None, Some(id)) => LineSource::FileId(id),
@@ -291,11 +660,152 @@ impl Line {
 		}
 	}
 }
+
+/// Checks if every `file_id` given in the config refers to one of the input files.
+fn validate_file_ids(graph: &GraphConfig, input: &[PathBuf]) -> Result<(), Error> {
+	let invalid_line = graph
+		.panels
+		.iter()
+		.flat_map(|panel| panel.lines.iter())
+		.find(|line| matches!(line.source(), LineSource::FileId(id) if id >= input.len()));
+
+	let Some(line) = invalid_line else { return Ok(()) };
+	let inputs = if input.is_empty() {
+		"none".to_string()
+	} else {
+		input
+			.iter()
+			.enumerate()
+			.map(|(id, file)| format!("{id}: '{}'", file.display()))
+			.collect::<Vec<_>>()
+			.join(", ")
+	};
+	Err(Error::InvalidFileId {
+		line: line.data_source.title(),
+		id: line.params.file_id.expect("file id is set for LineSource::FileId"),
+		inputs,
+	})
+}
+
+/// Expands the graph config (see [`expand_graph_config`]).
+///
+/// If many input files are drawn in shared panels, every file gets its own color (see
+/// [`ResolvedGraphConfig::assign_per_file_styles`]), or its own dash style and marker type with
+/// `--per-file-lines` (see [`ResolvedGraphConfig::assign_per_file_line_styles`]).
 pub fn expand_graph_config_with_ctx(
 	graph: &GraphConfig,
 	ctx: &GraphFullContext,
 ) -> Result<ResolvedGraphConfig, Error> {
-	expand_graph_config(graph, ctx.input(), ctx.output_graph_ctx.per_file_panels())
+	let per_file_panels = ctx.output_graph_ctx.per_file_panels();
+	let input = ctx.logical_input();
+	let repeated;
+	let graph = if graph.panels.iter().any(|panel| panel.params.repeat_for.is_some()) {
+		repeated = expand_repeated_panels(graph, &ctx.input_files_ctx)?;
+		&repeated
+	} else {
+		graph
+	};
+	let mut config = expand_graph_config(graph, &input, per_file_panels)?;
+	config.assign_input_labels(&ctx.input_files_ctx);
+	config.expand_title_placeholders();
+	if !per_file_panels && input.len() > 1 {
+		if ctx.output_graph_ctx.per_file_lines() {
+			config.assign_per_file_line_styles();
+		} else {
+			config.assign_per_file_styles(&input);
+		}
+	}
+	Ok(config)
+}
+
+/// Prepares the graph config with extracted data for rendering: applies the transforms, baselines,
+/// normalization and period folding of the lines, and resolves the panel ranges.
+///
+/// Must be called after [`crate::process_log::process_inputs`].
+pub fn prepare_for_render(
+	config: &mut ResolvedGraphConfig,
+	ctx: &GraphFullContext,
+) -> Result<(), Error> {
+	let resample = ctx.resample().map_err(graph_cli_builder::Error::GeneralCliParseError)?;
+	let has_multiple_input_files = ctx.logical_input().len() > 1;
+	transforms::apply_transforms(config, resample, has_multiple_input_files)?;
+	baseline::subtract_baselines(config, has_multiple_input_files)?;
+	transforms::normalize_lines(config, has_multiple_input_files)?;
+	if let Some(period) = ctx.fold_period() {
+		period_fold::fold_periods(config, period)?;
+	}
+
+	let now = Instant::now();
+	align_ranges::resolve_panels_ranges(config, ctx)?;
+	debug!(target:APPV, "Ranges resolved in: {:?}", now.elapsed());
+
+	if ctx.shared_yrange() {
+		process_log::resolve_shared_y_ranges(config)?;
+	}
+	Ok(())
+}
+
+/// Replaces the title placeholders (see [`ResolvedGraphConfig::expand_title_placeholders`]).
+fn expand_placeholders(
+	template: &str,
+	file: String,
+	line: Option<&ResolvedLine>,
+	index: usize,
+) -> String {
+	let guard = line.and_then(|line| line.guard().clone()).unwrap_or_default();
+	let pattern = line.map(ResolvedLine::raw_pattern).unwrap_or_default();
+	template
+		.replace("{file}", &file)
+		.replace("{guard}", &guard)
+		.replace("{pattern}", &pattern)
+		.replace("{index}", &index.to_string())
+}
+
+/// Instantiates the panels repeated per label (see [`PanelParams::repeat_for`]).
+///
+/// Every instance contains all lines of the panel restricted to the log lines with its label.
+/// Panels with no labels are skipped.
+fn expand_repeated_panels(
+	graph: &GraphConfig,
+	ctx: &InputFilesContext,
+) -> Result<GraphConfig, Error> {
+	let mut panels = vec![];
+	for panel in &graph.panels {
+		let Some(label_regex) = &panel.params.repeat_for else {
+			panels.push(Panel { lines: panel.lines.clone(), params: panel.params.clone() });
+			continue;
+		};
+		let labels = match &panel.params.repeat_labels {
+			Some(labels) => labels.clone(),
+			None => crate::process_log::discover_labels(label_regex, ctx)?,
+		};
+		if labels.is_empty() {
+			warn!("No labels captured with '{label_regex}', the repeated panel is skipped.");
+		}
+		for label in labels {
+			let params = PanelParams {
+				panel_title: Some(match &panel.params.panel_title {
+					Some(title) => format!("{title}: {label}"),
+					None => label.clone(),
+				}),
+				repeat_for: None,
+				repeat_labels: None,
+				..panel.params.clone()
+			};
+			let label_filter = LabelFilter { regex: label_regex.clone(), label };
+			let lines = panel
+				.lines
+				.iter()
+				.cloned()
+				.map(|mut line| {
+					line.params.label_filter = Some(label_filter.clone());
+					line
+				})
+				.collect();
+			panels.push(Panel { lines, params });
+		}
+	}
+	Ok(GraphConfig { panels })
 }
 
 /// Expands a generic `GraphConfig` using the given `SharedGraphContext`, producing a fully resolved
@@ -315,10 +825,11 @@ pub fn expand_graph_config(
 	input: &[PathBuf],
 	per_file_panels: bool,
 ) -> Result<ResolvedGraphConfig, Error> {
+	validate_file_ids(graph, input)?;
 	let mut resolved_panels = vec![];
 
 	if per_file_panels {
-		for panel in &graph.panels {
+		for (panel_index, panel) in graph.panels.iter().enumerate() {
 			let is_any_line_to_be_populated =
 				panel.lines.iter().any(|l| l.source() == LineSource::AllInputFiles);
 
@@ -366,8 +877,9 @@ pub fn expand_graph_config(
 					resolved_panels.push(ResolvedPanel {
 						params: panel.params.clone(),
 						lines,
-						time_range: None,
 						input_file_name: Some(input_file.clone()),
+						source_panel: Some(panel_index),
+						..Default::default()
 					});
 				}
 			} else {
@@ -432,6 +944,16 @@ pub fn expand_graph_config(
 	Ok(ResolvedGraphConfig { panels: resolved_panels })
 }
 
+/// Normalizes the height ratios to the fractions summing up to `1`. `None` stands for the panel
+/// taking no height, non-positive ratios are replaced with `1`.
+fn height_fractions(ratios: impl Iterator<Item = Option<f64>>) -> Vec<f64> {
+	let ratios = ratios
+		.map(|ratio| ratio.map(|r| if r.is_finite() && r > 0.0 { r } else { 1.0 }))
+		.collect::<Vec<_>>();
+	let total = ratios.iter().flatten().sum::<f64>();
+	ratios.into_iter().map(|ratio| ratio.map_or(0.0, |r| r / total)).collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use tracing::trace;
@@ -440,8 +962,8 @@ mod tests {
 	use crate::{
 		graph_cli_builder,
 		graph_config::{
-			DEFAULT_TIMESTAMP_FORMAT, DataSource, EventDeltaSpec, FieldCaptureSpec, Panel,
-			TimestampFormat,
+			CsvFileSpec, DEFAULT_TIMESTAMP_FORMAT, DataSource, EventDeltaSpec, FieldCaptureSpec,
+			Panel, TimestampFormat,
 		},
 		logging::init_tracing_test,
 	};
@@ -452,6 +974,10 @@ mod tests {
 				DataSource::EventValue { ref pattern, .. }
 				| DataSource::EventCount { ref pattern, .. }
 				| DataSource::EventDelta(EventDeltaSpec { ref pattern, .. })
+				| DataSource::Annotation { ref pattern, .. }
+				| DataSource::JsonField { path: ref pattern, .. }
+				| DataSource::LogfmtField { key: ref pattern, .. }
+				| DataSource::CsvFile(CsvFileSpec { value_column: ref pattern, .. })
 				| DataSource::FieldValue(FieldCaptureSpec { field: ref pattern, .. }) => pattern.clone(),
 			}
 		}
@@ -529,6 +1055,229 @@ mod tests {
 		check_lines!(resolved, 1, [2], vec![vec!["A", "B"]], vec![vec!["x", "y"]]);
 	}
 
+	#[test]
+	fn test_populate_invalid_file_id() {
+		let inputs = [
+			vec!["--input", "A,B", "--plot", "x", "--plot", "y", "--file-id", "2"],
+			vec!["--input", "A,B", "--per-file-panels", "--plot", "y", "--file-id", "2"],
+		];
+		for input in inputs {
+			let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+			let error = expand_graph_config_with_ctx(&config, &ctx).unwrap_err();
+			assert_eq!(error.code(), "invalid-file-id");
+			assert_eq!(
+				error.to_string(),
+				"Invalid file id 2 of line 'value of y'. Available input files: 0: 'A', 1: 'B'"
+			);
+		}
+	}
+
+	#[test]
+	fn test_input_labels() {
+		let input = vec![
+			"--input",
+			"logs/node1.log,logs/node3.log",
+			"--input-label",
+			"0=alpha,logs/node3.log=validator-3",
+			"--per-file-panels",
+			"--panel-title",
+			"P",
+			"--plot",
+			"x",
+		];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		assert_eq!(resolved.panels[0].title(), ["P", "[alpha]"]);
+		assert_eq!(resolved.panels[1].title(), ["P", "[validator-3]"]);
+		assert_eq!(resolved.panels[1].lines[0].title(true), "value of x (validator-3)");
+
+		let input = vec!["--input", "A,B", "--input-label", "1=b", "--plot", "x"];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let titles = resolved.panels[0].lines.iter().map(|l| l.title(true)).collect::<Vec<_>>();
+		assert_eq!(titles, ["value of x (A)", "value of x (b)"]);
+	}
+
+	#[test]
+	fn test_title_placeholders() {
+		let input = vec![
+			"--input",
+			"logs/node1.log,logs/node3.log",
+			"--input-label",
+			"1=validator-3",
+			"--per-file-panels",
+			"--panel-title",
+			"#{index} {file}: {pattern}",
+			"--plot",
+			"om",
+			"duration",
+			"--title",
+			"{guard} {pattern} {index}",
+			"--event-count",
+			"started",
+			"--title",
+			"{pattern} in {file}",
+			"--panel",
+			"--panel-title",
+			"{guard}/{unknown}",
+			"--plot",
+			"x",
+		];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let titles = |panel: &ResolvedPanel| {
+			(panel.title(), panel.lines.iter().map(|l| l.title(true)).collect::<Vec<_>>())
+		};
+		assert_eq!(
+			titles(&resolved.panels[0]),
+			(
+				vec!["#0 node1: duration".to_string()],
+				vec!["om duration 0 (node1)".to_string(), "started in node1".to_string()]
+			)
+		);
+		assert_eq!(
+			titles(&resolved.panels[1]),
+			(
+				vec!["#1 validator-3: duration".to_string()],
+				vec![
+					"om duration 0 (validator-3)".to_string(),
+					"started in validator-3".to_string()
+				]
+			)
+		);
+		assert_eq!(resolved.panels[2].title(), ["/{unknown}", "[node1]"]);
+	}
+
+	#[test]
+	fn test_paged_outputs() {
+		let outputs = |max_panels: &'static str| {
+			let input = vec![
+				"--input",
+				"A,B,C",
+				"--per-file-panels",
+				"--plot",
+				"x",
+				"--max-panels-per-page",
+				max_panels,
+			];
+			let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+			let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+			resolved
+				.into_outputs(&ctx)
+				.into_iter()
+				.map(|(suffix, config)| (suffix, config.panels.len()))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(outputs("2"), [(Some("1".to_string()), 2), (Some("2".to_string()), 1)]);
+		assert_eq!(outputs("3"), [(None, 3)]);
+	}
+
+	#[test]
+	fn test_per_file_styles() {
+		let input = vec!["--input", "A,B", "--plot", "x", "--plot", "y", "--panel", "--plot", "y"];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let styles = |panel: &ResolvedPanel| {
+			panel
+				.lines
+				.iter()
+				.map(|l| {
+					let file = l.source_file_name().to_string_lossy().into_owned();
+					(file, l.line.params.line_color.unwrap(), l.line.params.dash_style.unwrap())
+				})
+				.collect::<Vec<_>>()
+		};
+		let (a, b) = ("A".to_string(), "B".to_string());
+		assert_eq!(
+			styles(&resolved.panels[0]),
+			[
+				(a.clone(), Color::Red, DashStyle::Solid),
+				(b.clone(), Color::Blue, DashStyle::Solid),
+				(a.clone(), Color::Red, DashStyle::Dashed),
+				(b.clone(), Color::Blue, DashStyle::Dashed),
+			]
+		);
+		assert_eq!(
+			styles(&resolved.panels[1]),
+			[(a, Color::Red, DashStyle::Solid), (b, Color::Blue, DashStyle::Solid)]
+		);
+
+		let input = vec!["--input", "A,B", "--plot", "x", "--per-file-panels"];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		assert!(resolved.all_lines().all(|l| l.line.params.line_color.is_none()));
+	}
+
+	#[test]
+	fn test_per_file_line_styles() {
+		#[rustfmt::skip]
+		let input = vec![
+			"--input", "A,B", "--per-file-lines",
+			"--plot", "x",
+			"--plot", "y", "--marker-type", "plus",
+			"--plot", "z", "--file-name", "C",
+		];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let styles = resolved.panels[0]
+			.lines
+			.iter()
+			.map(|l| {
+				let params = &l.line.params;
+				(
+					l.source_file_name().to_string_lossy().into_owned(),
+					params.line_color.unwrap(),
+					params.dash_style.unwrap(),
+					params.marker_type.unwrap(),
+				)
+			})
+			.collect::<Vec<_>>();
+		let (a, b, c) = ("A".to_string(), "B".to_string(), "C".to_string());
+		assert_eq!(
+			styles,
+			[
+				(a.clone(), Color::Red, DashStyle::Solid, MarkerType::Dot),
+				(b.clone(), Color::Red, DashStyle::Dashed, MarkerType::TriangleFilled),
+				(a, Color::Blue, DashStyle::Solid, MarkerType::Plus),
+				(b, Color::Blue, DashStyle::Dashed, MarkerType::Plus),
+				(c, Color::DarkGreen, DashStyle::Solid, MarkerType::Dot),
+			]
+		);
+	}
+
+	#[test]
+	fn test_lines_in_drawing_order() {
+		#[rustfmt::skip]
+		let input = vec![
+			"--input", "A",
+			"--plot", "x", "--z-order", "1",
+			"--plot", "y",
+			"--plot", "z", "--z-order", "-1",
+			"--plot", "w",
+		];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let titles = resolved.panels[0]
+			.lines_in_drawing_order()
+			.into_iter()
+			.map(|l| l.line.data_source.title())
+			.collect::<Vec<_>>();
+		assert_eq!(titles, ["value of z", "value of y", "value of w", "value of x"]);
+	}
+
+	#[test]
+	fn test_unresolved_csv_filename() {
+		let input = vec!["--input", "A", "--plot", "x"];
+		let (config, ctx) = graph_cli_builder::build_from_cli_args(input).unwrap();
+		let mut resolved = expand_graph_config_with_ctx(&config, &ctx).unwrap();
+		let line = &mut resolved.panels[0].lines[0];
+		let err = line.resolved_csv_filename().unwrap_err();
+		assert!(err.to_string().contains("(input file: 'A') is not resolved"));
+
+		line.set_shared_csv_filename(Path::new("a.csv"));
+		assert_eq!(line.resolved_csv_filename().unwrap(), PathBuf::from("a.csv"));
+	}
+
 	#[test]
 	fn test_populate_to_panel_02() {
 		let input = vec!["--input", "A,B,C", "--plot", "x", "--plot", "y"];
@@ -903,4 +1652,13 @@ mod tests {
 			&c
 		);
 	}
+
+	#[test]
+	fn test_height_fractions() {
+		assert_eq!(height_fractions([Some(1.0), None, Some(3.0)].into_iter()), [0.25, 0.0, 0.75]);
+		assert_eq!(height_fractions([Some(2.0), Some(2.0)].into_iter()), [0.5, 0.5]);
+		assert_eq!(height_fractions([Some(-1.0), Some(3.0)].into_iter()), [0.25, 0.75]);
+		assert_eq!(height_fractions([None].into_iter()), [0.0]);
+		assert!(height_fractions([].into_iter()).is_empty());
+	}
 }
@@ -0,0 +1,397 @@
+//! Transforms of the extracted line values (see [`crate::graph_config::LineParams::transforms`]).
+//!
+//! This is the transform stage between the log processing and the backends: the plotted column
+//! of the line's CSV file is passed through the transforms in order, and the result is written
+//! into a new CSV file used by the line. The same executor applies `--resample` (to every line),
+//! and `--normalize` (after the baseline subtraction, see [`crate::baseline`]).
+
+use crate::{
+	baseline::value_column_index,
+	graph_config::{Normalize, Resample, ResamplePolicy, Transform},
+	logging::APPV,
+	process_log::{format_record_timestamp, parse_record_timestamp, record_derived_csvs},
+	resolved_graph_config::{ResolvedGraphConfig, ResolvedLine, UnresolvedCsvFileError},
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use std::{
+	collections::{HashMap, hash_map::Entry},
+	hash::{DefaultHasher, Hash, Hasher},
+	path::PathBuf,
+};
+use tracing::{debug, warn};
+
+/// Maximal number of samples of the single resampled line.
+const MAX_SAMPLES: usize = 1 << 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("CSV error while accessing file '{0}': {1}")]
+	CsvError(PathBuf, csv::Error),
+	#[error("Malformed record in CSV file '{0}' (this is bug)")]
+	MalformedRecord(PathBuf),
+	#[error(
+		"Too many samples ({0}) with the resampling interval of {1}s, use the longer interval."
+	)]
+	TooManySamples(usize, f64),
+	#[error(transparent)]
+	UnresolvedCsvFile(#[from] UnresolvedCsvFileError),
+}
+
+/// Value of the transformed series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Sample {
+	timestamp: NaiveDateTime,
+	value: f64,
+	/// Index of the CSV record the other columns of the sample are copied from.
+	source: usize,
+}
+
+/// Returns the `(offset, scale)` pair rescaling the given values as `(value - offset) / scale`,
+/// `None` if they cannot be rescaled (e.g. all of them are zeros).
+fn normalize_scaling(normalize: Normalize, values: &[f64]) -> Option<(f64, f64)> {
+	let (offset, scale) = match normalize {
+		Normalize::Max => (0.0, values.iter().map(|v| v.abs()).fold(0.0, f64::max) / 100.0),
+		Normalize::First => (0.0, values.first()?.abs() / 100.0),
+		Normalize::Zscore => {
+			let mean = values.iter().sum::<f64>() / values.len() as f64;
+			let variance =
+				values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+			(mean, variance.sqrt())
+		},
+	};
+	(scale.is_finite() && scale > 0.0).then_some((offset, scale))
+}
+
+/// Computes the samples of the series (sorted by time) at the multiples of the interval, within
+/// the time range of the series.
+fn resample(series: &[Sample], resample: Resample) -> Result<Vec<Sample>, Error> {
+	let (Some(first), Some(last)) = (series.first(), series.last()) else {
+		return Ok(vec![]);
+	};
+	let epoch = DateTime::UNIX_EPOCH.naive_utc();
+	let interval = TimeDelta::microseconds(((resample.interval * 1e6).round() as i64).max(1));
+	let interval_us = interval.num_microseconds().expect("interval fits into microseconds");
+	let micros = |ts: NaiveDateTime| (ts - epoch).num_microseconds().unwrap_or(i64::MAX);
+	let first_index = -(-micros(first.timestamp)).div_euclid(interval_us);
+	let last_index = micros(last.timestamp).div_euclid(interval_us);
+	let count = usize::try_from(last_index - first_index + 1).unwrap_or(0);
+	if count > MAX_SAMPLES {
+		return Err(Error::TooManySamples(count, resample.interval));
+	}
+
+	let mut samples = Vec::with_capacity(count);
+	for index in first_index..=last_index {
+		let timestamp = epoch + TimeDelta::microseconds(index * interval_us);
+		let Some(prev_index) = series.partition_point(|s| s.timestamp <= timestamp).checked_sub(1)
+		else {
+			continue;
+		};
+		let prev = series[prev_index];
+		let value = match resample.policy {
+			ResamplePolicy::Ffill => prev.value,
+			ResamplePolicy::Drop if timestamp - prev.timestamp < interval => prev.value,
+			ResamplePolicy::Drop => continue,
+			ResamplePolicy::Linear => match series.get(prev_index + 1) {
+				Some(next) if prev.timestamp != timestamp => {
+					let ratio = micros(timestamp).saturating_sub(micros(prev.timestamp)) as f64
+						/ micros(next.timestamp).saturating_sub(micros(prev.timestamp)) as f64;
+					prev.value + (next.value - prev.value) * ratio
+				},
+				_ => prev.value,
+			},
+		};
+		samples.push(Sample { timestamp, value, source: prev.source });
+	}
+	Ok(samples)
+}
+
+/// Replaces every value with the mean of the values within the preceding time window (in
+/// seconds), including the value itself.
+fn smooth(series: &mut [Sample], window: f64) {
+	let window = TimeDelta::microseconds((window * 1e6).round() as i64);
+	let values = series.iter().map(|s| s.value).collect::<Vec<_>>();
+	let (mut start, mut sum) = (0, 0.0);
+	for i in 0..series.len() {
+		sum += values[i];
+		while series[i].timestamp - series[start].timestamp >= window && start < i {
+			sum -= values[start];
+			start += 1;
+		}
+		series[i].value = sum / (i - start + 1) as f64;
+	}
+}
+
+/// Applies the single transform to the series sorted by time.
+///
+/// Returns `None` if the transform cannot be applied to the series (e.g. normalization of zeros).
+fn apply(transform: Transform, mut series: Vec<Sample>) -> Result<Option<Vec<Sample>>, Error> {
+	let map = |mut series: Vec<Sample>, f: &dyn Fn(f64) -> f64| {
+		series.iter_mut().for_each(|s| s.value = f(s.value));
+		series
+	};
+	Ok(Some(match transform {
+		Transform::Smooth(window) => {
+			smooth(&mut series, window);
+			series
+		},
+		Transform::Scale(factor) => map(series, &|v| v * factor),
+		Transform::Offset(offset) => map(series, &|v| v + offset),
+		Transform::Clip(min, max) => {
+			map(series, &|v| max.map_or(v, |max| v.min(max)).max(min.unwrap_or(f64::NEG_INFINITY)))
+		},
+		Transform::Resample(r) => resample(&series, r)?,
+		Transform::Normalize(mode) => {
+			let values = series.iter().map(|s| s.value).collect::<Vec<_>>();
+			let Some((offset, scale)) = normalize_scaling(mode, &values) else {
+				return Ok(None);
+			};
+			map(series, &|v| (v - offset) / scale)
+		},
+	}))
+}
+
+/// CSV file holding the transformed column.
+struct TransformedCsv {
+	path: PathBuf,
+	data_points_count: usize,
+	time_range: Option<(NaiveDateTime, NaiveDateTime)>,
+}
+
+/// Writes the transformed `column` of the CSV file, other columns are copied from the source
+/// record of every sample. Records without the value are dropped.
+fn write_transformed_csv(
+	line: &ResolvedLine,
+	transforms: &[Transform],
+	has_multiple_input_files: bool,
+) -> Result<TransformedCsv, Error> {
+	let path = line.resolved_csv_filename()?;
+	let column = line.csv_data_column_for_plot();
+	let csv_error = |e| Error::CsvError(path.clone(), e);
+	let mut rdr = csv::Reader::from_path(&path).map_err(csv_error)?;
+	let column_index = value_column_index(&mut rdr, &path, column)
+		.map_err(|_| Error::MalformedRecord(path.clone()))?;
+	let headers = rdr.headers().map_err(csv_error)?.clone();
+
+	let mut records = vec![];
+	for record in rdr.records() {
+		let record = record.map_err(csv_error)?;
+		let timestamp = record
+			.get(0)
+			.zip(record.get(1))
+			.and_then(|(date, time)| parse_record_timestamp(date, time))
+			.ok_or_else(|| Error::MalformedRecord(path.clone()))?;
+		if let Some(value) = record.get(column_index).and_then(|v| v.parse::<f64>().ok()) {
+			records.push((timestamp, value, record));
+		}
+	}
+	records.sort_by_key(|(timestamp, ..)| *timestamp);
+	let mut series = records
+		.iter()
+		.enumerate()
+		.map(|(source, (timestamp, value, _))| Sample {
+			timestamp: *timestamp,
+			value: *value,
+			source,
+		})
+		.collect::<Vec<_>>();
+	for transform in transforms {
+		match apply(*transform, series.clone())? {
+			Some(transformed) => series = transformed,
+			None => warn!(target:APPV, line = line.title(has_multiple_input_files),
+				"Transform '{transform}' cannot be applied to the values, skipped."),
+		}
+	}
+
+	let mut hasher = DefaultHasher::new();
+	transforms.iter().map(ToString::to_string).collect::<Vec<_>>().hash(&mut hasher);
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	let output_path =
+		path.with_file_name(format!("{stem}.{column}.transform-{:016x}.csv", hasher.finish()));
+	let output_error = |e| Error::CsvError(output_path.clone(), e);
+	let mut wtr = csv::Writer::from_path(&output_path).map_err(output_error)?;
+	wtr.write_record(&headers).map_err(output_error)?;
+	for sample in &series {
+		let (date, time) = format_record_timestamp(sample.timestamp);
+		let value = sample.value.to_string();
+		let record = records[sample.source].2.iter().enumerate().map(|(i, field)| match i {
+			0 => date.as_str(),
+			1 => time.as_str(),
+			i if i == column_index => value.as_str(),
+			_ => field,
+		});
+		wtr.write_record(record).map_err(output_error)?;
+	}
+	wtr.flush().map_err(|e| output_error(e.into()))?;
+	debug!(target:APPV, "Values transformed into {}", output_path.display());
+
+	let time_range = series.first().zip(series.last()).map(|(f, l)| (f.timestamp, l.timestamp));
+	record_derived_csvs(&[(output_path.clone(), (series.len(), time_range))]);
+	Ok(TransformedCsv { time_range, data_points_count: series.len(), path: output_path })
+}
+
+/// Passes the values of every non-empty line through the transforms returned by `transforms_of`.
+///
+/// Lines sharing the CSV file and the transforms share the transformed CSV file too.
+fn transform_lines(
+	config: &mut ResolvedGraphConfig,
+	has_multiple_input_files: bool,
+	transforms_of: impl Fn(&ResolvedLine) -> Vec<Transform>,
+) -> Result<(), Error> {
+	let mut transformed = HashMap::new();
+	for line in config.panels.iter_mut().flat_map(|panel| panel.lines.iter_mut()) {
+		let transforms = transforms_of(line);
+		if transforms.is_empty() || line.is_empty() {
+			continue;
+		}
+		let key = (
+			line.resolved_csv_filename()?,
+			line.csv_data_column_for_plot(),
+			transforms.iter().map(ToString::to_string).collect::<Vec<_>>(),
+		);
+		let csv = match transformed.entry(key) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => {
+				entry.insert(write_transformed_csv(line, &transforms, has_multiple_input_files)?)
+			},
+		};
+		line.set_shared_csv_filename(&csv.path);
+		line.set_data_points_count(csv.data_points_count);
+		if let Some((start, end)) = csv.time_range {
+			line.set_time_range(start, end);
+		}
+	}
+	Ok(())
+}
+
+/// Resamples every line (see `--resample`), and applies the transforms of every line (see
+/// [`crate::graph_config::LineParams::transforms`]).
+///
+/// Must be called after [`crate::process_log::process_inputs`], and before the time ranges are
+/// resolved. Empty lines are kept intact.
+pub fn apply_transforms(
+	config: &mut ResolvedGraphConfig,
+	resample: Option<Resample>,
+	has_multiple_input_files: bool,
+) -> Result<(), Error> {
+	transform_lines(config, has_multiple_input_files, |line| {
+		let transforms = line.line.params.transforms.iter().flat_map(|t| t.0.iter().copied());
+		resample.map(Transform::Resample).into_iter().chain(transforms).collect()
+	})
+}
+
+/// Rescales the values of every line with the normalization configured (see
+/// [`crate::graph_config::LineParams::normalize`]).
+///
+/// Must be called after [`crate::baseline::subtract_baselines`], and before the time ranges are
+/// resolved. Lines which cannot be rescaled (e.g. all values are zeros) are left intact.
+pub fn normalize_lines(
+	config: &mut ResolvedGraphConfig,
+	has_multiple_input_files: bool,
+) -> Result<(), Error> {
+	transform_lines(config, has_multiple_input_files, |line| {
+		line.line.params.normalize.map(Transform::Normalize).into_iter().collect()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ts(s: &str) -> NaiveDateTime {
+		NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").unwrap()
+	}
+
+	fn t(s: u32) -> NaiveDateTime {
+		ts(&format!("2020-01-01 00:00:{s:02}"))
+	}
+
+	fn series(values: &[(NaiveDateTime, f64)]) -> Vec<Sample> {
+		values
+			.iter()
+			.enumerate()
+			.map(|(source, (timestamp, value))| Sample {
+				timestamp: *timestamp,
+				value: *value,
+				source,
+			})
+			.collect()
+	}
+
+	fn transformed(
+		transform: &str,
+		values: &[(NaiveDateTime, f64)],
+	) -> Option<Vec<(NaiveDateTime, f64)>> {
+		apply(transform.parse().unwrap(), series(values))
+			.unwrap()
+			.map(|s| s.iter().map(|s| (s.timestamp, s.value)).collect())
+	}
+
+	fn values(transform: &str, values: &[f64]) -> Option<Vec<f64>> {
+		let values = values.iter().enumerate().map(|(i, v)| (t(i as u32), *v)).collect::<Vec<_>>();
+		transformed(transform, &values).map(|s| s.into_iter().map(|(_, v)| v).collect())
+	}
+
+	#[test]
+	fn test_resample() {
+		let values = [(ts("2020-01-01 00:00:00.5"), 1.0), (t(2), 4.0), (t(6), 8.0)];
+		assert_eq!(
+			transformed("resample(1s)", &values),
+			Some(vec![
+				(t(1), 2.0),
+				(t(2), 4.0),
+				(t(3), 5.0),
+				(t(4), 6.0),
+				(t(5), 7.0),
+				(t(6), 8.0)
+			])
+		);
+		assert_eq!(
+			transformed("resample(1s,ffill)", &values),
+			Some(vec![
+				(t(1), 1.0),
+				(t(2), 4.0),
+				(t(3), 4.0),
+				(t(4), 4.0),
+				(t(5), 4.0),
+				(t(6), 8.0)
+			])
+		);
+		assert_eq!(
+			transformed("resample(1s,drop)", &values),
+			Some(vec![(t(1), 1.0), (t(2), 4.0), (t(6), 8.0)])
+		);
+		assert_eq!(transformed("resample(1s)", &[]), Some(vec![]));
+	}
+
+	#[test]
+	fn test_resample_too_many_samples() {
+		let series = series(&[(ts("2020-01-01 00:00:00"), 1.0), (ts("2021-01-01 00:00:00"), 1.0)]);
+		let resample = Resample { interval: 0.001, policy: ResamplePolicy::Linear };
+		assert!(matches!(super::resample(&series, resample), Err(Error::TooManySamples(..))));
+	}
+
+	#[test]
+	fn test_normalize() {
+		let input = [2.0, -8.0, 4.0];
+		assert_eq!(values("normalize(max)", &input), Some(vec![25.0, -100.0, 50.0]));
+		assert_eq!(values("normalize(first)", &input), Some(vec![100.0, -400.0, 200.0]));
+		assert_eq!(values("normalize(zscore)", &[1.0, 3.0]), Some(vec![-1.0, 1.0]));
+		assert_eq!(values("normalize(max)", &[0.0, 0.0]), None);
+		assert_eq!(values("normalize(first)", &[0.0, 1.0]), None);
+		assert_eq!(values("normalize(zscore)", &[5.0, 5.0]), None);
+		assert_eq!(values("normalize(zscore)", &[]), None);
+		assert_eq!(values("normalize(first)", &[]), None);
+	}
+
+	#[test]
+	fn test_value_transforms() {
+		let input = [1.0, 3.0, 5.0, 7.0, 100.0];
+		assert_eq!(values("smooth(2s)", &input), Some(vec![1.0, 2.0, 4.0, 6.0, 53.5]));
+		assert_eq!(values("smooth(500ms)", &input), Some(input.to_vec()));
+		assert_eq!(values("scale(0.5)", &input), Some(vec![0.5, 1.5, 2.5, 3.5, 50.0]));
+		assert_eq!(values("offset(-1)", &input), Some(vec![0.0, 2.0, 4.0, 6.0, 99.0]));
+		assert_eq!(values("clip(2,6)", &input), Some(vec![2.0, 3.0, 5.0, 6.0, 6.0]));
+		assert_eq!(values("clip(,6)", &input), Some(vec![1.0, 3.0, 5.0, 6.0, 6.0]));
+		assert_eq!(values("clip(4,)", &input), Some(vec![4.0, 4.0, 5.0, 7.0, 100.0]));
+	}
+}
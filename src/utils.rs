@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn common_path_ancestor(paths: &[PathBuf]) -> Option<PathBuf> {
 	let canonicalized: Result<Vec<_>, _> = paths.iter().map(|p| p.canonicalize()).collect();
@@ -42,10 +42,78 @@ fn common_path_ancestor_inner(paths: &[PathBuf]) -> Option<PathBuf> {
 	Some(ancestor)
 }
 
+/// Expands the leading `~` (home directory) and the environment variables (`$VAR` or `${VAR}`)
+/// in the path.
+///
+/// On failure the name of the variable which is not set is returned.
+pub fn expand_path(path: &Path) -> Result<PathBuf, String> {
+	expand_path_with(path, |name| std::env::var(name).ok())
+}
+
+fn expand_path_with(path: &Path, var: impl Fn(&str) -> Option<String>) -> Result<PathBuf, String> {
+	// Non UTF-8 paths are kept intact.
+	let Some(path) = path.to_str() else {
+		return Ok(path.to_path_buf());
+	};
+	let path = match path.strip_prefix('~') {
+		Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("${{HOME}}{rest}"),
+		_ => path.to_string(),
+	};
+
+	let mut expanded = String::with_capacity(path.len());
+	let mut rest = path.as_str();
+	while let Some(start) = rest.find('$') {
+		expanded.push_str(&rest[..start]);
+		rest = &rest[start + 1..];
+		let braced = rest.strip_prefix('{').and_then(|r| r.find('}').map(|end| (r, end)));
+		let (name, len) = match braced {
+			Some((braced, end)) => (&braced[..end], end + 2),
+			None => {
+				let end = rest
+					.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+					.unwrap_or(rest.len());
+				(&rest[..end], end)
+			},
+		};
+		if name.is_empty() {
+			expanded.push('$');
+			continue;
+		}
+		expanded.push_str(&var(name).ok_or_else(|| name.to_string())?);
+		rest = &rest[len..];
+	}
+	expanded.push_str(rest);
+	Ok(expanded.into())
+}
+
 #[cfg(test)]
 mod tests {
-	use crate::{logging::init_tracing_test, utils::common_path_ancestor_inner};
-	use std::path::PathBuf;
+	use crate::{
+		logging::init_tracing_test,
+		utils::{common_path_ancestor_inner, expand_path_with},
+	};
+	use std::path::{Path, PathBuf};
+
+	#[test]
+	fn test_expand_path() {
+		let var = |name: &str| match name {
+			"HOME" => Some("/home/alice".to_string()),
+			"LOGS" => Some("/var/log".to_string()),
+			_ => None,
+		};
+		let expand = |path: &str| expand_path_with(Path::new(path), var);
+		assert_eq!(expand("~/logs/a.log"), Ok("/home/alice/logs/a.log".into()));
+		assert_eq!(expand("~"), Ok("/home/alice".into()));
+		assert_eq!(expand("$HOME/a.log"), Ok("/home/alice/a.log".into()));
+		assert_eq!(expand("${LOGS}/node-1/a.log"), Ok("/var/log/node-1/a.log".into()));
+		assert_eq!(expand("$LOGS-old/a.log"), Ok("/var/log-old/a.log".into()));
+		assert_eq!(expand("${LOGS}${HOME}"), Ok("/var/log/home/alice".into()));
+		assert_eq!(expand("a/~b/$/c$"), Ok("a/~b/$/c$".into()));
+		assert_eq!(expand("~bob/a.log"), Ok("~bob/a.log".into()));
+		assert_eq!(expand("${LOGS/a.log"), Ok("${LOGS/a.log".into()));
+		assert_eq!(expand("$MISSING/a.log"), Err("MISSING".into()));
+		assert_eq!(expand("${MISSING}"), Err("MISSING".into()));
+	}
 
 	#[test]
 	fn test_common_path_ancestor() {
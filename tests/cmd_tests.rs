@@ -45,6 +45,13 @@ fn compare_files(file: &str) {
 	compare_files_inner(&path1, &path2);
 }
 
+fn is_provenance(line: &str) -> bool {
+	["# version:", "# command:", "# input:", "# generated:"]
+		.iter()
+		.any(|key| line.starts_with(key))
+		|| line.trim_start().starts_with(r#"<meta name="plox-"#)
+}
+
 fn compare_files_inner(path1: &str, path2: &str) {
 	let file1 = File::open(path1).unwrap();
 	let file2 = File::open(path2).unwrap();
@@ -56,7 +63,10 @@ fn compare_files_inner(path1: &str, path2: &str) {
 		let line1 = line1.unwrap();
 		let line2 = line2.unwrap();
 
-		if line1.starts_with("csv_data_file_") && line2.starts_with("csv_data_file_") {
+		if is_provenance(&line1) && is_provenance(&line2) {
+			// Provenance (command line, input hashes, time) differs between environments.
+			continue;
+		} else if line1.starts_with("csv_data_file_") && line2.starts_with("csv_data_file_") {
 			let prefix1 = line1.split('=').next().unwrap_or("");
 			let prefix2 = line2.split('=').next().unwrap_or("");
 
@@ -302,6 +312,7 @@ fn test_cmd_bad_timestamp() {
 fn test_cmd_bad_guard() {
 	bash!(
 		plox graph --input  tests/examples/default.log --plot nonexistingguard x -f
+		  --output tests/.output/bad-guard.png
 	);
 }
 
@@ -425,6 +436,375 @@ fn test_cmd_stat2() {
 	cmd_stat2();
 }
 
+#[test]
+fn test_cmd_stat_custom_percentiles() {
+	let output = bash!(
+		plox stat
+		  --input tests/examples/checker.log
+		  --percentiles 50,99,99.9
+		  field-value TRACE duration
+	);
+	let stats: Vec<_> = output.lines().skip(4).take(3).collect();
+	assert_eq!(stats[0], "median: 1.0636225000000001");
+	assert_eq!(stats[1], "   q99: 2.06401263");
+	assert!(stats[2].starts_with(" q99.9: "));
+}
+
+#[test]
+#[should_panic(expected = "Execution of plox failed")]
+fn test_cmd_stat_bad_percentile() {
+	bash!(
+		plox stat --input tests/examples/checker.log --percentiles 101 field-value TRACE duration
+	);
+}
+
+#[test]
+fn test_cmd_stat_group_by() {
+	let group_by = r"duration=(\d)\.";
+	let output = bash!(
+		plox stat
+		  --input tests/examples/checker.log
+		  --percentiles 50
+		  --group-by $group_by
+		  field-value TRACE duration
+	);
+	let rows: Vec<_> = output.lines().map(|l| l.split_whitespace().collect::<Vec<_>>()).collect();
+	assert_eq!(rows[0], ["label", "count", "min", "max", "mean", "median"]);
+	assert_eq!(rows[1][..2], ["-", "378"]);
+	assert_eq!(rows[2][..2], ["1", "736"]);
+	assert_eq!(rows[3][..2], ["2", "14"]);
+	assert_eq!(rows[4], ["3", "2", "3.0598", "3.1142", "3.0870", "3.0870"]);
+	assert_eq!(rows.len(), 5);
+}
+
+#[test]
+#[should_panic(expected = "Execution of plox failed")]
+fn test_cmd_stat_group_by_invalid_regex() {
+	bash!(
+		plox stat --input tests/examples/checker.log --group-by "duration" field-value TRACE duration
+	);
+}
+
+#[test]
+fn test_cmd_stat_event_count() {
+	let output = bash!(
+		plox stat --input tests/examples/checker.log event-count --interval 5 TRACE duration
+	);
+	let stats: Vec<_> = output.lines().take(4).collect();
+	assert_eq!(stats, [" count: 3", "   min: 344", "   max: 394", "  mean: 376.6666666666667"]);
+}
+
+#[test]
+fn test_cmd_stat_event_rate() {
+	let output = bash!(
+		plox stat --input tests/examples/checker.log event-rate --interval 5 TRACE duration
+	);
+	let stats: Vec<_> = output.lines().take(3).collect();
+	assert_eq!(stats, [" count: 3", "   min: 68.8", "   max: 78.8"]);
+}
+
+#[test]
+fn test_cmd_cat_event_count() {
+	let output = bash!(
+		plox cat --input tests/examples/checker.log event-count --interval 5 TRACE duration
+	);
+	assert_eq!(output.lines().collect::<Vec<_>>(), ["344.0", "394.0", "392.0"]);
+}
+
+#[test]
+fn test_cmd_stat_event_delta() {
+	let output = bash!(plox stat --input tests/examples/checker.log event-delta TRACE duration);
+	assert_eq!(output.lines().next(), Some(" count: 1130"));
+}
+
+#[test]
+fn test_cmd_stat_show_outliers() {
+	let output = bash!(
+		plox stat --input tests/examples/checker.log --show-outliers 2 field-value TRACE duration
+	);
+	let lines: Vec<_> = output.lines().skip(9).take(7).collect();
+	assert_eq!(lines[1], "largest values:");
+	assert_eq!(
+		lines[2],
+		"    3.114183 [line 132]: 2025-05-12 16:46:41.534 TRACE txpool: checker duration=3.114183ms"
+	);
+	assert!(lines[3].starts_with("    3.059783 [line 344]: "));
+	assert_eq!(lines[5], "smallest values:");
+	assert_eq!(
+		lines[6],
+		"     0.13308 [line 545]: 2025-05-12 16:46:48.025 TRACE txpool: checker duration=133.08µs"
+	);
+}
+
+#[test]
+fn test_cmd_stat_metrics() {
+	let output = bash!(
+		plox stat
+		  --input tests/examples/checker.log
+		  --metrics count,sum,stddev,variance,mode
+		  field-value TRACE duration
+	);
+	let stats: Vec<_> = output.lines().take(6).collect();
+	assert_eq!(stats[0], " count: 1130");
+	assert_eq!(stats[1], "   sum: 1174.0757210000008");
+	assert!(stats[2].starts_with("stddev: 0.2714"));
+	assert!(stats[3].starts_with("   var: 0.0736"));
+	assert_eq!(stats[4], "  mode: 1.068438");
+	assert_eq!(stats[5], "");
+}
+
+#[test]
+fn test_cmd_stat_log_buckets() {
+	let output = bash!(
+		plox stat
+		  --input tests/examples/checker.log
+		  --log-buckets --buckets-count 4
+		  field-value TRACE duration
+	);
+	let buckets: Vec<_> = output
+		.lines()
+		.filter(|l| l.contains(" ]: "))
+		.map(|l| l.split(" ]: ").next().unwrap())
+		.collect();
+	assert_eq!(
+		buckets,
+		[
+			"    0.1331 -     0.2927 [   7",
+			"    0.2927 -     0.6438 [  63",
+			"    0.6438 -     1.4159 [ 991",
+			"    1.4159 -     3.1142 [  69",
+		]
+	);
+}
+
+#[test]
+fn test_cmd_cat_columns() {
+	let output = bash!(
+		plox cat --input tests/examples/checker.log --columns ts,value,count,delta,line field-value TRACE duration
+	);
+	let lines: Vec<_> = output.lines().take(2).collect();
+	assert_eq!(
+		lines,
+		["2025-05-12 16:46:40.010 1.070527 1 0.0 1", "2025-05-12 16:46:40.022 1.065988 2 12.0 2"]
+	);
+}
+
+#[test]
+fn test_cmd_cat_csv_format() {
+	let output = bash!(
+		plox cat --input tests/examples/checker.log --columns ts,value --format csv field-value TRACE duration
+	);
+	let lines: Vec<_> = output.lines().take(2).collect();
+	assert_eq!(lines, ["ts,value", "2025-05-12 16:46:40.010,1.070527"]);
+	assert_eq!(output.lines().count(), 1131);
+}
+
+#[test]
+fn test_cmd_cat_json_format() {
+	let output = bash!(
+		plox cat --input tests/examples/checker.log --columns value,ts,count --format json event-count --interval 5 TRACE duration
+	);
+	let lines: Vec<_> = output.lines().collect();
+	assert_eq!(
+		lines,
+		[
+			r#"{"value":344.0,"ts":"2025-05-12 16:46:40.010","count":null}"#,
+			r#"{"value":394.0,"ts":"2025-05-12 16:46:45.010","count":null}"#,
+			r#"{"value":392.0,"ts":"2025-05-12 16:46:50.010","count":null}"#,
+		]
+	);
+}
+
+#[test]
+#[should_panic(expected = "Execution of plox failed")]
+fn test_cmd_cat_follow_unsupported_source() {
+	bash!(
+		plox cat --input tests/examples/checker.log --follow event-count TRACE duration
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cmd_interrupted() {
+	use std::io::Read;
+	let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["cat", "--input", "tests/examples/checker.log", "--follow"])
+		.args(["field-value", "TRACE", "duration"])
+		.stdout(std::process::Stdio::piped())
+		.stderr(std::process::Stdio::piped())
+		.spawn()
+		.expect("process running");
+	// The interrupt handler is installed before anything is printed, so the first printed value
+	// tells that the process is ready to be interrupted.
+	let mut stdout = BufReader::new(child.stdout.take().unwrap());
+	let (tx, rx) = std::sync::mpsc::channel();
+	let reader = std::thread::spawn(move || {
+		let mut first_line = String::new();
+		stdout.read_line(&mut first_line).unwrap();
+		tx.send(first_line).unwrap();
+		let mut rest = String::new();
+		stdout.read_to_string(&mut rest).unwrap();
+		rest
+	});
+	let first_line = rx.recv_timeout(std::time::Duration::from_secs(30));
+	let status = std::process::Command::new("kill")
+		.args(["-INT", &child.id().to_string()])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	let status = child.wait().unwrap();
+	let rest = reader.join().unwrap();
+	// Values read before the interruption are printed.
+	let first_line = first_line.expect("no value printed before timeout");
+	assert!(!first_line.is_empty());
+	assert_eq!(status.code(), Some(130), "{status:?} {first_line}{rest}");
+}
+
+#[test]
+fn test_cmd_stat_cat_from_cache() {
+	let cache_dir = "tests/.output/from-cache";
+	let _ = std::fs::remove_dir_all(cache_dir);
+	bash!(
+		plox stat --input tests/examples/checker.log --cache-dir $cache_dir field-value TRACE duration
+	);
+	let csv_files: Vec<_> = walk_files(cache_dir.as_ref())
+		.into_iter()
+		.filter(|p| p.extension().is_some_and(|e| e == "csv"))
+		.collect();
+	assert_eq!(csv_files.len(), 1);
+	let csv_file = &csv_files[0];
+
+	let output = bash!(plox stat --from-cache $csv_file field-value duration);
+	let stats: Vec<_> = output.lines().take(3).collect();
+	assert_eq!(stats, [" count: 1130", "   min: 0.13308", "   max: 3.114183"]);
+
+	let output = bash!(plox cat --from-cache $csv_file --columns value,line field-value duration);
+	assert_eq!(output.lines().next(), Some("1.070527 1"));
+
+	// Log lines of outliers are not available, only line numbers are displayed.
+	let output = bash!(plox stat --from-cache $csv_file --show-outliers 1 field-value duration);
+	assert!(output.contains("\n    3.114183 [line 132]\n"), "{output}");
+}
+
+#[test]
+fn test_cmd_hist() {
+	let output_dir = "tests/.output/hist";
+	let _ = std::fs::remove_dir_all(output_dir);
+	std::fs::create_dir_all(output_dir).unwrap();
+	let image = format!("{output_dir}/hist.png");
+	bash!(
+		plox hist -x --input tests/examples/checker.log --buckets-count 4 --log-buckets --cdf
+			--output $image field-value TRACE duration
+	);
+	let script = std::fs::read_to_string(format!("{output_dir}/hist.gnuplot")).unwrap();
+	let data: Vec<_> = script
+		.lines()
+		.skip_while(|l| !l.starts_with("$histogram << EOD"))
+		.skip(1)
+		.take_while(|l| *l != "EOD")
+		.map(|l| l.split(',').nth(2).unwrap())
+		.collect();
+	assert_eq!(data, ["7", "63", "991", "69"]);
+	assert!(script.contains("axes x1y2 title 'CDF'"));
+
+	let html = format!("{output_dir}/hist.html");
+	bash!(
+		plox hist -x -p --input tests/examples/checker.log --output $html field-value TRACE duration
+	);
+	assert!(std::path::Path::new(&html).exists());
+}
+
+#[test]
+fn test_cmd_top() {
+	let label = r"(\w+)_module";
+	let output = bash!(
+		plox top --input tests/examples/default.log --label $label --by sum -n 2
+			event-count --interval 1000 _module
+	);
+	assert_eq!(
+		output.lines().collect::<Vec<_>>(),
+		[
+			"rank label    count          sum",
+			"   1 foo          1        90.00",
+			"   2 yam          1        31.00"
+		]
+	);
+}
+
+#[test]
+fn test_cmd_grep() {
+	let range = "2025-05-12 16:46:41.000,2025-05-12 16:46:41.030";
+	let output = bash!(
+		plox grep --input tests/examples/checker.log --time-range $range field-value TRACE duration
+	);
+	assert_eq!(
+		output.lines().collect::<Vec<_>>(),
+		[
+			"87: [value=1.069188 delta=12.0] 2025-05-12 16:46:41.011 TRACE txpool: checker duration=1.069188ms",
+			"88: [value=1.071227 delta=11.0] 2025-05-12 16:46:41.022 TRACE txpool: checker duration=1.071227ms",
+		]
+	);
+}
+
+#[test]
+fn test_cmd_grep_sort_timestamps() {
+	let _ = std::fs::remove_dir_all("tests/.output/grep-sort-cache");
+	let output = bash!(
+		plox grep --input tests/examples/out-of-order.log --sort-timestamps
+		  --cache-dir tests/.output/grep-sort-cache field-value om_module x
+	);
+	assert!(output.contains("out-of-order timestamps"), "{output}");
+	assert_eq!(
+		output.lines().skip(1).collect::<Vec<_>>(),
+		[
+			"2: [value=2.0 delta=0.0] 2020-01-01 00:00:00.000 om_module x=2",
+			"1: [value=1.0 delta=10000.0] 2020-01-01 00:00:10.000 om_module x=1",
+			"3: [value=3.0 delta=10000.0] 2020-01-01 00:00:20.000 om_module x=3",
+		],
+		"{output}"
+	);
+}
+
+#[test]
+fn test_cmd_stat_correlate() {
+	let output_dir = "tests/.output/correlate";
+	let _ = std::fs::remove_dir_all(output_dir);
+	std::fs::create_dir_all(output_dir).unwrap();
+	let image = format!("{output_dir}/scatter.png");
+	let output = bash!(
+		plox stat --input tests/examples/default.log --correlate x02 --scatter $image field-value x01
+	);
+	let lines: Vec<_> = output.lines().collect();
+	assert_eq!(lines[0], "   pairs: 28");
+	let coefficient = |l: &str| l.split(':').nth(1).unwrap().trim().parse::<f64>().unwrap();
+	assert!((coefficient(lines[1]) - 1.0).abs() < 1e-9);
+	assert!((coefficient(lines[2]) - 1.0).abs() < 1e-9);
+
+	let script = std::fs::read_to_string(format!("{output_dir}/scatter.gnuplot")).unwrap();
+	assert!(script.contains("11.2,21.2\n12.5,22.5\n"));
+}
+
+#[test]
+fn test_cmd_stat_periodogram() {
+	let output = bash!(
+		plox stat --input tests/examples/periodic.log --periodogram --resample-interval 10 --top-periods 2 field-value request latency
+	);
+	let lines: Vec<_> = output.lines().collect();
+	assert_eq!(lines[0], "samples: 180 (interval: 10s)", "{output}");
+	assert_eq!(lines.len(), 4, "{output}");
+	assert!(lines[2].trim_start().starts_with("90.000 "), "{output}");
+}
+
+fn walk_files(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+	std::fs::read_dir(dir)
+		.unwrap()
+		.flat_map(|e| {
+			let path = e.unwrap().path();
+			if path.is_dir() { walk_files(&path) } else { vec![path] }
+		})
+		.collect()
+}
+
 #[test]
 fn cmd_help() {
 	run_cmd! (
@@ -450,3 +830,1195 @@ fn cmd_help() {
 
 //something to consider:
 //datamash mean 1 count 1 max 1 min 1 perc:99 1 perc:95 1 perc:90 1 perc:75 1
+
+#[test]
+fn test_cmd_cat_closed_output() {
+	for format in ["plain", "json"] {
+		let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["cat", "--format", format, "--input", "tests/examples/default-other.log"])
+			.args(["field-value", "om_module", "x"])
+			.env("PLOX_DO_NOT_DISPLAY", "1")
+			.stdout(std::process::Stdio::piped())
+			.stderr(std::process::Stdio::piped())
+			.spawn()
+			.expect("process running");
+		// The reader goes away before anything is printed (as `head` does after enough lines).
+		drop(child.stdout.take());
+		let output = child.wait_with_output().unwrap();
+		let stderr = String::from_utf8(output.stderr).unwrap();
+		assert!(output.status.success(), "{format}: {stderr}");
+		assert!(!stderr.contains("panicked"), "{format}: {stderr}");
+	}
+}
+
+#[test]
+fn test_cmd_cat_too_many_intervals() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["--error-format", "json", "cat", "--input", "tests/examples/checker.log"])
+		.args(["--cache-dir", "tests/.output/too-many-intervals-cache"])
+		.args(["event-count", "--interval", "0.000000001", "TRACE", "duration"])
+		.env("PLOX_DO_NOT_DISPLAY", "1")
+		.output()
+		.expect("process running");
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert_eq!(output.status.code(), Some(2), "{stderr}");
+	assert!(stderr.starts_with(r#"{"code":"too-many-intervals","message":"#), "{stderr}");
+}
+
+#[test]
+fn test_cmd_error_format_json_cli_parse() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["--error-format", "json", "cat", "--no-such-flag"])
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(2));
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(
+		stderr.starts_with(r#"{"code":"cli-parse","message":"error: unexpected argument"#),
+		"{stderr}"
+	);
+	assert_eq!(stderr.lines().count(), 1, "{stderr}");
+}
+
+#[test]
+fn test_cmd_error_format_json() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["--error-format", "json", "cat", "--input", "tests/examples/no-such-file.log"])
+		.args(["field-value", "x01"])
+		.env("PLOX_DO_NOT_DISPLAY", "1")
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(6));
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.starts_with(r#"{"code":"input-io","message":"#), "{stderr}");
+}
+
+#[test]
+fn test_cmd_message_format_json() {
+	let dir = "tests/.output/message-format";
+	let _ = std::fs::remove_dir_all(dir);
+	let run = || {
+		let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["--message-format", "json", "graph", "--input", "tests/examples/default.log"])
+			.args(["--plot", "om_module", "x", "--plot", "om_module", "nomatch"])
+			.args(["--output", &format!("{dir}/graph.png"), "--cache-dir", dir])
+			.env("PLOX_SKIP_GNUPLOT", "1")
+			.output()
+			.expect("process running");
+		assert!(output.status.success(), "{output:?}");
+		String::from_utf8(output.stdout)
+			.unwrap()
+			.lines()
+			.map(|line| serde_json::from_str::<serde_json::Value>(line).expect("JSON line"))
+			.collect::<Vec<_>>()
+	};
+	let reasons = |messages: &[serde_json::Value]| {
+		messages
+			.iter()
+			.map(|m| m["reason"].as_str().unwrap().to_string())
+			.collect::<Vec<_>>()
+	};
+
+	let messages = run();
+	let first = reasons(&messages);
+	assert_eq!(first.first().unwrap(), "started");
+	assert_eq!(first.last().unwrap(), "finished");
+	assert_eq!(messages.last().unwrap()["exit_code"], 0);
+	for reason in ["cache-miss", "file-processed", "warning", "output"] {
+		assert!(first.iter().any(|r| r == reason), "{first:?}");
+	}
+	assert!(!first.iter().any(|r| r == "cache-hit"), "{first:?}");
+	let output = messages.iter().find(|m| m["reason"] == "output").unwrap();
+	assert_eq!(output["kind"], "script");
+	assert!(output["path"].as_str().unwrap().ends_with(&format!("{dir}/graph.gnuplot")));
+
+	let second = reasons(&run());
+	assert!(second.iter().any(|r| r == "cache-hit"), "{second:?}");
+	assert!(!second.iter().any(|r| r == "cache-miss" || r == "file-processed"), "{second:?}");
+}
+
+#[test]
+fn test_cmd_log_file() {
+	let dir = "tests/.output/log-file";
+	let _ = std::fs::remove_dir_all(dir);
+	std::fs::create_dir_all(dir).unwrap();
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["-vv", "--log-file", &format!("{dir}/plox.log"), "graph"])
+		.args(["--input", "tests/examples/default.log", "--plot", "om_module", "x"])
+		.args(["--output", &format!("{dir}/graph.png"), "--cache-dir", dir])
+		.env("PLOX_SKIP_GNUPLOT", "1")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert!(stdout.contains("Script saved:"), "{stdout}");
+	assert!(!stdout.contains("DEBUG") && !stdout.contains("TRACE"), "{stdout}");
+
+	let log = std::fs::read_to_string(format!("{dir}/plox.log")).unwrap();
+	assert!(log.contains("TRACE appverbose: Provided input graph config"), "{log}");
+	assert!(log.contains("DEBUG appverbose: Input files processed in"), "{log}");
+	assert!(log.contains("INFO appverbose: Script saved:"), "{log}");
+
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["--log-file", &format!("{dir}/no-such-dir/plox.log"), "styles"])
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(6), "{output:?}");
+}
+
+#[test]
+fn test_cmd_empty_graph() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["graph", "--input", "tests/examples/default.log", "--plot", "nonexistingguard", "x"])
+		.args(["--output", "tests/.output/empty-graph.html", "--plotly-backend", "-f"])
+		.env("PLOX_DO_NOT_DISPLAY", "1")
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(3));
+	let html = std::fs::read_to_string("tests/.output/empty-graph.html").unwrap();
+	assert!(html.contains(r#"<p class="empty-graph">No data for any line."#), "{html}");
+}
+
+#[test]
+fn test_cmd_output_to_stdout() {
+	let run = |extra: &[&str]| {
+		std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["graph", "--input", "tests/examples/default.log", "--plot", "om_module", "x"])
+			.args(["--output", "-"])
+			.args(extra)
+			.env("PLOX_DO_NOT_DISPLAY", "1")
+			.env("PLOX_SKIP_GNUPLOT", "1")
+			.output()
+			.expect("process running")
+	};
+
+	let output = run(&["--plotly-backend"]);
+	assert!(output.status.success());
+	let html = String::from_utf8_lossy(&output.stdout);
+	assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+	assert!(html.trim_end().ends_with("</html>"), "{html}");
+
+	// With gnuplot execution skipped, the script rendering the image to stdout is written.
+	let output = run(&["--script-only"]);
+	assert!(output.status.success());
+	let script = String::from_utf8_lossy(&output.stdout);
+	assert!(script.contains("set terminal pngcairo"), "{script}");
+	assert!(!script.contains("set output"), "{script}");
+	assert!(script.contains("plot \\"), "{script}");
+	assert!(!std::path::Path::new("-").exists());
+}
+
+#[test]
+fn test_cmd_script_only() {
+	let dir = "tests/.output/script-only";
+	let _ = std::fs::remove_dir_all(dir);
+	std::fs::create_dir_all(dir).unwrap();
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["graph", "--input", "tests/examples/default.log", "--plot", "om_module", "x"])
+		.args(["--output", &format!("{dir}/graph.png"), "--script-only", "--cache-dir", dir])
+		.env_remove("PLOX_SKIP_GNUPLOT")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	assert!(String::from_utf8_lossy(&output.stdout).contains("Script saved:"), "{output:?}");
+	assert!(std::path::Path::new(&format!("{dir}/graph.gnuplot")).exists());
+	assert!(!std::path::Path::new(&format!("{dir}/graph.png")).exists());
+	// CSV cache is created.
+	assert!(std::fs::read_dir(dir).unwrap().count() > 1);
+}
+
+#[test]
+fn test_cmd_gnuplot_bin_and_extra() {
+	let dir = "tests/.output/gnuplot-extra";
+	let _ = std::fs::remove_dir_all(dir);
+	std::fs::create_dir_all(dir).unwrap();
+	let extra = format!("{dir}/extra.gp");
+	std::fs::write(&extra, "set border lw 3\n").unwrap();
+	let run = |extra_args: &[&str]| {
+		std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["graph", "--input", "tests/examples/default.log", "--plot", "om_module", "x"])
+			.args(["--output", &format!("{dir}/graph.png"), "--cache-dir", dir])
+			.args(extra_args)
+			.env_remove("PLOX_SKIP_GNUPLOT")
+			.output()
+			.expect("process running")
+	};
+
+	let output = run(&["--gnuplot-extra", &extra, "--script-only"]);
+	assert!(output.status.success(), "{output:?}");
+	let script = std::fs::read_to_string(format!("{dir}/graph.gnuplot")).unwrap();
+	let extra_pos = script.find("set border lw 3").expect("extra preamble in script");
+	assert!(extra_pos < script.find("set multiplot").unwrap());
+
+	let output = run(&["--gnuplot-extra", &format!("{dir}/missing.gp")]);
+	assert_eq!(output.status.code(), Some(6), "{output:?}");
+
+	let output = run(&["--gnuplot-bin", "/nonexistent/gnuplot"]);
+	assert_eq!(output.status.code(), Some(5), "{output:?}");
+}
+
+#[test]
+fn test_cmd_auto_number_and_no_clobber() {
+	let dir = "tests/.output/auto-number";
+	let _ = std::fs::remove_dir_all(dir);
+	std::fs::create_dir_all(dir).unwrap();
+	let run = |extra: &[&str]| {
+		std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["graph", "--input", "tests/examples/default.log", "--plot", "om_module", "x"])
+			.args(["--output", &format!("{dir}/graph.html"), "--plotly-backend"])
+			.args(extra)
+			.env("PLOX_DO_NOT_DISPLAY", "1")
+			.output()
+			.expect("process running")
+	};
+
+	assert!(run(&["--auto-number"]).status.success());
+	assert!(run(&["--auto-number"]).status.success());
+	assert!(std::fs::exists(format!("{dir}/graph-001.html")).unwrap());
+	assert!(std::fs::exists(format!("{dir}/graph-002.html")).unwrap());
+	assert!(!std::fs::exists(format!("{dir}/graph.html")).unwrap());
+
+	assert!(run(&["--no-clobber"]).status.success());
+	let output = run(&["--no-clobber"]);
+	assert_eq!(output.status.code(), Some(6));
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert!(stdout.contains("graph.html' already exists"), "{stdout}");
+}
+
+#[test]
+fn test_cmd_shared_yrange() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log,tests/examples/default-other.log
+		  --output tests/.output/shared-yrange.png
+		  --per-file-panels
+		  --shared-yrange
+		  --plot om_module x
+	);
+	let script = std::fs::read_to_string("tests/.output/shared-yrange.gnuplot").unwrap();
+	let yranges = script
+		.lines()
+		.filter(|l| l.starts_with("set yrange") && !l.ends_with("[*:*]"))
+		.collect::<Vec<_>>();
+	assert_eq!(yranges.len(), 2, "{script}");
+	assert_eq!(yranges[0], yranges[1]);
+}
+
+#[test]
+fn test_cmd_gap_threshold() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/gap-threshold.png
+		  --plot om_module x --gap-threshold 1m --style lines
+	);
+	let script = std::fs::read_to_string("tests/.output/gap-threshold.gnuplot").unwrap();
+	assert!(script.contains("$csv_data_0000 << EOD\ndate,time,"), "{script}");
+	assert!(script.contains("00:03:10.000,809.81,"), "{script}");
+	assert!(script.contains("\n\n2020-01-01,00:04:17.000,742.74,"), "{script}");
+	assert!(script.contains("   $csv_data_0000 using"), "{script}");
+}
+
+#[test]
+fn test_cmd_inline_data() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/inline-data.png
+		  --inline-data
+		  --plot om_module x
+		  --panel --plot x_module x00
+	);
+	let script = std::fs::read_to_string("tests/.output/inline-data.gnuplot").unwrap();
+	assert!(!script.contains("csv_data_file_"), "{script}");
+	assert!(!script.contains(".plox"), "{script}");
+	assert_eq!(script.matches("$csv_data_0000 << EOD\ndate,time,").count(), 2, "{script}");
+	assert!(script.contains("\n2020-01-01,00:00:34.000,965.97,"), "{script}");
+	assert!(script.contains("   $csv_data_0000 using"), "{script}");
+}
+
+#[test]
+fn test_cmd_portable_script() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/portable.png
+		  --portable-script
+		  --plot om_module x
+	);
+	let script = std::fs::read_to_string("tests/.output/portable.gnuplot").unwrap();
+	assert!(script.contains("set output 'portable.png'\n"), "{script}");
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0000 = ")).unwrap();
+	let path = path.trim_matches('\'');
+	assert!(path.starts_with("portable-data/") && path.ends_with(".csv"), "{script}");
+	let csv = std::fs::read_to_string(std::path::Path::new("tests/.output").join(path)).unwrap();
+	assert!(csv.contains("2020-01-01,00:00:34.000,965.97,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_cat_keep_text() {
+	let output = bash!(
+		plox cat --input tests/examples/default-other.log --keep-text match --columns line,value,text
+		  field-value om_module x
+	);
+	assert!(output.starts_with("1 1000.0 x=1000.00\n"), "{output}");
+
+	let output = bash!(
+		plox cat --input tests/examples/default-other.log --keep-text line --columns text
+		  field-value om_module x
+	);
+	assert!(output.starts_with("2020-01-01 00:00:00.000 om_module x=1000.00\n"), "{output}");
+}
+
+#[test]
+fn test_cmd_number_locale() {
+	let output = bash!(
+		plox cat --input tests/examples/number-locale.log --number-locale eu --columns value
+		  field-value om_module x
+	);
+	assert_eq!(output, "1234.5\n12.25\n1000000.0");
+}
+
+#[test]
+fn test_cmd_skip_max_lines() {
+	let output = bash!(
+		plox cat --input tests/examples/number-locale.log --number-locale eu --columns line,value
+		  --skip-lines 1 --max-lines 1
+		  field-value om_module x
+	);
+	assert_eq!(output, "2 12.25");
+
+	// Different lines range shall not reuse the cached file.
+	let output = bash!(
+		plox cat --input tests/examples/number-locale.log --number-locale eu --columns line,value
+		  --skip-lines 1
+		  field-value om_module x
+	);
+	assert_eq!(output, "2 12.25\n3 1000000.0");
+}
+
+#[test]
+fn test_cmd_json_field() {
+	let json_field = |args: &[&str]| {
+		let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["cat", "--input", "tests/examples/json.log", "--columns", "line,value"])
+			.args(["--timestamp-format", r#"{"timestamp":"%Y-%m-%dT%H:%M:%S%.fZ""#])
+			.arg("json-field")
+			.args(args)
+			.output()
+			.expect("process running");
+		assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+		String::from_utf8(output.stdout).unwrap()
+	};
+	assert_eq!(json_field(&["fields.duration"]), "1 12.0\n2 3.0\n3 1500.0\n5 7.0\n");
+	assert_eq!(json_field(&["level=INFO", "fields.duration"]), "1 12.0\n3 1500.0\n5 7.0\n");
+}
+
+#[test]
+fn test_cmd_logfmt() {
+	let output = bash!(
+		plox cat --input tests/examples/logfmt.log --columns line,value logfmt duration
+	);
+	assert_eq!(output, "1 12.0\n3 1500.0");
+
+	// Regex-based field would take `offset=99ms` from the quoted message.
+	let output = bash!(
+		plox cat --input tests/examples/logfmt.log --columns line,value logfmt warn offset
+	);
+	assert_eq!(output, "2 -1.5");
+}
+
+#[test]
+fn test_cmd_sample() {
+	plox::logging::init_tracing_test();
+	let output = bash!(
+		plox cat --input tests/examples/default.log --columns line --sample 5
+		  field-value om_module x
+	);
+	let lines = output.lines().map(|l| l.parse::<u64>().unwrap()).collect::<Vec<_>>();
+	assert_eq!(lines.len(), 5);
+	assert!(lines.is_sorted(), "{output}");
+
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/sample.png
+		  --plot om_module x --sample 0.5
+	);
+	let script = std::fs::read_to_string("tests/.output/sample.gnuplot").unwrap();
+	assert!(script.contains("set label 'Sampled: 50% of matches per line'"), "{script}");
+	assert!(script.contains("__sample_0.5.csv"), "{script}");
+}
+
+#[test]
+fn test_cmd_concat() {
+	let output = bash!(
+		plox cat
+		  --input tests/examples/rotated/app.log,tests/examples/rotated/app.log.1,tests/examples/rotated/app.log.2.gz
+		  --concat --cache-dir tests/.output/concat-cache --force-csv-regen
+		  --columns line,value
+		  field-value om_module x
+	);
+	assert_eq!(output, "1 1.0\n2 2.0\n3 3.0\n4 4.0\n5 5.0");
+
+	let output = bash!(
+		plox grep
+		  --input tests/examples/rotated/app.log.1,tests/examples/rotated/app.log.2.gz
+		  --concat --cache-dir tests/.output/concat-cache
+		  field-value om_module x
+	);
+	assert!(output.starts_with("1: [value=1.0 delta=0.0] 2020-01-01 00:00:00.000"), "{output}");
+	assert!(output.ends_with("4: [value=4.0 delta=10000.0] 2020-01-01 00:00:30.000 om_module x=4"));
+}
+
+#[test]
+fn test_cmd_dedup() {
+	let _ = std::fs::remove_dir_all("tests/.output/dedup-cache");
+	let output = bash!(
+		plox cat
+		  --input tests/examples/overlap/snapshot-1.log,tests/examples/overlap/snapshot-2.log
+		  --concat --cache-dir tests/.output/dedup-cache
+		  --columns value
+		  field-value om_module x
+	);
+	// Overlapping part of the snapshots is repeated, going back in time.
+	assert!(output.contains("out-of-order timestamps"), "{output}");
+	assert!(output.ends_with("\n1.0\n2.0\n3.0\n2.0\n3.0\n4.0"), "{output}");
+
+	let output = bash!(
+		plox cat
+		  --input tests/examples/overlap/snapshot-1.log,tests/examples/overlap/snapshot-2.log
+		  --concat --dedup --cache-dir tests/.output/dedup-cache
+		  --columns line,value
+		  field-value om_module x
+	);
+	// Line numbers still refer to the concatenated input.
+	assert_eq!(
+		output,
+		"\u{1b}[32m INFO\u{1b}[0m Dropped 2 duplicated lines of: tests/examples/overlap/snapshot.concat\n\
+		 1 1.0\n2 2.0\n3 3.0\n7 4.0",
+		"{output}"
+	);
+}
+
+#[test]
+fn test_cmd_journal_input() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["cat", "--input", "journal:nginx", "--cache-dir", "tests/.output/journal-cache"])
+		.args(["--columns", "ts,value", "field-value", "nginx", "duration"])
+		.env("PLOX_JOURNALCTL", "tests/examples/fake-journalctl.sh")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert_eq!(stdout, "2025-04-03 11:32:48.027 12.0\n2025-04-03 11:32:49.500 15.0\n");
+
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["cat", "--input", "journal:nginx", "--cache-dir", "tests/.output/journal-cache"])
+		.args(["field-value", "nginx", "duration"])
+		.env("PLOX_JOURNALCTL", "no-such-journalctl")
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(6));
+	let log =
+		String::from_utf8(output.stdout).unwrap() + &String::from_utf8(output.stderr).unwrap();
+	assert!(log.contains("no-such-journalctl could not be started"), "{log}");
+}
+
+#[test]
+fn test_cmd_k8s_input() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["cat", "--input", "k8s://prod/nginx-7d9f/proxy", "--since", "2h"])
+		.args(["--cache-dir", "tests/.output/k8s-cache"])
+		.args(["--columns", "ts,value", "field-value", "request", "duration"])
+		.env("PLOX_KUBECTL", "tests/examples/fake-kubectl.sh")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert_eq!(stdout, "2025-04-03 09:32:48.027 12.0\n2025-04-03 09:32:49.500 15.0\n");
+}
+
+#[test]
+fn test_cmd_value_units() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/checker.log
+		  --output tests/.output/value-units.png
+		  --plot checker duration --value-units original
+	);
+	let script = std::fs::read_to_string("tests/.output/value-units.gnuplot").unwrap();
+	assert!(script.contains("set format y '%g ms'"), "{script}");
+	assert!(script.contains("__units_original.csv"), "{script}");
+}
+
+#[test]
+fn test_cmd_value_map() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/states.log
+		  --output tests/.output/value-map.png
+		  --plot worker state --value-map idle=0,busy=1,error=2 --style steps
+		  --write-config tests/.output/value-map.toml
+	);
+	let config = std::fs::read_to_string("tests/.output/value-map.toml").unwrap();
+	assert!(config.contains("busy = 1.0"), "{config}");
+
+	let csv = std::fs::read_dir("tests/examples/.plox")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.find(|path| {
+			path.to_string_lossy().contains("states.log")
+				&& path.to_string_lossy().contains("__map_")
+		})
+		.unwrap();
+	let values = std::fs::read_to_string(csv).unwrap();
+	let values = values.lines().skip(1).map(|l| l.split(',').nth(2).unwrap()).collect::<Vec<_>>();
+	assert_eq!(values, ["0.0", "1.0", "2.0", "0.0"]);
+}
+
+#[test]
+fn test_cmd_no_legend_entry() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/no-legend-entry.png
+		  --plot om_module x
+		  --plot x_module x00 --no-legend-entry
+	);
+	let script = std::fs::read_to_string("tests/.output/no-legend-entry.gnuplot").unwrap();
+	assert!(script.contains("title 'value of om_module x'"), "{script}");
+	assert!(script.contains("axes x1y1 notitle"), "{script}");
+}
+
+#[test]
+fn test_cmd_alpha() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/alpha.png
+		  --plot om_module x --line-color red --marker-color blue --alpha 0.5
+		  --plot x_module x00 --alpha 0.25
+		  --plot x_module x01 --line-color "#ff8800"
+	);
+	let script = std::fs::read_to_string("tests/.output/alpha.gnuplot").unwrap();
+	assert!(script.contains(r##"lc rgb "#80ff0000" ps 2 lc rgb "#800000ff""##), "{script}");
+	// Translucent line without explicit color gets the color of its default line type.
+	assert!(script.contains(r##"with points lc rgb "#bf0000ff""##), "{script}");
+	assert!(script.contains(r##"with points lc rgb "#ff8800""##), "{script}");
+}
+
+#[test]
+fn test_cmd_smooth_style() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/smooth.png
+		  --plot om_module x --style lines --smooth-style bezier
+	);
+	let script = std::fs::read_to_string("tests/.output/smooth.gnuplot").unwrap();
+	assert!(script.contains("smooth bezier with lines"), "{script}");
+}
+
+#[test]
+fn test_cmd_stats_box() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/stats-box.png
+		  --stats-box
+		  --plot om_module x
+	);
+	let script = std::fs::read_to_string("tests/.output/stats-box.gnuplot").unwrap();
+	assert!(
+		script.contains(
+			r#"set label "value of om_module x: min 27.03 mean 613.493 p95 979.582 max 1000" at graph 0.99,0.97"#
+		),
+		"{script}"
+	);
+}
+
+#[test]
+fn test_cmd_highlight_breaches() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/breaches.png
+		  --hline 900 --highlight-breaches
+		  --plot om_module x
+	);
+	let script = std::fs::read_to_string("tests/.output/breaches.gnuplot").unwrap();
+	assert!(
+		script.contains("set arrow from graph 0, first 900 to graph 1, first 900 nohead"),
+		"{script}"
+	);
+	assert!(
+		script.contains("(column('value') > 900 ? column('value') : 1/0) with points pt 7"),
+		"{script}"
+	);
+}
+
+#[test]
+fn test_cmd_swimlanes() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log,tests/examples/default-other.log
+		  --input-label 1=other
+		  --output tests/.output/swimlanes.png
+		  --swimlanes
+		  --event om_module "x=" 1
+	);
+	let script = std::fs::read_to_string("tests/.output/swimlanes.gnuplot").unwrap();
+	assert!(script.contains(r#"set ytics ("default" 1, "other" 0) noenhanced"#), "{script}");
+	assert!(script.contains("set yrange [-0.5:1.5]"), "{script}");
+	assert!(script.contains("using (combine_datetime('date','time')):(1) "), "{script}");
+	assert!(script.contains("using (combine_datetime('date','time')):(0) "), "{script}");
+}
+
+#[test]
+fn test_cmd_external_annotations() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/external-annotations.png
+		  --annotations tests/examples/deploys.csv
+		  --plot om_module x
+		  --panel
+		  --plot x_module x01
+	);
+	let script = std::fs::read_to_string("tests/.output/external-annotations.gnuplot").unwrap();
+	let deploy = "set label 'deploy v1.2' at \"2020-01-01T00:00:30.000\", graph 0.98";
+	assert_eq!(script.matches(deploy).count(), 2, "{script}");
+	assert!(script.contains("set label 'alert: high latency, node1' at"), "{script}");
+}
+
+#[test]
+fn test_cmd_fold_period() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/days.log
+		  --output tests/.output/fold-period.png
+		  --fold-period 1d
+		  --plot node load
+	);
+	let script = std::fs::read_to_string("tests/.output/fold-period.gnuplot").unwrap();
+	for day in ["2020-01-01", "2020-01-02", "2020-01-03"] {
+		assert!(script.contains(&format!("title 'value of node load [{day}]'")), "{script}");
+	}
+	assert!(
+		script.contains(r#"set xrange ["2020-01-01T00:15:00":"2020-01-01T21:15:00"]"#),
+		"{script}"
+	);
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0000 = ")).unwrap();
+	let csv = std::fs::read_to_string(path.trim_matches('\'')).unwrap();
+	assert!(csv.contains("2020-01-01,00:15:00.000,3.5,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_baseline() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log,tests/examples/default-other.log
+		  --output tests/.output/baseline.png
+		  --plot om_module x --file-id 0 --title reference
+		  --plot om_module x --file-id 1 --baseline reference
+	);
+	let script = std::fs::read_to_string("tests/.output/baseline.gnuplot").unwrap();
+	assert!(script.contains("- reference'"), "{script}");
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0001 = ")).unwrap();
+	let csv = std::fs::read_to_string(path.trim_matches('\'')).unwrap();
+	assert!(csv.contains("2020-01-01,00:00:00.000,0,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_normalize() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/normalize.png
+		  --plot om_module x --normalize first
+		  --plot x_module x00 --normalize max
+	);
+	let script = std::fs::read_to_string("tests/.output/normalize.gnuplot").unwrap();
+	assert!(script.contains("title 'value of om_module x (% of first)'"), "{script}");
+	assert!(script.contains("title 'value of x_module x00 (% of max)'"), "{script}");
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0000 = ")).unwrap();
+	let csv = std::fs::read_to_string(path.trim_matches('\'')).unwrap();
+	assert!(csv.contains("2020-01-01,00:00:00.000,100,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_resample() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/resample.png
+		  --resample 10s ffill
+		  --plot om_module x
+	);
+	let script = std::fs::read_to_string("tests/.output/resample.gnuplot").unwrap();
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0000 = ")).unwrap();
+	let csv = std::fs::read_to_string(path.trim_matches('\'')).unwrap();
+	let timestamps = csv.lines().skip(1).map(|l| &l[11..23]).take(3).collect::<Vec<_>>();
+	assert_eq!(timestamps, ["00:00:00.000", "00:00:10.000", "00:00:20.000"], "{csv}");
+	assert!(csv.contains("2020-01-01,00:00:10.000,1000,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_transforms() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/transforms.png
+		  --plot om_module x --transforms "scale(0.001),clip(,0.99)"
+	);
+	let script = std::fs::read_to_string("tests/.output/transforms.gnuplot").unwrap();
+	let path = script.lines().find_map(|l| l.strip_prefix("csv_data_file_0000 = ")).unwrap();
+	let csv = std::fs::read_to_string(path.trim_matches('\'')).unwrap();
+	assert!(csv.contains("2020-01-01,00:00:00.000,0.99,"), "{csv}");
+	assert!(csv.contains("2020-01-01,00:00:34.000,0.96597,"), "{csv}");
+}
+
+#[test]
+fn test_cmd_report() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox report -x
+		  --input  tests/examples/default.log
+		  --config tests/examples/report-om.toml
+		  --config tests/examples/report-x.toml
+		  --section-title "Incident <42>"
+		  --title "Write-up"
+		  --output tests/.output/report.html
+	);
+	let html = std::fs::read_to_string("tests/.output/report.html").unwrap();
+	assert!(html.contains("<h1>Write-up</h1>"), "{html}");
+	assert!(html.contains("<h2>Incident &lt;42&gt;</h2>"), "{html}");
+	// Config file name is used as the title of the section without explicit one.
+	assert!(html.contains("<h2>report-x</h2>"), "{html}");
+	for id in ["s0_plot0", "s1_plot0", "s1_plot1"] {
+		assert!(html.contains(&format!(r#"<div id="{id}" class="plot-panel">"#)), "{html}");
+	}
+	assert!(html.contains("<th>median</th>"), "{html}");
+	assert!(html.contains("<tr><td>value of om_module x</td><td>"), "{html}");
+	assert!(html.contains(r#"[["s0_plot0"], ["s1_plot0","s1_plot1"]]"#), "{html}");
+}
+
+#[test]
+fn test_cmd_styles() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["styles", "-x", "--sample", "tests/.output/styles.png"])
+		.env("PLOX_SKIP_GNUPLOT", "1")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	for expected in ["colors:\n  red\n", "  dark-turquoise\n", "markers:\n  dot\n", "  dash-dot\n"]
+	{
+		assert!(stdout.contains(expected), "{stdout}");
+	}
+	assert!(stdout.contains("plot styles:\n  points\n  steps\n  lines-points\n  lines\n"));
+
+	let script = std::fs::read_to_string("tests/.output/styles.gnuplot").unwrap();
+	assert!(
+		script.contains(r#"with lines lw 8 lc rgb "steelblue" title 'steel-blue'"#),
+		"{script}"
+	);
+	assert!(script.contains("with points pt 13 ps 3"), "{script}");
+	assert!(script.contains("with lines dt 4 lw 4"), "{script}");
+	assert!(
+		script.contains("0.3*sin(2*pi*x) with linespoints ps 2 title 'lines-points'"),
+		"{script}"
+	);
+}
+
+#[test]
+fn test_cmd_xtics_options() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/xtics.png
+		  --xtics-bottom-only --xtics-rotate -30 --xtick-format "%d %H:%M" --xtics 2m
+		  --plot om_module x
+		  --panel
+		  --plot x_module x00
+	);
+	let script = std::fs::read_to_string("tests/.output/xtics.gnuplot").unwrap();
+	assert!(script.contains("set format x '%d %H:%M'"), "{script}");
+	assert!(script.contains("set xtics rotate by -30 left"), "{script}");
+	assert!(script.contains("set xtics 120\n"), "{script}");
+	// Tick labels are hidden from the second panel (counting from the bottom) on.
+	let bottom = script.find("set origin 0.0,0\n").expect(&script);
+	let hidden = script.find("set format x ''").expect(&script);
+	let top_plot = script.rfind("plot ").expect(&script);
+	assert!(bottom < hidden && hidden < top_plot, "{script}");
+
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/xtics.html
+		  --plotly-backend --do-not-display
+		  --xtics-bottom-only --xtics-rotate 45 --xtics 90s
+		  --plot om_module x
+		  --panel
+		  --plot x_module x00
+	);
+	let html = std::fs::read_to_string("tests/.output/xtics.html").unwrap();
+	let range = r#""range":["2020-01-01 00:00:00.000","2020-01-01 00:16:23.000"]"#;
+	assert!(
+		html.contains(&format!(
+			r#"{{"dtick":90000.0,{range},"showticklabels":false,"tickangle":-45}}"#
+		)),
+		"{html}"
+	);
+	assert!(html.contains(&format!(r#"{{"dtick":90000.0,{range},"tickangle":-45}}"#)), "{html}");
+}
+
+#[test]
+fn test_cmd_panel_height() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/panel-height.png
+		  --plot om_module x
+		  --panel --height 3
+		  --plot x_module x00
+	);
+	let script = std::fs::read_to_string("tests/.output/panel-height.gnuplot").unwrap();
+	// Panels are drawn bottom-up.
+	let bottom = script.find("set origin 0.0,0\nset size 1.0,0.745\n").expect(&script);
+	let top = script.find("set origin 0.0,0.745\nset size 1.0,0.245\n").expect(&script);
+	assert!(bottom < top, "{script}");
+
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/panel-height.html
+		  --plotly-backend --do-not-display
+		  --plot om_module x
+		  --panel --height 3
+		  --plot x_module x00
+	);
+	let html = std::fs::read_to_string("tests/.output/panel-height.html").unwrap();
+	let top = html.find("}, 300));").expect(&html);
+	let bottom = html.find("}, 900));").expect(&html);
+	assert!(top < bottom, "{html}");
+}
+
+#[test]
+fn test_cmd_repeat_for() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/peers.log
+		  --output tests/.output/repeat.png
+		  --cache-dir tests/.output/repeat-cache
+		  --config tests/examples/repeat.toml
+	);
+	let script = std::fs::read_to_string("tests/.output/repeat.gnuplot").unwrap();
+	for peer in ["alice", "bob", "carol"] {
+		assert!(script.contains(&format!("Sync latency: {peer}")), "{script}");
+	}
+
+	bash!(
+		plox graph
+		  --input  tests/examples/peers.log
+		  --output tests/.output/repeat-labels.png
+		  --cache-dir tests/.output/repeat-cache
+		  --repeat-for "peer=(\\w+)" --repeat-labels bob,alice
+		  --plot sync latency
+	);
+	let script = std::fs::read_to_string("tests/.output/repeat-labels.gnuplot").unwrap();
+	assert!(!script.contains("carol"), "{script}");
+	// Panels are drawn bottom-up.
+	let bob = script.find("'bob'").expect(&script);
+	let alice = script.find("'alice'").expect(&script);
+	assert!(alice < bob, "{script}");
+	assert!(script.contains("__only_peer%3D%28%5Cw%2B%29_bob.csv"), "{script}");
+}
+
+#[test]
+fn test_cmd_csv_file_line() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/default.log
+		  --output tests/.output/csv-file.png
+		  --config tests/examples/csv-file.toml
+	);
+	let script = std::fs::read_to_string("tests/.output/csv-file.gnuplot").unwrap();
+	assert!(script.contains("metrics.csv_"), "{script}");
+
+	let csv = std::fs::read_dir("tests/examples/.plox")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.find(|path| path.to_string_lossy().contains("__csv_timestamp_latency"))
+		.unwrap();
+	let values = std::fs::read_to_string(csv).unwrap();
+	let values = values.lines().skip(1).map(|l| l.split(',').nth(2).unwrap()).collect::<Vec<_>>();
+	assert_eq!(values, ["12.0", "15.0", "1500.0"]);
+
+	// Rows with empty value are skipped, unknown columns are reported.
+	bash!(
+		plox graph
+		  --output tests/.output/csv-file-cli.png
+		  --csv-file tests/examples/metrics.csv timestamp throughput
+	);
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["graph", "--output", "tests/.output/csv-file-error.png"])
+		.args(["--csv-file", "tests/examples/metrics.csv", "timestamp", "no-such-column"])
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(2));
+	let log = String::from_utf8(output.stdout).unwrap();
+	assert!(log.contains("Column 'no-such-column' not found"), "{log}");
+}
+
+#[test]
+fn test_cmd_time_window_prefiltering() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/states.log
+		  --output tests/.output/time-window.png
+		  --event-count worker state
+		  --time-range "2020-01-01 00:00:05,2020-01-01 00:00:25"
+		  --timestamp-format "%Y-%m-%d %H:%M:%S"
+	);
+
+	let csv = std::fs::read_dir("tests/examples/.plox")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.find(|path| {
+			path.to_string_lossy()
+				.contains("__window_20200101T000005.000_20200101T000025.000")
+		})
+		.unwrap();
+	let times = std::fs::read_to_string(csv).unwrap();
+	let times = times.lines().skip(1).map(|l| l.split(',').nth(1).unwrap()).collect::<Vec<_>>();
+	assert_eq!(times, ["00:00:10.000", "00:00:20.000"]);
+}
+
+#[test]
+fn test_cmd_annotate() {
+	plox::logging::init_tracing_test();
+	bash!(
+		plox graph
+		  --input  tests/examples/states.log
+		  --output tests/.output/annotate.png
+		  --plot worker state --value-map idle=0,busy=1,error=2
+		  --annotate worker "state=(error|unknown)"
+	);
+	let script = std::fs::read_to_string("tests/.output/annotate.gnuplot").unwrap();
+	assert!(
+		script.contains("set label 'error' at \"2020-01-01T00:00:20.000\", graph 0.98"),
+		"{script}"
+	);
+	assert!(script.contains("set label 'unknown' at \"2020-01-01T00:00:30.000\""), "{script}");
+	assert!(script.contains("set arrow from \"2020-01-01T00:00:20.000\", graph 0"), "{script}");
+	assert!(!script.contains("annotation worker"), "{script}");
+
+	bash!(
+		plox graph
+		  --input  tests/examples/states.log
+		  --output tests/.output/annotate.html
+		  --plotly-backend
+		  --plot worker state --value-map idle=0,busy=1,error=2
+		  --annotate worker "state=(error|unknown)"
+	);
+	let html = std::fs::read_to_string("tests/.output/annotate.html").unwrap();
+	assert!(html.contains(r#""text":"error""#), "{html}");
+	assert!(html.contains(r#""x0":"2020-01-01 00:00:30.000""#), "{html}");
+}
+
+#[test]
+fn test_cmd_config_to_cli() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args([
+			"config",
+			"to-cli",
+			"tests/test-files/config01-with-timestamp-format-with-per-file-panel.toml",
+		])
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(
+		String::from_utf8_lossy(&output.stdout),
+		"plox graph --timestamp-format %s --per-file-panels --plot c1 d\n"
+	);
+
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["config", "to-cli", "tests/test-files/invalid-config.toml"])
+		.output()
+		.expect("process running");
+	assert_eq!(output.status.code(), Some(2), "{output:?}");
+}
+
+#[test]
+fn test_cmd_config_schema() {
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["config", "schema"])
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+	assert_eq!(schema["title"], "plox graph config");
+	assert_eq!(schema["additionalProperties"], false);
+	assert_eq!(schema["$defs"]["Color"]["type"], "string");
+	assert!(schema["$defs"]["Line"]["properties"]["line_color"].is_object(), "{schema:#}");
+
+	// Options of the config files used in tests are known to the schema.
+	for entry in std::fs::read_dir("tests/test-files").unwrap() {
+		let path = entry.unwrap().path();
+		let name = path.file_name().unwrap().to_string_lossy();
+		if !name.starts_with("config") || !name.ends_with(".toml") {
+			continue;
+		}
+		let config: toml::Table = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+		for key in config.keys() {
+			assert!(schema["properties"][key].is_object(), "{key} of {}", path.display());
+		}
+	}
+}
+
+#[test]
+fn test_cmd_config_paths_expansion() {
+	let config = "tests/.output/expand-paths.toml";
+	std::fs::create_dir_all("tests/.output").unwrap();
+	std::fs::write(
+		config,
+		r#"output = "${PLOX_TEST_OUTPUT}/expand-paths.png"
+
+[[panels]]
+
+[[panels.lines]]
+guard = "om_module"
+field = "x"
+file_name = "$PLOX_TEST_LOGS/default.log"
+"#,
+	)
+	.unwrap();
+	let _ = std::fs::remove_file("tests/.output/expand-paths.gnuplot");
+	let graph = |output: &str| {
+		let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_plox"));
+		command
+			.args(["graph", "--input", "tests/examples/default.log", "--config", config, "-x"])
+			.env("PLOX_SKIP_GNUPLOT", "1")
+			.env("PLOX_TEST_LOGS", "tests/examples");
+		if !output.is_empty() {
+			command.env("PLOX_TEST_OUTPUT", output);
+		}
+		command.output().expect("process running")
+	};
+
+	let output = graph("tests/.output");
+	assert!(output.status.success(), "{output:?}");
+	let script = std::fs::read_to_string("tests/.output/expand-paths.gnuplot").unwrap();
+	assert!(script.contains("set output './tests/.output/expand-paths.png'"), "{script}");
+
+	let output = graph("");
+	assert_eq!(output.status.code(), Some(2), "{output:?}");
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert!(stdout.contains("Environment variable 'PLOX_TEST_OUTPUT'"), "{stdout}");
+}
+
+#[test]
+fn test_cmd_auto_cache_dir() {
+	let cache_home = std::fs::canonicalize("tests/.output").unwrap().join("xdg-cache");
+	let _ = std::fs::remove_dir_all(&cache_home);
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["graph", "--input", "tests/examples/default.log", "--cache-dir", "auto"])
+		.args(["--plot", "om_module", "x", "--output", "tests/.output/auto-cache-dir.png", "-x"])
+		.env("PLOX_SKIP_GNUPLOT", "1")
+		.env("XDG_CACHE_HOME", &cache_home)
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	// Path of the log file is mirrored in the cache directory.
+	let log_dir = std::fs::canonicalize("tests/examples").unwrap();
+	let cache_dir = cache_home.join("plox").join(log_dir.strip_prefix("/").unwrap());
+	let csv_files = std::fs::read_dir(&cache_dir)
+		.unwrap()
+		.filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|e| e == "csv"))
+		.count();
+	assert_eq!(csv_files, 1, "{}", cache_dir.display());
+}
+
+#[test]
+fn test_cmd_cache_max_size() {
+	let cache_root = std::fs::canonicalize("tests/.output").unwrap().join("cache-max-size");
+	let _ = std::fs::remove_dir_all(&cache_root);
+	let run = |plot: [&str; 2], extra_args: &[&str]| {
+		let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+			.args(["graph", "--input", "tests/examples/default.log", "--plot", plot[0], plot[1]])
+			.args(["--output", "tests/.output/cache-max-size.png", "-x", "--cache-dir"])
+			.arg(&cache_root)
+			.args(extra_args)
+			.env("PLOX_SKIP_GNUPLOT", "1")
+			.output()
+			.expect("process running");
+		assert!(output.status.success(), "{output:?}");
+	};
+	let log_dir = std::fs::canonicalize("tests/examples").unwrap();
+	let cache_dir = cache_root.join(log_dir.strip_prefix("/").unwrap());
+	let csv_files = || {
+		std::fs::read_dir(&cache_dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.extension().is_some_and(|e| e == "csv"))
+			.collect::<Vec<_>>()
+	};
+
+	run(["x_module", "x00"], &[]);
+	run(["x_module", "x01"], &[]);
+	run(["om_module", "x"], &["--cache-max-size", "10G"]);
+	assert_eq!(csv_files().len(), 3);
+
+	// Only the file used by the run is left.
+	run(["om_module", "x"], &["--cache-max-size", "1"]);
+	let files = csv_files();
+	assert_eq!(files.len(), 1, "{files:?}");
+	assert!(files[0].to_string_lossy().contains("om_module"), "{files:?}");
+	let manifest = std::fs::read_to_string(cache_dir.join("manifest.toml")).unwrap();
+	assert!(manifest.contains("accessed") && !manifest.contains("x_module"), "{manifest}");
+}
+
+#[test]
+fn test_cmd_per_file_output() {
+	let dir = "tests/.output/per-file-output";
+	let _ = std::fs::remove_dir_all(dir);
+	std::fs::create_dir_all(dir).unwrap();
+	let output = std::process::Command::new(env!("CARGO_BIN_EXE_plox"))
+		.args(["graph", "--input", "tests/examples/default.log,tests/examples/default-other.log"])
+		.args(["--input-label", "1=other", "--plot", "om_module", "x", "--per-file-output"])
+		.args(["--output", &format!("{dir}/graph.png"), "--cache-dir", dir, "-x"])
+		.env("PLOX_SKIP_GNUPLOT", "1")
+		.output()
+		.expect("process running");
+	assert!(output.status.success(), "{output:?}");
+	assert!(!std::path::Path::new(&format!("{dir}/graph.gnuplot")).exists());
+	let script = std::fs::read_to_string(format!("{dir}/graph-default.gnuplot")).unwrap();
+	assert!(script.contains("(default)") && !script.contains("(other)"), "{script}");
+	let script = std::fs::read_to_string(format!("{dir}/graph-other.gnuplot")).unwrap();
+	assert!(script.contains("(other)") && !script.contains("(default)"), "{script}");
+}
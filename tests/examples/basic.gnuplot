@@ -1,3 +1,7 @@
+# version: plox 0.3.6
+# command: plox graph --input tests/examples/checker.log --output tests/.output/basic.png --plot duration
+# input: tests/examples/checker.log sha256:f9c16cd51bafe23e17527cc1b533843baf0ef730e1875f10eb6d06f3fc530674
+# generated: 2026-10-16 11:03:18 +0000
 set terminal pngcairo enhanced font 'arial,10' fontscale 3.0 size 7560, 5500
 set output './tests/.output/basic.png'
 set linetype 1 lc rgb "red" dt 1 pt 7 lw 2.0 ps 4.0
@@ -1,3 +1,7 @@
+# version: plox 0.3.6
+# command: plox graph --input tests/examples/default.log --output tests/.output/deltas.png --event-delta foo_module SOME_EVENT --yaxis-scale log --style points --marker-size 7 --marker-color olive --marker-type diamond --event-count foo_module SOME_EVENT --style steps --yaxis y2
+# input: tests/examples/default.log sha256:e497110f17a110fd2f19b0e833726499e06e515e90cf8e3f6761df4d6f628a30
+# generated: 2026-10-16 11:03:17 +0000
 set terminal pngcairo enhanced font 'arial,10' fontscale 3.0 size 7560, 5500
 set output './tests/.output/deltas.png'
 set linetype 1 lc rgb "red" dt 1 pt 7 lw 2.0 ps 4.0
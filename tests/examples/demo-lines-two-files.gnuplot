@@ -1,3 +1,8 @@
+# version: plox 0.3.6
+# command: plox graph --input tests/examples/default.log --input tests/examples/default-other.log --output tests/.output/demo-lines-two-files.png --timestamp-format '%Y-%m-%d %H:%M:%S%.3f' --per-file-panels --config tests/examples/demo-lines.toml
+# input: tests/examples/default.log sha256:e497110f17a110fd2f19b0e833726499e06e515e90cf8e3f6761df4d6f628a30
+# input: tests/examples/default-other.log sha256:0d817c4db5635bfa4b7be465f62e0f47a3d19070db9d4b44335fd302bb697e00
+# generated: 2026-10-16 11:03:18 +0000
 set terminal pngcairo enhanced font 'arial,10' fontscale 3.0 size 7560, 5500
 set output './tests/.output/demo-lines-two-files.png'
 set linetype 1 lc rgb "red" dt 1 pt 7 lw 2.0 ps 4.0
@@ -1,3 +1,7 @@
+# version: plox 0.3.6
+# command: plox graph --input tests/examples/some.log --timestamp-format '[%s]' --output tests/.output/demo-lines.png --config tests/examples/demo-lines.toml
+# input: tests/examples/some.log sha256:d4d6c4e5233346e737188790c8d81df4e5366b38c6e2a990119f90806e174064
+# generated: 2026-10-16 11:03:17 +0000
 set terminal pngcairo enhanced font 'arial,10' fontscale 3.0 size 7560, 5500
 set output './tests/.output/demo-lines.png'
 set linetype 1 lc rgb "red" dt 1 pt 7 lw 2.0 ps 4.0
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Fake `journalctl` used in tests: prints entries of the unit given with `--unit`.
+echo "2025-04-03T11:32:48.027123+02:00 host $2[123]: request duration=12ms"
+echo "2025-04-03T11:32:49.500000+02:00 host $2[123]: request duration=15ms"
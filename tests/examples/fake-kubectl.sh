@@ -0,0 +1,5 @@
+#!/bin/sh
+# Fake `kubectl` used in tests: checks the arguments of `kubectl logs` and prints timestamped entries.
+[ "$*" = "logs --namespace prod nginx-7d9f --timestamps --container proxy --since=2h" ] || exit 1
+echo "2025-04-03T09:32:48.027123456Z request from 10.0.0.1 duration=12ms"
+echo "2025-04-03T09:32:49.500000000Z request from 10.0.0.2 duration=15ms"